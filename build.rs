@@ -1,4 +1,53 @@
-const COMMANDS: &[&str] = &["start_recording", "stop_recording"];
+const COMMANDS: &[&str] = &[
+    "start_recording",
+    "stop_recording",
+    "get_audit_log",
+    "disable_recorder",
+    "enable_recorder",
+    "detect_watermark",
+    "process_channels",
+    "get_recording_metadata",
+    "detect_chapters",
+    "add_marker",
+    "get_recorder_state",
+    "force_stop",
+    "redirect_recording",
+    "snapshot_recording",
+    "split_recording",
+    "set_namespace_quota",
+    "get_namespace_usage",
+    "list_namespace_usage",
+    "detect_tempo",
+    "fingerprint_recording",
+    "compare_fingerprints",
+    "get_resumable_session",
+    "resume_session",
+    "discard_session",
+    "get_mic_permission",
+    "shutdown",
+    "export_template",
+    "import_template",
+    "set_recording_expiry",
+    "get_recording_expiry",
+    "delete_expired_recordings",
+    "update_recordings",
+    "search_recordings",
+    "list_recordings",
+    "export_m4a",
+    "export_captioned",
+    "create_manifest",
+    "verify_manifest",
+    "find_duplicates",
+    "update_sync_states",
+    "set_webhook",
+    "get_webhook",
+    "set_upload_schedule",
+    "get_upload_schedule",
+    "list_input_devices",
+    "get_recorder_snapshot",
+    "get_capabilities",
+    "request_usb_permission",
+];
 
 fn main() {
     tauri_plugin::Builder::new(COMMANDS).build();
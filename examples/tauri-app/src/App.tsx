@@ -1,12 +1,34 @@
 import { Button, message, Space, Typography } from "antd";
-import { useState } from "react";
-import { startRecording, stopRecording } from "tauri-plugin-mic-recorder-api";
+import { useEffect, useState } from "react";
+import {
+  EVENT,
+  RecorderError,
+  RecordingErrorPayload,
+  startRecording,
+  stopRecording,
+} from "tauri-plugin-mic-recorder-api";
 import { convertFileSrc } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 
 const App = () => {
   const [isRecording, setIsRecording] = useState<boolean>(false);
   const [savePath, setSavePath] = useState<string>("");
 
+  useEffect(() => {
+    const unlisten = listen<RecordingErrorPayload>(
+      EVENT.RECORDING_ERROR,
+      ({ payload }) => {
+        setIsRecording(false);
+        setSavePath(payload.path ?? "");
+        message.error(payload.error.message);
+      },
+    );
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
   return (
     <Space direction="vertical">
       <Space>
@@ -18,7 +40,7 @@ const App = () => {
               setIsRecording(true);
             } catch (error) {
               setIsRecording(false);
-              message.error(String(error));
+              message.error((error as RecorderError).message);
             }
           }}
         >
@@ -31,7 +53,7 @@ const App = () => {
               const path = await stopRecording();
               setSavePath(path);
             } catch (error) {
-              message.error(String(error));
+              message.error((error as RecorderError).message);
             } finally {
               setIsRecording(false);
             }
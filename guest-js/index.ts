@@ -1,13 +1,1077 @@
-import { invoke } from "@tauri-apps/api/core";
+import { Channel, invoke } from "@tauri-apps/api/core";
 
 export const COMMAND = {
   START_RECORDING: "plugin:mic-recorder|start_recording",
   STOP_RECORDING: "plugin:mic-recorder|stop_recording",
+  GET_AUDIT_LOG: "plugin:mic-recorder|get_audit_log",
+  DISABLE_RECORDER: "plugin:mic-recorder|disable_recorder",
+  ENABLE_RECORDER: "plugin:mic-recorder|enable_recorder",
+  DETECT_WATERMARK: "plugin:mic-recorder|detect_watermark",
+  PROCESS_CHANNELS: "plugin:mic-recorder|process_channels",
+  GET_RECORDING_METADATA: "plugin:mic-recorder|get_recording_metadata",
+  DETECT_CHAPTERS: "plugin:mic-recorder|detect_chapters",
+  ADD_MARKER: "plugin:mic-recorder|add_marker",
+  GET_RECORDER_STATE: "plugin:mic-recorder|get_recorder_state",
+  FORCE_STOP: "plugin:mic-recorder|force_stop",
+  REDIRECT_RECORDING: "plugin:mic-recorder|redirect_recording",
+  SNAPSHOT_RECORDING: "plugin:mic-recorder|snapshot_recording",
+  SPLIT_RECORDING: "plugin:mic-recorder|split_recording",
+  SET_NAMESPACE_QUOTA: "plugin:mic-recorder|set_namespace_quota",
+  GET_NAMESPACE_USAGE: "plugin:mic-recorder|get_namespace_usage",
+  LIST_NAMESPACE_USAGE: "plugin:mic-recorder|list_namespace_usage",
+  DETECT_TEMPO: "plugin:mic-recorder|detect_tempo",
+  FINGERPRINT_RECORDING: "plugin:mic-recorder|fingerprint_recording",
+  COMPARE_FINGERPRINTS: "plugin:mic-recorder|compare_fingerprints",
+  GET_RESUMABLE_SESSION: "plugin:mic-recorder|get_resumable_session",
+  RESUME_SESSION: "plugin:mic-recorder|resume_session",
+  DISCARD_SESSION: "plugin:mic-recorder|discard_session",
+  GET_MIC_PERMISSION: "plugin:mic-recorder|get_mic_permission",
+  SHUTDOWN: "plugin:mic-recorder|shutdown",
+  EXPORT_TEMPLATE: "plugin:mic-recorder|export_template",
+  IMPORT_TEMPLATE: "plugin:mic-recorder|import_template",
+  SET_RECORDING_EXPIRY: "plugin:mic-recorder|set_recording_expiry",
+  GET_RECORDING_EXPIRY: "plugin:mic-recorder|get_recording_expiry",
+  DELETE_EXPIRED_RECORDINGS: "plugin:mic-recorder|delete_expired_recordings",
+  UPDATE_RECORDINGS: "plugin:mic-recorder|update_recordings",
+  SEARCH_RECORDINGS: "plugin:mic-recorder|search_recordings",
+  LIST_RECORDINGS: "plugin:mic-recorder|list_recordings",
+  EXPORT_M4A: "plugin:mic-recorder|export_m4a",
+  EXPORT_CAPTIONED: "plugin:mic-recorder|export_captioned",
+  CREATE_MANIFEST: "plugin:mic-recorder|create_manifest",
+  VERIFY_MANIFEST: "plugin:mic-recorder|verify_manifest",
+  FIND_DUPLICATES: "plugin:mic-recorder|find_duplicates",
+  UPDATE_SYNC_STATES: "plugin:mic-recorder|update_sync_states",
+  SET_WEBHOOK: "plugin:mic-recorder|set_webhook",
+  GET_WEBHOOK: "plugin:mic-recorder|get_webhook",
+  SET_UPLOAD_SCHEDULE: "plugin:mic-recorder|set_upload_schedule",
+  GET_UPLOAD_SCHEDULE: "plugin:mic-recorder|get_upload_schedule",
+  LIST_INPUT_DEVICES: "plugin:mic-recorder|list_input_devices",
+  GET_RECORDER_SNAPSHOT: "plugin:mic-recorder|get_recorder_snapshot",
+  GET_CAPABILITIES: "plugin:mic-recorder|get_capabilities",
+  REQUEST_USB_PERMISSION: "plugin:mic-recorder|request_usb_permission",
 };
 
+export const EVENT = {
+  RECORDING_STARTED: "recording-started",
+  RECORDING_STOPPED: "recording-stopped",
+  RECORDING_FINALIZING: "recording-finalizing",
+  RECORDING_FINALIZED: "recording-finalized",
+  RECORDING_ERROR: "recording-error",
+  RECORDER_DISABLED: "recorder-disabled",
+  RECORDER_ENABLED: "recorder-enabled",
+  MIC_MUTE_CHANGED: "mic-mute-changed",
+  MARKER_ADDED: "marker-added",
+  AUDIO_DROPPED: "audio-dropped",
+  BUFFER_SIZE_CHANGED: "buffer-size-changed",
+  RECORDING_IO_ERROR: "recording-io-error",
+  RECORDER_HEARTBEAT: "recorder-heartbeat",
+  PITCH_DETECTED: "pitch-detected",
+  SESSION_RESUMABLE: "session-resumable",
+  MIC_PERMISSION_CHANGED: "mic-permission-changed",
+  RECORDING_EXPIRED: "recording-expired",
+  SYNC_STATE_CHANGED: "sync-state-changed",
+  CHUNK_HASHED: "chunk-hashed",
+  INPUT_DEVICE_CHANGED: "input-device-changed",
+  INPUT_DEVICES_CHANGED: "input-devices-changed",
+  HANDS_FREE_INPUT: "hands-free-input",
+  RECORDER_SNAPSHOT: "recorder-snapshot",
+  WASM_METRIC: "wasm-metric",
+  WASM_STAGE_FAILED: "wasm-stage-failed",
+};
+
+export interface RecordingOptions {
+  /**
+   * Input device names in order of preference. The first one that is connected and can be opened is recorded, `default` standing for the default input.
+   */
+  preferredDevices?: string[];
+  /**
+   * Stop writing audio while the hardware mic-mute key is engaged. Windows only.
+   */
+  autoPauseOnMute?: boolean;
+  /**
+   * Embed an inaudible marker that `detectWatermark` can find later.
+   */
+  watermark?: WatermarkOptions;
+  /**
+   * Record this many channels, e.g. `2` for stereo, instead of the device's default. Must be one of the device's `channelCounts`.
+   */
+  channelCount?: number;
+  /**
+   * Move over to the system's default input whenever it changes, e.g. to or from AirPods on macOS, or when the device being recorded goes away, converting the new input to the file's format.
+   */
+  followDefaultInput?: boolean;
+  /**
+   * Move over to the first of `preferredDevices` that is connected whenever that changes, e.g. to a USB audio interface plugged into a phone and back once it is unplugged, converting it like `followDefaultInput`.
+   */
+  followPreferred?: boolean;
+  /**
+   * Write the file with this many bits per sample, capturing in a format at least as precise, e.g. 24-bit integer samples from a 32-bit interface. The device's own width by default.
+   */
+  bitDepth?: 16 | 24 | 32;
+  /**
+   * Record a 4-channel ambisonic mic and tag the file with how its channels are ordered and normalized.
+   */
+  ambisonic?: AmbisonicFormat;
+  /**
+   * Rewire the left and right channels while capturing.
+   */
+  channels?: ChannelOptions;
+  /**
+   * Pad the start with silence so the file begins on a wall-clock boundary or shared start signal.
+   */
+  align?: AlignOptions;
+  /**
+   * Decode LTC timecode present on one input channel.
+   */
+  ltc?: LtcOptions;
+  /**
+   * Mark a new chapter wherever the input resumes after a long silence.
+   */
+  chapters?: ChapterOptions;
+  /**
+   * A global shortcut, e.g. `CommandOrControl+Shift+B`, that adds a bookmark even while the app is unfocused. Requires the `global-shortcut` feature.
+   */
+  bookmarkShortcut?: string;
+  /**
+   * How audio streamed to `onAudio` is buffered.
+   */
+  stream?: StreamOptions;
+  /**
+   * Grow the input buffer whenever the backend drops audio.
+   */
+  adaptiveBuffer?: AdaptiveBufferOptions;
+  /**
+   * How much audio, in milliseconds, is held in memory when the file cannot be written. Defaults to `60000`.
+   */
+  ioErrorBufferMs?: number;
+  /**
+   * The priority of the threads that capture and stream audio. Left untouched if unset.
+   */
+  threadPriority?: ThreadPriority;
+  /**
+   * Store the recording in its own directory for a user or workspace, subject to that namespace's quota.
+   */
+  namespace?: string;
+  /**
+   * Record the app's playback into a separate track next to the recording, for echo cancellation afterwards.
+   */
+  reference?: ReferenceOptions;
+  /**
+   * Play a backing track while recording, and line the take up with it. Cannot be combined with `align`.
+   */
+  overdub?: OverdubOptions;
+  /**
+   * Play a click on the output while recording, which is not recorded. Cannot be combined with `align`.
+   */
+  metronome?: MetronomeOptions;
+  /**
+   * Emit `pitch-detected` events for the input, e.g. to build a tuner.
+   */
+  tuner?: TunerOptions;
+  /**
+   * Run the input through a WebAssembly module. Requires the `wasm` feature.
+   */
+  wasm?: WasmOptions;
+  /**
+   * Leave long silences out of the file, marking where they were.
+   */
+  compressSilence?: CompressSilenceOptions;
+  /**
+   * Write a small low-rate copy of the recording next to it, e.g. to upload right away while the full-quality file waits for Wi-Fi.
+   */
+  proxy?: ProxyOptions;
+  /**
+   * Persist the session, so that it can be resumed into a new file if the app crashes and is restarted soon enough.
+   */
+  resume?: ResumeOptions;
+  /**
+   * Write a second copy of the recording, e.g. to another disk, so that a single failing file or disk does not lose the take.
+   */
+  redundant?: RedundantOptions;
+  /**
+   * Delete the recording this many milliseconds after it was started, e.g. `30 * 24 * 60 * 60 * 1000` for 30 days.
+   */
+  expireAfterMs?: number;
+  /**
+   * Hash the recording in chunks while it is captured, and upload them, so only the tail is left to transfer once it is stopped.
+   */
+  chunks?: ChunkOptions;
+  /**
+   * Identifies this start, so that retrying it, e.g. after a webview reload, returns the recording it started instead of starting another one. Never written to templates or sessions.
+   */
+  idempotencyKey?: string;
+  /**
+   * How long to wait for a start or stop that is already in progress before failing with `Timeout`. Waits for as long as it takes by default.
+   */
+  timeoutMs?: number;
+  /**
+   * Write the file to this directory while recording, e.g. on a fast local disk, and move it to the save directory once it has been finalized.
+   */
+  tempDir?: string;
+}
+
+export interface StartedRecording {
+  path: string;
+  device: string;
+  /**
+   * Whether the idempotency key had been used before, so this is the recording started back then, which may have been stopped since.
+   */
+  replayed: boolean;
+}
+
+export interface ChunkOptions {
+  /**
+   * The size of every chunk but the last, at least 64 KiB. Defaults to 8 MiB.
+   */
+  sizeBytes?: number;
+  /**
+   * Upload the chunks with the uploader set up with the plugin, on its schedule, rather than only hashing them.
+   */
+  upload?: boolean;
+}
+
+export interface RedundantOptions {
+  /**
+   * The directory the copy is written to, under the recording's file name. Must not be the directory of the recording itself.
+   */
+  directory: string;
+}
+
+export interface ResumeOptions {
+  /**
+   * The time from the last sign of the recording to the restart of the app, in milliseconds. Defaults to `300000`.
+   */
+  graceMs?: number;
+}
+
+export interface ProxyOptions {
+  /**
+   * Defaults to `8000`, which gives a bitrate of 64 kbps.
+   */
+  sampleRate?: number;
+}
+
+export interface WasmOptions {
+  /**
+   * The `.wasm` file to load.
+   */
+  module: string;
+  /**
+   * The instructions the module may run for each buffer before it is bypassed. Defaults to `10000000`.
+   */
+  fuelPerBuffer?: number;
+}
+
+export interface TunerOptions {
+  /**
+   * The frequency of A4. Defaults to `440`.
+   */
+  referenceHz?: number;
+  /**
+   * How often the pitch is measured, in milliseconds. Defaults to `100`.
+   */
+  intervalMs?: number;
+  /**
+   * The lowest pitch detected, in hertz. Defaults to `50`.
+   */
+  minFrequency?: number;
+  /**
+   * The highest pitch detected, in hertz. Defaults to `2000`.
+   */
+  maxFrequency?: number;
+}
+
+export interface MetronomeOptions {
+  bpm: number;
+  /**
+   * The beats in a bar, the first of which is accented. Defaults to `4`.
+   */
+  beatsPerBar?: number;
+  /**
+   * Bars clicked before the recording, and any backing track, begins. Defaults to `0`.
+   */
+  countInBars?: number;
+  /**
+   * The volume from 0 to 1. Defaults to `0.5`.
+   */
+  volume?: number;
+  /**
+   * The output device to play it on. Defaults to the one of the backing track, or the default output.
+   */
+  device?: string;
+}
+
+export interface OverdubOptions {
+  /**
+   * The WAV file to play from the start of the recording.
+   */
+  backingTrack: string;
+  /**
+   * The output device to play it on. Defaults to the default output.
+   */
+  device?: string;
+  /**
+   * Latency the audio backend does not report, e.g. of the converters, in milliseconds. Positive values drop more of the start of the take.
+   */
+  latencyMs?: number;
+}
+
+export interface ReferenceOptions {
+  /**
+   * The device to capture, e.g. a monitor source or a virtual loopback device. Defaults to the default output device on Windows, and has to be set elsewhere.
+   */
+  device?: string;
+}
+
+/**
+ * `realtime` falls back to `high`, and `high` to the default, where the OS does not allow it.
+ */
+export type ThreadPriority = "normal" | "high" | "realtime";
+
+export interface AdaptiveBufferOptions {
+  /**
+   * The largest buffer to grow to, in milliseconds. Defaults to `200`.
+   */
+  maxBufferMs?: number;
+}
+
+export type DropPolicy = "dropOldest" | "dropNewest" | "stopWithError";
+
+/**
+ * Buffering of the audio streamed to the `onAudio` callback.
+ */
+export interface StreamOptions {
+  /**
+   * The most audio, in bytes, held for a consumer that falls behind. Defaults to 8 MiB.
+   */
+  maxBufferedBytes?: number;
+  /**
+   * What to do once `maxBufferedBytes` is reached. Defaults to `dropOldest`.
+   */
+  dropPolicy?: DropPolicy;
+  /**
+   * Encrypt every chunk before it reaches the frontend, so whatever relays the stream never sees the audio.
+   */
+  encryption?: StreamEncryption;
+  /**
+   * Frame every chunk with a sequence number and a timestamp, and add parity to recover lost packets, for relays over lossy networks.
+   */
+  packets?: PacketOptions;
+}
+
+/**
+ * How streamed audio is framed for receivers that may lose packets.
+ */
+export interface PacketOptions {
+  /**
+   * The share of packets expected to be lost, up to 50. From 1 on, a parity packet follows every `100 / lossPercent` packets, at least every other one. Defaults to 0, for no parity.
+   */
+  lossPercent?: number;
+}
+
+/**
+ * The key audio is streamed under. Each chunk is sealed with ChaCha20-Poly1305 as its 12-byte nonce, the ciphertext and the 16-byte tag, without associated data.
+ */
+export interface StreamEncryption {
+  /**
+   * The 32-byte key as 64 hex digits. It is left out of sessions and templates, so it never reaches the disk.
+   */
+  key: string;
+}
+
+/**
+ * How long and how quiet a silence must be to start a new chapter.
+ */
+export interface ChapterOptions {
+  /**
+   * The RMS level in dBFS below which the input counts as silent. Defaults to `-45`.
+   */
+  thresholdDb?: number;
+  /**
+   * The shortest silence, in milliseconds, that separates chapters. Defaults to `3000`.
+   */
+  minSilenceMs?: number;
+}
+
+/**
+ * How long and how quiet a silence must be to be left out of the file.
+ */
+export interface CompressSilenceOptions {
+  /**
+   * The RMS level in dBFS below which the input counts as silent. Defaults to `-50`.
+   */
+  thresholdDb?: number;
+  /**
+   * How much of a silence, in milliseconds, is kept before the rest of it is left out. Defaults to `2000`.
+   */
+  minSilenceMs?: number;
+}
+
+export interface LtcOptions {
+  /**
+   * The zero-based input channel carrying the timecode signal.
+   */
+  channel: number;
+}
+
+export interface Marker {
+  /**
+   * The position in sample frames from the start of the file.
+   */
+  frame: number;
+  label: string;
+}
+
+export interface RecordingMetadata {
+  sampleRate: number;
+  /**
+   * The BWF `TimeReference`: the file's start in samples since midnight.
+   */
+  timeReference: number | null;
+  markers: Marker[];
+  /**
+   * How the channels are laid out, if the file was recorded from an ambisonic mic.
+   */
+  ambisonic: AmbisonicFormat | null;
+}
+
+/**
+ * How the four channels of a first-order ambisonic recording are laid out: `aFormat` for the raw capsules of a tetrahedral mic (FLU, FRD, BLD, BRU), `ambiX` for B-format in ACN order with SN3D normalization (W, Y, Z, X), `fuMa` for B-format in Furse-Malham order (W, X, Y, Z).
+ */
+export type AmbisonicFormat = "aFormat" | "ambiX" | "fuMa";
+
+/**
+ * Where the first sample of the recording should sit in wall-clock time. Exactly one of the fields must be set.
+ */
+export interface AlignOptions {
+  /**
+   * Start on the previous multiple of this many milliseconds since the Unix epoch, e.g. `1000` for the start of the current second.
+   */
+  boundaryMs?: number;
+  /**
+   * Start at this Unix time in milliseconds, shared with the other devices being aligned. Input captured before it is dropped.
+   */
+  startAt?: number;
+}
+
+export type Channel = "left" | "right";
+
+/**
+ * Channel manipulation, applied in the order swap, invert, mid/side encode.
+ */
+export interface ChannelOptions {
+  /**
+   * Swap the left and right channels.
+   */
+  swap?: boolean;
+  /**
+   * Invert the phase of one channel.
+   */
+  invert?: Channel;
+  /**
+   * Encode left/right into mid (L+R)/2 and side (L-R)/2.
+   */
+  midSide?: boolean;
+}
+
+export interface StopOptions {
+  /**
+   * How long to wait for the stream and writer to shut down before failing with `Timeout`, including the time spent waiting for a start in progress. Defaults to `5000`.
+   */
+  timeoutMs?: number;
+  /**
+   * Identifies this stop, so that retrying it returns the file it stopped instead of failing with `NotRecording`.
+   */
+  idempotencyKey?: string;
+}
+
+export interface WatermarkOptions {
+  /**
+   * The secret the marker is derived from; the same key must be passed to `detectWatermark`.
+   */
+  key: string;
+}
+
+export interface WatermarkDetection {
+  detected: boolean;
+  confidence: number;
+}
+
+export type ErrorKind =
+  | "AlreadyRecording"
+  | "NotRecording"
+  | "RecorderDisabled"
+  | "ShutDown"
+  | "InvalidOptions"
+  | "DeviceNotFound"
+  | "PermissionDenied"
+  | "UnsupportedSampleFormat"
+  | "StreamError"
+  | "Timeout"
+  | "Overflow"
+  | "QuotaExceeded"
+  | "Vetoed"
+  | "Io"
+  | "Internal";
+
+/**
+ * The error every command rejects with.
+ */
+export interface RecorderError {
+  kind: ErrorKind;
+  /**
+   * A stable key for the specific error, e.g. `invalidOptions.tempoRange`, to look up in a message catalog.
+   */
+  code: string;
+  /**
+   * The values of the placeholders in the message, by name.
+   */
+  params: Record<string, string>;
+  /**
+   * The message in English.
+   */
+  message: string;
+}
+
+export interface RecordingStartedPayload {
+  path: string;
+  device: string;
+}
+
+export interface RecordingStoppedPayload {
+  path: string;
+  durationMs: number | null;
+}
+
+export type FinalizeStage = "flushing" | "writingMetadata" | "syncing" | "moving";
+
+export interface RecordingFinalizingPayload {
+  path: string;
+  stage: FinalizeStage;
+  /**
+   * The fraction of the stages completed so far, from 0 to 1.
+   */
+  progress: number;
+}
+
+export interface RecordingFinalizedPayload {
+  path: string;
+  durationMs: number | null;
+}
+
+export type RecorderState = "idle" | "recording" | "finalizing" | "failed";
+
+export interface RecorderSnapshot {
+  /**
+   * Increases with every change, so a window can tell whether the snapshot it queried is older than the one it was sent.
+   */
+  revision: number;
+  state: RecorderState;
+  disabled: boolean;
+  paused: boolean;
+  /**
+   * The file being recorded into.
+   */
+  path: string | null;
+  device: string | null;
+  namespace: string | null;
+  /**
+   * When recording into the current file began, as Unix time in milliseconds.
+   */
+  startedAt: number | null;
+}
+
+export type RecorderSnapshotPayload = RecorderSnapshot;
+
+export interface Capability {
+  /**
+   * Whether the feature was compiled into the plugin for this platform.
+   */
+  compiled: boolean;
+  /**
+   * Whether the feature works right now, e.g. with the JACK server running.
+   */
+  usable: boolean;
+}
+
+export interface Capabilities {
+  globalShortcut: Capability;
+  jack: Capability;
+  muteDetection: Capability;
+  permissionState: Capability;
+  handsFreeDetection: Capability;
+  loopbackReference: Capability;
+  meteredNetwork: Capability;
+  threadPriority: Capability;
+  fileStorage: Capability;
+  wasm: Capability;
+}
+
+export interface RecordingErrorPayload {
+  error: RecorderError;
+  path: string | null;
+}
+
+export interface MarkerAddedPayload {
+  frame: number;
+  timeMs: number;
+  label: string;
+}
+
+export interface PitchDetectedPayload {
+  frequency: number;
+  /**
+   * The nearest note in scientific pitch notation, e.g. `A4`.
+   */
+  note: string;
+  midiNote: number;
+  /**
+   * How far the pitch is from the nearest note, from -50 to 50.
+   */
+  cents: number;
+  /**
+   * How clearly periodic the input is, from 0 to 1.
+   */
+  clarity: number;
+}
+
+export interface RecordingInfo {
+  path: string;
+  /**
+   * Unix time in milliseconds at which the recording started.
+   */
+  recordedAt: number;
+  durationMs: number | null;
+  device: string | null;
+  sizeBytes: number;
+  namespace: string | null;
+  title: string | null;
+  tags: string[];
+  /**
+   * A transcript the app has made of the recording.
+   */
+  transcript: string | null;
+  /**
+   * Any other values the app keeps for the recording.
+   */
+  custom: Record<string, string>;
+  /**
+   * Whether the app has uploaded the recording.
+   */
+  sync: SyncState;
+}
+
+export type SyncStatus = "pending" | "uploaded" | "failed";
+
+/**
+ * Where a recording stands in the app's uploads. The plugin only keeps the books; uploading is left to the app.
+ */
+export interface SyncState {
+  status: SyncStatus;
+  /**
+   * Why the last upload failed.
+   */
+  error: string | null;
+  /**
+   * Where the recording was uploaded to, e.g. a URL or an object key.
+   */
+  remote: string | null;
+  /**
+   * The uploads that failed since the last one that succeeded.
+   */
+  failures: number;
+  /**
+   * Unix time in milliseconds of the last change, or 0 if there was none.
+   */
+  updatedAt: number;
+}
+
+/**
+ * A change of the sync state of one recording.
+ */
+export interface SyncUpdate {
+  path: string;
+  status: SyncStatus;
+  /**
+   * Why the upload failed, kept only with `failed`.
+   */
+  error?: string;
+  /**
+   * Where the recording was uploaded to, replacing the previous value if set.
+   */
+  remote?: string;
+}
+
+export interface SyncStateChangedPayload {
+  path: string;
+  state: SyncState;
+  previous: SyncStatus;
+}
+
+/**
+ * A fixed-size piece of a recording, hashed while it is captured.
+ */
+export interface RecordingChunk {
+  index: number;
+  /**
+   * Where the chunk begins in the file.
+   */
+  offset: number;
+  len: number;
+  /**
+   * The SHA-256 hash of the chunk as lowercase hex.
+   */
+  sha256: string;
+}
+
+export interface WasmMetricPayload {
+  /**
+   * The id the module reported the metric under.
+   */
+  id: number;
+  value: number;
+  /**
+   * The frame of the file the buffer being processed starts at.
+   */
+  position: number;
+}
+
+export interface WasmStageFailedPayload {
+  error: string;
+  /**
+   * The frame of the file from which the input is no longer processed.
+   */
+  position: number;
+}
+
+export interface ChunkHashedPayload {
+  path: string;
+  chunk: RecordingChunk;
+  /**
+   * Whether the chunk has been uploaded, rather than left for later.
+   */
+  uploaded: boolean;
+}
+
+/**
+ * What `searchRecordings` looks for. Recordings have to match every criterion that is set.
+ */
+export interface SearchQuery {
+  /**
+   * Words that all have to appear, ignoring case, in the title, tags or transcript.
+   */
+  text?: string;
+  /**
+   * Tags the recording must all have.
+   */
+  tags?: string[];
+  /**
+   * Unix time in milliseconds.
+   */
+  recordedAfter?: number;
+  /**
+   * Unix time in milliseconds.
+   */
+  recordedBefore?: number;
+  minDurationMs?: number;
+  maxDurationMs?: number;
+  device?: string;
+  namespace?: string;
+  syncStatus?: SyncStatus;
+  /**
+   * The number of matching recordings to skip.
+   */
+  offset?: number;
+  /**
+   * The most recordings to return, up to 1000. Defaults to `50`.
+   */
+  limit?: number;
+}
+
+/**
+ * Which page of the index `listRecordings` returns.
+ */
+export interface ListOptions {
+  /**
+   * Defaults to `recordedAt`.
+   */
+  sortBy?: "recordedAt" | "duration" | "size";
+  /**
+   * Sort in ascending order. Defaults to descending, newest first.
+   */
+  ascending?: boolean;
+  /**
+   * The number of recordings to skip.
+   */
+  offset?: number;
+  /**
+   * The most recordings to return, up to 1000. Defaults to `50`.
+   */
+  limit?: number;
+}
+
+export interface RecordingPage {
+  recordings: RecordingInfo[];
+  /**
+   * The number of recordings on all pages.
+   */
+  total: number;
+  /**
+   * The offset of the next page, or `null` if this is the last one.
+   */
+  nextOffset: number | null;
+}
+
+/**
+ * Changes to the metadata of one recording. Fields left out are kept.
+ */
+export interface RecordingUpdate {
+  path: string;
+  /**
+   * The new title, or an empty string to remove it.
+   */
+  title?: string;
+  /**
+   * Replaces all tags.
+   */
+  tags?: string[];
+  addTags?: string[];
+  removeTags?: string[];
+  /**
+   * The new transcript, or an empty string to remove it.
+   */
+  transcript?: string;
+  /**
+   * The custom values to set, removing those that are `null`.
+   */
+  custom?: Record<string, string | null>;
+}
+
+export interface RecordingTemplate {
+  /**
+   * The version of the format, filled in on export.
+   */
+  version?: number;
+  name: string;
+  description?: string | null;
+  /**
+   * The options to pass to `startRecording`.
+   */
+  options: RecordingOptions;
+}
+
+export interface ResumableSession {
+  id: string;
+  /**
+   * The files recorded before the interruption, in order.
+   */
+  segments: string[];
+  /**
+   * Unix time in milliseconds.
+   */
+  startedAt: number;
+  /**
+   * Unix time in milliseconds at which the recording was last seen.
+   */
+  interruptedAt: number;
+}
+
+export type SessionResumablePayload = ResumableSession;
+
+export interface RecorderHeartbeatPayload {
+  state: RecorderState;
+  paused: boolean;
+  /**
+   * The time since the input stream last delivered audio. A value that keeps growing means the backend has stalled.
+   */
+  lastCallbackAgeMs: number;
+  /**
+   * The number of frames written to the file so far.
+   */
+  frames: number;
+}
+
+export interface BufferSizeChangedPayload {
+  /**
+   * The new size of the input buffer in frames.
+   */
+  bufferFrames: number;
+  previousFrames: number;
+  /**
+   * The frames lost to the overrun that triggered the change.
+   */
+  lostFrames: number;
+}
+
+export interface InputDeviceChangedPayload {
+  device: string;
+  previousDevice: string | null;
+  /**
+   * The position in sample frames from the start of the recording at which the new device takes over.
+   */
+  frame: number;
+  /**
+   * The sample rate of the new device, e.g. 16000 for AirPods, which caps the quality of the rest of the file.
+   */
+  sampleRate: number;
+  channels: number;
+  /**
+   * The sample rate of the file, which the input is resampled to.
+   */
+  fileSampleRate: number;
+  /**
+   * Whether the input is resampled or remixed to fit the file.
+   */
+  converted: boolean;
+  /**
+   * Whether the new device captures over the Bluetooth hands-free profile.
+   */
+  handsFree: boolean;
+  /**
+   * Whether the new device is connected over USB.
+   */
+  usb: boolean;
+}
+
+export interface InputDevicesChangedPayload {
+  /**
+   * The names of the inputs that have been attached.
+   */
+  attached: string[];
+  detached: string[];
+  /**
+   * Which of the attached inputs are connected over USB, such as audio interfaces.
+   */
+  attachedUsb: string[];
+}
+
+export interface HandsFreeInputPayload {
+  device: string;
+}
+
+export interface RecordingIoErrorPayload {
+  error: RecorderError;
+  /**
+   * The file that could not be written.
+   */
+  path: string | null;
+  /**
+   * How much audio is held in memory before the rest is lost.
+   */
+  bufferMs: number;
+}
+
+export interface InputDevice {
+  name: string;
+  isDefault: boolean;
+  /**
+   * Whether the device is a Bluetooth headset that captures over the hands-free profile, at telephone quality. `false` where the plugin cannot tell.
+   */
+  isHandsFree: boolean;
+  /**
+   * Whether the device is connected over USB, such as an audio interface. `false` where the plugin cannot tell.
+   */
+  isUsb: boolean;
+  /**
+   * The configuration recorded unless `channelCount` asks for another.
+   */
+  defaultConfig: InputConfig;
+  /**
+   * The channel counts the device supports, in ascending order, e.g. `[1, 2]` for a mic that can record in mono or stereo.
+   */
+  channelCounts: number[];
+  configs: InputConfigRange[];
+}
+
+export interface InputConfig {
+  channels: number;
+  sampleRate: number;
+  /**
+   * As named by cpal, e.g. `i16` or `f32`.
+   */
+  sampleFormat: string;
+}
+
+export interface InputConfigRange {
+  channels: number;
+  minSampleRate: number;
+  maxSampleRate: number;
+  sampleFormat: string;
+}
+
+export interface RecordingRedirected {
+  path: string;
+  previousPath: string | null;
+  /**
+   * The frames captured after the memory buffer was full, which are lost.
+   */
+  lostFrames: number;
+}
+
+export interface AudioDroppedPayload {
+  /**
+   * The number of frames dropped since the previous event.
+   */
+  frames: number;
+}
+
+export interface MicMuteChangedPayload {
+  muted: boolean;
+  paused: boolean;
+}
+
+/**
+ * Whether the app may use the microphone. `notDetermined` means the user has not been asked yet, on macOS; `unknown` means the platform does not expose the permission.
+ */
+export type PermissionState = "granted" | "denied" | "notDetermined" | "unknown";
+
+export interface RecordingExpiredPayload {
+  path: string;
+}
+
+export interface MicPermissionChangedPayload {
+  state: PermissionState;
+  previous: PermissionState;
+}
+
+export type AuditOperation =
+  | "startRecording"
+  | "stopRecording"
+  | "forceStop"
+  | "resumeSession"
+  | "disableRecorder"
+  | "enableRecorder"
+  | "shutdown";
+
+export interface AuditEntry {
+  timestamp: string;
+  user: string;
+  webview: string | null;
+  operation: AuditOperation;
+  device: string | null;
+  durationMs: number | null;
+  path: string | null;
+  error: string | null;
+}
+
+export interface NamespaceUsage {
+  /**
+   * The namespace, or `null` for recordings made without one.
+   */
+  namespace: string | null;
+  usedBytes: number;
+  quotaBytes: number | null;
+  recordings: number;
+}
+
 /**
  * Starts recording audio.
  *
+ * @param options Options for the recording.
+ * @param onAudio Called with a copy of the written audio, as interleaved little-endian `Float32` samples.
+ *
+ * @returns Returns the recording that was started, or the one started before with the same `idempotencyKey`.
+ *
  * @example
  * ```
  * import { startRecording } from 'tauri-plugin-mic-recorder-api';
@@ -17,12 +1081,29 @@ export const COMMAND = {
  * });
  * ```
  */
-export const startRecording = () => {
-  return invoke(COMMAND.START_RECORDING);
+export const startRecording = (
+  options?: RecordingOptions,
+  onAudio?: (chunk: ArrayBuffer) => void,
+) => {
+  let channel: Channel<ArrayBuffer> | undefined;
+
+  if (onAudio) {
+    channel = new Channel<ArrayBuffer>();
+    channel.onmessage = onAudio;
+  }
+
+  return invoke<StartedRecording>(COMMAND.START_RECORDING, {
+    options,
+    onAudio: channel,
+  });
 };
 
 /**
- * Stops recording audio.
+ * Stops recording audio. Resolves as soon as capture has stopped; the file is complete once `recording-finalized` is emitted.
+ *
+ * Rejects with `Timeout` if the stream or writer does not shut down in time, leaving the recording in progress so that `forceStop` can abandon it.
+ *
+ * @param options Options for stopping the recording.
  *
  * @returns Returns the path where the recording file is stored.
  *
@@ -34,6 +1115,1041 @@ export const startRecording = () => {
  * console.log("Recording saved at:", savePath);
  * ```
  */
-export const stopRecording = () => {
-  return invoke<string>(COMMAND.STOP_RECORDING);
+export const stopRecording = (options?: StopOptions) => {
+  return invoke<string>(COMMAND.STOP_RECORDING, { options });
+};
+
+/**
+ * Abandons the recording in progress without waiting for its stream or writer, e.g. after `stopRecording` timed out because of a hung driver. Whatever audio had been flushed to disk is kept.
+ *
+ * @returns Returns the path where the recording file is stored.
+ *
+ * @example
+ * ```
+ * import { forceStop, stopRecording } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const savePath = await stopRecording({ timeoutMs: 2000 }).catch(() => forceStop());
+ * console.log("Recording saved at:", savePath);
+ * ```
+ */
+export const forceStop = () => {
+  return invoke<string>(COMMAND.FORCE_STOP);
+};
+
+/**
+ * Shuts the recorder down: stops the recording in progress, waits until every stopped recording has been written to disk, and ends all background threads. From then on `startRecording` rejects with `ShutDown`. A recording that does not stop within `timeoutMs` is abandoned as with `forceStop`.
+ *
+ * Rejects with `Timeout` if background threads are still running after `timeoutMs`.
+ *
+ * @param options Options for stopping the recording, whose `timeoutMs` also bounds the wait for the background threads.
+ *
+ * @example
+ * ```
+ * import { shutdown } from 'tauri-plugin-mic-recorder-api';
+ *
+ * afterEach(() => shutdown({ timeoutMs: 2000 }));
+ * ```
+ */
+export const shutdown = (options?: StopOptions) => {
+  return invoke(COMMAND.SHUTDOWN, { options });
+};
+
+/**
+ * Gets the audit log of all recorder operations, oldest first.
+ *
+ * @returns Returns every operation that has been recorded in the audit log.
+ *
+ * @example
+ * ```
+ * import { getAuditLog } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const entries = await getAuditLog();
+ * console.log("Audit log:", entries);
+ * ```
+ */
+export const getAuditLog = () => {
+  return invoke<AuditEntry[]>(COMMAND.GET_AUDIT_LOG);
+};
+
+/**
+ * Disables the recorder, so that every start attempt fails with `RecorderDisabled` until `enableRecorder` is called. A recording that is in progress is stopped.
+ *
+ * @example
+ * ```
+ * import { disableRecorder } from 'tauri-plugin-mic-recorder-api';
+ *
+ * await disableRecorder();
+ * ```
+ */
+export const disableRecorder = () => {
+  return invoke(COMMAND.DISABLE_RECORDER);
+};
+
+/**
+ * Enables the recorder again after `disableRecorder`.
+ *
+ * @example
+ * ```
+ * import { enableRecorder } from 'tauri-plugin-mic-recorder-api';
+ *
+ * await enableRecorder();
+ * ```
+ */
+export const enableRecorder = () => {
+  return invoke(COMMAND.ENABLE_RECORDER);
+};
+
+/**
+ * Checks whether a recording carries the watermark embedded with `key`.
+ *
+ * @param path The path of the recording, relative to the save directory or allowed in the fs scope.
+ * @param key The key the watermark was embedded with.
+ *
+ * @returns Returns whether the watermark was found, and how confidently.
+ *
+ * @example
+ * ```
+ * import { detectWatermark } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const { detected } = await detectWatermark(savePath, "my-app-secret");
+ * console.log("Recorded by this app:", detected);
+ * ```
+ */
+export const detectWatermark = (path: string, key: string) => {
+  return invoke<WatermarkDetection>(COMMAND.DETECT_WATERMARK, { path, key });
+};
+
+/**
+ * Applies channel manipulation to a finished recording, writing the result next to it with a `-channels` suffix.
+ *
+ * @param path The path of the recording, relative to the save directory or allowed in the fs scope.
+ * @param options The channel manipulation to apply.
+ *
+ * @returns Returns the path of the processed file.
+ *
+ * @example
+ * ```
+ * import { processChannels } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const processedPath = await processChannels(savePath, { swap: true });
+ * console.log("Processed file:", processedPath);
+ * ```
+ */
+export const processChannels = (path: string, options: ChannelOptions) => {
+  return invoke<string>(COMMAND.PROCESS_CHANNELS, { path, options });
+};
+
+/**
+ * Gets the metadata stored in a recording, such as its BWF time reference and markers.
+ *
+ * @param path The path of the recording.
+ *
+ * @returns Returns the metadata read from the recording.
+ *
+ * @example
+ * ```
+ * import { getRecordingMetadata } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const { markers } = await getRecordingMetadata(savePath);
+ * console.log("Markers:", markers);
+ * ```
+ */
+export const getRecordingMetadata = (path: string) => {
+  return invoke<RecordingMetadata>(COMMAND.GET_RECORDING_METADATA, { path });
+};
+
+/**
+ * Detects long silences in a finished recording and stores a chapter marker wherever the audio resumes, replacing earlier chapter markers.
+ *
+ * @param path The path of the recording.
+ * @param options How long and how quiet a silence must be to start a new chapter.
+ *
+ * @returns Returns all markers of the recording.
+ *
+ * @example
+ * ```
+ * import { detectChapters } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const markers = await detectChapters(savePath, { minSilenceMs: 5000 });
+ * console.log("Chapters:", markers);
+ * ```
+ */
+export const detectChapters = (path: string, options?: ChapterOptions) => {
+  return invoke<Marker[]>(COMMAND.DETECT_CHAPTERS, { path, options });
+};
+
+export interface M4aOptions {
+  /**
+   * Write an `.m4b` audiobook instead of an `.m4a` file, which podcast and audiobook apps open with chapter navigation and resume.
+   */
+  audiobook?: boolean;
+  /**
+   * The title shown by players, and of the chapter before the first marker. Defaults to the file name.
+   */
+  title?: string;
+}
+
+/**
+ * Exports a finished recording next to it as an M4A, or an M4B audiobook, with a chapter starting at each of its markers.
+ *
+ * @param path The path of the recording.
+ * @param options How the file is written.
+ *
+ * @returns Returns the path of the exported file.
+ *
+ * @example
+ * ```
+ * import { detectChapters, exportM4a } from 'tauri-plugin-mic-recorder-api';
+ *
+ * await detectChapters(savePath);
+ * const audiobookPath = await exportM4a(savePath, { audiobook: true, title: "Lecture 3" });
+ * console.log("Exported file:", audiobookPath);
+ * ```
+ */
+export const exportM4a = (path: string, options?: M4aOptions) => {
+  return invoke<string>(COMMAND.EXPORT_M4A, { path, options });
+};
+
+export interface Caption {
+  startMs: number;
+  endMs: number;
+  text: string;
+}
+
+export interface CaptionOptions {
+  /**
+   * Add a track showing the time every second: the time of day if the recording has a BWF time reference, and otherwise the time since its start. Defaults to `true`.
+   */
+  timestamps?: boolean;
+  /**
+   * Add a track showing the label of each marker until the next one. Defaults to `true`.
+   */
+  markers?: boolean;
+  /**
+   * Timed captions to add as a track of their own, e.g. the segments of a transcript.
+   */
+  captions?: Caption[];
+}
+
+/**
+ * Exports a finished recording next to it as an MP4 with subtitle tracks for a running timestamp, its markers and any timed captions.
+ *
+ * @param path The path of the recording.
+ * @param options The subtitle tracks to add.
+ *
+ * @returns Returns the path of the exported file.
+ *
+ * @example
+ * ```
+ * import { exportCaptioned } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const reviewPath = await exportCaptioned(savePath, {
+ *   captions: [{ startMs: 0, endMs: 4200, text: "Welcome back." }],
+ * });
+ * console.log("Exported file:", reviewPath);
+ * ```
+ */
+export const exportCaptioned = (path: string, options?: CaptionOptions) => {
+  return invoke<string>(COMMAND.EXPORT_CAPTIONED, { path, options });
+};
+
+export interface TempoOptions {
+  /**
+   * Defaults to `60`.
+   */
+  minBpm?: number;
+  /**
+   * Defaults to `200`.
+   */
+  maxBpm?: number;
+}
+
+export interface TempoDetection {
+  /**
+   * The estimated tempo, or `null` if the recording has no steady beat.
+   */
+  bpm: number | null;
+  /**
+   * The positions of the beats in sample frames.
+   */
+  beats: number[];
+  /**
+   * How strongly the onsets repeat at the tempo, from 0 to 1.
+   */
+  confidence: number;
+}
+
+/**
+ * Estimates the tempo of a finished recording and where its beats fall.
+ *
+ * @param path The path of the recording.
+ * @param options The range of tempos to consider.
+ *
+ * @returns Returns the tempo in BPM, if there is a steady beat, and the beat positions.
+ *
+ * @example
+ * ```
+ * import { detectTempo } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const { bpm, beats } = await detectTempo(savePath);
+ * console.log("Tempo:", bpm, "beats:", beats.length);
+ * ```
+ */
+export const detectTempo = (path: string, options?: TempoOptions) => {
+  return invoke<TempoDetection>(COMMAND.DETECT_TEMPO, { path, options });
+};
+
+export interface AudioFingerprint {
+  durationMs: number;
+  /**
+   * One 32-bit value per eighth of a second.
+   */
+  fingerprint: number[];
+}
+
+/**
+ * Computes an acoustic fingerprint of a finished recording, so that copies of it can be recognized even after re-encoding.
+ *
+ * @param path The path of the recording.
+ *
+ * @returns Returns the fingerprint and the duration of the recording.
+ *
+ * @example
+ * ```
+ * import { fingerprintRecording } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const { fingerprint } = await fingerprintRecording(savePath);
+ * ```
+ */
+export const fingerprintRecording = (path: string) => {
+  return invoke<AudioFingerprint>(COMMAND.FINGERPRINT_RECORDING, { path });
+};
+
+/**
+ * Compares two fingerprints from `fingerprintRecording`.
+ *
+ * @returns Returns how alike the recordings are, from 0 to 1. Unrelated audio scores around 0.6.
+ *
+ * @example
+ * ```
+ * import { compareFingerprints, fingerprintRecording } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const a = await fingerprintRecording(firstPath);
+ * const b = await fingerprintRecording(secondPath);
+ * const isDuplicate = (await compareFingerprints(a.fingerprint, b.fingerprint)) > 0.85;
+ * ```
+ */
+export const compareFingerprints = (a: number[], b: number[]) => {
+  return invoke<number>(COMMAND.COMPARE_FINGERPRINTS, { a, b });
+};
+
+/**
+ * Adds a marker at the current position of the recording in progress.
+ *
+ * @param label The label of the marker. Defaults to `Bookmark N`.
+ *
+ * @returns Returns the marker that was added.
+ *
+ * @example
+ * ```
+ * import { addMarker } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const marker = await addMarker("Question");
+ * console.log("Marker added at frame:", marker.frame);
+ * ```
+ */
+export const addMarker = (label?: string) => {
+  return invoke<Marker>(COMMAND.ADD_MARKER, { label });
+};
+
+/**
+ * Lists the input devices along with the channel counts, sample rates and sample formats each can record in. Devices that cannot be opened are left out.
+ *
+ * @returns Returns the input devices.
+ *
+ * @example
+ * ```
+ * import { listInputDevices, startRecording } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const devices = await listInputDevices();
+ * const mic = devices.find((device) => device.isDefault);
+ * if (mic?.channelCounts.includes(2)) {
+ *   await startRecording({ channelCount: 2 });
+ * }
+ * ```
+ */
+export const listInputDevices = () => {
+  return invoke<InputDevice[]>(COMMAND.LIST_INPUT_DEVICES);
+};
+
+/**
+ * Gets what the recorder is doing. `finalizing` means capture has stopped but a recording is still being written to disk.
+ *
+ * @returns Returns the state of the recorder.
+ *
+ * @example
+ * ```
+ * import { getRecorderState } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const state = await getRecorderState();
+ * console.log("Recorder is:", state);
+ * ```
+ */
+export const getRecorderState = () => {
+  return invoke<RecorderState>(COMMAND.GET_RECORDER_STATE);
+};
+
+/**
+ * Gets a compact snapshot of the recorder, for a window to render the right state as soon as it opens, e.g. in the middle of a recording. The same snapshot is broadcast as `recorder-snapshot` to every window whenever it changes.
+ *
+ * @returns Returns the snapshot last broadcast.
+ *
+ * @example
+ * ```
+ * import { listen } from '@tauri-apps/api/event';
+ * import { getRecorderSnapshot, EVENT, type RecorderSnapshot } from 'tauri-plugin-mic-recorder-api';
+ *
+ * let latest: RecorderSnapshot | undefined;
+ * const render = (snapshot: RecorderSnapshot) => {
+ *   if (latest && latest.revision >= snapshot.revision) return;
+ *   latest = snapshot;
+ *   console.log("Recorder is:", snapshot.state);
+ * };
+ *
+ * await listen<RecorderSnapshot>(EVENT.RECORDER_SNAPSHOT, (event) => render(event.payload));
+ * render(await getRecorderSnapshot());
+ * ```
+ */
+export const getRecorderSnapshot = () => {
+  return invoke<RecorderSnapshot>(COMMAND.GET_RECORDER_SNAPSHOT);
+};
+
+/**
+ * Gets which optional features were compiled into the plugin and can be used on this platform, so the UI can hide what would only fail.
+ *
+ * @returns Returns whether each optional feature is compiled in and usable.
+ *
+ * @example
+ * ```
+ * import { getCapabilities } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const { globalShortcut } = await getCapabilities();
+ * console.log("Bookmark shortcut available:", globalShortcut.usable);
+ * ```
+ */
+export const getCapabilities = () => {
+  return invoke<Capabilities>(COMMAND.GET_CAPABILITIES);
+};
+
+/**
+ * Gets whether the app may use the microphone, without prompting the user. Windows and macOS only; elsewhere the state is `unknown`.
+ *
+ * @returns Returns the permission state.
+ *
+ * @example
+ * ```
+ * import { getMicPermission } from 'tauri-plugin-mic-recorder-api';
+ *
+ * if (await getMicPermission() === "denied") {
+ *   console.log("Microphone access is turned off in the system settings");
+ * }
+ * ```
+ */
+export const getMicPermission = () => {
+  return invoke<PermissionState>(COMMAND.GET_MIC_PERMISSION);
+};
+
+/**
+ * Asks the user to let the app open a USB device, e.g. an audio interface listed with `isUsb`, waiting for them to answer the dialog. Only Android asks.
+ *
+ * @param device The name of the device, as listed by `listInputDevices`.
+ * @param timeoutMs How long to wait for the user. Defaults to 30 seconds.
+ * @returns Returns `denied` if the user declined or did not answer in time, and `unknown` on platforms other than Android.
+ *
+ * @example
+ * ```
+ * import { listInputDevices, requestUsbPermission, startRecording } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const usb = (await listInputDevices()).find((device) => device.isUsb);
+ * if (usb && (await requestUsbPermission(usb.name)) !== "denied") {
+ *   await startRecording({ preferredDevices: [usb.name] });
+ * }
+ * ```
+ */
+export const requestUsbPermission = (device: string, timeoutMs?: number) => {
+  return invoke<PermissionState>(COMMAND.REQUEST_USB_PERMISSION, { device, timeoutMs });
+};
+
+/**
+ * Continues the recording in progress in a new file, e.g. after `recording-io-error` reported that the disk is full. The audio held in memory since writing failed is written first.
+ *
+ * @param path The path of the new file, relative to the save directory or allowed in the fs scope.
+ *
+ * @returns Returns the new file, and how much audio could not be held in memory.
+ *
+ * @example
+ * ```
+ * import { listen } from '@tauri-apps/api/event';
+ * import { EVENT, redirectRecording } from 'tauri-plugin-mic-recorder-api';
+ *
+ * listen(EVENT.RECORDING_IO_ERROR, async () => {
+ *   const { lostFrames } = await redirectRecording("/Volumes/Backup/recording.wav");
+ *   console.log("Frames lost:", lostFrames);
+ * });
+ * ```
+ */
+export const redirectRecording = (path: string) => {
+  return invoke<RecordingRedirected>(COMMAND.REDIRECT_RECORDING, { path });
+};
+
+/**
+ * Copies the audio recorded so far into a separate, playable file next to the recording, without interrupting it. Markers added so far are included.
+ *
+ * @returns Returns the path of the snapshot.
+ *
+ * @example
+ * ```
+ * import { snapshotRecording } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const snapshotPath = await snapshotRecording();
+ * console.log("Recording so far:", snapshotPath);
+ * ```
+ */
+export const snapshotRecording = () => {
+  return invoke<string>(COMMAND.SNAPSHOT_RECORDING);
+};
+
+/**
+ * Finishes the current file and goes on recording into a new one in the save directory, without losing any audio in between. The finished file is finalized in the background, like after `stopRecording`, and `recording-finalized` is emitted for it.
+ *
+ * @returns Returns the path of the finished file.
+ *
+ * @example
+ * ```
+ * import { splitRecording } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const finishedPath = await splitRecording();
+ * console.log("Finished part:", finishedPath);
+ * ```
+ */
+export const splitRecording = () => {
+  return invoke<string>(COMMAND.SPLIT_RECORDING);
+};
+
+/**
+ * Gets the session interrupted by a crash shortly before the app was started, if it was recorded with `resume` set.
+ *
+ * @returns Returns the recorded segments of the session, or `null` if there is none.
+ *
+ * @example
+ * ```
+ * import { getResumableSession, resumeSession } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const session = await getResumableSession();
+ * if (session && confirm("Continue the interrupted recording?")) {
+ *   await resumeSession();
+ * }
+ * ```
+ */
+export const getResumableSession = () => {
+  return invoke<ResumableSession | null>(COMMAND.GET_RESUMABLE_SESSION);
+};
+
+/**
+ * Resumes the session interrupted by a crash, recording into a new segment next to its previous ones with the options it was started with. `align` and `overdub` are left out, as their timing refers to the start of the session.
+ *
+ * @param onAudio Called with a copy of the written audio, as interleaved little-endian `Float32` samples.
+ * @param streamKey The key of an encrypted stream, which is not kept with the session.
+ * @param watermarkKey The key of a watermark, which is not kept with the session.
+ *
+ * @returns Returns the path of the new segment.
+ */
+export const resumeSession = (
+  onAudio?: (chunk: ArrayBuffer) => void,
+  streamKey?: string,
+  watermarkKey?: string
+) => {
+  let channel: Channel<ArrayBuffer> | undefined;
+
+  if (onAudio) {
+    channel = new Channel<ArrayBuffer>();
+    channel.onmessage = onAudio;
+  }
+
+  return invoke<string>(COMMAND.RESUME_SESSION, {
+    onAudio: channel,
+    streamKey,
+    watermarkKey,
+  });
+};
+
+/**
+ * Discards the session interrupted by a crash, so it is no longer offered. Its segments are kept.
+ */
+export const discardSession = () => {
+  return invoke(COMMAND.DISCARD_SESSION);
+};
+
+/**
+ * Lists the recordings in the index one page at a time, sorted by when they were recorded, their duration or their size.
+ *
+ * @param options The order and page to return.
+ *
+ * @returns Returns the page, and how many recordings there are in all.
+ *
+ * @example
+ * ```
+ * import { listRecordings } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const { recordings, total } = await listRecordings({ sortBy: "size", limit: 100 });
+ * ```
+ */
+export const listRecordings = (options?: ListOptions) => {
+  return invoke<RecordingPage>(COMMAND.LIST_RECORDINGS, { options });
+};
+
+export interface DuplicateOptions {
+  /**
+   * Also compare the fingerprints of recordings with about the same duration, finding copies that were converted or trimmed, not just identical files. This decodes every such recording and takes longer.
+   */
+  fingerprints?: boolean;
+  /**
+   * The fingerprint similarity from 0 to 1 at which recordings count as copies. Defaults to `0.9`; unrelated audio scores around 0.6.
+   */
+  minSimilarity?: number;
+  /**
+   * How far apart the durations of copies may be, in milliseconds, when comparing fingerprints. Defaults to `2000`.
+   */
+  maxDurationDifferenceMs?: number;
+}
+
+export interface DuplicateGroup {
+  /**
+   * Oldest first, which is usually the one to keep.
+   */
+  recordings: RecordingInfo[];
+  /**
+   * Whether the files are byte for byte the same, rather than only sounding alike.
+   */
+  identical: boolean;
+  /**
+   * The storage freed by deleting all but the first recording.
+   */
+  reclaimableBytes: number;
+}
+
+/**
+ * Finds recordings in the index that are likely copies of each other. Nothing is deleted.
+ *
+ * @param options How copies are recognized.
+ *
+ * @returns Returns the groups of copies, those freeing the most storage first.
+ *
+ * @example
+ * ```
+ * import { findDuplicates } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const groups = await findDuplicates({ fingerprints: true });
+ * console.log("Reclaimable bytes:", groups.reduce((sum, x) => sum + x.reclaimableBytes, 0));
+ * ```
+ */
+export const findDuplicates = (options?: DuplicateOptions) => {
+  return invoke<DuplicateGroup[]>(COMMAND.FIND_DUPLICATES, { options });
+};
+
+/**
+ * Searches the index for recordings by text and filters, newest first.
+ *
+ * @param query What to look for, and which page of the matches to return.
+ *
+ * @returns Returns the page of matches, and how many there are in all.
+ *
+ * @example
+ * ```
+ * import { searchRecordings } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const page = await searchRecordings({ text: "budget", minDurationMs: 60000, limit: 20 });
+ * const next = page.nextOffset !== null
+ *   ? await searchRecordings({ text: "budget", minDurationMs: 60000, limit: 20, offset: page.nextOffset })
+ *   : null;
+ * ```
+ */
+export const searchRecordings = (query: SearchQuery = {}) => {
+  return invoke<RecordingPage>(COMMAND.SEARCH_RECORDINGS, { query });
+};
+
+/**
+ * Updates the title, tags, transcript or custom metadata of any number of recordings in the index at once. Rejects with `invalidOptions.unknownRecording` if a recording is not in the index, in which case none is updated.
+ *
+ * @param updates The changes to make, one per recording.
+ *
+ * @returns Returns the updated recordings, in the order of `updates`.
+ *
+ * @example
+ * ```
+ * import { updateRecordings } from 'tauri-plugin-mic-recorder-api';
+ *
+ * await updateRecordings(selected.map((path) => ({ path, addTags: ["interview"] })));
+ * ```
+ */
+export const updateRecordings = (updates: RecordingUpdate[]) => {
+  return invoke<RecordingInfo[]>(COMMAND.UPDATE_RECORDINGS, { updates });
+};
+
+/**
+ * Records the outcome of the app's uploads in the index, for any number of recordings at once. Emits `sync-state-changed` for each of them. Rejects with `invalidOptions.unknownRecording` if a recording is not in the index, in which case none is updated.
+ *
+ * @param updates The new sync states, one per recording.
+ *
+ * @returns Returns the updated recordings, in the order of `updates`.
+ *
+ * @example
+ * ```
+ * import { searchRecordings, updateSyncStates } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const { recordings } = await searchRecordings({ syncStatus: "pending" });
+ * for (const { path } of recordings) {
+ *   try {
+ *     const remote = await upload(path);
+ *     await updateSyncStates([{ path, status: "uploaded", remote }]);
+ *   } catch (error) {
+ *     await updateSyncStates([{ path, status: "failed", error: String(error) }]);
+ *   }
+ * }
+ * ```
+ */
+export const updateSyncStates = (updates: SyncUpdate[]) => {
+  return invoke<RecordingInfo[]>(COMMAND.UPDATE_SYNC_STATES, { updates });
+};
+
+export type WebhookEvent = "finalized" | "uploaded";
+
+/**
+ * An HTTP endpoint that is sent a JSON request about new recordings.
+ */
+export interface WebhookOptions {
+  /**
+   * An `https` URL that is sent a `POST` request, or an `http` one on this machine.
+   */
+  url: string;
+  /**
+   * The events to call the webhook for. Defaults to both.
+   */
+  events?: WebhookEvent[];
+  /**
+   * The JSON body, in which strings can refer to the event and the recording's metadata as `{{event}}`, `{{path}}`, `{{sync.remote}}` and so on. A string that is a single reference takes the value as is, e.g. a number. Defaults to `{ event, recording }`.
+   */
+  template?: unknown;
+  /**
+   * Extra request headers, e.g. for authorization.
+   */
+  headers?: Record<string, string>;
+  /**
+   * How long to wait for the endpoint on each attempt. Defaults to `10000`.
+   */
+  timeoutMs?: number;
+}
+
+/**
+ * Sets the webhook that is called after a recording has been finalized or uploaded, replacing the previous one, or removes it. It is kept across restarts. Rejects with `invalidOptions.webhookUrl` or `invalidOptions.webhookHeader` if it cannot be called.
+ *
+ * @param options The webhook, or `null` to remove it.
+ *
+ * @example
+ * ```
+ * import { setWebhook } from 'tauri-plugin-mic-recorder-api';
+ *
+ * await setWebhook({
+ *   url: "https://archive.example.com/hooks/recordings",
+ *   events: ["uploaded"],
+ *   template: { text: "New recording {{title}}", location: "{{sync.remote}}", durationMs: "{{durationMs}}" },
+ * });
+ * ```
+ */
+export const setWebhook = (options: WebhookOptions | null) => {
+  return invoke<void>(COMMAND.SET_WEBHOOK, { options });
+};
+
+/**
+ * Gets the webhook set with `setWebhook`.
+ *
+ * @returns Returns the webhook, or `null` if there is none.
+ */
+export const getWebhook = () => {
+  return invoke<WebhookOptions | null>(COMMAND.GET_WEBHOOK);
+};
+
+/**
+ * A time of day during which uploads may run, in local time.
+ */
+export interface UploadWindow {
+  /**
+   * As `HH:MM`, e.g. `01:00`.
+   */
+  start: string;
+  /**
+   * As `HH:MM`. An end before the start crosses midnight, e.g. `22:00` to `06:00`.
+   */
+  end: string;
+}
+
+/**
+ * When and how fast the uploader set up with the plugin uploads recordings.
+ */
+export interface UploadSchedule {
+  /**
+   * Limits the upload rate. Unlimited by default.
+   */
+  maxBytesPerSecond?: number | null;
+  /**
+   * Only uploads over connections that are not metered, such as Wi-Fi or Ethernet rather than cellular. Where the plugin cannot tell, uploads wait on Android and iOS and go ahead on desktops.
+   */
+  wifiOnly?: boolean;
+  /**
+   * The times of day uploads may run. Always when empty.
+   */
+  windows?: UploadWindow[];
+  /**
+   * How often a failed upload is retried before it is left to the app. Defaults to `3`.
+   */
+  maxRetries?: number;
+}
+
+/**
+ * Sets when and how fast recordings are uploaded. It is kept across restarts and applies to uploads in progress within about a second. Rejects with `invalidOptions.uploadWindow` if a window is not made of times of day as `HH:MM`.
+ *
+ * @param schedule The schedule.
+ *
+ * @example
+ * ```
+ * import { setUploadSchedule } from 'tauri-plugin-mic-recorder-api';
+ *
+ * await setUploadSchedule({
+ *   maxBytesPerSecond: 512 * 1024,
+ *   wifiOnly: true,
+ *   windows: [{ start: "22:00", end: "06:00" }],
+ * });
+ * ```
+ */
+export const setUploadSchedule = (schedule: UploadSchedule) => {
+  return invoke<void>(COMMAND.SET_UPLOAD_SCHEDULE, { schedule });
+};
+
+/**
+ * Gets the schedule set with `setUploadSchedule`.
+ *
+ * @returns Returns the schedule, or the default one if none was set.
+ */
+export const getUploadSchedule = () => {
+  return invoke<Required<UploadSchedule>>(COMMAND.GET_UPLOAD_SCHEDULE);
+};
+
+/**
+ * Sets when a recording is deleted, or keeps it for good. Only recordings in the save directory can expire.
+ *
+ * @param path The path of the recording.
+ * @param expiresAt Unix time in milliseconds, or `null` to keep the recording.
+ *
+ * @example
+ * ```
+ * import { setRecordingExpiry } from 'tauri-plugin-mic-recorder-api';
+ *
+ * await setRecordingExpiry(savePath, Date.now() + 30 * 24 * 60 * 60 * 1000);
+ * ```
+ */
+export const setRecordingExpiry = (path: string, expiresAt: number | null) => {
+  return invoke(COMMAND.SET_RECORDING_EXPIRY, { path, expiresAt });
+};
+
+/**
+ * Gets when a recording is deleted.
+ *
+ * @param path The path of the recording.
+ *
+ * @returns Returns Unix time in milliseconds, or `null` if the recording does not expire.
+ */
+export const getRecordingExpiry = (path: string) => {
+  return invoke<number | null>(COMMAND.GET_RECORDING_EXPIRY, { path });
+};
+
+/**
+ * Deletes the recordings that have expired right away, instead of waiting for the hourly sweep.
+ *
+ * @returns Returns the paths of the deleted recordings.
+ */
+export const deleteExpiredRecordings = () => {
+  return invoke<string[]>(COMMAND.DELETE_EXPIRED_RECORDINGS);
+};
+
+/**
+ * Writes a recording template to a JSON file, so the same setup can be imported on another machine.
+ *
+ * @param path The path of the JSON file, relative to the save directory or allowed in the fs scope.
+ * @param template The template to write.
+ *
+ * @example
+ * ```
+ * import { exportTemplate } from 'tauri-plugin-mic-recorder-api';
+ *
+ * await exportTemplate("/shared/podcast.json", {
+ *   name: "Podcast",
+ *   options: { channels: { swap: true }, compressSilence: {} },
+ * });
+ * ```
+ */
+export const exportTemplate = (path: string, template: RecordingTemplate) => {
+  return invoke(COMMAND.EXPORT_TEMPLATE, { path, template });
+};
+
+/**
+ * Reads a recording template written by `exportTemplate`. Rejects with `invalidOptions.templateVersion` if it is from a newer version of the plugin.
+ *
+ * @param path The path of the JSON file.
+ * @param watermarkKey The key of a watermark, which is not written to templates.
+ *
+ * @returns Returns the template, with defaults for the options it lacks.
+ *
+ * @example
+ * ```
+ * import { importTemplate, startRecording } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const template = await importTemplate("/shared/podcast.json");
+ * await startRecording(template.options);
+ * ```
+ */
+export const importTemplate = (path: string, watermarkKey?: string) => {
+  return invoke<RecordingTemplate>(COMMAND.IMPORT_TEMPLATE, { path, watermarkKey });
+};
+
+/**
+ * Sets the most storage the recordings of a namespace may take up. Once it is used up, `startRecording` rejects with `QuotaExceeded`; a recording in progress is never cut short.
+ *
+ * @param namespace The namespace, e.g. a user or workspace id.
+ * @param quotaBytes The quota in bytes, or `null` to remove it.
+ *
+ * @example
+ * ```
+ * import { setNamespaceQuota } from 'tauri-plugin-mic-recorder-api';
+ *
+ * await setNamespaceQuota("user-42", 500 * 1024 * 1024);
+ * ```
+ */
+export const setNamespaceQuota = (
+  namespace: string,
+  quotaBytes: number | null
+) => {
+  return invoke(COMMAND.SET_NAMESPACE_QUOTA, { namespace, quotaBytes });
+};
+
+/**
+ * Gets how much storage the recordings of a namespace take up.
+ *
+ * @param namespace The namespace, or omitted for recordings made without one.
+ *
+ * @example
+ * ```
+ * import { getNamespaceUsage } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const { usedBytes, quotaBytes } = await getNamespaceUsage("user-42");
+ * console.log("Used:", usedBytes, "of", quotaBytes);
+ * ```
+ */
+export const getNamespaceUsage = (namespace?: string) => {
+  return invoke<NamespaceUsage>(COMMAND.GET_NAMESPACE_USAGE, { namespace });
+};
+
+/**
+ * Gets the usage of every namespace that has recordings or a quota, preceded by that of the recordings made without one.
+ *
+ * @example
+ * ```
+ * import { listNamespaceUsage } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const usages = await listNamespaceUsage();
+ * console.log("Namespaces:", usages);
+ * ```
+ */
+export const listNamespaceUsage = () => {
+  return invoke<NamespaceUsage[]>(COMMAND.LIST_NAMESPACE_USAGE);
+};
+
+export interface ManifestEntry {
+  /**
+   * The path relative to the folder, separated by `/` on every platform.
+   */
+  path: string;
+  sizeBytes: number;
+  /**
+   * The SHA-256 hash of the file as lowercase hex.
+   */
+  sha256: string;
+  /**
+   * The duration of WAV files.
+   */
+  durationMs: number | null;
+}
+
+export interface ChecksumManifest {
+  /**
+   * Unix time in milliseconds at which the manifest was created.
+   */
+  createdAt: number;
+  entries: ManifestEntry[];
+}
+
+export interface ManifestVerification {
+  /**
+   * The number of files that still match.
+   */
+  verified: number;
+  /**
+   * Files in the manifest that are gone.
+   */
+  missing: string[];
+  /**
+   * Files whose content changed, e.g. through bit rot or a bad copy.
+   */
+  changed: string[];
+  /**
+   * Files that are not in the manifest.
+   */
+  added: string[];
+}
+
+/**
+ * Writes a manifest with the size, SHA-256 hash and duration of every file in a recordings folder to `checksums.json` in it.
+ *
+ * @param directory The folder to describe, including its subfolders.
+ *
+ * @returns Returns the manifest that was written.
+ *
+ * @example
+ * ```
+ * import { createManifest } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const manifest = await createManifest(archiveDir);
+ * console.log("Files:", manifest.entries.length);
+ * ```
+ */
+export const createManifest = (directory: string) => {
+  return invoke<ChecksumManifest>(COMMAND.CREATE_MANIFEST, { directory });
+};
+
+/**
+ * Hashes the files of a folder again and compares them to its `checksums.json`.
+ *
+ * @param directory The folder with the manifest.
+ *
+ * @returns Returns the files that are missing, changed or new.
+ *
+ * @example
+ * ```
+ * import { verifyManifest } from 'tauri-plugin-mic-recorder-api';
+ *
+ * const { missing, changed } = await verifyManifest(backupDir);
+ * console.log("Damaged files:", [...missing, ...changed]);
+ * ```
+ */
+export const verifyManifest = (directory: string) => {
+  return invoke<ManifestVerification>(COMMAND.VERIFY_MANIFEST, { directory });
 };
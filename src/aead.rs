@@ -0,0 +1,147 @@
+//! ChaCha20-Poly1305 as specified in RFC 8439, for sealing the audio
+//! streamed to the frontend so that whatever relays it only sees ciphertext.
+
+use crate::error::{Error, Message, Result};
+use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, KeyInit};
+
+/// The length of the nonce every sealed chunk begins with.
+pub(crate) const NONCE_LEN: usize = 12;
+
+/// The length of the tag every sealed chunk ends with.
+pub(crate) const TAG_LEN: usize = 16;
+
+/// Seals the chunks of one stream under an app-provided key. Every chunk
+/// gets its own nonce: a random prefix for the stream followed by a
+/// counter, so chunks can never share a nonce, even across recordings.
+pub(crate) struct Sealer {
+    cipher: ChaCha20Poly1305,
+    prefix: [u8; 8],
+    counter: u32,
+}
+
+impl Sealer {
+    /// Creates a sealer for a key given as 64 hex digits.
+    pub fn new(key: &str) -> Result<Self> {
+        let invalid = || {
+            Error::InvalidOptions(Message::new(
+                "invalidOptions.streamKey",
+                "The stream key must be 32 bytes as 64 hex digits.",
+            ))
+        };
+
+        // `from_str_radix` alone would also take a sign, as in "+f".
+        if key.len() != 64 || !key.bytes().all(|x| x.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let mut bytes = [0; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&key[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+
+        let mut sealer = Self {
+            cipher: ChaCha20Poly1305::new(&bytes.into()),
+            prefix: [0; 8],
+            counter: 0,
+        };
+        sealer.renew()?;
+
+        Ok(sealer)
+    }
+
+    fn renew(&mut self) -> Result<()> {
+        getrandom::fill(&mut self.prefix)
+            .map_err(|err| Error::Io(std::io::Error::other(err.to_string())))?;
+        self.counter = 0;
+
+        Ok(())
+    }
+
+    /// Seals a chunk as its nonce, the ciphertext and the tag.
+    pub fn seal(&mut self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        if self.counter == u32::MAX {
+            self.renew()?;
+        }
+
+        let mut nonce = [0; NONCE_LEN];
+        nonce[..8].copy_from_slice(&self.prefix);
+        nonce[8..].copy_from_slice(&self.counter.to_be_bytes());
+        self.counter += 1;
+
+        let mut sealed = Vec::with_capacity(NONCE_LEN + plaintext.len() + TAG_LEN);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(plaintext);
+        let tag = self
+            .cipher
+            .encrypt_in_place_detached(&nonce.into(), &[], &mut sealed[NONCE_LEN..])
+            .map_err(|_| Error::Io(std::io::Error::other("the chunk is too long to seal")))?;
+        sealed.extend_from_slice(&tag);
+
+        Ok(sealed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chacha20poly1305::aead::Aead;
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    fn open(sealed: &[u8]) -> Vec<u8> {
+        let key: [u8; 32] = std::array::from_fn(|i| i as u8);
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+        ChaCha20Poly1305::new(&key.into())
+            .decrypt(nonce.into(), ciphertext)
+            .unwrap()
+    }
+
+    #[test]
+    fn seals_what_the_key_opens() {
+        let mut sealer = Sealer::new(KEY).unwrap();
+        let sealed = sealer.seal(b"pcm").unwrap();
+
+        assert_eq!(sealed.len(), NONCE_LEN + 3 + TAG_LEN);
+        assert_ne!(&sealed[NONCE_LEN..NONCE_LEN + 3], b"pcm");
+        assert_eq!(open(&sealed), b"pcm");
+    }
+
+    #[test]
+    fn gives_every_chunk_its_own_nonce() {
+        let mut first = Sealer::new(KEY).unwrap();
+        let mut second = Sealer::new(KEY).unwrap();
+
+        let a = first.seal(b"pcm").unwrap();
+        let b = first.seal(b"pcm").unwrap();
+        let c = second.seal(b"pcm").unwrap();
+        assert_eq!(a[..8], b[..8]);
+        assert_eq!(a[8..NONCE_LEN], 0_u32.to_be_bytes());
+        assert_eq!(b[8..NONCE_LEN], 1_u32.to_be_bytes());
+        assert_ne!(a[..8], c[..8]);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn renews_the_prefix_before_the_counter_runs_out() {
+        let mut sealer = Sealer::new(KEY).unwrap();
+        sealer.counter = u32::MAX - 1;
+
+        let last = sealer.seal(b"pcm").unwrap();
+        let renewed = sealer.seal(b"pcm").unwrap();
+        assert_eq!(last[8..NONCE_LEN], (u32::MAX - 1).to_be_bytes());
+        assert_eq!(renewed[8..NONCE_LEN], 0_u32.to_be_bytes());
+        assert_ne!(last[..8], renewed[..8]);
+        assert_eq!(open(&renewed), b"pcm");
+    }
+
+    #[test]
+    fn rejects_malformed_keys() {
+        let signed = format!("+f{}", &KEY[2..]);
+        let non_ascii = format!("é{}", &KEY[2..]);
+
+        for key in ["", &KEY[2..], &signed, &non_ascii, &KEY.replace('a', "g")] {
+            assert!(Sealer::new(key).is_err(), "accepted {key:?}");
+        }
+        assert!(Sealer::new(&KEY.to_uppercase()).is_ok());
+    }
+}
@@ -0,0 +1,57 @@
+use jni::{
+    errors::Result,
+    objects::{JObject, JString, JValue},
+    JNIEnv, JavaVM,
+};
+
+/// Runs `f` with the JNI environment of the current thread, attaching it to
+/// the VM if needed, and the Android context of the app. `None` if the VM
+/// cannot be reached or `f` fails, e.g. because a Java method threw.
+pub(crate) fn with_context<T, F>(f: F) -> Option<T>
+where
+    F: for<'local> FnOnce(&mut JNIEnv<'local>, &JObject<'local>) -> Result<T>,
+{
+    let context = ndk_context::android_context();
+    let vm = unsafe { JavaVM::from_raw(context.vm().cast()) }.ok()?;
+    let mut env = vm.attach_current_thread().ok()?;
+    // A global reference the app holds on to for as long as it runs.
+    let context = unsafe { JObject::from_raw(context.context().cast()) };
+
+    let result = f(&mut env, &context);
+    if env.exception_check().unwrap_or(false) {
+        env.exception_clear().ok();
+    }
+
+    result.ok()
+}
+
+/// Calls `Context.getSystemService` for the service called `name`.
+pub(crate) fn system_service<'local>(
+    env: &mut JNIEnv<'local>,
+    context: &JObject<'local>,
+    name: &str,
+) -> Result<JObject<'local>> {
+    let name = env.new_string(name)?;
+
+    env.call_method(
+        context,
+        "getSystemService",
+        "(Ljava/lang/String;)Ljava/lang/Object;",
+        &[JValue::Object(&name)],
+    )?
+    .l()
+}
+
+/// Converts a `CharSequence` to a Rust string, `None` if it is null.
+pub(crate) fn to_string(env: &mut JNIEnv, sequence: &JObject) -> Result<Option<String>> {
+    if sequence.is_null() {
+        return Ok(None);
+    }
+
+    let string: JString = env
+        .call_method(sequence, "toString", "()Ljava/lang/String;", &[])?
+        .l()?
+        .into();
+
+    Ok(Some(env.get_string(&string)?.into()))
+}
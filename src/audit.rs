@@ -0,0 +1,104 @@
+use crate::error::Result;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Serializes appends so concurrent operations never interleave lines.
+static AUDIT_LOCK: Mutex<()> = Mutex::new(());
+
+/// The recorder operation an audit entry describes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditOperation {
+    StartRecording,
+    StopRecording,
+    ForceStop,
+    ResumeSession,
+    DisableRecorder,
+    EnableRecorder,
+    Shutdown,
+}
+
+/// A single line of the audit log.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// RFC 3339 local time at which the operation happened.
+    pub timestamp: String,
+    /// The operating system user the app runs as.
+    pub user: String,
+    /// The label of the webview that invoked the operation.
+    pub webview: Option<String>,
+    pub operation: AuditOperation,
+    /// The name of the input device used for the recording.
+    pub device: Option<String>,
+    /// The length of the recording in milliseconds, set when it stops.
+    pub duration_ms: Option<u64>,
+    /// The recording file the operation refers to.
+    pub path: Option<PathBuf>,
+    /// The error message if the operation failed.
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    pub(crate) fn new(operation: AuditOperation, webview: Option<String>) -> Self {
+        Self {
+            timestamp: Local::now().to_rfc3339(),
+            user: current_user(),
+            webview,
+            operation,
+            device: None,
+            duration_ms: None,
+            path: None,
+            error: None,
+        }
+    }
+}
+
+/// Gets the name of the operating system user.
+fn current_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Appends an entry to the audit log, never rewriting existing lines.
+pub(crate) fn append(log_path: &Path, entry: &AuditEntry) -> Result<()> {
+    let _guard = AUDIT_LOCK.lock()?;
+
+    let line = serde_json::to_string(entry)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)?;
+
+    writeln!(file, "{line}")?;
+
+    Ok(())
+}
+
+/// Reads every entry from the audit log, oldest first.
+pub(crate) fn read(log_path: &Path) -> Result<Vec<AuditEntry>> {
+    let _guard = AUDIT_LOCK.lock()?;
+
+    if !log_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(log_path)?;
+
+    BufReader::new(file)
+        .lines()
+        .filter(|line| line.as_ref().map(|x| !x.trim().is_empty()).unwrap_or(true))
+        .map(|line| {
+            let line = line?;
+            Ok(serde_json::from_str(&line)?)
+        })
+        .collect()
+}
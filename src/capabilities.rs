@@ -0,0 +1,73 @@
+use crate::{
+    models::{Capabilities, Capability},
+    mute, permission, priority, storage,
+};
+
+/// Finds out which optional features were compiled into the plugin and can
+/// be used on this platform.
+pub(crate) fn get() -> Capabilities {
+    let global_shortcut = cfg!(feature = "global-shortcut");
+    let jack = cfg!(all(
+        any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd"
+        ),
+        feature = "jack"
+    ));
+    let mute_detection = mute::is_supported();
+    let permission_state = permission::is_supported();
+    // Elsewhere only the names of some hands-free inputs give them away.
+    let hands_free_detection = cfg!(any(windows, target_os = "macos", target_os = "android"));
+    // Only WASAPI records what an output device plays.
+    let loopback_reference = cfg!(windows);
+    let metered_network = cfg!(any(windows, target_os = "linux", target_os = "android"));
+    let thread_priority = cfg!(any(unix, windows));
+    let file_storage = storage::is_local();
+    let wasm = cfg!(feature = "wasm");
+
+    Capabilities {
+        global_shortcut: Capability::new(global_shortcut, global_shortcut),
+        jack: Capability::new(jack, jack && jack_has_input()),
+        mute_detection: Capability::new(mute_detection, mute_detection),
+        permission_state: Capability::new(permission_state, permission_state),
+        hands_free_detection: Capability::new(hands_free_detection, hands_free_detection),
+        loopback_reference: Capability::new(loopback_reference, loopback_reference),
+        metered_network: Capability::new(metered_network, metered_network),
+        thread_priority: Capability::new(thread_priority, thread_priority && priority::can_raise()),
+        file_storage: Capability::new(true, file_storage),
+        wasm: Capability::new(wasm, wasm),
+    }
+}
+
+/// Whether a JACK server is running that has an input to record from.
+#[cfg(all(
+    any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd"
+    ),
+    feature = "jack"
+))]
+fn jack_has_input() -> bool {
+    use cpal::traits::HostTrait;
+
+    cpal::host_from_id(cpal::HostId::Jack)
+        .map(|host| host.default_input_device().is_some())
+        .unwrap_or(false)
+}
+
+#[cfg(not(all(
+    any(
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd"
+    ),
+    feature = "jack"
+)))]
+fn jack_has_input() -> bool {
+    false
+}
@@ -0,0 +1,459 @@
+use crate::{
+    dsp::{align::Aligner, silence::SilenceGate, Pipeline},
+    error::{Error, Result},
+    heartbeat::Liveness,
+    mirror::Mirror,
+    models::ThreadPriority,
+    priority,
+    proxy::Proxy,
+    storage::SinkWriter,
+    stream::AudioQueue,
+};
+use cpal::{FromSample, InputCallbackInfo, Sample, StreamInstant};
+use std::{
+    io,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+pub(crate) type WavWriterHandle = Arc<Mutex<Option<SinkWriter>>>;
+
+/// A 24-bit sample, held in the low bits of an `i32`, for writing 32-bit
+/// and float input to a 24-bit WAV file.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Int24(i32);
+
+impl Int24 {
+    const MAX: i32 = (1 << 23) - 1;
+}
+
+impl FromSample<i8> for Int24 {
+    fn from_sample_(sample: i8) -> Self {
+        Self((sample as i32) << 16)
+    }
+}
+
+impl FromSample<i16> for Int24 {
+    fn from_sample_(sample: i16) -> Self {
+        Self((sample as i32) << 8)
+    }
+}
+
+impl FromSample<i32> for Int24 {
+    /// Drops the lowest 8 bits, which 24-bit hardware leaves at zero.
+    fn from_sample_(sample: i32) -> Self {
+        Self(sample >> 8)
+    }
+}
+
+impl FromSample<f32> for Int24 {
+    fn from_sample_(sample: f32) -> Self {
+        Self((sample.clamp(-1.0, 1.0) * Self::MAX as f32).round() as i32)
+    }
+}
+
+impl hound::Sample for Int24 {
+    fn write<W: io::Write>(self, writer: &mut W, bits: u16) -> hound::Result<()> {
+        self.0.write(writer, bits)
+    }
+
+    fn write_padded<W: io::Write>(
+        self,
+        writer: &mut W,
+        bits: u16,
+        byte_width: u16,
+    ) -> hound::Result<()> {
+        self.0.write_padded(writer, bits, byte_width)
+    }
+
+    fn read<R: io::Read>(
+        reader: &mut R,
+        format: hound::SampleFormat,
+        bytes: u16,
+        bits: u16,
+    ) -> hound::Result<Self> {
+        i32::read(reader, format, bytes, bits).map(Self)
+    }
+
+    fn as_i16(self) -> i16 {
+        (self.0 >> 8) as i16
+    }
+}
+
+/// Holds audio in memory while the file cannot be written, e.g. because
+/// the disk is full, until the recording is redirected to another file.
+pub(crate) struct Spill {
+    samples: Vec<f32>,
+    max_samples: usize,
+    lost_samples: u64,
+    active: bool,
+}
+
+impl Spill {
+    pub fn new(max_samples: usize) -> Self {
+        Self {
+            samples: Vec::new(),
+            max_samples,
+            lost_samples: 0,
+            active: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn push(&mut self, sample: f32) {
+        if self.samples.len() < self.max_samples {
+            self.samples.push(sample);
+        } else {
+            self.lost_samples += 1;
+        }
+    }
+
+    /// Takes the held samples and the number of samples that did not fit,
+    /// resuming writing to the file.
+    pub fn take(&mut self) -> (Vec<f32>, u64) {
+        self.active = false;
+
+        (
+            std::mem::take(&mut self.samples),
+            std::mem::take(&mut self.lost_samples),
+        )
+    }
+}
+
+/// Writes one sample, or holds it in `spill` once the file cannot be
+/// written. Returns the error that made writing stop.
+fn put<U: hound::Sample>(
+    writer: &mut SinkWriter,
+    spill: &mut Spill,
+    value: U,
+    sample: f32,
+) -> Option<hound::Error> {
+    if spill.active {
+        spill.push(sample);
+        return None;
+    }
+
+    let err = writer.write_sample(value).err()?;
+    spill.active = true;
+    spill.push(sample);
+
+    Some(err)
+}
+
+/// Input the backend dropped because a callback ran late.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Overrun {
+    /// The size of the buffer that followed the gap.
+    pub buffer_frames: u32,
+    pub lost_frames: u64,
+}
+
+/// Detects overruns from gaps between the capture timestamps of buffers.
+pub(crate) struct OverrunDetector {
+    sample_rate: u32,
+    next_capture: Option<StreamInstant>,
+}
+
+impl OverrunDetector {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            next_capture: None,
+        }
+    }
+
+    /// Checks a buffer of `frames`, which must follow the previous one.
+    pub fn check(&mut self, frames: usize, info: &InputCallbackInfo) -> Option<Overrun> {
+        let captured_at = info.timestamp().capture;
+        let buffer = Duration::from_secs_f64(frames as f64 / self.sample_rate as f64);
+        let expected = self.next_capture.replace(captured_at.add(buffer)?)?;
+
+        // Tolerate the jitter of backends that estimate their timestamps.
+        let gap = captured_at.duration_since(&expected)?;
+        if gap <= buffer / 2 {
+            return None;
+        }
+
+        Some(Overrun {
+            buffer_frames: frames as u32,
+            lost_frames: (gap.as_secs_f64() * self.sample_rate as f64).round() as u64,
+        })
+    }
+
+    /// Forgets the previous buffer, e.g. because the stream was rebuilt.
+    pub fn reset(&mut self) {
+        self.next_capture = None;
+    }
+}
+
+/// The state owned by the input stream callback.
+pub(crate) struct Capture {
+    pub writer: WavWriterHandle,
+    pub paused: Arc<AtomicBool>,
+    pub channels: usize,
+    pub pipeline: Pipeline,
+    pub aligner: Option<Aligner>,
+    /// Leaves long silences out of the file.
+    pub gate: Option<SilenceGate>,
+    /// Receives a copy of the written audio for the low-rate proxy.
+    pub proxy: Option<Proxy>,
+    /// Receives the same samples as the file, for the redundant copy.
+    pub mirror: Option<Mirror>,
+    /// The number of frames written to the file so far.
+    pub position: Arc<AtomicU64>,
+    /// Touched on every callback, so heartbeats can tell a stalled stream.
+    pub liveness: Arc<Liveness>,
+    /// Receives a copy of the written audio as little-endian `f32` samples.
+    pub stream: Option<Arc<AudioQueue>>,
+    pub overruns: OverrunDetector,
+    /// Called whenever an overrun is detected, if the buffer may be grown.
+    pub on_overrun: Option<Box<dyn FnMut(Overrun) + Send>>,
+    /// Applied to the callback thread in its first call.
+    pub thread_priority: Option<ThreadPriority>,
+    pub priority_applied: bool,
+    pub spill: Spill,
+    /// Called when writing to the file fails. Capture goes on into `spill`.
+    pub on_io_error: Box<dyn FnMut(Error) + Send>,
+    /// Called once if writing fails or panics, after which input is discarded.
+    pub on_error: Box<dyn FnMut(Error) + Send>,
+    /// Set once writing has failed, since its state can no longer be trusted.
+    pub failed: bool,
+}
+
+impl Capture {
+    /// Writes input data to the WAV writer, unless the recording is paused.
+    ///
+    /// A panic, e.g. in a DSP stage, must not unwind into the audio backend,
+    /// so it is reported through `on_error` instead.
+    pub fn write<T, U>(&mut self, input: &[T], info: &InputCallbackInfo)
+    where
+        T: Sample,
+        U: Copy + hound::Sample + FromSample<T> + FromSample<f32>,
+        f32: FromSample<T>,
+    {
+        self.guard(input.len(), info, |capture| {
+            capture.write_unguarded::<T, U>(input, info)
+        });
+    }
+
+    /// Writes 16-bit input to a 16-bit WAV writer, copying the buffer in one
+    /// go instead of converting every sample when nothing has to process it.
+    pub fn write_i16(&mut self, input: &[i16], info: &InputCallbackInfo) {
+        self.guard(input.len(), info, |capture| {
+            let aligned = capture.aligner.as_ref().map_or(true, Aligner::is_done);
+
+            let simple = capture.pipeline.is_empty()
+                && capture.gate.is_none()
+                && capture.proxy.is_none()
+                && capture.stream.is_none();
+
+            if aligned && simple && !capture.spill.is_active() {
+                capture.write_i16_unguarded(input)
+            } else {
+                capture.write_unguarded::<i16, i16>(input, info)
+            }
+        });
+    }
+
+    /// Runs `f` unless writing has already failed, reporting its error or
+    /// panic through `on_error`.
+    fn guard<F>(&mut self, samples: usize, info: &InputCallbackInfo, f: F)
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        if self.failed {
+            return;
+        }
+
+        self.liveness.touch();
+
+        if let (Some(thread_priority), false) = (self.thread_priority, self.priority_applied) {
+            self.priority_applied = true;
+
+            if !priority::set_current(thread_priority) {
+                eprintln!("failed to raise the priority of the capture thread");
+            }
+        }
+
+        if let Some(on_overrun) = self.on_overrun.as_mut() {
+            if let Some(overrun) = self.overruns.check(samples / self.channels, info) {
+                on_overrun(overrun);
+            }
+        }
+
+        let result = catch_unwind(AssertUnwindSafe(|| f(self)))
+            .unwrap_or_else(|payload| Err(Error::from_panic(payload)));
+
+        if let Err(err) = result {
+            self.failed = true;
+            (self.on_error)(err);
+        }
+    }
+
+    /// Prepares for callbacks from a newly built stream, which may run on
+    /// another thread.
+    pub fn stream_rebuilt(&mut self) {
+        self.overruns.reset();
+        self.priority_applied = false;
+    }
+
+    fn write_i16_unguarded(&mut self, input: &[i16]) -> Result<()> {
+        if self.paused.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let Ok(mut guard) = self.writer.try_lock() else {
+            return Ok(());
+        };
+        let Some(writer) = guard.as_mut() else {
+            return Ok(());
+        };
+
+        let mut samples = writer.get_i16_writer(input.len() as u32);
+        for &sample in input.iter() {
+            samples.write_sample(sample);
+        }
+
+        if let Some(mirror) = self.mirror.as_mut() {
+            for &sample in input.iter() {
+                mirror.write(sample);
+            }
+            mirror.flush();
+        }
+
+        // It is unknown how much of a failed buffer made it to the file, so
+        // all of it is held.
+        if let Err(err) = samples.flush() {
+            self.spill.active = true;
+            for &sample in input.iter() {
+                self.spill.push(f32::from_sample(sample));
+            }
+            (self.on_io_error)(err.into());
+        }
+
+        self.position
+            .fetch_add((input.len() / self.channels) as u64, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn write_unguarded<T, U>(&mut self, input: &[T], info: &InputCallbackInfo) -> Result<()>
+    where
+        T: Sample,
+        U: Copy + hound::Sample + FromSample<T> + FromSample<f32>,
+        f32: FromSample<T>,
+    {
+        if self.paused.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let Ok(mut guard) = self.writer.try_lock() else {
+            return Ok(());
+        };
+        let Some(writer) = guard.as_mut() else {
+            return Ok(());
+        };
+
+        let mut input = input;
+        let mut io_error = None;
+
+        if let Some(aligner) = self.aligner.as_mut() {
+            let timestamp = info.timestamp();
+            let latency = timestamp
+                .callback
+                .duration_since(&timestamp.capture)
+                .unwrap_or_default();
+
+            if let Some(alignment) = aligner.align(input.len() / self.channels, latency) {
+                for _ in 0..alignment.pad_frames * self.channels as u64 {
+                    let silence = <U as FromSample<f32>>::from_sample_(0.0);
+                    io_error = io_error.or(put(writer, &mut self.spill, silence, 0.0));
+                    if let Some(proxy) = self.proxy.as_mut() {
+                        proxy.push(0.0);
+                    }
+                    if let Some(mirror) = self.mirror.as_mut() {
+                        mirror.write(silence);
+                    }
+                }
+                self.position
+                    .fetch_add(alignment.pad_frames, Ordering::Relaxed);
+
+                input = &input[alignment.skip_frames * self.channels..];
+            }
+        }
+
+        let position = self.position.load(Ordering::Relaxed);
+        let mut chunk = Vec::new();
+        let mut frames = (input.len() / self.channels) as u64;
+
+        if self.pipeline.is_empty() && self.gate.is_none() {
+            for &sample in input.iter() {
+                let value = f32::from_sample(sample);
+                if self.stream.is_some() {
+                    chunk.extend(value.to_le_bytes());
+                }
+                if let Some(proxy) = self.proxy.as_mut() {
+                    proxy.push(value);
+                }
+
+                let sample = <U as FromSample<T>>::from_sample_(sample);
+                if let Some(mirror) = self.mirror.as_mut() {
+                    mirror.write(sample);
+                }
+                io_error = io_error.or(put(writer, &mut self.spill, sample, value));
+            }
+        } else {
+            let output = self.pipeline.process(input, position);
+            let streaming = self.stream.is_some();
+            let spill = &mut self.spill;
+            let proxy = &mut self.proxy;
+            let mirror = &mut self.mirror;
+            let mut write = |samples: &[f32]| {
+                for &value in samples.iter() {
+                    if streaming {
+                        chunk.extend(value.to_le_bytes());
+                    }
+                    if let Some(proxy) = proxy.as_mut() {
+                        proxy.push(value);
+                    }
+
+                    let sample = <U as FromSample<f32>>::from_sample_(value);
+                    if let Some(mirror) = mirror.as_mut() {
+                        mirror.write(sample);
+                    }
+                    io_error = io_error.take().or(put(writer, spill, sample, value));
+                }
+            };
+
+            match self.gate.as_mut() {
+                Some(gate) => frames = gate.filter(output, self.channels, position, write),
+                None => write(output),
+            }
+        }
+
+        if let Some(err) = io_error {
+            (self.on_io_error)(err.into());
+        }
+        if let Some(proxy) = self.proxy.as_mut() {
+            proxy.flush();
+        }
+        if let Some(mirror) = self.mirror.as_mut() {
+            mirror.flush();
+        }
+
+        self.position.fetch_add(frames, Ordering::Relaxed);
+
+        if let Some(stream) = self.stream.as_ref() {
+            stream.push(chunk)?;
+        }
+
+        Ok(())
+    }
+}
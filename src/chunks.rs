@@ -0,0 +1,228 @@
+use crate::{manifest, storage::SharedSink};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex, PoisonError},
+};
+
+/// A fixed-size piece of a recording, hashed while it is captured so that
+/// it can be uploaded before the recording is stopped.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingChunk {
+    pub index: u32,
+    /// Where the chunk begins in the file.
+    pub offset: u64,
+    pub len: u64,
+    /// The SHA-256 hash of the chunk as lowercase hex.
+    pub sha256: String,
+}
+
+/// The chunks of a file, and whether the uploader has each of them.
+struct ChunkLog {
+    size_bytes: u64,
+    /// Whether the chunks are uploaded, rather than only hashed.
+    upload: bool,
+    /// Set once the file has been finalized and hashed for the last time.
+    finished: bool,
+    chunks: Vec<(RecordingChunk, bool)>,
+}
+
+/// The chunks of the files being recorded, and of finished ones until they
+/// have been uploaded. Kept in memory only; after a restart, recordings are
+/// uploaded as a whole.
+static CHUNKS: LazyLock<Mutex<HashMap<PathBuf, ChunkLog>>> = LazyLock::new(Default::default);
+
+fn with_chunks<T>(f: impl FnOnce(&mut HashMap<PathBuf, ChunkLog>) -> T) -> T {
+    f(&mut CHUNKS.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Reads `len` bytes of a recording from `offset` on, without getting in
+/// the way of the writer.
+pub(crate) struct ChunkReader {
+    sink: SharedSink,
+    offset: u64,
+    remaining: u64,
+}
+
+impl ChunkReader {
+    pub fn new(sink: &SharedSink, chunk: &RecordingChunk) -> Self {
+        Self {
+            sink: sink.clone(),
+            offset: chunk.offset,
+            remaining: chunk.len,
+        }
+    }
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.remaining as usize);
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let len = self.sink.read_at(self.offset, &mut buf[..len])?;
+        self.offset += len as u64;
+        self.remaining -= len as u64;
+
+        Ok(len)
+    }
+}
+
+fn hash_chunk(sink: &SharedSink, index: u32, offset: u64, len: u64) -> io::Result<RecordingChunk> {
+    let mut chunk = RecordingChunk {
+        index,
+        offset,
+        len,
+        sha256: String::new(),
+    };
+    chunk.sha256 = manifest::hash_reader(ChunkReader::new(sink, &chunk))?.1;
+
+    Ok(chunk)
+}
+
+/// Starts keeping the chunks of a new file.
+pub(crate) fn track(path: &Path, size_bytes: u64, upload: bool) {
+    with_chunks(|chunks| {
+        chunks.insert(
+            path.to_path_buf(),
+            ChunkLog {
+                size_bytes,
+                upload,
+                finished: false,
+                chunks: Vec::new(),
+            },
+        );
+    });
+}
+
+/// Whether the chunks of the file are kept.
+pub(crate) fn is_tracked(path: &Path) -> bool {
+    with_chunks(|chunks| chunks.contains_key(path))
+}
+
+/// Hashes the chunks of a file in progress that have been written in full
+/// since the last call, returning them along with whether to upload them.
+pub(crate) fn hash_written(
+    path: &Path,
+    sink: &SharedSink,
+) -> io::Result<(Vec<RecordingChunk>, bool)> {
+    let Some((size_bytes, upload, mut offset, mut index)) = with_chunks(|chunks| {
+        let log = chunks.get(path).filter(|x| !x.finished)?;
+        let next = log.chunks.len() as u64;
+        Some((
+            log.size_bytes,
+            log.upload,
+            next * log.size_bytes,
+            next as u32,
+        ))
+    }) else {
+        return Ok((Vec::new(), false));
+    };
+
+    let len = sink.len()?;
+    let mut hashed = Vec::new();
+    while offset + size_bytes <= len {
+        hashed.push(hash_chunk(sink, index, offset, size_bytes)?);
+        offset += size_bytes;
+        index += 1;
+    }
+
+    with_chunks(|chunks| {
+        if let Some(log) = chunks.get_mut(path).filter(|x| !x.finished) {
+            log.chunks.extend(hashed.iter().map(|x| (x.clone(), false)));
+        }
+    });
+
+    Ok((hashed, upload))
+}
+
+/// Hashes every chunk of a finalized file again, returning those that are
+/// new, such as the tail, or changed, such as the first one with the header
+/// rewritten. Only files whose chunks are uploaded are kept after this.
+pub(crate) fn finish(path: &Path, sink: &SharedSink) -> io::Result<Vec<RecordingChunk>> {
+    let Some(size_bytes) = with_chunks(|chunks| chunks.get(path).map(|x| x.size_bytes)) else {
+        return Ok(Vec::new());
+    };
+
+    let len = sink.len()?;
+    let mut final_chunks = Vec::new();
+    let mut offset = 0;
+    while offset < len {
+        let chunk_len = size_bytes.min(len - offset);
+        final_chunks.push(hash_chunk(
+            sink,
+            final_chunks.len() as u32,
+            offset,
+            chunk_len,
+        )?);
+        offset += chunk_len;
+    }
+
+    Ok(with_chunks(|chunks| {
+        let Some(log) = chunks.get_mut(path) else {
+            return Vec::new();
+        };
+
+        log.finished = true;
+        let mut changed = Vec::new();
+        let previous = std::mem::take(&mut log.chunks);
+        for chunk in final_chunks {
+            let uploaded = previous
+                .get(chunk.index as usize)
+                .is_some_and(|(x, uploaded)| *x == chunk && *uploaded);
+            if previous.get(chunk.index as usize).map(|x| &x.0) != Some(&chunk) {
+                changed.push(chunk.clone());
+            }
+            log.chunks.push((chunk, uploaded));
+        }
+
+        if !log.upload {
+            chunks.remove(path);
+        }
+
+        changed
+    }))
+}
+
+/// Marks a chunk as uploaded, unless it changed in the meantime.
+pub(crate) fn mark_uploaded(path: &Path, chunk: &RecordingChunk) {
+    with_chunks(|chunks| {
+        let entry = chunks
+            .get_mut(path)
+            .and_then(|x| x.chunks.get_mut(chunk.index as usize));
+        if let Some((x, uploaded)) = entry {
+            if x == chunk {
+                *uploaded = true;
+            }
+        }
+    });
+}
+
+/// Gets the chunks of a finalized file along with whether each has been
+/// uploaded, or `None` if it is uploaded as a whole.
+pub(crate) fn get(path: &Path) -> Option<Vec<(RecordingChunk, bool)>> {
+    with_chunks(|chunks| {
+        chunks
+            .get(path)
+            .filter(|x| x.finished)
+            .map(|x| x.chunks.clone())
+    })
+}
+
+/// Keeps the chunks of a file that has been moved under its new path.
+pub(crate) fn rename(path: &Path, new_path: &Path) {
+    with_chunks(|chunks| {
+        if let Some(log) = chunks.remove(path) {
+            chunks.insert(new_path.to_path_buf(), log);
+        }
+    });
+}
+
+/// Forgets the chunks of a file, e.g. once it has been uploaded.
+pub(crate) fn forget(path: &Path) {
+    with_chunks(|chunks| chunks.remove(path));
+}
@@ -1,49 +1,353 @@
+use crate::{
+    aead::Sealer,
+    audit::{self, AuditEntry, AuditOperation},
+    capabilities,
+    capture::{Capture, Int24, Overrun, OverrunDetector, Spill, WavWriterHandle},
+    chunks::{self, ChunkReader},
+    dsp::{
+        self,
+        align::Aligner,
+        channels::ChannelMixer,
+        convert::Converter,
+        fingerprint::{self, AudioFingerprint},
+        ltc::LtcReader,
+        pitch::PitchDetector,
+        silence::{ChapterMarker, SilenceGate},
+        tempo::{self, TempoDetection},
+        watermark::{self, Watermark, WatermarkDetection},
+        Pipeline, Processor,
+    },
+    duplicates::{self, DuplicateGroup, DuplicateOptions},
+    error::{Error, Message, Result},
+    events::{
+        self, AudioDropped, BufferSizeChanged, ChunkHashed, FinalizeStage, HandsFreeInput,
+        InputDeviceChanged, InputDevicesChanged, MarkerAdded, MicMuteChanged, MicPermissionChanged,
+        RecorderHeartbeat, RecordingError, RecordingExpired, RecordingFinalized,
+        RecordingFinalizing, RecordingIoError, RecordingStarted, RecordingStopped,
+        SyncStateChanged,
+    },
+    heartbeat::{self, Liveness},
+    hooks,
+    library::{
+        self, ListOptions, RecordingInfo, RecordingPage, RecordingUpdate, SearchQuery, SyncStatus,
+        SyncUpdate,
+    },
+    manifest::{self, ChecksumManifest, ManifestVerification},
+    metadata::{self, Marker, MetadataHandle, RecordingMetadata},
+    mirror::Mirror,
+    models::{
+        Capabilities, CaptionOptions, ChannelOptions, ChapterOptions, ChunkOptions, InputConfig,
+        InputConfigRange, InputDevice, M4aOptions, MetronomeOptions, OverdubOptions,
+        PermissionState, ProxyOptions, RecorderSnapshot, RecorderState, RecordingOptions,
+        RecordingRedirected, RedundantOptions, ReferenceOptions, StartedRecording, StopOptions,
+        TempoOptions, ThreadPriority, WasmOptions,
+    },
+    mp4::{Cue, Mp4Writer, TextKind, TextTrack},
+    mute, permission,
+    playback::{self, BackingTrack, Click, Mixer},
+    priority,
+    proxy::Proxy,
+    quota::{self, NamespaceUsage},
+    reference::Reference,
+    retention::{self, Policy},
+    session::{self, ResumableSession, Session},
+    snapshot,
+    storage::{self, SharedSink, SinkWriter},
+    stream::{AudioQueue, Packetizer, Popped},
+    tasks,
+    template::{self, RecordingTemplate},
+    transport,
+    upload::{self, Throttled, UploadSchedule, Uploader},
+    webhook::{self, WebhookEvent, WebhookOptions},
+};
 use chrono::Local;
 use clap::Parser;
 use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
-    FromSample, Sample, Stream,
+    FromSample, Stream,
 };
-use hound::{SampleFormat, WavSpec, WavWriter};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
 use std::{
-    fs::{create_dir_all, File},
-    io::BufWriter,
+    fs::{self, create_dir_all},
+    io::{self, Read, Write},
     marker::{Send, Sync},
-    path::PathBuf,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::{Component, Path, PathBuf},
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, LazyLock, Mutex,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc, LazyLock, Mutex, MutexGuard, OnceLock, PoisonError, TryLockError,
     },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tauri::{
+    command,
+    ipc::{Channel, InvokeResponseBody, JavaScriptChannelId},
+    AppHandle, Emitter, Manager, Runtime, Webview,
 };
-use tauri::{command, AppHandle, Manager, Runtime};
+use tauri_plugin_fs::FsExt;
+
+/// The files a start has created, which are removed again if it fails
+/// before the recording is set up, so a rejected start leaves none behind.
+#[derive(Default)]
+struct CreatedFiles {
+    /// Created with the configured storage.
+    recordings: Vec<PathBuf>,
+    /// Created on the local file system, such as the redundant copy.
+    files: Vec<PathBuf>,
+}
 
-type WavWriterHandle = Arc<Mutex<Option<WavWriter<BufWriter<File>>>>>;
+impl CreatedFiles {
+    /// Keeps the files, as the start has succeeded.
+    fn keep(&mut self) {
+        self.recordings.clear();
+        self.files.clear();
+    }
+}
+
+impl Drop for CreatedFiles {
+    fn drop(&mut self) {
+        for path in &self.recordings {
+            storage::remove(path).ok();
+        }
+        for path in &self.files {
+            fs::remove_file(path).ok();
+        }
+    }
+}
 
 struct SafeStream(Stream);
 
 unsafe impl Send for SafeStream {}
 unsafe impl Sync for SafeStream {}
 
+impl SafeStream {
+    /// Stops the stream, which can block for as long as the driver does.
+    fn close(self) {
+        drop(self.0);
+    }
+}
+
 struct State {
     is_recording: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
     save_path: Arc<Mutex<Option<PathBuf>>>,
     writer: WavWriterHandle,
     stream: Arc<Mutex<Option<SafeStream>>>,
+    device_name: Option<String>,
+    started_at: Option<Instant>,
+    /// `started_at` as Unix time in milliseconds.
+    started_at_ms: Option<u64>,
+    metadata: MetadataHandle,
+    sample_rate: u32,
+    /// The sample rate of the device being recorded, which differs from the
+    /// file's once the recording has moved over to another device.
+    input_sample_rate: u32,
+    /// Whether the recording moves over to the default input when it changes.
+    follow_default_input: bool,
+    /// The preferred devices the recording moves over to instead, with
+    /// `follow_preferred`.
+    followed_devices: Vec<String>,
+    /// The number of frames written to the file so far.
+    position: Arc<AtomicU64>,
+    /// Run once the recording is torn down, e.g. to release global shortcuts.
+    cleanups: Vec<Box<dyn FnOnce() + Send>>,
+    adaptive: Option<AdaptiveStream>,
+    capture: Option<Arc<Mutex<Capture>>>,
+    /// The position at which the current file begins, once the recording
+    /// has been redirected to a new file.
+    file_start: u64,
+    /// The storage the writer writes to, which outlives the writer.
+    sink: Option<SharedSink>,
+    /// Where the current file is written until it has been finalized, if
+    /// not at `save_path`.
+    temp_path: Option<PathBuf>,
+    /// The directory new files of the recording are written to first.
+    temp_dir: Option<PathBuf>,
+    /// The namespace new files of the recording are stored in.
+    namespace: Option<String>,
+    /// The file the session is persisted in, while it is resumable.
+    session_path: Option<PathBuf>,
+    /// When the files of the recording expire.
+    retention: Option<Policy>,
+    /// The idempotency key of the latest start, and what it started.
+    start_key: Option<(String, StartedRecording)>,
+    /// The idempotency key of the latest stop, and the file it stopped.
+    stop_key: Option<(String, PathBuf)>,
+}
+
+/// What is needed to rebuild the input stream with a larger buffer.
+struct AdaptiveStream {
+    device: cpal::Device,
+    config: cpal::SupportedStreamConfig,
+    capture: Arc<Mutex<Capture>>,
+    /// The fixed buffer size in frames, or `None` while the default is used.
+    buffer_frames: Option<u32>,
+    max_frames: u32,
+    /// Set while the buffer is being grown, and for good once it cannot grow.
+    resizing: Arc<AtomicBool>,
 }
 
 impl State {
     fn new() -> Self {
         Self {
             is_recording: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
             save_path: Arc::new(Mutex::new(None)),
             writer: Arc::new(Mutex::new(None)),
             stream: Arc::new(Mutex::new(None)),
+            device_name: None,
+            started_at: None,
+            started_at_ms: None,
+            metadata: MetadataHandle::default(),
+            sample_rate: 0,
+            input_sample_rate: 0,
+            follow_default_input: false,
+            followed_devices: Vec::new(),
+            position: Arc::new(AtomicU64::new(0)),
+            cleanups: Vec::new(),
+            adaptive: None,
+            capture: None,
+            file_start: 0,
+            sink: None,
+            temp_path: None,
+            temp_dir: None,
+            namespace: None,
+            session_path: None,
+            retention: None,
+            start_key: None,
+            stop_key: None,
+        }
+    }
+}
+
+/// What is left of a recording once its stream has been torn down, and
+/// still has to be written out to disk.
+struct Finished {
+    save_path: PathBuf,
+    device_name: Option<String>,
+    duration_ms: Option<u64>,
+    writer: Option<SinkWriter>,
+    sink: SharedSink,
+    /// Where the recording was written to, if not at `save_path`. It is
+    /// moved over once it has been finalized.
+    temp_path: Option<PathBuf>,
+    /// Set when the writer was abandoned by `force_stop`, so the header of
+    /// whatever reached the disk has to be repaired instead.
+    salvage: bool,
+    metadata: RecordingMetadata,
+    _finalizing: FinalizingGuard,
+}
+
+impl Finished {
+    const STAGES: [FinalizeStage; 4] = [
+        FinalizeStage::Flushing,
+        FinalizeStage::WritingMetadata,
+        FinalizeStage::Syncing,
+        FinalizeStage::Moving,
+    ];
+
+    /// Writes the recording out to disk, calling `on_stage` with the
+    /// fraction of work done as each stage begins. Returns the sink to read
+    /// the finalized recording from.
+    fn finalize<F>(self, mut on_stage: F) -> Result<SharedSink>
+    where
+        F: FnMut(FinalizeStage, f64),
+    {
+        let mut writer = self.writer;
+        let mut sink = self.sink;
+        // Only a recording made in a temporary directory is moved.
+        let stages = match self.temp_path {
+            Some(_) => &Self::STAGES[..],
+            None => &Self::STAGES[..3],
+        };
+
+        for (index, &stage) in stages.iter().enumerate() {
+            on_stage(stage, index as f64 / stages.len() as f64);
+
+            match stage {
+                FinalizeStage::Flushing => {
+                    if let Some(writer) = writer.take() {
+                        writer.finalize()?;
+                    } else if self.salvage {
+                        metadata::repair(&mut **sink.lock())?;
+                    }
+                }
+                FinalizeStage::WritingMetadata => {
+                    metadata::append(&mut **sink.lock(), &self.metadata)?
+                }
+                FinalizeStage::Syncing => sink.lock().sync()?,
+                FinalizeStage::Moving => {
+                    if let Some(temp_path) = &self.temp_path {
+                        storage::move_file(temp_path, &self.save_path)?;
+                        sink = storage::open_file(&self.save_path)?;
+                    }
+                }
+            }
         }
+
+        Ok(sink)
+    }
+}
+
+/// The number of stopped recordings that are still being written to disk.
+static FINALIZING: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts a finalization in `FINALIZING` for as long as it is alive.
+struct FinalizingGuard;
+
+impl FinalizingGuard {
+    fn new() -> Self {
+        FINALIZING.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for FinalizingGuard {
+    fn drop(&mut self) {
+        FINALIZING.fetch_sub(1, Ordering::SeqCst);
     }
 }
 
+/// How often expired recordings are deleted.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often the chunks written since are hashed while recording.
+const CHUNK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the input to follow is checked with `follow_default_input` or
+/// `follow_preferred`.
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The smallest chunks a recording can be hashed in.
+const MIN_CHUNK_BYTES: u64 = 64 * 1024;
+
+/// How often the uploader looks for recordings to upload.
+const UPLOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long `stop_recording` waits for the stream and writer by default.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long `request_usb_permission` waits for the user by default.
+const USB_PERMISSION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a command with a timeout checks whether the one it waits for
+/// is done.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The save path handed out last, which a storage may not know about until
+/// the recording is created.
+static LAST_SAVE_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
 static STATE: LazyLock<Arc<Mutex<State>>> = LazyLock::new(|| Arc::new(Mutex::new(State::new())));
 
+/// Set by `disable_recorder`, makes every start attempt fail until cleared.
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Set when the last recording ended because of an error, until the next
+/// recording starts.
+static FAILED: AtomicBool = AtomicBool::new(false);
+
 #[derive(Parser, Debug)]
 struct Opt {
     /// The audio device to use
@@ -65,8 +369,55 @@ struct Opt {
     jack: bool,
 }
 
+/// Picks the first of `names` that is connected and reports an input
+/// config, skipping devices that are unplugged or fail to open. `default`
+/// stands for the default input and `usb` for the first USB input.
+fn preferred_input_device(host: &cpal::Host, names: &[String]) -> Result<cpal::Device> {
+    for name in names {
+        let device = match name.as_str() {
+            "default" => host.default_input_device(),
+            "usb" => host
+                .input_devices()?
+                .find(|x| x.name().is_ok_and(|y| transport::is_usb(&y) == Some(true))),
+            _ => host
+                .input_devices()?
+                .find(|x| x.name().map(|y| &y == name).unwrap_or(false)),
+        };
+
+        match device {
+            Some(device) if device.default_input_config().is_ok() => return Ok(device),
+            Some(_) => eprintln!("preferred input device cannot be opened: {}", name),
+            None => {}
+        }
+    }
+
+    Err(Error::DeviceNotFound(
+        Message::new(
+            "deviceNotFound.preferred",
+            format!(
+                "None of the preferred input devices is available: {}",
+                names.join(", ")
+            ),
+        )
+        .param("names", names.join(", ")),
+    ))
+}
+
 /// Starts recording audio.
 ///
+/// Emits `recording-started` once audio is being written, and
+/// `recording-error` if the stream fails before the recording is stopped.
+/// While recording, hardware mic-mute key presses are surfaced as
+/// `mic-mute-changed` on platforms that expose them, and `recorder-heartbeat`
+/// is emitted every two seconds.
+///
+/// If `on_audio` is given, a copy of the written audio is streamed to it as
+/// little-endian `f32` samples, buffered according to `options.stream`.
+///
+/// If `options.idempotency_key` is the key of the latest start, nothing is
+/// started and the recording that start returned is returned again, with
+/// `replayed` set.
+///
 /// # Examples
 /// ```
 /// use tauri_plugin_mic_recorder::start_recording;
@@ -74,12 +425,126 @@ struct Opt {
 /// start_recording().unwrap();
 /// ```
 #[command]
-pub async fn start_recording<R: Runtime>(app_handle: AppHandle<R>) -> Result<(), String> {
-    let mut state = STATE.lock().map_err(|err| err.to_string())?;
+pub async fn start_recording<R: Runtime>(
+    app_handle: AppHandle<R>,
+    webview: Webview<R>,
+    options: Option<RecordingOptions>,
+    on_audio: Option<JavaScriptChannelId>,
+) -> Result<StartedRecording> {
+    let mut entry = AuditEntry::new(
+        AuditOperation::StartRecording,
+        Some(webview.label().to_string()),
+    );
+
+    let on_audio = on_audio.map(|x| x.channel_on(webview.clone()));
+    let result = start(
+        &app_handle,
+        options.unwrap_or_default(),
+        on_audio,
+        None,
+        &mut entry,
+    );
+
+    audited_start(&app_handle, result, entry)
+}
+
+/// Writes the audit entry of a start, emitting `recording-started` if it
+/// succeeded. A replayed start did nothing to write or emit.
+fn audited_start<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    result: Result<StartedRecording>,
+    mut entry: AuditEntry,
+) -> Result<StartedRecording> {
+    if result.as_ref().is_ok_and(|x| x.replayed) {
+        return result;
+    }
+    if let Err(err) = &result {
+        entry.error = Some(err.to_string());
+    }
+
+    audit::append(&get_audit_log_path(app_handle)?, &entry)?;
+
+    if result.is_ok() {
+        app_handle.emit(
+            events::RECORDING_STARTED,
+            RecordingStarted {
+                path: entry.path.unwrap_or_default(),
+                device: entry.device.unwrap_or_default(),
+            },
+        )?;
+        publish_snapshot(app_handle, &*STATE.lock()?);
+    }
+
+    result
+}
+
+/// Broadcasts the snapshot of the recorder after `state` changed.
+fn publish_snapshot<R: Runtime>(app_handle: &AppHandle<R>, state: &State) {
+    let is_recording = state.is_recording.load(Ordering::SeqCst);
+    let path = state
+        .save_path
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .filter(|_| is_recording);
+
+    snapshot::publish(app_handle, |snapshot| {
+        snapshot.state = recorder_state(is_recording);
+        snapshot.disabled = DISABLED.load(Ordering::SeqCst);
+        snapshot.paused = is_recording && state.paused.load(Ordering::SeqCst);
+        snapshot.path = path;
+        if is_recording {
+            snapshot.device = state.device_name.clone();
+            snapshot.namespace = state.namespace.clone();
+            snapshot.started_at = state.started_at_ms;
+        } else {
+            snapshot.device = None;
+            snapshot.namespace = None;
+            snapshot.started_at = None;
+        }
+    });
+}
+
+/// Publishes the snapshot from a thread that does not hold the state.
+fn refresh_snapshot<R: Runtime>(app_handle: &AppHandle<R>) {
+    match STATE.lock() {
+        Ok(state) => publish_snapshot(app_handle, &state),
+        Err(err) => eprintln!("failed to publish the recorder snapshot: {}", err),
+    }
+}
+
+/// Starts a recording, continuing `resumed` in a new segment if it is set.
+fn start<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    options: RecordingOptions,
+    on_audio: Option<Channel>,
+    resumed: Option<Session>,
+    entry: &mut AuditEntry,
+) -> Result<StartedRecording> {
+    let mut state = match options.timeout_ms {
+        Some(timeout_ms) => lock_state(Duration::from_millis(timeout_ms))?,
+        None => STATE.lock()?,
+    };
+    if let Some(mut started) = replayed(&state.start_key, options.idempotency_key.as_deref()) {
+        started.replayed = true;
+        return Ok(started);
+    }
+    if tasks::is_cancelled() {
+        return Err(Error::ShutDown);
+    }
+    if DISABLED.load(Ordering::SeqCst) {
+        return Err(Error::RecorderDisabled);
+    }
     if state.is_recording.load(Ordering::SeqCst) {
-        return Err("Recording is already in progress.".to_string());
+        return Err(Error::AlreadyRecording);
+    }
+    hooks::before_start(&options)?;
+    if let Some(namespace) = &options.namespace {
+        check_quota(app_handle, namespace)?;
+    }
+    if let Some(chunks) = &options.chunks {
+        check_chunks(chunks)?;
     }
-    state.is_recording.store(true, Ordering::SeqCst);
 
     let opt = Opt::parse();
 
@@ -100,9 +565,11 @@ pub async fn start_recording<R: Runtime>(app_handle: AppHandle<R>) -> Result<(),
             cpal::available_hosts()
                 .into_iter()
                 .find(|id| *id == cpal::HostId::Jack)
-                .ok_or("JACK host not available. Make sure --features jack is specified.")?,
-        )
-        .map_err(|err| err.to_string())?
+                .ok_or(Error::DeviceNotFound(Message::new(
+                    "deviceNotFound.jackUnavailable",
+                    "JACK host not available. Make sure --features jack is specified.",
+                )))?,
+        )?
     } else {
         cpal::default_host()
     };
@@ -118,168 +585,3750 @@ pub async fn start_recording<R: Runtime>(app_handle: AppHandle<R>) -> Result<(),
     ))]
     let host = cpal::default_host();
 
-    // Set up the input device and stream with the default input config.
-    let device = if opt.device == "default" {
+    if options.follow_default_input
+        && (!options.preferred_devices.is_empty() || opt.device != "default")
+    {
+        return Err(Error::InvalidOptions(Message::new(
+            "invalidOptions.followDefaultInput",
+            "`followDefaultInput` records the default input and takes no other device.",
+        )));
+    }
+    if options.follow_preferred && options.preferred_devices.is_empty() {
+        return Err(Error::InvalidOptions(Message::new(
+            "invalidOptions.followPreferred",
+            "`followPreferred` requires `preferredDevices`.",
+        )));
+    }
+
+    // Set up the input device and stream with the default input config, or
+    // the one with the requested channel count.
+    let device = if !options.preferred_devices.is_empty() {
+        preferred_input_device(&host, &options.preferred_devices)?
+    } else if opt.device == "default" {
         host.default_input_device()
-            .ok_or("No default input device available")?
+            .ok_or(Error::DeviceNotFound(Message::new(
+                "deviceNotFound.noDefaultInput",
+                "No default input device available",
+            )))?
     } else {
-        host.input_devices()
-            .map_err(|err| err.to_string())?
+        host.input_devices()?
             .find(|x| x.name().map(|y| y == opt.device).unwrap_or(false))
-            .ok_or(format!("No input device found with name: {}", opt.device))?
+            .ok_or_else(|| {
+                Error::DeviceNotFound(
+                    Message::new(
+                        "deviceNotFound.input",
+                        format!("No input device found with name: {}", opt.device),
+                    )
+                    .param("name", &opt.device),
+                )
+            })?
     };
 
-    let config = device
-        .default_input_config()
-        .map_err(|err| err.to_string())?;
+    let device_name = device.name()?;
+    entry.device = Some(device_name.clone());
+
+    if let Some(bit_depth) = options.bit_depth.filter(|x| ![16, 24, 32].contains(x)) {
+        return Err(Error::InvalidOptions(
+            Message::new(
+                "invalidOptions.bitDepth",
+                format!("{bit_depth} bits per sample are not supported, only 16, 24 or 32."),
+            )
+            .param("bitDepth", bit_depth),
+        ));
+    }
+    let channel_count = match (options.ambisonic, options.channel_count) {
+        (Some(_), None | Some(4)) => Some(4),
+        (Some(_), Some(channel_count)) => {
+            return Err(Error::InvalidOptions(
+                Message::new(
+                    "invalidOptions.ambisonicChannels",
+                    format!("Ambisonic recordings take 4 channels, not {channel_count}."),
+                )
+                .param("channelCount", channel_count),
+            ))
+        }
+        (None, channel_count) => channel_count,
+    };
+    let config = input_config(&device, channel_count, options.bit_depth)?;
+
+    // The processing applied to every input buffer before it is written.
+    let mut pipeline = Pipeline::new(config.channels() as usize);
+    if let Some(watermark) = &options.watermark {
+        pipeline.push(Watermark::new(&watermark.key, config.sample_rate().0)?);
+    }
+    if let Some(channels) = &options.channels {
+        pipeline.push(ChannelMixer::new(channels.clone()));
+    }
+
+    let metadata = MetadataHandle::default();
+    metadata.lock()?.ambisonic = options.ambisonic;
+    if let Some(ltc) = &options.ltc {
+        if ltc.channel >= config.channels() {
+            return Err(Error::InvalidOptions(
+                Message::new(
+                    "invalidOptions.ltcChannel",
+                    format!(
+                        "The LTC channel {} does not exist, the device has {} channels.",
+                        ltc.channel,
+                        config.channels()
+                    ),
+                )
+                .param("channel", ltc.channel)
+                .param("channels", config.channels()),
+            ));
+        }
+
+        pipeline.push(LtcReader::new(
+            ltc.channel as usize,
+            config.sample_rate().0,
+            metadata.clone(),
+        ));
+    }
+    if let Some(chapters) = &options.chapters {
+        pipeline.push(ChapterMarker::new(
+            chapters,
+            config.sample_rate().0,
+            metadata.clone(),
+        ));
+    }
+    if let Some(tuner) = &options.tuner {
+        let (sender, receiver) = mpsc::channel();
+        pipeline.push(PitchDetector::new(tuner, config.sample_rate().0, sender)?);
+
+        // Ends once the pipeline, and with it the sender, is dropped.
+        let app_handle = app_handle.clone();
+        tasks::spawn(move || {
+            for pitch in receiver {
+                app_handle.emit(events::PITCH_DETECTED, pitch).ok();
+            }
+        });
+    }
+    if let Some(wasm) = &options.wasm {
+        push_wasm_stage(app_handle, &mut pipeline, wasm)?;
+    }
+
+    let audio_queue = match (&on_audio, &options.stream) {
+        (Some(_), stream) => {
+            let stream = stream.clone().unwrap_or_default();
+            Some(Arc::new(AudioQueue::new(
+                stream.max_buffered_bytes,
+                stream.drop_policy,
+                config.channels() as usize * size_of::<f32>(),
+            )))
+        }
+        (None, Some(_)) => {
+            return Err(Error::InvalidOptions(Message::new(
+                "invalidOptions.streamWithoutChannel",
+                "The `stream` options require an `onAudio` channel.",
+            )))
+        }
+        (None, None) => None,
+    };
+    let sealer = options
+        .stream
+        .as_ref()
+        .and_then(|x| x.encryption.as_ref())
+        .map(|x| Sealer::new(&x.key))
+        .transpose()?;
+    let packetizer = options
+        .stream
+        .as_ref()
+        .and_then(|x| x.packets.as_ref())
+        .map(|x| Packetizer::new(x, config.channels() as usize * size_of::<f32>()))
+        .transpose()?;
+
+    // Set to when the backing track or the first bar after the count-in is
+    // heard, which the take begins at.
+    let playback_start = Arc::new(OnceLock::new());
+    let plays = options.overdub.is_some() || options.metronome.is_some();
+    let aligner = match (&options.align, plays) {
+        (Some(_), true) => {
+            return Err(Error::InvalidOptions(Message::new(
+                "invalidOptions.alignWithPlayback",
+                "The `align` option cannot be combined with `overdub` or `metronome`.",
+            )))
+        }
+        (Some(align), false) => Some(Aligner::new(align, config.sample_rate().0)?),
+        (None, true) => Some(Aligner::at_signal(
+            playback_start.clone(),
+            config.sample_rate().0,
+        )),
+        (None, false) => None,
+    };
+    // Checked before any file is created, so a rejected start leaves none
+    // behind.
+    if let Some(proxy) = &options.proxy {
+        check_proxy(proxy, &config)?;
+    }
+    if let Some(metronome) = &options.metronome {
+        check_metronome(metronome)?;
+    }
+    let reference_device = options
+        .reference
+        .as_ref()
+        .map(|reference| find_reference_device(&host, reference))
+        .transpose()?;
+    // Opens the backing track, so a missing or unreadable one is reported
+    // before any file is created.
+    let playback = plays
+        .then(|| {
+            open_playback(
+                &host,
+                options.overdub.as_ref(),
+                options.metronome.as_ref(),
+                playback_start,
+            )
+        })
+        .transpose()?;
 
-    let save_path = get_save_path(&app_handle)?;
+    let save_path = match &resumed {
+        Some(session) => session.next_segment_path(),
+        None => get_save_path(app_handle, options.namespace.as_deref())?,
+    };
+    entry.path = Some(save_path.clone());
+    if let Some(redundant) = &options.redundant {
+        check_mirror(redundant, &save_path)?;
+    }
+    let temp_path = options
+        .temp_dir
+        .as_deref()
+        .map(|temp_dir| get_temp_path(temp_dir, &save_path, &options))
+        .transpose()?
+        .filter(|x| *x != save_path);
+    // Dropped last, so the files are closed by the time they are removed.
+    let mut created = CreatedFiles::default();
     // The WAV file we're recording to.
-    let spec = wav_spec_from_config(&config);
-    let writer = WavWriter::create(&save_path, spec).map_err(|err| err.to_string())?;
+    let spec = wav_spec_from_config(&config, options.bit_depth);
+    let recording_path = temp_path.as_ref().unwrap_or(&save_path);
+    let (writer, sink) = storage::create_writer(recording_path, spec)?;
+    created.recordings.push(recording_path.clone());
     let writer = Arc::new(Mutex::new(Some(writer)));
+    let paused = Arc::new(AtomicBool::new(false));
+    let position = Arc::new(AtomicU64::new(0));
+
+    let proxy = options
+        .proxy
+        .as_ref()
+        .map(|proxy| start_proxy(proxy, &save_path, &config))
+        .transpose()?;
+    if proxy.is_some() {
+        created.recordings.push(proxy_path(&save_path));
+    }
+    let mirror = options
+        .redundant
+        .as_ref()
+        .map(|redundant| start_mirror(redundant, &save_path, spec))
+        .transpose()?;
+    if let Some(redundant) = &options.redundant {
+        created.files.push(mirror_path(redundant, &save_path));
+    }
+
+    let resizing = Arc::new(AtomicBool::new(false));
+    let on_overrun = options.adaptive_buffer.as_ref().map(|_| {
+        let app_handle = app_handle.clone();
+        let resizing = resizing.clone();
+
+        Box::new(move |overrun| {
+            if !resizing.swap(true, Ordering::SeqCst) {
+                let app_handle = app_handle.clone();
+                thread::spawn(move || grow_buffer(&app_handle, overrun));
+            }
+        }) as Box<dyn FnMut(Overrun) + Send>
+    });
+
+    let app_handle_6 = app_handle.clone();
+    let save_path_6 = state.save_path.clone();
+    let io_error_buffer_ms = options.io_error_buffer_ms.unwrap_or(60_000);
 
     // Run the input stream on a separate thread.
-    let writer_2 = writer.clone();
+    let app_handle_4 = app_handle.clone();
+    let liveness = Arc::new(Liveness::new());
+    let capture = Capture {
+        writer: writer.clone(),
+        paused: paused.clone(),
+        channels: config.channels() as usize,
+        pipeline,
+        aligner,
+        gate: options
+            .compress_silence
+            .as_ref()
+            .map(|x| SilenceGate::new(x, config.sample_rate().0, metadata.clone())),
+        proxy,
+        mirror,
+        position: position.clone(),
+        liveness: liveness.clone(),
+        stream: audio_queue.clone(),
+        overruns: OverrunDetector::new(config.sample_rate().0),
+        on_overrun,
+        thread_priority: options.thread_priority,
+        priority_applied: false,
+        spill: Spill::new(
+            (config.sample_rate().0 as u64 * config.channels() as u64 * io_error_buffer_ms as u64
+                / 1000) as usize,
+        ),
+        on_io_error: Box::new(move |err| {
+            let app_handle = app_handle_6.clone();
+            let save_path = save_path_6.clone();
 
-    let err_fn = move |err: cpal::StreamError| {
-        eprintln!("an error occurred on stream: {}", err);
+            thread::spawn(move || {
+                let path = save_path.lock().ok().and_then(|x| x.clone());
+                app_handle
+                    .emit(
+                        events::RECORDING_IO_ERROR,
+                        RecordingIoError {
+                            error: err.payload(),
+                            path,
+                            buffer_ms: io_error_buffer_ms,
+                        },
+                    )
+                    .ok();
+            });
+        }),
+        on_error: Box::new(move |err| {
+            let app_handle = app_handle_4.clone();
+            thread::spawn(move || handle_stream_error(&app_handle, err));
+        }),
+        failed: false,
     };
+    let capture = Arc::new(Mutex::new(capture));
 
-    let stream = match config.sample_format() {
-        cpal::SampleFormat::I8 => device
-            .build_input_stream(
-                &config.into(),
-                move |data, _: &_| write_input_data::<i8, i8>(data, &writer_2),
-                err_fn,
-                None,
-            )
-            .map_err(|err| err.to_string())?,
-        cpal::SampleFormat::I16 => device
-            .build_input_stream(
-                &config.into(),
-                move |data, _: &_| write_input_data::<i16, i16>(data, &writer_2),
-                err_fn,
-                None,
+    let stream = build_stream(
+        app_handle,
+        &device,
+        &config,
+        cpal::BufferSize::Default,
+        capture.clone(),
+    )?;
+
+    let reference = reference_device
+        .map(|device| {
+            start_reference(
+                device,
+                &save_path,
+                position.clone(),
+                paused.clone(),
+                config.sample_rate().0,
             )
-            .map_err(|err| err.to_string())?,
-        cpal::SampleFormat::I32 => device
-            .build_input_stream(
-                &config.into(),
-                move |data, _: &_| write_input_data::<i32, i32>(data, &writer_2),
-                err_fn,
-                None,
+        })
+        .transpose()?;
+    if reference.is_some() {
+        created.recordings.push(reference_path(&save_path));
+    }
+
+    let playback = playback.map(Playback::play).transpose()?;
+
+    let is_recording = Arc::new(AtomicBool::new(true));
+
+    let retention = options
+        .expire_after_ms
+        .map(|expire_after_ms| {
+            let policy = Policy {
+                expiry_path: get_expiry_path(app_handle)?,
+                expire_after_ms,
+            };
+            policy.apply(&save_path)?;
+
+            Ok::<_, Error>(policy)
+        })
+        .transpose()?;
+
+    let session_path = options
+        .resume
+        .as_ref()
+        .map(|resume| {
+            let session = match resumed {
+                Some(mut session) => {
+                    session.segments.push(save_path.clone());
+                    session.last_seen = session::now_ms();
+                    session
+                }
+                None => Session::new(&save_path, resume.grace_ms, options.clone()),
+            };
+
+            let session_path = get_session_path(app_handle)?;
+            session::write(&session_path, &session)?;
+            // A new resumable session takes the place of the one on offer.
+            session::take_resumable()?;
+
+            Ok::<_, Error>(session_path)
+        })
+        .transpose()?;
+
+    if let Some(session_path) = session_path.clone() {
+        heartbeat::watch(is_recording.clone(), liveness.clone(), move |_| {
+            let result = session::update(&session_path, |x| x.last_seen = session::now_ms());
+            if let Err(err) = result {
+                eprintln!("failed to persist the session: {}", err);
+            }
+        });
+    }
+
+    if let Some(chunks) = options.chunks.clone() {
+        chunks::track(&save_path, chunks.size_bytes, chunks.upload);
+        watch_chunks(app_handle, is_recording.clone(), chunks);
+    }
+
+    let app_handle_3 = app_handle.clone();
+    let paused_3 = paused.clone();
+    mute::watch(is_recording.clone(), move |muted| {
+        if options.auto_pause_on_mute {
+            paused_3.store(muted, Ordering::SeqCst);
+            snapshot::publish(&app_handle_3, |snapshot| {
+                snapshot.paused = muted && snapshot.state == RecorderState::Recording;
+            });
+        }
+
+        app_handle_3
+            .emit(
+                events::MIC_MUTE_CHANGED,
+                MicMuteChanged {
+                    muted,
+                    paused: paused_3.load(Ordering::SeqCst),
+                },
             )
-            .map_err(|err| err.to_string())?,
-        cpal::SampleFormat::F32 => device
-            .build_input_stream(
-                &config.into(),
-                move |data, _: &_| write_input_data::<f32, f32>(data, &writer_2),
-                err_fn,
-                None,
+            .ok();
+    });
+
+    if options.follow_default_input || options.follow_preferred {
+        watch_input(app_handle, is_recording.clone());
+    }
+
+    let app_handle_5 = app_handle.clone();
+    let paused_5 = paused.clone();
+    let position_5 = position.clone();
+    heartbeat::watch(is_recording.clone(), liveness, move |age| {
+        app_handle_5
+            .emit(
+                events::RECORDER_HEARTBEAT,
+                RecorderHeartbeat {
+                    state: RecorderState::Recording,
+                    paused: paused_5.load(Ordering::SeqCst),
+                    last_callback_age_ms: age.as_millis() as u64,
+                    frames: position_5.load(Ordering::Relaxed),
+                },
             )
-            .map_err(|err| err.to_string())?,
-        _ => return Err("Unsupported sample format".to_string()),
-    };
+            .ok();
+    });
 
-    stream.play().map_err(|err| err.to_string())?;
+    // Nothing fails from here on until the recording is set up.
+    created.keep();
 
-    *state.save_path.lock().map_err(|err| err.to_string())? = Some(save_path);
+    // A fresh flag per recording lets threads tied to a previous recording
+    // see that it is over even if a new one starts right away.
+    state.is_recording = is_recording;
+    state.paused = paused;
+    *state.save_path.lock()? = Some(save_path);
     state.writer = writer;
-    *state.stream.lock().map_err(|err| err.to_string())? = Some(SafeStream(stream));
+    *state.stream.lock()? = Some(SafeStream(stream));
+    state.device_name = Some(device_name);
+    state.started_at = Some(Instant::now());
+    state.started_at_ms = Some(session::now_ms());
+    state.metadata = metadata;
+    state.sample_rate = spec.sample_rate;
+    state.input_sample_rate = spec.sample_rate;
+    state.follow_default_input = options.follow_default_input;
+    state.followed_devices = match options.follow_preferred {
+        true => options.preferred_devices.clone(),
+        false => Vec::new(),
+    };
+    state.position = position;
+    state.capture = Some(capture.clone());
+    state.file_start = 0;
+    state.namespace = options.namespace.clone();
+    state.sink = Some(sink);
+    state.temp_path = temp_path;
+    state.temp_dir = options.temp_dir.clone();
+    state.session_path = session_path.clone();
+    state.retention = retention;
+    state.adaptive = options.adaptive_buffer.as_ref().map(|adaptive| {
+        let mut max_frames = config.sample_rate().0 * adaptive.max_buffer_ms / 1000;
+        if let cpal::SupportedBufferSize::Range { max, .. } = config.buffer_size() {
+            max_frames = max_frames.min(*max);
+        }
 
-    Ok(())
-}
+        AdaptiveStream {
+            device,
+            config: config.clone(),
+            capture: capture.clone(),
+            buffer_frames: None,
+            max_frames,
+            resizing,
+        }
+    });
+    FAILED.store(false, Ordering::SeqCst);
 
-/// Stops recording audio.
-///
-/// # Returns
-/// - `Ok(PathBuf)`: Returns the path where the recording file is stored.
-/// - `Err(String)`: An error message string on failure.
-///
-/// # Examples
-/// ```
-/// use tauri_plugin_mic_recorder::stop_recording;
-///
-/// let save_path = stop_recording().unwrap();
-/// println!("Recording saved to: {:?}", save_path);
-/// ```
-#[command]
-pub async fn stop_recording() -> Result<PathBuf, String> {
-    let state = STATE.lock().map_err(|err| err.to_string())?;
-    if !state.is_recording.load(Ordering::SeqCst) {
-        return Err("No recording in progress.".to_string());
+    if let (Some(audio_queue), Some(on_audio)) = (audio_queue, on_audio) {
+        forward_audio(
+            app_handle,
+            audio_queue.clone(),
+            on_audio,
+            sealer,
+            packetizer,
+            options.thread_priority,
+        );
+        state.cleanups.push(Box::new(move || audio_queue.close()));
     }
-    state.is_recording.store(false, Ordering::SeqCst);
 
-    // Stop the stream
-    if let Some(stream) = state.stream.lock().map_err(|err| err.to_string())?.take() {
-        drop(stream.0);
+    if let Some(playback) = playback {
+        state.cleanups.push(Box::new(move || playback.close()));
     }
 
-    // Finalize the writer
-    if let Some(writer) = state.writer.lock().map_err(|err| err.to_string())?.take() {
-        writer.finalize().map_err(|err| err.to_string())?;
+    // The session only outlives the recording if the app crashes.
+    if let Some(session_path) = session_path {
+        state.cleanups.push(Box::new(move || {
+            if let Err(err) = session::remove(&session_path) {
+                eprintln!("failed to remove the session: {}", err);
+            }
+        }));
     }
 
-    // Get and clear the save path
-    let save_path = state
-        .save_path
-        .lock()
-        .map_err(|err| err.to_string())?
-        .take()
-        .ok_or("No recording in progress or save path not set.".to_string())?;
+    if options.proxy.is_some() {
+        let capture = capture.clone();
+        state.cleanups.push(Box::new(move || {
+            let proxy = lock_capture(&capture).proxy.take();
+            if let Some(Err(err)) = proxy.map(Proxy::finish) {
+                eprintln!("failed to finalize the proxy: {}", err);
+            }
+        }));
+    }
 
-    Ok(save_path)
-}
+    if options.redundant.is_some() {
+        state.cleanups.push(Box::new(move || {
+            let mirror = lock_capture(&capture).mirror.take();
+            if let Some(Err(err)) = mirror.map(Mirror::finish) {
+                eprintln!("failed to finalize the redundant copy: {}", err);
+            }
+        }));
+    }
 
-/// Gets the path where the recording file is stored.
-fn get_save_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf, String> {
-    let save_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|err| err.to_string())?
-        .join("tauri-plugin-mic-recorder");
+    // Run once the microphone has stopped, so the track is padded to its end.
+    if let Some(cleanup) = reference {
+        state.cleanups.push(cleanup);
+    }
 
-    create_dir_all(&save_dir).map_err(|err| err.to_string())?;
+    if let Some(shortcut) = options.bookmark_shortcut {
+        register_bookmark_shortcut(app_handle, &mut state, shortcut)?;
+    }
 
-    let timestamp = Local::now().format("%Y%m%d%H%M%S").to_string();
-    let save_path = save_dir.join(format!("{timestamp}.wav"));
+    let started = StartedRecording {
+        path: entry.path.clone().unwrap_or_default(),
+        device: entry.device.clone().unwrap_or_default(),
+        replayed: false,
+    };
 
-    Ok(save_path)
-}
+    // Opening the mic has switched most headsets over to the hands-free
+    // profile. The app is only told once the start can no longer be
+    // rejected, so a failed start sends no warning.
+    if transport::is_hands_free(&started.device) == Some(true) {
+        app_handle
+            .emit(
+                events::HANDS_FREE_INPUT,
+                HandsFreeInput {
+                    device: started.device.clone(),
+                },
+            )
+            .ok();
+    }
 
-/// Converts a cpal::SampleFormat to a hound::SampleFormat.
-fn sample_format(format: cpal::SampleFormat) -> SampleFormat {
-    if format.is_float() {
-        SampleFormat::Float
-    } else {
-        SampleFormat::Int
+    if let Some(key) = options.idempotency_key {
+        state.start_key = Some((key, started.clone()));
     }
+
+    Ok(started)
 }
 
-/// Creates a WavSpec from a cpal::SupportedStreamConfig.
-fn wav_spec_from_config(config: &cpal::SupportedStreamConfig) -> WavSpec {
-    WavSpec {
-        channels: config.channels() as _,
-        sample_rate: config.sample_rate().0 as _,
-        bits_per_sample: (config.sample_format().sample_size() * 8) as _,
-        sample_format: sample_format(config.sample_format()),
+/// Locks the state, failing with `Timeout` if another command holds it for
+/// longer than `timeout`, e.g. a start opening a device whose driver hangs.
+fn lock_state(timeout: Duration) -> Result<MutexGuard<'static, State>> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        match STATE.try_lock() {
+            Ok(state) => return Ok(state),
+            Err(TryLockError::Poisoned(err)) => return Err(err.into()),
+            Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                thread::sleep(LOCK_POLL_INTERVAL)
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Err(Error::Timeout(
+                    Message::new(
+                        "timeout.busy",
+                        format!(
+                            "Another start or stop did not finish within {} ms.",
+                            timeout.as_millis()
+                        ),
+                    )
+                    .param("timeoutMs", timeout.as_millis()),
+                ))
+            }
+        }
     }
 }
 
-/// Writes input data to the WAV writer.
-fn write_input_data<T, U>(input: &[T], writer: &WavWriterHandle)
+/// What the latest command with an idempotency key returned, if `key` is
+/// the same.
+fn replayed<T: Clone>(latest: &Option<(String, T)>, key: Option<&str>) -> Option<T> {
+    let (latest_key, value) = latest.as_ref()?;
+
+    (Some(latest_key.as_str()) == key).then(|| value.clone())
+}
+
+/// Builds and plays an input stream that feeds `capture`.
+fn build_stream<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    device: &cpal::Device,
+    config: &cpal::SupportedStreamConfig,
+    buffer_size: cpal::BufferSize,
+    capture: Arc<Mutex<Capture>>,
+) -> Result<Stream> {
+    let app_handle = app_handle.clone();
+    let err_fn = move |err: cpal::StreamError| {
+        let app_handle = app_handle.clone();
+
+        // Tearing the stream down from its own callback can deadlock the
+        // backend, so the recording is finalized from another thread.
+        thread::spawn(move || handle_stream_error(&app_handle, err.into()));
+    };
+
+    let mut stream_config: cpal::StreamConfig = config.clone().into();
+    stream_config.buffer_size = buffer_size;
+
+    // The file's sample width, which may differ from the device's, e.g. for
+    // 24-bit files from 32-bit input.
+    let bits = lock_capture(&capture)
+        .writer
+        .lock()?
+        .as_ref()
+        .map(|x| x.spec().bits_per_sample)
+        .ok_or(Error::NotRecording)?;
+
+    use cpal::SampleFormat::{F32, I16, I32, I8};
+    let stream = match (config.sample_format(), bits) {
+        (I8, 8) => input_stream::<i8, i8>(device, &stream_config, capture, err_fn)?,
+        (I16, 16) => device.build_input_stream(
+            &stream_config,
+            move |data, info: &_| lock_capture(&capture).write_i16(data, info),
+            err_fn,
+            None,
+        )?,
+        (I32, 32) => input_stream::<i32, i32>(device, &stream_config, capture, err_fn)?,
+        (F32, 32) => input_stream::<f32, f32>(device, &stream_config, capture, err_fn)?,
+        (I8, 16) => input_stream::<i8, i16>(device, &stream_config, capture, err_fn)?,
+        (I32, 16) => input_stream::<i32, i16>(device, &stream_config, capture, err_fn)?,
+        (F32, 16) => input_stream::<f32, i16>(device, &stream_config, capture, err_fn)?,
+        (I32, 24) => input_stream::<i32, Int24>(device, &stream_config, capture, err_fn)?,
+        (F32, 24) => input_stream::<f32, Int24>(device, &stream_config, capture, err_fn)?,
+        (sample_format, _) => {
+            return Err(Error::UnsupportedSampleFormat(sample_format.to_string()))
+        }
+    };
+
+    stream.play()?;
+
+    Ok(stream)
+}
+
+/// The sample formats input streams can be built for.
+const CAPTURE_FORMATS: [cpal::SampleFormat; 4] = [
+    cpal::SampleFormat::I8,
+    cpal::SampleFormat::I16,
+    cpal::SampleFormat::I32,
+    cpal::SampleFormat::F32,
+];
+
+/// Builds an input stream of `T` samples that `capture` writes as `U`.
+fn input_stream<T, U>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    capture: Arc<Mutex<Capture>>,
+    err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
+) -> Result<Stream>
 where
-    T: Sample,
-    U: Sample + hound::Sample + FromSample<T>,
+    T: cpal::SizedSample,
+    U: Copy + hound::Sample + FromSample<T> + FromSample<f32>,
+    f32: FromSample<T>,
 {
-    if let Ok(mut guard) = writer.try_lock() {
-        if let Some(writer) = guard.as_mut() {
-            for &sample in input.iter() {
-                let sample: U = U::from_sample(sample);
-                writer.write_sample(sample).ok();
+    Ok(device.build_input_stream(
+        config,
+        move |data: &[T], info: &_| lock_capture(&capture).write::<T, U>(data, info),
+        err_fn,
+        None,
+    )?)
+}
+
+/// The backing track of an overdub and the metronome, opened on their
+/// output device but not playing yet.
+struct Playback {
+    device: cpal::Device,
+    config: cpal::SupportedStreamConfig,
+    mixer: Mixer,
+}
+
+impl Playback {
+    fn play(self) -> Result<SafeStream> {
+        Ok(SafeStream(playback::play(
+            &self.device,
+            &self.config,
+            self.mixer,
+        )?))
+    }
+}
+
+/// Opens the backing track of an overdub and the metronome on their output
+/// device, which sets `start` to the Unix time in milliseconds at which the
+/// first frame after the count-in is heard once it plays.
+fn open_playback(
+    host: &cpal::Host,
+    overdub: Option<&OverdubOptions>,
+    metronome: Option<&MetronomeOptions>,
+    start: Arc<OnceLock<f64>>,
+) -> Result<Playback> {
+    let device_name = overdub
+        .and_then(|x| x.device.as_ref())
+        .or(metronome.and_then(|x| x.device.as_ref()));
+    let device = match device_name {
+        Some(name) => host
+            .output_devices()?
+            .find(|x| x.name().map(|y| y == *name).unwrap_or(false))
+            .ok_or_else(|| {
+                Error::DeviceNotFound(
+                    Message::new(
+                        "deviceNotFound.output",
+                        format!("No output device found with name: {name}"),
+                    )
+                    .param("name", name),
+                )
+            })?,
+        None => host.default_output_device().ok_or(no_default_output())?,
+    };
+    let config = device.default_output_config()?;
+    let channels = config.channels() as usize;
+    let sample_rate = config.sample_rate().0;
+
+    let count_in_ms = metronome.map_or(0.0, |x| {
+        x.count_in_bars as f64 * x.beats_per_bar as f64 * 60_000.0 / x.bpm
+    });
+
+    let mut mixer = Mixer::new(channels);
+    if let Some(metronome) = metronome {
+        mixer.push(Click::new(
+            sample_rate,
+            metronome.bpm,
+            metronome.beats_per_bar,
+            metronome.volume.clamp(0.0, 1.0),
+        ));
+    }
+    if let Some(overdub) = overdub {
+        mixer.push(BackingTrack::open(
+            &overdub.backing_track,
+            sample_rate,
+            channels,
+            (count_in_ms / 1000.0 * sample_rate as f64) as usize,
+        )?);
+    }
+
+    let latency_ms = overdub.map_or(0.0, |x| x.latency_ms);
+    mixer.on_start(move |delay| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        start
+            .set((now + delay).as_secs_f64() * 1000.0 + count_in_ms + latency_ms)
+            .ok();
+    });
+
+    Ok(Playback {
+        device,
+        config,
+        mixer,
+    })
+}
+
+/// Runs the input through the WebAssembly module of the `wasm` option,
+/// emitting what it reports.
+#[cfg(feature = "wasm")]
+fn push_wasm_stage<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    pipeline: &mut Pipeline,
+    options: &WasmOptions,
+) -> Result<()> {
+    use crate::dsp::wasm::{WasmEvent, WasmStage};
+
+    let (sender, receiver) = mpsc::channel();
+    pipeline.push(WasmStage::new(options, sender)?);
+
+    // Ends once the pipeline, and with it the sender, is dropped.
+    let app_handle = app_handle.clone();
+    tasks::spawn(move || {
+        for event in receiver {
+            match event {
+                WasmEvent::Metric(metric) => app_handle.emit(events::WASM_METRIC, metric),
+                WasmEvent::Failed(failed) => app_handle.emit(events::WASM_STAGE_FAILED, failed),
             }
+            .ok();
         }
+    });
+
+    Ok(())
+}
+
+#[cfg(not(feature = "wasm"))]
+fn push_wasm_stage<R: Runtime>(
+    _app_handle: &AppHandle<R>,
+    _pipeline: &mut Pipeline,
+    _options: &WasmOptions,
+) -> Result<()> {
+    Err(Error::InvalidOptions(Message::new(
+        "invalidOptions.wasmUnsupported",
+        "The `wasm` option requires the `wasm` feature.",
+    )))
+}
+
+/// Checks that the metronome has a tempo to click at.
+fn check_metronome(options: &MetronomeOptions) -> Result<()> {
+    if !options.bpm.is_finite() || options.bpm <= 0.0 {
+        return Err(Error::InvalidOptions(Message::new(
+            "invalidOptions.metronomeBpm",
+            "The metronome's `bpm` must be greater than zero.",
+        )));
+    }
+
+    Ok(())
+}
+
+/// Checks that the proxy's sample rate is one it can be resampled to.
+fn check_proxy(options: &ProxyOptions, config: &cpal::SupportedStreamConfig) -> Result<()> {
+    if options.sample_rate < 4000 || options.sample_rate > config.sample_rate().0 {
+        return Err(Error::InvalidOptions(
+            Message::new(
+                "invalidOptions.proxySampleRate",
+                format!(
+                    "The proxy's `sampleRate` must be between 4000 and the input's {} Hz.",
+                    config.sample_rate().0
+                ),
+            )
+            .param("maxSampleRate", config.sample_rate().0),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The path of the proxy of the file at `save_path`.
+fn proxy_path(save_path: &Path) -> PathBuf {
+    save_path.with_file_name(format!(
+        "{}-proxy.wav",
+        save_path.file_stem().unwrap_or_default().to_string_lossy()
+    ))
+}
+
+/// Creates the proxy next to the file at `save_path`.
+fn start_proxy(
+    options: &ProxyOptions,
+    save_path: &Path,
+    config: &cpal::SupportedStreamConfig,
+) -> Result<Proxy> {
+    Proxy::create(
+        storage::create(&proxy_path(save_path))?,
+        options.sample_rate,
+        config.sample_rate().0,
+        config.channels() as usize,
+    )
+}
+
+/// Checks that the chunks are large enough and can be uploaded if asked to.
+fn check_chunks(options: &ChunkOptions) -> Result<()> {
+    if options.size_bytes < MIN_CHUNK_BYTES {
+        return Err(Error::InvalidOptions(
+            Message::new(
+                "invalidOptions.chunkSize",
+                format!("Chunks must be at least {MIN_CHUNK_BYTES} bytes."),
+            )
+            .param("minBytes", MIN_CHUNK_BYTES),
+        ));
+    }
+    if options.upload && !upload::uploader().is_some_and(|x| x.supports_chunks()) {
+        return Err(Error::InvalidOptions(Message::new(
+            "invalidOptions.chunkUploader",
+            "Uploading chunks requires an uploader that supports them.",
+        )));
+    }
+
+    Ok(())
+}
+
+/// Hashes the chunks of the recording as they are written, every
+/// `CHUNK_INTERVAL` until it stops, following it to new files.
+fn watch_chunks<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    is_recording: Arc<AtomicBool>,
+    options: ChunkOptions,
+) {
+    let app_handle = app_handle.clone();
+
+    tasks::spawn(move || {
+        while tasks::sleep(CHUNK_INTERVAL) && is_recording.load(Ordering::SeqCst) {
+            if let Err(err) = hash_chunks(&app_handle, &options) {
+                eprintln!("failed to hash the recording: {}", err);
+            }
+        }
+    });
+}
+
+/// Hashes the chunks of the current file written in full since the last
+/// call, uploading them while the schedule allows, and emits `chunk-hashed`
+/// for each. Chunks that are not uploaded now are once the file is done.
+fn hash_chunks<R: Runtime>(app_handle: &AppHandle<R>, options: &ChunkOptions) -> Result<()> {
+    let (path, sink) = {
+        let state = STATE.lock()?;
+        let path = state.save_path.lock()?.clone();
+        (path, state.sink.clone())
+    };
+    let (Some(path), Some(sink)) = (path, sink) else {
+        return Ok(());
+    };
+
+    if !chunks::is_tracked(&path) {
+        chunks::track(&path, options.size_bytes, options.upload);
+    }
+    let (hashed, upload) = chunks::hash_written(&path, &sink)?;
+    let uploader = upload.then(upload::uploader).flatten();
+    let schedule_path = get_upload_schedule_path(app_handle)?;
+    let schedule = match &uploader {
+        Some(_) => upload::get(&schedule_path)?,
+        None => UploadSchedule::default(),
+    };
+
+    for chunk in hashed {
+        let mut uploaded = false;
+        if let Some(uploader) = uploader.as_ref().filter(|_| schedule.allows_now()) {
+            let mut body =
+                Throttled::new(ChunkReader::new(&sink, &chunk), &schedule, &schedule_path);
+            match uploader.upload_chunk(&path, &chunk, &mut body) {
+                Ok(()) => {
+                    chunks::mark_uploaded(&path, &chunk);
+                    uploaded = true;
+                }
+                Err(_) if body.is_paused() => {}
+                Err(err) => eprintln!("failed to upload a chunk: {}", err),
+            }
+        }
+
+        app_handle
+            .emit(
+                events::CHUNK_HASHED,
+                ChunkHashed {
+                    path: path.clone(),
+                    chunk,
+                    uploaded,
+                },
+            )
+            .ok();
+    }
+
+    Ok(())
+}
+
+/// Checks that the redundant copy of the file at `save_path` would not
+/// replace it.
+fn check_mirror(options: &RedundantOptions, save_path: &Path) -> Result<()> {
+    if save_path.parent() == Some(options.directory.as_path()) {
+        return Err(Error::InvalidOptions(Message::new(
+            "invalidOptions.redundantDirectory",
+            "The redundant copy must be written to another directory than the recording.",
+        )));
+    }
+
+    Ok(())
+}
+
+/// The path of the redundant copy of the file at `save_path`.
+fn mirror_path(options: &RedundantOptions, save_path: &Path) -> PathBuf {
+    options
+        .directory
+        .join(save_path.file_name().unwrap_or_default())
+}
+
+/// Creates the redundant copy of the file at `save_path`.
+fn start_mirror(options: &RedundantOptions, save_path: &Path, spec: WavSpec) -> Result<Mirror> {
+    Mirror::create(&mirror_path(options, save_path), spec)
+}
+
+fn no_default_output() -> Error {
+    Error::DeviceNotFound(Message::new(
+        "deviceNotFound.noDefaultOutput",
+        "No default output device available",
+    ))
+}
+
+/// Finds the device to capture the playback reference from.
+fn find_reference_device(host: &cpal::Host, options: &ReferenceOptions) -> Result<cpal::Device> {
+    let device = match &options.device {
+        Some(name) => host
+            .input_devices()?
+            .chain(host.output_devices()?)
+            .find(|x| x.name().map(|y| y == *name).unwrap_or(false))
+            .ok_or_else(|| {
+                Error::DeviceNotFound(
+                    Message::new(
+                        "deviceNotFound.reference",
+                        format!("No reference device found with name: {name}"),
+                    )
+                    .param("name", name),
+                )
+            })?,
+        // WASAPI captures what an output device plays when it is opened
+        // for input.
+        None if cfg!(windows) => host.default_output_device().ok_or(no_default_output())?,
+        None => {
+            return Err(Error::InvalidOptions(Message::new(
+                "invalidOptions.referenceDeviceRequired",
+                "The reference device has to be named on this platform.",
+            )))
+        }
+    };
+
+    Ok(device)
+}
+
+/// The path of the playback reference of the file at `save_path`.
+fn reference_path(save_path: &Path) -> PathBuf {
+    save_path.with_file_name(format!(
+        "{}-reference.wav",
+        save_path.file_stem().unwrap_or_default().to_string_lossy()
+    ))
+}
+
+/// Starts capturing the playback reference into a file next to the one at
+/// `save_path`, returning the cleanup that stops and finalizes it.
+fn start_reference(
+    device: cpal::Device,
+    save_path: &Path,
+    position: Arc<AtomicU64>,
+    paused: Arc<AtomicBool>,
+    mic_sample_rate: u32,
+) -> Result<Box<dyn FnOnce() + Send>> {
+    let config = device
+        .default_input_config()
+        .or_else(|_| device.default_output_config())?;
+
+    let spec = WavSpec {
+        channels: config.channels(),
+        sample_rate: config.sample_rate().0,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+    let (writer, _) = storage::create_writer(&reference_path(save_path), spec)?;
+    let reference = Arc::new(Mutex::new(Some(Reference::new(
+        writer,
+        spec.sample_rate,
+        mic_sample_rate,
+        position,
+        paused,
+    ))));
+
+    let stream_config = config.clone().into();
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::I8 => {
+            build_reference_stream::<i8>(&device, &stream_config, reference.clone())?
+        }
+        cpal::SampleFormat::I16 => {
+            build_reference_stream::<i16>(&device, &stream_config, reference.clone())?
+        }
+        cpal::SampleFormat::I32 => {
+            build_reference_stream::<i32>(&device, &stream_config, reference.clone())?
+        }
+        cpal::SampleFormat::F32 => {
+            build_reference_stream::<f32>(&device, &stream_config, reference.clone())?
+        }
+        sample_format => return Err(Error::UnsupportedSampleFormat(sample_format.to_string())),
+    };
+    stream.play()?;
+
+    let stream = SafeStream(stream);
+    Ok(Box::new(move || {
+        stream.close();
+
+        let reference = reference
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(Err(err)) = reference.map(Reference::finish) {
+            eprintln!("failed to finalize the reference track: {}", err);
+        }
+    }))
+}
+
+fn build_reference_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    reference: Arc<Mutex<Option<Reference>>>,
+) -> Result<Stream>
+where
+    T: cpal::SizedSample,
+    f32: FromSample<T>,
+{
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &_| {
+            if let Some(reference) = reference
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .as_mut()
+            {
+                reference.write(data);
+            }
+        },
+        // The reference is a best effort, so its failures never end the
+        // recording.
+        |err| eprintln!("an error occurred on the reference stream: {}", err),
+        None,
+    )?;
+
+    Ok(stream)
+}
+
+/// Locks the capture from a stream callback. It is only contended while the
+/// stream is being rebuilt, and recovers from a panic caught while writing.
+fn lock_capture(capture: &Mutex<Capture>) -> MutexGuard<'_, Capture> {
+    capture.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Checks every second for a new input to follow and moves the recording to it.
+fn watch_input<R: Runtime>(app_handle: &AppHandle<R>, is_recording: Arc<AtomicBool>) {
+    let app_handle = app_handle.clone();
+
+    tasks::spawn(move || {
+        let mut inputs = input_names();
+
+        while tasks::sleep(DEVICE_CHECK_INTERVAL) && is_recording.load(Ordering::SeqCst) {
+            // Reported before switching, so the attached input is known by
+            // the time the recording moves over to it.
+            let current = input_names();
+            if let (Some(previous), Some(current)) = (&inputs, &current) {
+                if previous != current {
+                    let attached = current
+                        .iter()
+                        .filter(|x| !previous.contains(x))
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    let changed = InputDevicesChanged {
+                        attached_usb: attached
+                            .iter()
+                            .filter(|x| transport::is_usb(x) == Some(true))
+                            .cloned()
+                            .collect(),
+                        attached,
+                        detached: previous
+                            .iter()
+                            .filter(|x| !current.contains(x))
+                            .cloned()
+                            .collect(),
+                    };
+                    app_handle.emit(events::INPUT_DEVICES_CHANGED, changed).ok();
+                }
+            }
+            if current.is_some() {
+                inputs = current;
+            }
+
+            let switched = STATE.lock().map_err(Error::from).and_then(|mut state| {
+                if !state.is_recording.load(Ordering::SeqCst) {
+                    return Ok(None);
+                }
+                switch_input(&app_handle, &mut state, false)
+            });
+
+            match switched {
+                Ok(Some(changed)) => {
+                    app_handle.emit(events::INPUT_DEVICE_CHANGED, changed).ok();
+                    refresh_snapshot(&app_handle);
+                }
+                Ok(None) => {}
+                // Without a stream left, the recording is over.
+                Err(err)
+                    if STATE
+                        .lock()
+                        .is_ok_and(|x| x.stream.lock().is_ok_and(|x| x.is_none())) =>
+                {
+                    handle_stream_error(&app_handle, err)
+                }
+                Err(err) => eprintln!("failed to switch the input: {}", err),
+            }
+        }
+    });
+}
+
+/// The names of the connected input devices, `None` if they cannot be
+/// listed right now.
+fn input_names() -> Option<Vec<String>> {
+    let devices = cpal::default_host().input_devices().ok()?;
+
+    Some(devices.filter_map(|x| x.name().ok()).collect())
+}
+
+/// Moves the recording over to the input it follows, the first connected of
+/// the followed devices or else the default input, if it is another device
+/// than the one being recorded, or runs at another sample rate. Its input is
+/// converted to the channel count and sample rate of the file, and a marker
+/// notes where it took over.
+///
+/// With `force`, e.g. after the stream failed, the stream is rebuilt even
+/// for the same device, and a missing input is an error. The
+/// recording is left without a stream if this fails after the previous one
+/// was closed.
+fn switch_input<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    state: &mut State,
+    force: bool,
+) -> Result<Option<InputDeviceChanged>> {
+    let host = cpal::default_host();
+    let device = match state.followed_devices.is_empty() {
+        true => host.default_input_device().ok_or_else(|| {
+            Error::DeviceNotFound(Message::new(
+                "deviceNotFound.noDefaultInput",
+                "No default input device available",
+            ))
+        }),
+        false => preferred_input_device(&host, &state.followed_devices),
+    };
+    let found = device.and_then(|device| {
+        let name = device.name()?;
+        let config = device.default_input_config()?;
+        Ok((device, name, config))
+    });
+    // The input may briefly be missing or unusable while the system
+    // switches, in which case the next check tries again.
+    let (device, name, config) = match found {
+        Ok(found) => found,
+        Err(err) if force => return Err(err),
+        Err(_) => return Ok(None),
+    };
+    let sample_rate = config.sample_rate().0;
+    let unchanged =
+        state.device_name.as_ref() == Some(&name) && state.input_sample_rate == sample_rate;
+    if unchanged && !force {
+        return Ok(None);
+    }
+
+    let capture = state.capture.clone().ok_or(Error::NotRecording)?;
+    let spec = state
+        .writer
+        .lock()?
+        .as_ref()
+        .map(|x| x.spec())
+        .ok_or(Error::NotRecording)?;
+    let converter = Converter::new(
+        config.channels() as usize,
+        sample_rate,
+        spec.channels as usize,
+        spec.sample_rate,
+    );
+    let stream = converting_stream(
+        app_handle,
+        &device,
+        &config,
+        spec,
+        converter,
+        capture.clone(),
+    )?;
+
+    if let Some(previous) = state.stream.lock()?.take() {
+        previous.close();
+    }
+    stream.play()?;
+    lock_capture(&capture).stream_rebuilt();
+    *state.stream.lock()? = Some(SafeStream(stream));
+
+    // Growing the buffer would rebuild the stream without conversion.
+    if let Some(adaptive) = state.adaptive.take() {
+        adaptive.resizing.store(true, Ordering::SeqCst);
+    }
+
+    if unchanged {
+        return Ok(None);
+    }
+
+    let frame = state.position.load(Ordering::Relaxed);
+    state.metadata.lock()?.markers.push(Marker {
+        frame,
+        label: format!("Input: {name} ({sample_rate} Hz)"),
+    });
+
+    let previous_device = state.device_name.replace(name.clone());
+    state.input_sample_rate = sample_rate;
+
+    Ok(Some(InputDeviceChanged {
+        hands_free: transport::is_hands_free(&name).unwrap_or(false),
+        usb: transport::is_usb(&name).unwrap_or(false),
+        device: name,
+        previous_device,
+        frame,
+        sample_rate,
+        channels: config.channels(),
+        file_sample_rate: spec.sample_rate,
+        converted: sample_rate != spec.sample_rate || config.channels() != spec.channels,
+    }))
+}
+
+/// Builds an input stream, without playing it, that converts the input of
+/// `device` to the format of the file `capture` writes.
+fn converting_stream<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    device: &cpal::Device,
+    config: &cpal::SupportedStreamConfig,
+    spec: WavSpec,
+    converter: Converter,
+    capture: Arc<Mutex<Capture>>,
+) -> Result<Stream> {
+    let app_handle = app_handle.clone();
+    let err_fn = move |err: cpal::StreamError| {
+        let app_handle = app_handle.clone();
+        thread::spawn(move || handle_stream_error(&app_handle, err.into()));
+    };
+
+    let stream_config: cpal::StreamConfig = config.clone().into();
+    match config.sample_format() {
+        cpal::SampleFormat::I8 => {
+            converting_input::<i8>(device, &stream_config, spec, converter, capture, err_fn)
+        }
+        cpal::SampleFormat::I16 => {
+            converting_input::<i16>(device, &stream_config, spec, converter, capture, err_fn)
+        }
+        cpal::SampleFormat::I32 => {
+            converting_input::<i32>(device, &stream_config, spec, converter, capture, err_fn)
+        }
+        cpal::SampleFormat::F32 => {
+            converting_input::<f32>(device, &stream_config, spec, converter, capture, err_fn)
+        }
+        sample_format => Err(Error::UnsupportedSampleFormat(sample_format.to_string())),
+    }
+}
+
+fn converting_input<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    spec: WavSpec,
+    converter: Converter,
+    capture: Arc<Mutex<Capture>>,
+    err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
+) -> Result<Stream>
+where
+    T: cpal::SizedSample,
+    f32: FromSample<T>,
+{
+    match (spec.sample_format, spec.bits_per_sample) {
+        (SampleFormat::Float, _) => converted::<T, f32>(device, config, converter, capture, err_fn),
+        (SampleFormat::Int, 8) => converted::<T, i8>(device, config, converter, capture, err_fn),
+        (SampleFormat::Int, 16) => converted::<T, i16>(device, config, converter, capture, err_fn),
+        (SampleFormat::Int, 24) => {
+            converted::<T, Int24>(device, config, converter, capture, err_fn)
+        }
+        (SampleFormat::Int, _) => converted::<T, i32>(device, config, converter, capture, err_fn),
+    }
+}
+
+fn converted<T, U>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut converter: Converter,
+    capture: Arc<Mutex<Capture>>,
+    err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
+) -> Result<Stream>
+where
+    T: cpal::SizedSample,
+    U: Copy + hound::Sample + FromSample<f32>,
+    f32: FromSample<T>,
+{
+    Ok(device.build_input_stream(
+        config,
+        move |data: &[T], info: &_| {
+            let mut capture = lock_capture(&capture);
+            capture.write::<f32, U>(converter.process(data), info)
+        },
+        err_fn,
+        None,
+    )?)
+}
+
+/// Rebuilds the stream of the current recording with twice the buffer
+/// after an overrun, emitting `buffer-size-changed`.
+fn grow_buffer<R: Runtime>(app_handle: &AppHandle<R>, overrun: Overrun) {
+    match resize_buffer(app_handle, overrun) {
+        Ok(Some(changed)) => {
+            app_handle.emit(events::BUFFER_SIZE_CHANGED, changed).ok();
+        }
+        Ok(None) => {}
+        Err(err) => handle_stream_error(app_handle, err),
+    }
+}
+
+fn resize_buffer<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    overrun: Overrun,
+) -> Result<Option<BufferSizeChanged>> {
+    let state = STATE.lock()?;
+    let Some(adaptive) = state.adaptive.as_ref() else {
+        return Ok(None);
+    };
+    if !state.is_recording.load(Ordering::SeqCst) {
+        return Ok(None);
+    }
+
+    let previous_frames = adaptive.buffer_frames.unwrap_or(overrun.buffer_frames);
+    let buffer_frames = (previous_frames * 2).min(adaptive.max_frames);
+
+    // Leaving `resizing` set stops any further attempts.
+    if buffer_frames <= previous_frames {
+        return Ok(None);
+    }
+
+    if let Some(stream) = state.stream.lock()?.take() {
+        stream.close();
+    }
+
+    let stream = match build_stream(
+        app_handle,
+        &adaptive.device,
+        &adaptive.config,
+        cpal::BufferSize::Fixed(buffer_frames),
+        adaptive.capture.clone(),
+    ) {
+        Ok(stream) => stream,
+        Err(err) => {
+            eprintln!("failed to grow the input buffer: {}", err);
+
+            // Keep recording with the buffer that worked.
+            let buffer_size = adaptive
+                .buffer_frames
+                .map_or(cpal::BufferSize::Default, cpal::BufferSize::Fixed);
+            let stream = build_stream(
+                app_handle,
+                &adaptive.device,
+                &adaptive.config,
+                buffer_size,
+                adaptive.capture.clone(),
+            )?;
+            *state.stream.lock()? = Some(SafeStream(stream));
+
+            return Ok(None);
+        }
+    };
+
+    lock_capture(&adaptive.capture).stream_rebuilt();
+    *state.stream.lock()? = Some(SafeStream(stream));
+
+    let mut state = state;
+    if let Some(adaptive) = state.adaptive.as_mut() {
+        adaptive.buffer_frames = Some(buffer_frames);
+        adaptive.resizing.store(false, Ordering::SeqCst);
+    }
+
+    Ok(Some(BufferSizeChanged {
+        buffer_frames,
+        previous_frames,
+        lost_frames: overrun.lost_frames,
+    }))
+}
+
+/// Sends queued audio to the frontend on a separate thread until the queue
+/// is closed, emitting `audio-dropped` whenever some had to be discarded.
+fn forward_audio<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    queue: Arc<AudioQueue>,
+    channel: Channel,
+    mut sealer: Option<Sealer>,
+    mut packetizer: Option<Packetizer>,
+    thread_priority: Option<ThreadPriority>,
+) {
+    let app_handle = app_handle.clone();
+
+    tasks::spawn(move || {
+        if let Some(thread_priority) = thread_priority {
+            if !priority::set_current(thread_priority) {
+                eprintln!("failed to raise the priority of the streaming thread");
+            }
+        }
+
+        let mut send = |packet: Vec<u8>| {
+            let packet = match &mut sealer {
+                Some(sealer) => match sealer.seal(&packet) {
+                    Ok(sealed) => sealed,
+                    Err(err) => {
+                        eprintln!("failed to encrypt the stream: {}", err);
+                        return;
+                    }
+                },
+                None => packet,
+            };
+            channel.send(InvokeResponseBody::Raw(packet)).ok();
+        };
+
+        while let Ok(Some(Popped {
+            dropped_frames,
+            chunk,
+        })) = queue.pop()
+        {
+            // The gap lies before the chunk, so its timestamp has to account
+            // for it.
+            if dropped_frames > 0 {
+                if let Some(packetizer) = &mut packetizer {
+                    packetizer.skip(dropped_frames);
+                }
+                app_handle
+                    .emit(
+                        events::AUDIO_DROPPED,
+                        AudioDropped {
+                            frames: dropped_frames,
+                        },
+                    )
+                    .ok();
+            }
+
+            match &mut packetizer {
+                Some(packetizer) => packetizer.packetize(&chunk).into_iter().for_each(&mut send),
+                None => send(chunk),
+            }
+        }
+
+        if let Ok(frames @ 1..) = queue.take_dropped_frames() {
+            app_handle
+                .emit(events::AUDIO_DROPPED, AudioDropped { frames })
+                .ok();
+        }
+
+        if let Some(packet) = packetizer.as_mut().and_then(Packetizer::finish) {
+            send(packet);
+        }
+    });
+}
+
+#[cfg(feature = "global-shortcut")]
+fn register_bookmark_shortcut<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    state: &mut State,
+    shortcut: String,
+) -> Result<()> {
+    let registered = crate::shortcut::register(app_handle, &shortcut, |app_handle| {
+        let app_handle = app_handle.clone();
+
+        // Shortcut handlers run on the main thread, which must never wait
+        // for the recorder state.
+        thread::spawn(move || {
+            if let Err(err) = add_marker_inner(&app_handle, None) {
+                eprintln!("failed to add bookmark: {}", err);
+            }
+        });
+    });
+
+    if let Err(err) = registered {
+        finish(state, STOP_TIMEOUT)
+            .and_then(|x| x.finalize(|_, _| {}))
+            .ok();
+        return Err(err);
+    }
+
+    let app_handle = app_handle.clone();
+    state.cleanups.push(Box::new(move || {
+        crate::shortcut::unregister(&app_handle, &shortcut)
+    }));
+
+    Ok(())
+}
+
+#[cfg(not(feature = "global-shortcut"))]
+fn register_bookmark_shortcut<R: Runtime>(
+    _app_handle: &AppHandle<R>,
+    state: &mut State,
+    _shortcut: String,
+) -> Result<()> {
+    finish(state, STOP_TIMEOUT)
+        .and_then(|x| x.finalize(|_, _| {}))
+        .ok();
+
+    Err(Error::InvalidOptions(Message::new(
+        "invalidOptions.shortcutUnsupported",
+        "Bookmark shortcuts require the `global-shortcut` feature.",
+    )))
+}
+
+/// Stops recording audio.
+///
+/// Returns as soon as capture has stopped, emitting `recording-stopped`. The
+/// file is then written out in the background, emitting `recording-finalizing`
+/// as each stage begins and `recording-finalized` once it is complete.
+///
+/// Fails with `Timeout` if the stream or writer does not shut down within
+/// `timeoutMs`, leaving the recording in progress so that `force_stop` can
+/// abandon it.
+///
+/// If `options.idempotency_key` is the key of the latest stop, nothing is
+/// stopped and the file that stop returned is returned again.
+///
+/// # Returns
+/// - `Ok(PathBuf)`: Returns the path where the recording file is stored.
+/// - `Err(Error)`: The reason the recording could not be stopped.
+///
+/// # Examples
+/// ```
+/// use tauri_plugin_mic_recorder::stop_recording;
+///
+/// let save_path = stop_recording().unwrap();
+/// println!("Recording saved to: {:?}", save_path);
+/// ```
+#[command]
+pub async fn stop_recording<R: Runtime>(
+    app_handle: AppHandle<R>,
+    webview: Webview<R>,
+    options: Option<StopOptions>,
+) -> Result<PathBuf> {
+    let mut entry = AuditEntry::new(
+        AuditOperation::StopRecording,
+        Some(webview.label().to_string()),
+    );
+
+    let options = options.unwrap_or_default();
+    let timeout = options
+        .timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(STOP_TIMEOUT);
+
+    let mut state = lock_state(timeout)?;
+    // Neither fails with `NotRecording` nor stops a recording started since.
+    if let Some(path) = replayed(&state.stop_key, options.idempotency_key.as_deref()) {
+        return Ok(path);
+    }
+
+    let result = stop_locked(&mut state, &mut entry, timeout);
+    if let (Ok(finished), Some(key)) = (&result, options.idempotency_key) {
+        state.stop_key = Some((key, finished.save_path.clone()));
+    }
+    drop(state);
+
+    if let Err(err) = &result {
+        entry.error = Some(err.to_string());
+    }
+
+    audit::append(&get_audit_log_path(&app_handle)?, &entry)?;
+
+    let finished = result?;
+    let save_path = finished.save_path.clone();
+    emit_stopped(&app_handle, &save_path, &entry)?;
+
+    // The result is the file the hook returns, so it has to wait for it.
+    if hooks::has_after_stop() {
+        return finalize_stopped(&app_handle, finished);
+    }
+    tasks::spawn(move || finalize_in_background(&app_handle, finished));
+
+    Ok(save_path)
+}
+
+fn emit_stopped<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    save_path: &Path,
+    entry: &AuditEntry,
+) -> Result<()> {
+    app_handle.emit(
+        events::RECORDING_STOPPED,
+        RecordingStopped {
+            path: save_path.to_path_buf(),
+            duration_ms: entry.duration_ms,
+        },
+    )?;
+    refresh_snapshot(app_handle);
+
+    Ok(())
+}
+
+/// Abandons the recording in progress without waiting for its stream or
+/// writer, e.g. after `stop_recording` timed out because of a hung driver.
+///
+/// Whatever audio had been flushed to disk is kept and its WAV header
+/// repaired. Emits the same events as `stop_recording`.
+///
+/// # Returns
+/// - `Ok(PathBuf)`: Returns the path where the recording file is stored.
+/// - `Err(Error)`: The reason the recording could not be abandoned.
+#[command]
+pub async fn force_stop<R: Runtime>(
+    app_handle: AppHandle<R>,
+    webview: Webview<R>,
+) -> Result<PathBuf> {
+    let mut entry = AuditEntry::new(AuditOperation::ForceStop, Some(webview.label().to_string()));
+
+    let result = abandon(&mut entry);
+    if let Err(err) = &result {
+        entry.error = Some(err.to_string());
+    }
+
+    audit::append(&get_audit_log_path(&app_handle)?, &entry)?;
+
+    let finished = result?;
+    let save_path = finished.save_path.clone();
+    emit_stopped(&app_handle, &save_path, &entry)?;
+
+    if hooks::has_after_stop() {
+        return finalize_stopped(&app_handle, finished);
+    }
+    tasks::spawn(move || finalize_in_background(&app_handle, finished));
+
+    Ok(save_path)
+}
+
+/// Shuts the plugin down: stops the recording in progress, waits until
+/// every stopped recording has been written to disk, and ends all
+/// background threads, such as heartbeats and the permission watcher.
+///
+/// From then on every start attempt fails with `ShutDown`, so a test or an
+/// exiting app can rely on nothing being left running. A recording that
+/// does not stop within `timeoutMs` is abandoned as with `force_stop`.
+/// Also called when the app exits.
+///
+/// # Returns
+/// - `Err(Error)`: `Timeout` if background threads are still running after
+///   `timeoutMs`.
+#[command]
+pub async fn shutdown<R: Runtime>(
+    app_handle: AppHandle<R>,
+    webview: Webview<R>,
+    options: Option<StopOptions>,
+) -> Result<()> {
+    let timeout = options
+        .and_then(|x| x.timeout_ms)
+        .map(Duration::from_millis)
+        .unwrap_or(STOP_TIMEOUT);
+
+    shut_down(&app_handle, Some(webview.label().to_string()), timeout)
+}
+
+pub(crate) fn shut_down<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    label: Option<String>,
+    timeout: Duration,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    tasks::cancel();
+
+    let log_path = get_audit_log_path(app_handle)?;
+    audit::append(
+        &log_path,
+        &AuditEntry::new(AuditOperation::Shutdown, label.clone()),
+    )?;
+
+    let mut entry = AuditEntry::new(AuditOperation::StopRecording, label.clone());
+    let finished = match stop(&mut entry, timeout) {
+        Err(Error::Timeout(_)) => {
+            entry = AuditEntry::new(AuditOperation::ForceStop, label);
+            abandon(&mut entry).map(Some)
+        }
+        Err(Error::NotRecording) => Ok(None),
+        result => result.map(Some),
+    };
+    if let Err(err) = &finished {
+        entry.error = Some(err.to_string());
+    }
+
+    if let Some(finished) = finished? {
+        audit::append(&log_path, &entry)?;
+        emit_stopped(app_handle, &finished.save_path, &entry)?;
+        finalize_in_background(app_handle, finished);
+    }
+
+    match tasks::join(deadline) {
+        0 => Ok(()),
+        pending => Err(Error::Timeout(
+            Message::new(
+                "timeout.shutdown",
+                format!(
+                    "{} background threads did not end within {} ms.",
+                    pending,
+                    timeout.as_millis()
+                ),
+            )
+            .param("pending", pending)
+            .param("timeoutMs", timeout.as_millis()),
+        )),
+    }
+}
+
+fn abandon(entry: &mut AuditEntry) -> Result<Finished> {
+    let mut state = STATE.lock()?;
+    if !state.is_recording.load(Ordering::SeqCst) {
+        return Err(Error::NotRecording);
+    }
+
+    // The stream is dropped on a thread of its own, which is left behind if
+    // the driver never returns.
+    if let Some(stream) = state.stream.lock()?.take() {
+        thread::spawn(move || stream.close());
+    }
+
+    // A writer that is still locked belongs to a wedged callback or stop.
+    let writer = state.writer.try_lock().ok().and_then(|mut x| x.take());
+    state.writer = Arc::new(Mutex::new(None));
+
+    let finished = release(&mut state, writer, true)?;
+    entry.device = finished.device_name.clone();
+    entry.duration_ms = finished.duration_ms;
+    entry.path = Some(finished.save_path.clone());
+
+    Ok(finished)
+}
+
+fn stop(entry: &mut AuditEntry, timeout: Duration) -> Result<Finished> {
+    stop_locked(&mut *lock_state(timeout)?, entry, timeout)
+}
+
+fn stop_locked(state: &mut State, entry: &mut AuditEntry, timeout: Duration) -> Result<Finished> {
+    if !state.is_recording.load(Ordering::SeqCst) {
+        return Err(Error::NotRecording);
+    }
+
+    let finished = finish(state, timeout)?;
+    entry.device = finished.device_name.clone();
+    entry.duration_ms = finished.duration_ms;
+    entry.path = Some(finished.save_path.clone());
+
+    Ok(finished)
+}
+
+/// Finalizes a stopped recording with progress events, reporting a failure
+/// as `recording-error` since nothing is waiting on the result.
+fn finalize_in_background<R: Runtime>(app_handle: &AppHandle<R>, finished: Finished) {
+    finalize_stopped(app_handle, finished).ok();
+}
+
+/// Finalizes a stopped recording with progress events and hands it to the
+/// `on_after_stop` hook, returning where it ended up. A failure is
+/// reported as `recording-error` as well.
+fn finalize_stopped<R: Runtime>(app_handle: &AppHandle<R>, finished: Finished) -> Result<PathBuf> {
+    // Counts the hook and indexing as part of the finalization.
+    let _finalizing = FinalizingGuard::new();
+    let path = finished.save_path.clone();
+    let duration_ms = finished.duration_ms;
+    let device_name = finished.device_name.clone();
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        finished.finalize(|stage, progress| {
+            app_handle
+                .emit(
+                    events::RECORDING_FINALIZING,
+                    RecordingFinalizing {
+                        path: path.clone(),
+                        stage,
+                        progress,
+                    },
+                )
+                .ok();
+        })
+    }))
+    .unwrap_or_else(|payload| Err(Error::from_panic(payload)))
+    .and_then(|sink| after_stop(app_handle, &path, sink));
+
+    let result = match result {
+        Ok((final_path, sink)) => {
+            let info = RecordingInfo::new(final_path.clone(), duration_ms, device_name, None);
+            index_recording(app_handle, info, &sink);
+            app_handle
+                .emit(
+                    events::RECORDING_FINALIZED,
+                    RecordingFinalized {
+                        path: final_path.clone(),
+                        duration_ms,
+                    },
+                )
+                .ok();
+
+            Ok(final_path)
+        }
+        Err(err) => {
+            eprintln!("failed to finalize recording: {}", err);
+            FAILED.store(true, Ordering::SeqCst);
+            app_handle
+                .emit(
+                    events::RECORDING_ERROR,
+                    RecordingError {
+                        error: err.payload(),
+                        path: Some(path),
+                    },
+                )
+                .ok();
+
+            Err(err)
+        }
+    };
+    refresh_snapshot(app_handle);
+
+    result
+}
+
+/// Hands a finalized recording to the `on_after_stop` hook. If the hook
+/// moved or replaced it, its chunks and expiry go with it, and it is
+/// indexed from the new file. Returns where the recording is now, along
+/// with the sink to index it from.
+fn after_stop<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    path: &Path,
+    sink: SharedSink,
+) -> Result<(PathBuf, SharedSink)> {
+    let new_path = hooks::after_stop(path)?;
+    if new_path == path {
+        return Ok((new_path, sink));
+    }
+
+    chunks::rename(path, &new_path);
+    let expiry_path = get_expiry_path(app_handle)?;
+    if let Some(expires_at) = retention::get(&expiry_path, path)? {
+        retention::set(&expiry_path, path, None)?;
+        // Only recordings in the save directory can expire.
+        if retention::validate(&get_save_dir(app_handle)?, &new_path).is_ok() {
+            retention::set(&expiry_path, &new_path, Some(expires_at))?;
+        }
+    }
+
+    let sink = storage::open_file(&new_path)?;
+
+    Ok((new_path, sink))
+}
+
+/// Tears down the stream of the current recording and takes what is needed
+/// to finalize its file, giving up with `Timeout` after `timeout`.
+fn finish(state: &mut State, timeout: Duration) -> Result<Finished> {
+    let stream = state.stream.lock()?.take();
+    let writer = state.writer.clone();
+    let (sender, receiver) = mpsc::channel();
+
+    // If this outlives the timeout, the writer is dropped here instead,
+    // which still updates its header. A writer poisoned by a panic in the
+    // capture callback is still finalized.
+    thread::spawn(move || {
+        if let Some(stream) = stream {
+            stream.close();
+        }
+        sender
+            .send(writer.lock().unwrap_or_else(PoisonError::into_inner).take())
+            .ok();
+    });
+
+    let writer = receiver.recv_timeout(timeout).map_err(|_| {
+        Error::Timeout(
+            Message::new(
+                "timeout.stop",
+                format!(
+                    "The recording did not stop within {} ms, use `force_stop` to abandon it.",
+                    timeout.as_millis()
+                ),
+            )
+            .param("timeoutMs", timeout.as_millis()),
+        )
+    })?;
+
+    release(state, writer, false)
+}
+
+/// Marks the current recording as over and takes what is left of it.
+fn release(state: &mut State, writer: Option<SinkWriter>, salvage: bool) -> Result<Finished> {
+    state.is_recording.store(false, Ordering::SeqCst);
+    state.adaptive = None;
+    state.capture = None;
+    state.session_path = None;
+    state.retention = None;
+
+    let device_name = state.device_name.take();
+    let duration_ms = state
+        .started_at
+        .take()
+        .map(|started_at| started_at.elapsed().as_millis() as u64);
+
+    for cleanup in state.cleanups.drain(..) {
+        cleanup();
+    }
+
+    // Get and clear the save path
+    let save_path = state.save_path.lock()?.take().ok_or(Error::NotRecording)?;
+    let sink = state.sink.take().ok_or(Error::NotRecording)?;
+    let temp_path = state.temp_path.take();
+    state.temp_dir = None;
+
+    // A panicking DSP stage may have poisoned the metadata, which is still
+    // worth keeping.
+    let mut metadata = std::mem::take(
+        &mut *state
+            .metadata
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner),
+    );
+
+    let file_start = std::mem::take(&mut state.file_start);
+    shift_metadata(&mut metadata, file_start);
+
+    Ok(Finished {
+        save_path,
+        device_name,
+        duration_ms,
+        salvage: salvage && writer.is_none(),
+        writer,
+        sink,
+        temp_path,
+        metadata,
+        _finalizing: FinalizingGuard::new(),
+    })
+}
+
+/// Makes metadata collected since the start of the recording relative to a
+/// file that begins `file_start` frames in, e.g. after a redirect.
+fn shift_metadata(metadata: &mut RecordingMetadata, file_start: u64) {
+    if file_start == 0 {
+        return;
+    }
+
+    metadata.time_reference = metadata.time_reference.map(|x| x + file_start);
+    metadata.markers.retain(|marker| marker.frame >= file_start);
+    for marker in metadata.markers.iter_mut() {
+        marker.frame -= file_start;
+    }
+}
+
+/// Finalizes the recording after its stream or the writing in its callback
+/// failed, and notifies the frontend.
+fn handle_stream_error<R: Runtime>(app_handle: &AppHandle<R>, error: Error) {
+    let finished = match STATE.lock() {
+        Ok(mut state) if state.is_recording.load(Ordering::SeqCst) => {
+            // A device that went away is replaced by the input followed.
+            let follows = state.follow_default_input || !state.followed_devices.is_empty();
+            if follows && matches!(error, Error::DeviceNotFound(_)) {
+                match switch_input(app_handle, &mut state, true) {
+                    Ok(changed) => {
+                        if let Some(changed) = changed {
+                            app_handle.emit(events::INPUT_DEVICE_CHANGED, changed).ok();
+                            publish_snapshot(app_handle, &state);
+                        }
+                        return;
+                    }
+                    Err(err) => eprintln!("failed to switch the input: {}", err),
+                }
+            }
+
+            finish(&mut state, STOP_TIMEOUT)
+        }
+        // The recording was stopped before the error could be handled.
+        Ok(_) => return,
+        Err(err) => Err(err.into()),
+    };
+
+    FAILED.store(true, Ordering::SeqCst);
+
+    let mut entry = AuditEntry::new(AuditOperation::StopRecording, None);
+    entry.error = Some(error.to_string());
+
+    let path = match finished {
+        Ok(finished) => {
+            entry.device = finished.device_name.clone();
+            entry.duration_ms = finished.duration_ms;
+            entry.path = Some(finished.save_path.clone());
+
+            let save_path = finished.save_path.clone();
+            let duration_ms = finished.duration_ms;
+            let device_name = finished.device_name.clone();
+            let result = catch_unwind(AssertUnwindSafe(|| finished.finalize(|_, _| {})))
+                .unwrap_or_else(|payload| Err(Error::from_panic(payload)))
+                .and_then(|sink| after_stop(app_handle, &save_path, sink));
+
+            match result {
+                Ok((final_path, sink)) => {
+                    let info =
+                        RecordingInfo::new(final_path.clone(), duration_ms, device_name, None);
+                    index_recording(app_handle, info, &sink);
+                    Some(final_path)
+                }
+                Err(err) => {
+                    eprintln!("failed to finalize recording after stream error: {}", err);
+                    None
+                }
+            }
+        }
+        Err(err) => {
+            eprintln!("failed to finalize recording after stream error: {}", err);
+            None
+        }
+    };
+
+    if let Err(err) = get_audit_log_path(app_handle).and_then(|x| audit::append(&x, &entry)) {
+        eprintln!("failed to write audit log: {}", err);
+    }
+
+    app_handle
+        .emit(
+            events::RECORDING_ERROR,
+            RecordingError {
+                error: error.payload(),
+                path,
+            },
+        )
+        .ok();
+    refresh_snapshot(app_handle);
+}
+
+/// Disables the recorder, so that every start attempt fails with
+/// `RecorderDisabled` until `enable_recorder` is called.
+///
+/// A recording that is in progress is stopped and finalized. Emits
+/// `recorder-disabled`.
+#[command]
+pub async fn disable_recorder<R: Runtime>(
+    app_handle: AppHandle<R>,
+    webview: Webview<R>,
+) -> Result<()> {
+    let label = webview.label().to_string();
+    let log_path = get_audit_log_path(&app_handle)?;
+
+    DISABLED.store(true, Ordering::SeqCst);
+    audit::append(
+        &log_path,
+        &AuditEntry::new(AuditOperation::DisableRecorder, Some(label.clone())),
+    )?;
+
+    let mut entry = AuditEntry::new(AuditOperation::StopRecording, Some(label));
+    match stop(&mut entry, STOP_TIMEOUT) {
+        Ok(finished) => {
+            audit::append(&log_path, &entry)?;
+            emit_stopped(&app_handle, &finished.save_path, &entry)?;
+
+            let app_handle = app_handle.clone();
+            tasks::spawn(move || finalize_in_background(&app_handle, finished));
+        }
+        Err(Error::NotRecording) => {}
+        Err(err) => return Err(err),
+    }
+
+    app_handle.emit(events::RECORDER_DISABLED, ())?;
+    refresh_snapshot(&app_handle);
+
+    Ok(())
+}
+
+/// Enables the recorder again after `disable_recorder`. Emits `recorder-enabled`.
+#[command]
+pub async fn enable_recorder<R: Runtime>(
+    app_handle: AppHandle<R>,
+    webview: Webview<R>,
+) -> Result<()> {
+    DISABLED.store(false, Ordering::SeqCst);
+    audit::append(
+        &get_audit_log_path(&app_handle)?,
+        &AuditEntry::new(
+            AuditOperation::EnableRecorder,
+            Some(webview.label().to_string()),
+        ),
+    )?;
+
+    app_handle.emit(events::RECORDER_ENABLED, ())?;
+    refresh_snapshot(&app_handle);
+
+    Ok(())
+}
+
+/// Copies the audio recorded so far into a separate, playable file next to
+/// the recording, without interrupting it. Markers added so far are
+/// included.
+///
+/// # Returns
+/// - `Ok(PathBuf)`: Returns the path of the snapshot.
+/// - `Err(Error)`: The reason the snapshot could not be taken.
+#[command]
+pub async fn snapshot_recording() -> Result<PathBuf> {
+    let (save_path, sink, len, mut metadata, file_start) = {
+        let state = STATE.lock()?;
+        if !state.is_recording.load(Ordering::SeqCst) {
+            return Err(Error::NotRecording);
+        }
+
+        let capture = state.capture.clone().ok_or(Error::NotRecording)?;
+        let save_path = state.save_path.lock()?.clone().ok_or(Error::NotRecording)?;
+        let sink = state.sink.clone().ok_or(Error::NotRecording)?;
+
+        // The callback waits on the capture rather than skipping the buffer
+        // it cannot write, and only for as long as the flush takes.
+        let _capture = lock_capture(&capture);
+        if let Some(writer) = state.writer.lock()?.as_mut() {
+            writer.flush()?;
+        }
+        let len = sink.len()?;
+        let metadata = state.metadata.lock()?.clone();
+
+        (save_path, sink, len, metadata, state.file_start)
+    };
+
+    let snapshot_path = save_path.with_file_name(format!(
+        "{}-snapshot-{}.wav",
+        save_path.file_stem().unwrap_or_default().to_string_lossy(),
+        Local::now().format("%Y%m%d%H%M%S")
+    ));
+
+    // Only the flushed part is copied, as capture is appending to the rest.
+    // The recording is locked one chunk at a time to keep the callback going.
+    let snapshot = storage::create(&snapshot_path)?;
+    let mut snapshot = snapshot.lock();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut offset = 0;
+    while offset < len {
+        let max = buffer.len().min((len - offset) as usize);
+        let read = sink.read_at(offset, &mut buffer[..max])?;
+        if read == 0 {
+            break;
+        }
+        snapshot.write_all(&buffer[..read])?;
+        offset += read as u64;
+    }
+
+    metadata::repair(&mut **snapshot)?;
+    shift_metadata(&mut metadata, file_start);
+    metadata::append(&mut **snapshot, &metadata)?;
+    snapshot.sync()?;
+
+    Ok(snapshot_path)
+}
+
+/// Continues the recording in progress in a new file at `path`, e.g. after
+/// `recording-io-error` reported that the disk is full.
+///
+/// The audio held in memory since writing failed is written first. The
+/// previous file is finalized as far as its disk allows, and metadata such
+/// as markers is written to the new file once the recording stops. `path`
+/// has to lie in the save directory or the fs scope, see `scoped_path`.
+///
+/// # Returns
+/// - `Ok(RecordingRedirected)`: The new file, and how much audio could not be held.
+/// - `Err(Error)`: The reason the recording could not be redirected.
+#[command]
+pub async fn redirect_recording<R: Runtime>(
+    app_handle: AppHandle<R>,
+    path: PathBuf,
+) -> Result<RecordingRedirected> {
+    let path = scoped_path(&app_handle, &path)?;
+    let mut state = STATE.lock()?;
+    if !state.is_recording.load(Ordering::SeqCst) {
+        return Err(Error::NotRecording);
+    }
+
+    let switched = switch_file(&mut state, &path, None)?;
+    publish_snapshot(&app_handle, &state);
+    if let Some(previous) = switched.writer {
+        previous.finalize().ok();
+    }
+    // What could be written of a file in a temporary directory is moved
+    // to where it was going to be.
+    if let (Some(temp_path), Some(previous_path)) = (&switched.temp_path, &switched.path) {
+        if let Err(err) = storage::move_file(temp_path, previous_path) {
+            eprintln!("failed to move the previous file: {}", err);
+        }
+    }
+
+    Ok(RecordingRedirected {
+        path,
+        previous_path: switched.path,
+        lost_frames: switched.lost_frames,
+    })
+}
+
+/// Finishes the current file and goes on recording into a new one in the
+/// save directory, returning the path of the finished file. No audio is
+/// lost in between, and the finished file is finalized in the background.
+#[command]
+pub async fn split_recording<R: Runtime>(app_handle: AppHandle<R>) -> Result<PathBuf> {
+    let mut state = STATE.lock()?;
+    if !state.is_recording.load(Ordering::SeqCst) {
+        return Err(Error::NotRecording);
+    }
+
+    let file_start = state.file_start;
+    let save_path = get_save_path(&app_handle, state.namespace.as_deref())?;
+    let temp_path = state
+        .temp_dir
+        .as_ref()
+        .and_then(|x| save_path.file_name().map(|name| x.join(name)));
+    let switched = switch_file(&mut state, &save_path, temp_path)?;
+    let save_path = switched.path.ok_or(Error::NotRecording)?;
+    let sink = switched.sink.ok_or(Error::NotRecording)?;
+
+    // Markers past the split belong to the new file.
+    let mut metadata = state
+        .metadata
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    metadata
+        .markers
+        .retain(|marker| marker.frame < state.file_start);
+    shift_metadata(&mut metadata, file_start);
+
+    let frames = state.file_start - file_start;
+    let finished = Finished {
+        save_path: save_path.clone(),
+        device_name: state.device_name.clone(),
+        duration_ms: Some(frames * 1000 / state.sample_rate.max(1) as u64),
+        writer: switched.writer,
+        sink,
+        temp_path: switched.temp_path,
+        salvage: false,
+        metadata,
+        _finalizing: FinalizingGuard::new(),
+    };
+    state.started_at = Some(Instant::now());
+    state.started_at_ms = Some(session::now_ms());
+    publish_snapshot(&app_handle, &state);
+    drop(state);
+
+    if hooks::has_after_stop() {
+        return finalize_stopped(&app_handle, finished);
+    }
+    tasks::spawn(move || finalize_in_background(&app_handle, finished));
+
+    Ok(save_path)
+}
+
+/// The file a recording was writing to before `switch_file`.
+struct SwitchedFile {
+    writer: Option<SinkWriter>,
+    sink: Option<SharedSink>,
+    path: Option<PathBuf>,
+    temp_path: Option<PathBuf>,
+    lost_frames: u64,
+}
+
+/// Moves the recording in progress over to a new file at `path`, starting
+/// with whatever audio was held in memory after a write failure. The file
+/// is written to `temp_path` until it is finalized, if given.
+fn switch_file(state: &mut State, path: &Path, temp_path: Option<PathBuf>) -> Result<SwitchedFile> {
+    let capture = state.capture.clone().ok_or(Error::NotRecording)?;
+    // Holding the capture keeps the callback from writing while the files
+    // are swapped.
+    let mut capture = lock_capture(&capture);
+    let spec = state
+        .writer
+        .lock()?
+        .as_ref()
+        .map(|x| x.spec())
+        .ok_or(Error::NotRecording)?;
+
+    let (mut writer, sink) = storage::create_writer(temp_path.as_deref().unwrap_or(path), spec)?;
+
+    let (samples, lost_samples) = capture.spill.take();
+    for &sample in samples.iter() {
+        dsp::write_sample(&mut writer, sample)?;
+    }
+
+    let previous = state.writer.lock()?.replace(writer);
+    let previous_sink = state.sink.replace(sink);
+    let previous_temp_path = std::mem::replace(&mut state.temp_path, temp_path);
+
+    let channels = spec.channels.max(1) as u64;
+    let previous_path = state.save_path.lock()?.replace(path.to_path_buf());
+    state.file_start =
+        state.position.load(Ordering::Relaxed) - (samples.len() as u64 + lost_samples) / channels;
+
+    if let Some(session_path) = &state.session_path {
+        let result = session::update(session_path, |x| x.segments.push(path.to_path_buf()));
+        if let Err(err) = result {
+            eprintln!("failed to persist the session: {}", err);
+        }
+    }
+    if let Some(Err(err)) = state.retention.as_ref().map(|x| x.apply(path)) {
+        eprintln!("failed to set the expiry: {}", err);
+    }
+
+    Ok(SwitchedFile {
+        writer: previous,
+        sink: previous_sink,
+        path: previous_path,
+        temp_path: previous_temp_path,
+        lost_frames: lost_samples / channels,
+    })
+}
+
+/// Gets whether the app may use the microphone, without prompting the user.
+///
+/// # Returns
+/// - `PermissionState`: `Unknown` on platforms other than Windows and macOS.
+#[command]
+pub async fn get_mic_permission() -> Result<PermissionState> {
+    Ok(permission::state())
+}
+
+/// Asks the user to let the app open the USB device called `device`, e.g.
+/// an audio interface listed with `isUsb`, waiting up to `timeout_ms` for
+/// them to answer the dialog.
+///
+/// # Returns
+/// - `PermissionState`: `Denied` if the user declined or did not answer in
+///   time, and `Unknown` on platforms other than Android, which open USB
+///   audio devices without asking.
+#[command]
+pub async fn request_usb_permission(
+    device: String,
+    timeout_ms: Option<u64>,
+) -> Result<PermissionState> {
+    let timeout = timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(USB_PERMISSION_TIMEOUT);
+
+    permission::request_usb(&device, timeout)
+}
+
+/// Lists the input devices along with the channel counts, sample rates and
+/// sample formats each can record in, e.g. to offer stereo wherever the mic
+/// supports it. Devices that cannot be opened are left out.
+#[command]
+pub async fn list_input_devices() -> Result<Vec<InputDevice>> {
+    let host = cpal::default_host();
+    let default_name = host.default_input_device().and_then(|x| x.name().ok());
+
+    let mut devices = Vec::new();
+    for device in host.input_devices()? {
+        let (Ok(name), Ok(default), Ok(ranges)) = (
+            device.name(),
+            device.default_input_config(),
+            device.supported_input_configs(),
+        ) else {
+            continue;
+        };
+        let ranges = ranges.collect::<Vec<_>>();
+
+        devices.push(InputDevice {
+            is_default: default_name.as_ref() == Some(&name),
+            is_hands_free: transport::is_hands_free(&name).unwrap_or(false),
+            is_usb: transport::is_usb(&name).unwrap_or(false),
+            name,
+            default_config: InputConfig {
+                channels: default.channels(),
+                sample_rate: default.sample_rate().0,
+                sample_format: default.sample_format().to_string(),
+            },
+            channel_counts: channel_counts(&ranges),
+            configs: ranges
+                .iter()
+                .map(|x| InputConfigRange {
+                    channels: x.channels(),
+                    min_sample_rate: x.min_sample_rate().0,
+                    max_sample_rate: x.max_sample_rate().0,
+                    sample_format: x.sample_format().to_string(),
+                })
+                .collect(),
+        });
+    }
+
+    Ok(devices)
+}
+
+/// Gets what the recorder is doing. `Finalizing` means capture has stopped
+/// but a recording is still being written to disk, and `Failed` that the
+/// last recording ended with `recording-error`.
+#[command]
+pub async fn get_recorder_state() -> Result<RecorderState> {
+    Ok(recorder_state(
+        STATE.lock()?.is_recording.load(Ordering::SeqCst),
+    ))
+}
+
+fn recorder_state(is_recording: bool) -> RecorderState {
+    if is_recording {
+        RecorderState::Recording
+    } else if FINALIZING.load(Ordering::SeqCst) > 0 {
+        RecorderState::Finalizing
+    } else if FAILED.load(Ordering::SeqCst) {
+        RecorderState::Failed
+    } else {
+        RecorderState::Idle
+    }
+}
+
+/// Gets a compact snapshot of the recorder, for a window to render the
+/// right state as soon as it opens, e.g. in the middle of a recording. The
+/// same snapshot is broadcast as `recorder-snapshot` to every window
+/// whenever it changes, so a window stays in sync by rendering the one
+/// with the highest `revision`.
+#[command]
+pub async fn get_recorder_snapshot() -> Result<RecorderSnapshot> {
+    Ok(snapshot::get())
+}
+
+/// Gets which optional features were compiled into the plugin and can be
+/// used on this platform, so a frontend can hide what would only fail.
+#[command]
+pub async fn get_capabilities() -> Result<Capabilities> {
+    Ok(capabilities::get())
+}
+
+/// Gets the audit log of all recorder operations, oldest first.
+///
+/// # Returns
+/// - `Ok(Vec<AuditEntry>)`: Every operation that has been recorded in the audit log.
+/// - `Err(Error)`: The reason the audit log could not be read.
+#[command]
+pub async fn get_audit_log<R: Runtime>(app_handle: AppHandle<R>) -> Result<Vec<AuditEntry>> {
+    audit::read(&get_audit_log_path(&app_handle)?)
+}
+
+/// Sets the most storage the recordings of a namespace may take up. Once
+/// it is used up, `start_recording` fails with `QuotaExceeded`; a recording
+/// in progress is never cut short.
+///
+/// # Arguments
+/// - `namespace`: The namespace, e.g. a user or workspace id.
+/// - `quota_bytes`: The quota in bytes, or `None` to remove it.
+#[command]
+pub async fn set_namespace_quota<R: Runtime>(
+    app_handle: AppHandle<R>,
+    namespace: String,
+    quota_bytes: Option<u64>,
+) -> Result<()> {
+    quota::validate(&namespace)?;
+    quota::set(&get_quota_path(&app_handle)?, &namespace, quota_bytes)
+}
+
+/// Gets how much storage the recordings of a namespace take up.
+///
+/// # Arguments
+/// - `namespace`: The namespace, or `None` for recordings made without one.
+#[command]
+pub async fn get_namespace_usage<R: Runtime>(
+    app_handle: AppHandle<R>,
+    namespace: Option<String>,
+) -> Result<NamespaceUsage> {
+    let mut dir = get_save_dir(&app_handle)?;
+    let mut quota_bytes = None;
+
+    if let Some(namespace) = &namespace {
+        quota::validate(namespace)?;
+        dir.push(namespace);
+        quota_bytes = quota::read(&get_quota_path(&app_handle)?)?
+            .get(namespace)
+            .copied();
+    }
+
+    quota::usage(&dir, namespace, quota_bytes)
+}
+
+/// Gets the usage of every namespace that has recordings or a quota, and
+/// of the recordings made without one.
+#[command]
+pub async fn list_namespace_usage<R: Runtime>(
+    app_handle: AppHandle<R>,
+) -> Result<Vec<NamespaceUsage>> {
+    let save_dir = get_save_dir(&app_handle)?;
+    let quotas = quota::read(&get_quota_path(&app_handle)?)?;
+
+    let mut namespaces: Vec<String> = quotas.keys().cloned().collect();
+    for entry in std::fs::read_dir(&save_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        if entry.file_type()?.is_dir() && !namespaces.contains(&name) {
+            namespaces.push(name);
+        }
+    }
+    namespaces.sort();
+
+    let mut usages = vec![quota::usage(&save_dir, None, None)?];
+    for namespace in namespaces {
+        let quota_bytes = quotas.get(&namespace).copied();
+        usages.push(quota::usage(
+            &save_dir.join(&namespace),
+            Some(namespace),
+            quota_bytes,
+        )?);
+    }
+
+    Ok(usages)
+}
+
+/// Gets the session interrupted by a crash shortly before the app was
+/// started, if it was recorded with `resume` set.
+///
+/// # Returns
+/// - `Ok(Option<ResumableSession>)`: The recorded segments of the session, if there is one.
+/// - `Err(Error)`: The reason the session could not be read.
+#[command]
+pub async fn get_resumable_session() -> Result<Option<ResumableSession>> {
+    Ok(session::resumable()?.map(|x| x.summary()))
+}
+
+/// Resumes the session interrupted by a crash, recording into a new segment
+/// next to its previous ones with the options it was started with.
+///
+/// `align` and `overdub` are left out, as their timing refers to the start of
+/// the session. The keys of an encrypted stream and of a watermark are not
+/// kept with the session, so they have to be given again as `stream_key` and
+/// `watermark_key`.
+///
+/// # Returns
+/// - `Ok(PathBuf)`: The path of the new segment.
+/// - `Err(Error)`: The reason the session could not be resumed.
+#[command]
+pub async fn resume_session<R: Runtime>(
+    app_handle: AppHandle<R>,
+    webview: Webview<R>,
+    on_audio: Option<JavaScriptChannelId>,
+    stream_key: Option<String>,
+    watermark_key: Option<String>,
+) -> Result<PathBuf> {
+    let mut entry = AuditEntry::new(
+        AuditOperation::ResumeSession,
+        Some(webview.label().to_string()),
+    );
+
+    let session = session::resumable()?.ok_or(Error::InvalidOptions(Message::new(
+        "invalidOptions.noSession",
+        "There is no session to resume.",
+    )))?;
+    let mut options = session.options.clone();
+    options.align = None;
+    options.overdub = None;
+    if let Some(encryption) = options.stream.as_mut().and_then(|x| x.encryption.as_mut()) {
+        encryption.key = stream_key.unwrap_or_default();
+    }
+    if let Some(watermark) = options.watermark.as_mut() {
+        watermark.key = watermark_key.unwrap_or_default();
+    }
+
+    let on_audio = on_audio.map(|x| x.channel_on(webview.clone()));
+    let result = start(&app_handle, options, on_audio, Some(session), &mut entry);
+
+    Ok(audited_start(&app_handle, result, entry)?.path)
+}
+
+/// Writes a recording template to a JSON file, so the same setup can be
+/// imported on another machine.
+#[command]
+pub async fn export_template<R: Runtime>(
+    app_handle: AppHandle<R>,
+    path: PathBuf,
+    template: RecordingTemplate,
+) -> Result<()> {
+    template::export(&scoped_path(&app_handle, &path)?, &template)
+}
+
+/// Reads a recording template written by `export_template`, whose options
+/// can be passed to `start_recording`. The key of a watermark is not written
+/// to templates, so it has to be given as `watermark_key`.
+///
+/// # Returns
+/// - `Ok(RecordingTemplate)`: The template, with defaults for the options it lacks.
+/// - `Err(Error)`: `InvalidOptions` if the file is from a newer version of the plugin.
+#[command]
+pub async fn import_template(
+    path: PathBuf,
+    watermark_key: Option<String>,
+) -> Result<RecordingTemplate> {
+    let mut template = template::import(&path)?;
+    if let Some(watermark) = template.options.watermark.as_mut() {
+        watermark.key = watermark_key.unwrap_or_default();
+    }
+
+    Ok(template)
+}
+
+/// Discards the session interrupted by a crash, so it is no longer offered.
+#[command]
+pub async fn discard_session<R: Runtime>(app_handle: AppHandle<R>) -> Result<()> {
+    if session::take_resumable()?.is_some() {
+        session::remove(&get_session_path(&app_handle)?)?;
+    }
+
+    Ok(())
+}
+
+/// Checks whether a recording carries the watermark embedded with `key`.
+///
+/// # Returns
+/// - `Ok(WatermarkDetection)`: Whether the watermark was found, and how confidently.
+/// - `Err(Error)`: The reason the recording could not be analyzed.
+#[command]
+pub async fn detect_watermark<R: Runtime>(
+    app_handle: AppHandle<R>,
+    path: PathBuf,
+    key: String,
+) -> Result<WatermarkDetection> {
+    watermark::detect(&scoped_path(&app_handle, &path)?, &key)
+}
+
+/// Estimates the tempo of a finished recording and where its beats fall.
+///
+/// # Returns
+/// - `Ok(TempoDetection)`: The tempo in BPM, if there is a steady beat, and the beat positions.
+/// - `Err(Error)`: The reason the recording could not be analyzed.
+#[command]
+pub async fn detect_tempo(path: PathBuf, options: Option<TempoOptions>) -> Result<TempoDetection> {
+    tempo::detect(&path, &options.unwrap_or_default())
+}
+
+/// Computes an acoustic fingerprint of a finished recording, so that copies
+/// of it can be recognized even after re-encoding.
+///
+/// # Returns
+/// - `Ok(AudioFingerprint)`: The fingerprint and the duration of the recording.
+/// - `Err(Error)`: The reason the recording could not be analyzed.
+#[command]
+pub async fn fingerprint_recording(path: PathBuf) -> Result<AudioFingerprint> {
+    fingerprint::compute(&path)
+}
+
+/// Compares two fingerprints from `fingerprint_recording`.
+///
+/// # Returns
+/// - `f32`: How alike the recordings are, from 0 to 1. Unrelated audio scores around 0.6.
+#[command]
+pub async fn compare_fingerprints(a: Vec<u32>, b: Vec<u32>) -> f32 {
+    fingerprint::similarity(&a, &b)
+}
+
+/// Applies channel manipulation to a finished recording, writing the result
+/// next to it with a `-channels` suffix.
+///
+/// # Returns
+/// - `Ok(PathBuf)`: Returns the path of the processed file.
+/// - `Err(Error)`: The reason the recording could not be processed.
+#[command]
+pub async fn process_channels<R: Runtime>(
+    app_handle: AppHandle<R>,
+    path: PathBuf,
+    options: ChannelOptions,
+) -> Result<PathBuf> {
+    let path = scoped_path(&app_handle, &path)?;
+    let output_path = scoped_path(
+        &app_handle,
+        &path.with_file_name(format!(
+            "{}-channels.wav",
+            path.file_stem().unwrap_or_default().to_string_lossy()
+        )),
+    )?;
+
+    let mut reader = WavReader::open(&path)?;
+    let spec = reader.spec();
+    let channels = spec.channels as usize;
+
+    let mut writer = WavWriter::create(&output_path, spec)?;
+    let mut mixer = ChannelMixer::new(options);
+    let mut frame = Vec::with_capacity(channels);
+    let mut position = 0;
+
+    for sample in dsp::read_samples(&mut reader) {
+        frame.push(sample?);
+
+        if frame.len() == channels {
+            mixer.process(&mut frame, channels, position);
+            position += 1;
+            for &sample in frame.iter() {
+                dsp::write_sample(&mut writer, sample)?;
+            }
+            frame.clear();
+        }
+    }
+
+    writer.finalize()?;
+
+    Ok(output_path)
+}
+
+/// Gets the metadata stored in a recording, such as its BWF time reference
+/// and markers.
+///
+/// # Returns
+/// - `Ok(RecordingMetadata)`: The metadata read from the recording.
+/// - `Err(Error)`: The reason the recording could not be read.
+#[command]
+pub async fn get_recording_metadata(path: PathBuf) -> Result<RecordingMetadata> {
+    metadata::read(&path)
+}
+
+/// Detects long silences in a finished recording and stores a chapter
+/// marker wherever the audio resumes, replacing earlier chapter markers.
+///
+/// # Returns
+/// - `Ok(Vec<Marker>)`: Returns all markers of the recording.
+/// - `Err(Error)`: The reason the recording could not be processed.
+#[command]
+pub async fn detect_chapters(
+    path: PathBuf,
+    options: Option<ChapterOptions>,
+) -> Result<Vec<Marker>> {
+    let mut reader = WavReader::open(&path)?;
+    let spec = reader.spec();
+    let channels = spec.channels as usize;
+
+    let chapters = MetadataHandle::default();
+    let mut marker = ChapterMarker::new(
+        &options.unwrap_or_default(),
+        spec.sample_rate,
+        chapters.clone(),
+    );
+    let mut buffer = Vec::with_capacity(channels * 1024);
+    let mut position = 0;
+
+    for sample in dsp::read_samples(&mut reader) {
+        buffer.push(sample?);
+
+        if buffer.len() == buffer.capacity() {
+            marker.process(&mut buffer, channels, position);
+            position += (buffer.len() / channels) as u64;
+            buffer.clear();
+        }
+    }
+    marker.process(&mut buffer, channels, position);
+    drop(reader);
+
+    let chapters = std::mem::take(&mut chapters.lock()?.markers);
+    let metadata = metadata::update(&path, |metadata| {
+        metadata
+            .markers
+            .retain(|marker| !marker.label.starts_with("Chapter "));
+        metadata.markers.extend(chapters);
+        metadata.markers.sort_by_key(|marker| marker.frame);
+    })?;
+
+    Ok(metadata.markers)
+}
+
+/// Exports a finished recording next to it as an M4A, or an M4B
+/// audiobook, with a chapter starting at each of its markers, e.g. the
+/// chapters found by `detect_chapters`. The audio is stored as 16-bit
+/// Apple Lossless.
+///
+/// # Returns
+/// - `Ok(PathBuf)`: Returns the path of the exported file.
+/// - `Err(Error)`: The reason the recording could not be exported.
+#[command]
+pub async fn export_m4a(path: PathBuf, options: Option<M4aOptions>) -> Result<PathBuf> {
+    let options = options.unwrap_or_default();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let title = options.title.unwrap_or_else(|| stem.to_string());
+    let (extension, brand) = if options.audiobook {
+        ("m4b", *b"M4B ")
+    } else {
+        ("m4a", *b"M4A ")
+    };
+
+    let metadata = metadata::read(&path)?;
+    let mut markers = metadata.markers;
+    markers.sort_by_key(|marker| marker.frame);
+
+    let mut chapters: Vec<Cue> = Vec::new();
+    for marker in markers {
+        let start_ms = marker.frame * 1000 / metadata.sample_rate.max(1) as u64;
+        let text = if marker.label.is_empty() {
+            format!("Chapter {}", chapters.len() + 1)
+        } else {
+            marker.label
+        };
+
+        // Markers within the same millisecond start a single chapter.
+        match chapters.last_mut() {
+            Some(last) if last.start_ms == start_ms => last.text = text,
+            _ => chapters.push(Cue {
+                start_ms,
+                end_ms: None,
+                text,
+            }),
+        }
+    }
+    // Players expect the first chapter to start with the file.
+    if chapters.first().is_some_and(|x| x.start_ms > 0) {
+        chapters.insert(
+            0,
+            Cue {
+                start_ms: 0,
+                end_ms: None,
+                text: title.clone(),
+            },
+        );
+    }
+
+    let output_path = path.with_file_name(format!("{stem}.{extension}"));
+    let tracks = [TextTrack {
+        kind: TextKind::Chapters,
+        cues: chapters,
+    }];
+    write_mp4(&path, &output_path, brand, &tracks, &title)?;
+
+    Ok(output_path)
+}
+
+/// Writes a finished recording to an MPEG-4 file along with text tracks.
+fn write_mp4(
+    path: &Path,
+    output_path: &Path,
+    brand: [u8; 4],
+    tracks: &[TextTrack],
+    title: &str,
+) -> Result<()> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+
+    if spec.channels > 2 {
+        return Err(Error::InvalidOptions(
+            Message::new(
+                "invalidOptions.exportChannels",
+                format!(
+                    "Only mono and stereo recordings can be exported, not {} channels.",
+                    spec.channels
+                ),
+            )
+            .param("channels", spec.channels),
+        ));
+    }
+
+    let mut writer = Mp4Writer::create(output_path, brand, spec.sample_rate, spec.channels)?;
+    for sample in dsp::read_samples(&mut reader) {
+        writer.write(sample?)?;
+    }
+
+    writer.finish(tracks, Some(title))
+}
+
+/// Exports a finished recording next to it as an MP4 with subtitle tracks
+/// for reviewing it in video players and archival tools: a running
+/// timestamp, the labels of its markers, and any timed captions passed in,
+/// such as the segments of a transcript. It is written with a `-captioned`
+/// suffix.
+///
+/// # Returns
+/// - `Ok(PathBuf)`: Returns the path of the exported file.
+/// - `Err(Error)`: The reason the recording could not be exported.
+#[command]
+pub async fn export_captioned(path: PathBuf, options: Option<CaptionOptions>) -> Result<PathBuf> {
+    let options = options.unwrap_or_default();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let metadata = metadata::read(&path)?;
+    let sample_rate = metadata.sample_rate.max(1) as u64;
+    let mut tracks = Vec::new();
+
+    if options.timestamps {
+        let seconds = WavReader::open(&path)?.duration() as u64 / sample_rate;
+        // The time of day if the recording carries one, and otherwise the
+        // time since its start.
+        let first = metadata.time_reference.map_or(0, |x| x / sample_rate);
+
+        tracks.push(TextTrack {
+            kind: TextKind::Subtitles,
+            cues: (0..=seconds)
+                .map(|second| {
+                    let time = (first + second) % 86_400;
+                    Cue {
+                        start_ms: second * 1000,
+                        end_ms: None,
+                        text: format!("{:02}:{:02}:{:02}", time / 3600, time / 60 % 60, time % 60),
+                    }
+                })
+                .collect(),
+        });
+    }
+
+    if options.markers {
+        tracks.push(TextTrack {
+            kind: TextKind::Subtitles,
+            cues: metadata
+                .markers
+                .into_iter()
+                .filter(|marker| !marker.label.is_empty())
+                .map(|marker| Cue {
+                    start_ms: marker.frame * 1000 / sample_rate,
+                    end_ms: None,
+                    text: marker.label,
+                })
+                .collect(),
+        });
+    }
+
+    if !options.captions.is_empty() {
+        tracks.push(TextTrack {
+            kind: TextKind::Subtitles,
+            cues: options
+                .captions
+                .into_iter()
+                .map(|caption| Cue {
+                    start_ms: caption.start_ms,
+                    end_ms: Some(caption.end_ms),
+                    text: caption.text,
+                })
+                .collect(),
+        });
+    }
+
+    let output_path = path.with_file_name(format!("{stem}-captioned.mp4"));
+    write_mp4(&path, &output_path, *b"mp42", &tracks, &stem)?;
+
+    Ok(output_path)
+}
+
+/// Writes a manifest with the size, SHA-256 hash and duration of every file
+/// in a recordings folder and its subfolders to `checksums.json` in it,
+/// replacing an earlier one.
+///
+/// # Returns
+/// - `Ok(ChecksumManifest)`: Returns the manifest that was written.
+/// - `Err(Error)`: The reason the folder could not be hashed.
+#[command]
+pub async fn create_manifest(directory: PathBuf) -> Result<ChecksumManifest> {
+    manifest::create(&directory)
+}
+
+/// Hashes the files of a folder again and compares them to its
+/// `checksums.json`, to check a backup or detect bit rot.
+///
+/// # Returns
+/// - `Ok(ManifestVerification)`: Returns the files that are missing, changed
+///   or new.
+/// - `Err(Error)`: The reason the folder could not be checked.
+#[command]
+pub async fn verify_manifest(directory: PathBuf) -> Result<ManifestVerification> {
+    manifest::verify(&directory)
+}
+
+/// Adds a marker at the current position of the recording in progress.
+/// Emits `marker-added`.
+///
+/// # Returns
+/// - `Ok(Marker)`: Returns the marker that was added.
+/// - `Err(Error)`: The reason the marker could not be added.
+#[command]
+pub async fn add_marker<R: Runtime>(
+    app_handle: AppHandle<R>,
+    label: Option<String>,
+) -> Result<Marker> {
+    add_marker_inner(&app_handle, label)
+}
+
+fn add_marker_inner<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    label: Option<String>,
+) -> Result<Marker> {
+    let state = STATE.lock()?;
+    if !state.is_recording.load(Ordering::SeqCst) {
+        return Err(Error::NotRecording);
+    }
+
+    let frame = state.position.load(Ordering::Relaxed);
+    let marker = {
+        let mut metadata = state.metadata.lock()?;
+        let bookmarks = metadata
+            .markers
+            .iter()
+            .filter(|marker| marker.label.starts_with("Bookmark "))
+            .count();
+
+        let marker = Marker {
+            frame,
+            label: label.unwrap_or_else(|| format!("Bookmark {}", bookmarks + 1)),
+        };
+        metadata.markers.push(marker.clone());
+
+        marker
+    };
+
+    app_handle.emit(
+        events::MARKER_ADDED,
+        MarkerAdded {
+            frame,
+            time_ms: frame * 1000 / state.sample_rate.max(1) as u64,
+            label: marker.label.clone(),
+        },
+    )?;
+
+    Ok(marker)
+}
+
+/// Gets the directory where the plugin stores its files.
+fn get_save_dir<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf> {
+    let save_dir = app_handle
+        .path()
+        .app_data_dir()?
+        .join("tauri-plugin-mic-recorder");
+
+    create_dir_all(&save_dir)?;
+
+    Ok(save_dir)
+}
+
+/// Resolves a path the webview passed in, relative to the save directory,
+/// and checks that it lies in the save directory or in the scope of the fs
+/// plugin, if the app uses it, so the webview cannot reach files the app
+/// has not allowed.
+fn scoped_path<R: Runtime>(app_handle: &AppHandle<R>, path: &Path) -> Result<PathBuf> {
+    let save_dir = get_save_dir(app_handle)?;
+    let path = save_dir.join(path);
+
+    let allowed = !path.components().any(|x| x == Component::ParentDir)
+        && (path.starts_with(&save_dir)
+            || app_handle
+                .try_fs_scope()
+                .is_some_and(|scope| scope.is_allowed(&path)));
+    if !allowed {
+        return Err(Error::InvalidOptions(
+            Message::new(
+                "invalidOptions.pathNotAllowed",
+                format!(
+                    "{} is neither in the save directory nor in the fs scope.",
+                    path.display()
+                ),
+            )
+            .param("path", path.display()),
+        ));
+    }
+
+    Ok(path)
+}
+
+/// Gets the path of the append-only audit log.
+fn get_audit_log_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf> {
+    Ok(get_save_dir(app_handle)?.join("audit.log"))
+}
+
+/// Gets the path of the file a resumable session is persisted in.
+fn get_session_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf> {
+    Ok(get_save_dir(app_handle)?.join("session.json"))
+}
+
+/// Looks for a session interrupted by a crash when the plugin is set up.
+pub(crate) fn recover_session<R: Runtime>(app_handle: &AppHandle<R>) {
+    if let Err(err) = get_session_path(app_handle).and_then(|x| session::recover(&x)) {
+        eprintln!("failed to recover the session: {}", err);
+    }
+}
+
+/// Emits `session-resumable` if a session interrupted by a crash is on
+/// offer, e.g. once a webview has been created.
+pub(crate) fn offer_session<R: Runtime>(app_handle: &AppHandle<R>) {
+    if let Ok(Some(session)) = session::resumable() {
+        app_handle
+            .emit(events::SESSION_RESUMABLE, session.summary())
+            .ok();
+    }
+}
+
+/// Emits `mic-permission-changed` whenever the microphone permission
+/// changes while the app runs.
+pub(crate) fn watch_permission<R: Runtime>(app_handle: &AppHandle<R>) {
+    let app_handle = app_handle.clone();
+
+    permission::watch(move |state, previous| {
+        app_handle
+            .emit(
+                events::MIC_PERMISSION_CHANGED,
+                MicPermissionChanged { state, previous },
+            )
+            .ok();
+    });
+}
+
+/// Gets the path of the index of finished recordings.
+fn get_library_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf> {
+    Ok(get_save_dir(app_handle)?.join("library.json"))
+}
+
+/// Adds a finalized recording to the index, along with the namespace its
+/// directory stands for.
+fn index_recording<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    mut info: RecordingInfo,
+    sink: &SharedSink,
+) {
+    let result = get_save_dir(app_handle).and_then(|save_dir| {
+        let dir = info.path.parent().unwrap_or(&save_dir);
+        // A file an `on_after_stop` hook moved elsewhere has no namespace.
+        if dir != save_dir && dir.parent() == Some(&save_dir) {
+            info.namespace = dir.file_name().map(|x| x.to_string_lossy().to_string());
+        }
+        info.size_bytes = sink.len()?;
+
+        // Hashed before the recording is indexed, where the uploader can
+        // find it.
+        for chunk in chunks::finish(&info.path, sink)? {
+            app_handle
+                .emit(
+                    events::CHUNK_HASHED,
+                    ChunkHashed {
+                        path: info.path.clone(),
+                        chunk,
+                        uploaded: false,
+                    },
+                )
+                .ok();
+        }
+
+        library::add(&get_library_path(app_handle)?, info.clone())?;
+        webhook::notify(
+            &get_webhook_path(app_handle)?,
+            WebhookEvent::Finalized,
+            &info,
+        );
+
+        // Only the tail is left to upload, so it goes out right away.
+        if chunks::get(&info.path).is_some() {
+            upload_soon(app_handle);
+        }
+
+        Ok(())
+    });
+
+    if let Err(err) = result {
+        eprintln!("failed to index the recording: {}", err);
+    }
+}
+
+/// Updates the title, tags, transcript or custom metadata of any number of
+/// recordings in the index at once.
+///
+/// # Returns
+/// - `Ok(Vec<RecordingInfo>)`: The updated recordings, in the order of `updates`.
+/// - `Err(Error)`: `InvalidOptions` if a recording is not in the index, in
+///   which case none is updated.
+#[command]
+pub async fn update_recordings<R: Runtime>(
+    app_handle: AppHandle<R>,
+    updates: Vec<RecordingUpdate>,
+) -> Result<Vec<RecordingInfo>> {
+    library::update(&get_library_path(&app_handle)?, updates)
+}
+
+/// Records the outcome of the app's uploads in the index, in a single write
+/// for all given recordings. Emits `sync-state-changed` for each of them.
+///
+/// Pending, failed or uploaded recordings are found with `search_recordings`.
+///
+/// # Returns
+/// - `Ok(Vec<RecordingInfo>)`: The updated recordings, in the order of `updates`.
+/// - `Err(Error)`: `InvalidOptions` if a recording is not in the index, in
+///   which case none is updated.
+#[command]
+pub async fn update_sync_states<R: Runtime>(
+    app_handle: AppHandle<R>,
+    updates: Vec<SyncUpdate>,
+) -> Result<Vec<RecordingInfo>> {
+    apply_sync_updates(&app_handle, updates)
+}
+
+/// Records sync states in the index, calls the webhook for new uploads and
+/// emits `sync-state-changed`.
+fn apply_sync_updates<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    updates: Vec<SyncUpdate>,
+) -> Result<Vec<RecordingInfo>> {
+    let updated = library::update_sync(&get_library_path(app_handle)?, updates)?;
+    let webhook_path = get_webhook_path(app_handle)?;
+
+    Ok(updated
+        .into_iter()
+        .map(|(info, previous)| {
+            if info.sync.status == SyncStatus::Uploaded && previous.status != SyncStatus::Uploaded {
+                webhook::notify(&webhook_path, WebhookEvent::Uploaded, &info);
+            }
+            app_handle
+                .emit(
+                    events::SYNC_STATE_CHANGED,
+                    SyncStateChanged {
+                        path: info.path.clone(),
+                        state: info.sync.clone(),
+                        previous: previous.status,
+                    },
+                )
+                .ok();
+            info
+        })
+        .collect())
+}
+
+/// Lists the recordings in the index one page at a time, sorted by when
+/// they were recorded, their duration or their size.
+///
+/// # Returns
+/// - `Ok(RecordingPage)`: The page starting at `offset`, and how many recordings there are in all.
+#[command]
+pub async fn list_recordings<R: Runtime>(
+    app_handle: AppHandle<R>,
+    options: Option<ListOptions>,
+) -> Result<RecordingPage> {
+    library::list(
+        &get_library_path(&app_handle)?,
+        &options.unwrap_or_default(),
+    )
+}
+
+/// Finds recordings in the index that are likely copies of each other, e.g.
+/// after a sync mishap: those that are identical byte for byte and, if
+/// asked for, those whose fingerprints match. Nothing is deleted.
+///
+/// # Returns
+/// - `Ok(Vec<DuplicateGroup>)`: The groups of copies, those freeing the most storage first.
+#[command]
+pub async fn find_duplicates<R: Runtime>(
+    app_handle: AppHandle<R>,
+    options: Option<DuplicateOptions>,
+) -> Result<Vec<DuplicateGroup>> {
+    let recordings = library::all(&get_library_path(&app_handle)?)?;
+    Ok(duplicates::find(recordings, &options.unwrap_or_default()))
+}
+
+/// Searches the index for recordings by text and filters, newest first.
+///
+/// # Returns
+/// - `Ok(RecordingPage)`: The page of matches starting at `offset`, and how many there are in all.
+#[command]
+pub async fn search_recordings<R: Runtime>(
+    app_handle: AppHandle<R>,
+    query: SearchQuery,
+) -> Result<RecordingPage> {
+    library::search(&get_library_path(&app_handle)?, &query)
+}
+
+/// Gets the path of the file the upload schedule is persisted in.
+fn get_upload_schedule_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf> {
+    Ok(get_save_dir(app_handle)?.join("uploads.json"))
+}
+
+/// Sets when and how fast the uploader set up with the plugin uploads
+/// recordings. It is kept across restarts and applies to uploads in
+/// progress within about a second.
+///
+/// # Returns
+/// - `Err(Error)`: `InvalidOptions` if a window is not made of times of day as `HH:MM`.
+#[command]
+pub async fn set_upload_schedule<R: Runtime>(
+    app_handle: AppHandle<R>,
+    schedule: UploadSchedule,
+) -> Result<()> {
+    schedule.validate()?;
+    upload::set(&get_upload_schedule_path(&app_handle)?, &schedule)
+}
+
+/// Gets the schedule set with `set_upload_schedule`.
+///
+/// # Returns
+/// - `Ok(UploadSchedule)`: The schedule, or the default one if none was set.
+#[command]
+pub async fn get_upload_schedule<R: Runtime>(app_handle: AppHandle<R>) -> Result<UploadSchedule> {
+    upload::get(&get_upload_schedule_path(&app_handle)?)
+}
+
+/// Uploads the pending recordings, and the failed ones that have retries
+/// left, oldest first, for as long as the schedule allows.
+fn upload_pending<R: Runtime>(app_handle: &AppHandle<R>, uploader: &dyn Uploader) -> Result<()> {
+    let _guard = upload::UPLOAD_LOCK.lock()?;
+    let schedule_path = get_upload_schedule_path(app_handle)?;
+    let schedule = upload::get(&schedule_path)?;
+    let mut recordings = library::all(&get_library_path(app_handle)?)?;
+    recordings.retain(|x| match x.sync.status {
+        SyncStatus::Pending => true,
+        SyncStatus::Failed => x.sync.failures <= schedule.max_retries,
+        SyncStatus::Uploaded => false,
+    });
+    recordings.sort_by_key(|x| x.recorded_at);
+
+    for info in recordings {
+        if tasks::is_cancelled() || !schedule.allows_now() {
+            break;
+        }
+
+        let update = match upload_recording(uploader, &info, &schedule, &schedule_path) {
+            Ok(None) => break,
+            Ok(Some(remote)) => SyncUpdate {
+                path: info.path,
+                status: SyncStatus::Uploaded,
+                error: None,
+                remote,
+            },
+            Err(err) => SyncUpdate {
+                path: info.path,
+                status: SyncStatus::Failed,
+                error: Some(err.to_string()),
+                remote: None,
+            },
+        };
+
+        // The recording may have been deleted in the meantime.
+        if let Err(err) = apply_sync_updates(app_handle, vec![update]) {
+            eprintln!("failed to record the upload: {}", err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Uploads one recording, or the chunks of it that are still missing if
+/// they were uploaded while it was captured. Returns `None` if the upload
+/// has to wait for the schedule, and where it was uploaded to otherwise.
+fn upload_recording(
+    uploader: &dyn Uploader,
+    info: &RecordingInfo,
+    schedule: &UploadSchedule,
+    schedule_path: &Path,
+) -> Result<Option<Option<String>>> {
+    let mut body = storage::open(&info.path)?;
+
+    let Some(recording_chunks) = chunks::get(&info.path) else {
+        let mut body = Throttled::new(body, schedule, schedule_path);
+        let result = uploader.upload(info, &mut body);
+        if body.is_paused() {
+            return Ok(None);
+        }
+        return Ok(Some(result?));
+    };
+
+    for (chunk, uploaded) in &recording_chunks {
+        let mut part = Read::take(&mut body, chunk.len);
+        if *uploaded {
+            io::copy(&mut part, &mut io::sink())?;
+            continue;
+        }
+
+        let mut part = Throttled::new(part, schedule, schedule_path);
+        let result = uploader.upload_chunk(&info.path, chunk, &mut part);
+        if part.is_paused() {
+            return Ok(None);
+        }
+        result?;
+        chunks::mark_uploaded(&info.path, chunk);
+    }
+
+    let recording_chunks: Vec<_> = recording_chunks.into_iter().map(|(x, _)| x).collect();
+    let remote = uploader.complete_chunks(info, &recording_chunks)?;
+    chunks::forget(&info.path);
+
+    Ok(Some(remote))
+}
+
+/// Runs a single round of uploads on a separate thread, e.g. once a
+/// recording whose chunks were uploaded while it was captured is done.
+fn upload_soon<R: Runtime>(app_handle: &AppHandle<R>) {
+    let Some(uploader) = upload::uploader() else {
+        return;
+    };
+    let app_handle = app_handle.clone();
+
+    tasks::spawn(move || {
+        if let Err(err) = upload_pending(&app_handle, uploader.as_ref()) {
+            eprintln!("failed to upload recordings: {}", err);
+        }
+    });
+}
+
+/// Uploads recordings with the uploader set up with the plugin every
+/// `UPLOAD_INTERVAL`, until it is shut down. Without an uploader, nothing
+/// is spawned.
+pub(crate) fn watch_uploads<R: Runtime>(app_handle: &AppHandle<R>) {
+    let Some(uploader) = upload::uploader() else {
+        return;
+    };
+    let app_handle = app_handle.clone();
+
+    tasks::spawn(move || loop {
+        if let Err(err) = upload_pending(&app_handle, uploader.as_ref()) {
+            eprintln!("failed to upload recordings: {}", err);
+        }
+
+        if !tasks::sleep(UPLOAD_INTERVAL) {
+            break;
+        }
+    });
+}
+
+/// Gets the path of the file the webhook is persisted in.
+fn get_webhook_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf> {
+    Ok(get_save_dir(app_handle)?.join("webhook.json"))
+}
+
+/// Sets the webhook that is called after a recording has been finalized or
+/// uploaded, replacing the previous one, or removes it when `options` is
+/// `None`. It is kept across restarts.
+///
+/// # Returns
+/// - `Err(Error)`: `InvalidOptions` if the URL is not an `http` URL or a
+///   header is invalid.
+#[command]
+pub async fn set_webhook<R: Runtime>(
+    app_handle: AppHandle<R>,
+    options: Option<WebhookOptions>,
+) -> Result<()> {
+    if let Some(options) = &options {
+        options.validate()?;
+    }
+
+    webhook::set(&get_webhook_path(&app_handle)?, options.as_ref())
+}
+
+/// Gets the webhook set with `set_webhook`.
+///
+/// # Returns
+/// - `Ok(Option<WebhookOptions>)`: The webhook, or `None` if there is none.
+#[command]
+pub async fn get_webhook<R: Runtime>(app_handle: AppHandle<R>) -> Result<Option<WebhookOptions>> {
+    webhook::get(&get_webhook_path(&app_handle)?)
+}
+
+/// Gets the path of the file holding when recordings expire.
+fn get_expiry_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf> {
+    Ok(get_save_dir(app_handle)?.join("expiry.json"))
+}
+
+/// Deletes the expired recordings, emitting `recording-expired` for each.
+fn delete_expired<R: Runtime>(app_handle: &AppHandle<R>) -> Result<Vec<PathBuf>> {
+    // Taken up front, as `start_recording` sets the expiry while it holds
+    // the state.
+    let save_path = STATE.lock()?.save_path.clone();
+    let deleted = retention::sweep(&get_expiry_path(app_handle)?, |path| {
+        let save_path = save_path.lock().unwrap_or_else(PoisonError::into_inner);
+        save_path.as_deref() == Some(path)
+    })?;
+
+    library::remove(&get_library_path(app_handle)?, &deleted)?;
+
+    for path in deleted.iter() {
+        app_handle
+            .emit(
+                events::RECORDING_EXPIRED,
+                RecordingExpired { path: path.clone() },
+            )
+            .ok();
+    }
+
+    Ok(deleted)
+}
+
+/// Deletes expired recordings when the plugin is set up and then every
+/// `RETENTION_INTERVAL`, until it is shut down.
+pub(crate) fn watch_retention<R: Runtime>(app_handle: &AppHandle<R>) {
+    let app_handle = app_handle.clone();
+
+    tasks::spawn(move || loop {
+        if let Err(err) = delete_expired(&app_handle) {
+            eprintln!("failed to delete expired recordings: {}", err);
+        }
+
+        if !tasks::sleep(RETENTION_INTERVAL) {
+            break;
+        }
+    });
+}
+
+/// Sets when a recording is deleted, as Unix time in milliseconds, or keeps
+/// it for good when `expires_at` is `None`. Only recordings in the save
+/// directory can expire.
+#[command]
+pub async fn set_recording_expiry<R: Runtime>(
+    app_handle: AppHandle<R>,
+    path: PathBuf,
+    expires_at: Option<u64>,
+) -> Result<()> {
+    retention::validate(&get_save_dir(&app_handle)?, &path)?;
+    retention::set(&get_expiry_path(&app_handle)?, &path, expires_at)
+}
+
+/// Gets when a recording is deleted, as Unix time in milliseconds.
+///
+/// # Returns
+/// - `Ok(Option<u64>)`: `None` if the recording does not expire.
+#[command]
+pub async fn get_recording_expiry<R: Runtime>(
+    app_handle: AppHandle<R>,
+    path: PathBuf,
+) -> Result<Option<u64>> {
+    retention::get(&get_expiry_path(&app_handle)?, &path)
+}
+
+/// Deletes the recordings that have expired right away, instead of waiting
+/// for the hourly sweep.
+///
+/// # Returns
+/// - `Ok(Vec<PathBuf>)`: The recordings that were deleted.
+#[command]
+pub async fn delete_expired_recordings<R: Runtime>(
+    app_handle: AppHandle<R>,
+) -> Result<Vec<PathBuf>> {
+    delete_expired(&app_handle)
+}
+
+/// Gets the path of the file holding the namespace quotas.
+fn get_quota_path<R: Runtime>(app_handle: &AppHandle<R>) -> Result<PathBuf> {
+    Ok(get_save_dir(app_handle)?.join("quotas.json"))
+}
+
+/// Fails with `QuotaExceeded` once a namespace has used up its quota.
+fn check_quota<R: Runtime>(app_handle: &AppHandle<R>, namespace: &str) -> Result<()> {
+    quota::validate(namespace)?;
+
+    let Some(&quota_bytes) = quota::read(&get_quota_path(app_handle)?)?.get(namespace) else {
+        return Ok(());
+    };
+    let usage = quota::usage(
+        &get_save_dir(app_handle)?.join(namespace),
+        Some(namespace.to_string()),
+        Some(quota_bytes),
+    )?;
+
+    if usage.used_bytes >= quota_bytes {
+        return Err(Error::QuotaExceeded(
+            Message::new(
+                "quotaExceeded.namespace",
+                format!(
+                    "The namespace \"{namespace}\" has used {} of its {quota_bytes} bytes.",
+                    usage.used_bytes
+                ),
+            )
+            .param("namespace", namespace)
+            .param("usedBytes", usage.used_bytes)
+            .param("quotaBytes", quota_bytes),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Gets the path where the recording file is stored.
+fn get_save_path<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    namespace: Option<&str>,
+) -> Result<PathBuf> {
+    let mut save_dir = get_save_dir(app_handle)?;
+    if let Some(namespace) = namespace {
+        save_dir.push(namespace);
+        create_dir_all(&save_dir)?;
+    }
+
+    let timestamp = Local::now().format("%Y%m%d%H%M%S").to_string();
+    let mut save_path = save_dir.join(format!("{timestamp}.wav"));
+
+    // Splitting a recording can start a new file within the same second.
+    let mut last_save_path = LAST_SAVE_PATH.lock()?;
+    let mut index = 1;
+    while storage::exists(&save_path) || last_save_path.as_ref() == Some(&save_path) {
+        save_path = save_dir.join(format!("{timestamp}-{index}.wav"));
+        index += 1;
+    }
+    *last_save_path = Some(save_path.clone());
+
+    Ok(save_path)
+}
+
+/// Gets where a recording bound for `save_path` is written while it is in
+/// progress with `options.temp_dir`.
+fn get_temp_path(temp_dir: &Path, save_path: &Path, options: &RecordingOptions) -> Result<PathBuf> {
+    if !storage::is_local() {
+        return Err(Error::InvalidOptions(Message::new(
+            "invalidOptions.tempDirStorage",
+            "The `tempDir` option only works with recordings written to files.",
+        )));
+    }
+    // A session is resumed from the files it was written to.
+    if options.resume.is_some() {
+        return Err(Error::InvalidOptions(Message::new(
+            "invalidOptions.tempDirResume",
+            "The `tempDir` option cannot be combined with `resume`.",
+        )));
+    }
+
+    create_dir_all(temp_dir)?;
+
+    Ok(temp_dir.join(save_path.file_name().unwrap_or_default()))
+}
+
+/// Converts a cpal::SampleFormat to a hound::SampleFormat.
+fn sample_format(format: cpal::SampleFormat) -> SampleFormat {
+    if format.is_float() {
+        SampleFormat::Float
+    } else {
+        SampleFormat::Int
+    }
+}
+
+/// Gets the config to capture `device` in: the default one, or the closest
+/// to it with `channel_count` channels and samples of at least `bit_depth`
+/// bits.
+fn input_config(
+    device: &cpal::Device,
+    channel_count: Option<u16>,
+    bit_depth: Option<u16>,
+) -> Result<cpal::SupportedStreamConfig> {
+    let default = device.default_input_config()?;
+    let channel_count = channel_count.unwrap_or(default.channels());
+    let precise = |format: cpal::SampleFormat| {
+        bit_depth.map_or(true, |bits| format.sample_size() * 8 >= bits as usize)
+    };
+    if default.channels() == channel_count && precise(default.sample_format()) {
+        return Ok(default);
+    }
+
+    // Prefers keeping the default sample format, then integer samples, then
+    // the default sample rate, falling back to the rate closest to it.
+    let sample_rate = default.sample_rate();
+    let ranges = device.supported_input_configs()?.collect::<Vec<_>>();
+    let best = ranges
+        .iter()
+        .filter(|x| x.channels() == channel_count && precise(x.sample_format()))
+        .filter(|x| CAPTURE_FORMATS.contains(&x.sample_format()))
+        .min_by_key(|x| {
+            let rate = sample_rate.clamp(x.min_sample_rate(), x.max_sample_rate());
+            (
+                x.sample_format() != default.sample_format(),
+                x.sample_format().is_float(),
+                rate.0.abs_diff(sample_rate.0),
+            )
+        });
+    if let Some(range) = best {
+        let rate = sample_rate.clamp(range.min_sample_rate(), range.max_sample_rate());
+        return Ok(range.with_sample_rate(rate));
+    }
+
+    let counts = channel_counts(&ranges);
+    if let (true, Some(bit_depth)) = (counts.contains(&channel_count), bit_depth) {
+        return Err(Error::InvalidOptions(
+            Message::new(
+                "invalidOptions.bitDepth",
+                format!(
+                    "The device cannot capture {bit_depth}-bit audio in {channel_count} channels."
+                ),
+            )
+            .param("bitDepth", bit_depth)
+            .param("channelCount", channel_count),
+        ));
+    }
+
+    let supported = counts
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Err(Error::InvalidOptions(
+        Message::new(
+            "invalidOptions.channelCount",
+            format!("The device cannot record {channel_count} channels, only {supported}."),
+        )
+        .param("channelCount", channel_count)
+        .param("supported", supported),
+    ))
+}
+
+/// The distinct channel counts among `ranges`, in ascending order.
+fn channel_counts(ranges: &[cpal::SupportedStreamConfigRange]) -> Vec<u16> {
+    let mut counts = ranges.iter().map(|x| x.channels()).collect::<Vec<_>>();
+    counts.sort_unstable();
+    counts.dedup();
+    counts
+}
+
+/// Creates a WavSpec from a cpal::SupportedStreamConfig, with integer
+/// samples of `bit_depth` bits if set.
+fn wav_spec_from_config(config: &cpal::SupportedStreamConfig, bit_depth: Option<u16>) -> WavSpec {
+    let bits_per_sample = (config.sample_format().sample_size() * 8) as u16;
+
+    WavSpec {
+        channels: config.channels() as _,
+        sample_rate: config.sample_rate().0 as _,
+        bits_per_sample: bit_depth.unwrap_or(bits_per_sample),
+        sample_format: match bit_depth {
+            Some(bits) if bits != bits_per_sample || !config.sample_format().is_float() => {
+                SampleFormat::Int
+            }
+            _ => sample_format(config.sample_format()),
+        },
     }
 }
@@ -0,0 +1,237 @@
+use crate::{
+    error::{Error, Message, Result},
+    models::AlignOptions,
+};
+use std::{
+    sync::{Arc, OnceLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+enum Target {
+    /// Start the file on the previous multiple of this many milliseconds.
+    Boundary(u64),
+    /// Start the file at this Unix time in milliseconds.
+    StartAt(u64),
+    /// Start the file at a Unix time in milliseconds that is only known
+    /// once something else has started, e.g. a backing track.
+    Signal(Arc<OnceLock<f64>>),
+}
+
+/// How the first input buffers have to be adjusted to start on the target.
+pub(crate) struct Alignment {
+    /// Frames of silence to write before the input.
+    pub pad_frames: u64,
+    /// Leading frames of the input to drop.
+    pub skip_frames: usize,
+}
+
+/// Lines the first written sample up with a wall-clock boundary or a shared
+/// start signal, by padding with silence or dropping early input.
+pub(crate) struct Aligner {
+    target: Target,
+    sample_rate: u32,
+    done: bool,
+}
+
+impl Aligner {
+    pub fn new(options: &AlignOptions, sample_rate: u32) -> Result<Self> {
+        let target = match (options.boundary_ms, options.start_at) {
+            (Some(0), None) => {
+                return Err(Error::InvalidOptions(Message::new(
+                    "invalidOptions.alignBoundary",
+                    "The alignment boundary must be greater than zero.",
+                )))
+            }
+            (Some(boundary_ms), None) => Target::Boundary(boundary_ms),
+            (None, Some(start_at)) => Target::StartAt(start_at),
+            _ => {
+                return Err(Error::InvalidOptions(Message::new(
+                    "invalidOptions.alignTarget",
+                    "Exactly one of `boundaryMs` and `startAt` must be set.",
+                )))
+            }
+        };
+
+        Ok(Self {
+            target,
+            sample_rate,
+            done: false,
+        })
+    }
+
+    /// Starts the file at the time `start` is set to, dropping the input
+    /// until then.
+    pub fn at_signal(start: Arc<OnceLock<f64>>, sample_rate: u32) -> Self {
+        Self {
+            target: Target::Signal(start),
+            sample_rate,
+            done: false,
+        }
+    }
+
+    /// Whether the recording has been aligned, so input can be written as is.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Called with every input buffer until the recording is aligned.
+    ///
+    /// `latency` is how long ago the first frame of the buffer was captured.
+    pub fn align(&mut self, frames: usize, latency: Duration) -> Option<Alignment> {
+        let captured_at = SystemTime::now()
+            .checked_sub(latency)
+            .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default()
+            .as_secs_f64()
+            * 1000.0;
+
+        self.align_at(frames, captured_at)
+    }
+
+    /// Aligns a buffer whose first frame was captured at `captured_at`, as
+    /// Unix time in milliseconds.
+    fn align_at(&mut self, frames: usize, captured_at: f64) -> Option<Alignment> {
+        if self.done {
+            return None;
+        }
+
+        let target_ms = match self.target {
+            Target::Boundary(boundary_ms) => {
+                (captured_at / boundary_ms as f64).floor() * boundary_ms as f64
+            }
+            Target::StartAt(start_at) => start_at as f64,
+            Target::Signal(ref start) => match start.get() {
+                Some(&start) => start,
+                None => {
+                    return Some(Alignment {
+                        pad_frames: 0,
+                        skip_frames: frames,
+                    })
+                }
+            },
+        };
+
+        let offset_frames = (captured_at - target_ms) / 1000.0 * self.sample_rate as f64;
+
+        if offset_frames >= 0.0 {
+            self.done = true;
+
+            return Some(Alignment {
+                pad_frames: offset_frames.round() as u64,
+                skip_frames: 0,
+            });
+        }
+
+        // The start signal lies ahead, so the input is dropped until it arrives.
+        let skip_frames = (-offset_frames).round() as usize;
+        self.done = skip_frames < frames;
+
+        Some(Alignment {
+            pad_frames: 0,
+            skip_frames: skip_frames.min(frames),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+    /// 10 ms at 48 kHz.
+    const BUFFER_FRAMES: usize = 480;
+
+    fn aligner(boundary_ms: Option<u64>, start_at: Option<u64>) -> Aligner {
+        let options = AlignOptions {
+            boundary_ms,
+            start_at,
+        };
+
+        Aligner::new(&options, SAMPLE_RATE).unwrap()
+    }
+
+    /// Feeds 10 ms buffers captured from `captured_at` on until the aligner
+    /// is done, and returns when the first written frame was captured, or
+    /// would have been for padding.
+    fn first_frame_at(aligner: &mut Aligner, mut captured_at: f64) -> f64 {
+        let ms_per_frame = 1000.0 / SAMPLE_RATE as f64;
+
+        loop {
+            let alignment = aligner.align_at(BUFFER_FRAMES, captured_at).unwrap();
+            if aligner.is_done() {
+                return captured_at + alignment.skip_frames as f64 * ms_per_frame
+                    - alignment.pad_frames as f64 * ms_per_frame;
+            }
+
+            assert_eq!(alignment.pad_frames, 0);
+            assert_eq!(alignment.skip_frames, BUFFER_FRAMES);
+            captured_at += BUFFER_FRAMES as f64 * ms_per_frame;
+        }
+    }
+
+    #[test]
+    fn pads_back_to_the_boundary() {
+        let mut aligner = aligner(Some(1000), None);
+
+        let alignment = aligner
+            .align_at(BUFFER_FRAMES, 1_700_000_012_345.5)
+            .unwrap();
+        assert_eq!(alignment.pad_frames, 16_584);
+        assert_eq!(alignment.skip_frames, 0);
+        assert!(aligner.is_done());
+        assert!(aligner
+            .align_at(BUFFER_FRAMES, 1_700_000_012_355.5)
+            .is_none());
+    }
+
+    #[test]
+    fn starts_exactly_on_the_boundary() {
+        let mut aligner = aligner(Some(500), None);
+
+        assert_eq!(first_frame_at(&mut aligner, 1_000_123.25), 1_000_000.0);
+        assert!(aligner.align_at(BUFFER_FRAMES, 1_000_133.25).is_none());
+    }
+
+    #[test]
+    fn drops_the_input_until_a_later_start() {
+        let mut aligner = aligner(None, Some(10_055));
+
+        assert_eq!(first_frame_at(&mut aligner, 10_000.0), 10_055.0);
+    }
+
+    #[test]
+    fn pads_up_to_an_earlier_start() {
+        let mut aligner = aligner(None, Some(9_990));
+
+        let alignment = aligner.align_at(BUFFER_FRAMES, 10_000.0).unwrap();
+        assert_eq!(alignment.pad_frames, 480);
+        assert!(aligner.is_done());
+    }
+
+    #[test]
+    fn waits_for_the_signal() {
+        let start = Arc::new(OnceLock::new());
+        let mut aligner = Aligner::at_signal(start.clone(), SAMPLE_RATE);
+
+        for captured_at in [10_000.0, 10_010.0, 10_020.0] {
+            let alignment = aligner.align_at(BUFFER_FRAMES, captured_at).unwrap();
+            assert_eq!(alignment.skip_frames, BUFFER_FRAMES);
+            assert!(!aligner.is_done());
+        }
+
+        start.set(10_032.5).unwrap();
+        assert_eq!(first_frame_at(&mut aligner, 10_030.0), 10_032.5);
+    }
+
+    #[test]
+    fn rejects_a_zero_boundary_and_ambiguous_targets() {
+        for (boundary_ms, start_at) in [(Some(0), None), (Some(1000), Some(5)), (None, None)] {
+            let options = AlignOptions {
+                boundary_ms,
+                start_at,
+            };
+
+            assert!(Aligner::new(&options, SAMPLE_RATE).is_err());
+        }
+    }
+}
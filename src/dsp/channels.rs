@@ -0,0 +1,42 @@
+use super::Processor;
+use crate::models::{Channel, ChannelOptions};
+
+/// Rewires the first two channels of every frame. Mono input is left alone.
+pub(crate) struct ChannelMixer {
+    options: ChannelOptions,
+}
+
+impl ChannelMixer {
+    pub fn new(options: ChannelOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl Processor for ChannelMixer {
+    fn process(&mut self, samples: &mut [f32], channels: usize, _position: u64) {
+        if channels < 2 {
+            return;
+        }
+
+        for frame in samples.chunks_mut(channels) {
+            let (mut left, mut right) = (frame[0], frame[1]);
+
+            if self.options.swap {
+                (left, right) = (right, left);
+            }
+
+            match self.options.invert {
+                Some(Channel::Left) => left = -left,
+                Some(Channel::Right) => right = -right,
+                None => {}
+            }
+
+            if self.options.mid_side {
+                (left, right) = ((left + right) * 0.5, (left - right) * 0.5);
+            }
+
+            frame[0] = left;
+            frame[1] = right;
+        }
+    }
+}
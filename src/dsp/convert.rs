@@ -0,0 +1,249 @@
+use cpal::{FromSample, Sample};
+use std::f64::consts::TAU;
+
+/// The cutoff of the anti-aliasing filter relative to the output sample
+/// rate, a little below its Nyquist frequency.
+const CUTOFF: f64 = 0.45;
+/// The Q of the two sections of a fourth-order Butterworth low-pass.
+const BUTTERWORTH_Q: [f64; 2] = [0.541_196_1, 1.306_563];
+
+/// A second-order low-pass section, run on every channel of a frame.
+struct Biquad {
+    b: [f32; 3],
+    a: [f32; 2],
+    /// The transposed direct form II state of every channel.
+    state: Vec<[f32; 2]>,
+}
+
+impl Biquad {
+    fn low_pass(cutoff: f64, sample_rate: f64, q: f64, channels: usize) -> Self {
+        let w0 = TAU * cutoff / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha;
+
+        Self {
+            b: [
+                ((1.0 - cos) / 2.0 / a0) as f32,
+                ((1.0 - cos) / a0) as f32,
+                ((1.0 - cos) / 2.0 / a0) as f32,
+            ],
+            a: [(-2.0 * cos / a0) as f32, ((1.0 - alpha) / a0) as f32],
+            state: vec![[0.0; 2]; channels],
+        }
+    }
+
+    fn process(&mut self, frame: &mut [f32]) {
+        for (sample, state) in frame.iter_mut().zip(self.state.iter_mut()) {
+            let input = *sample;
+            let output = self.b[0] * input + state[0];
+            state[0] = self.b[1] * input - self.a[0] * output + state[1];
+            state[1] = self.b[2] * input - self.a[1] * output;
+            *sample = output;
+        }
+    }
+}
+
+/// Converts the input of a device the recording moved over to into the
+/// file's channel count and sample rate, so the file keeps its format.
+/// Input at a higher sample rate is low-pass filtered first, so what lies
+/// above the file's Nyquist frequency does not fold back as aliasing.
+pub(crate) struct Converter {
+    input_channels: usize,
+    output_channels: usize,
+    /// The input frames per output frame.
+    step: f64,
+    /// How far the next output frame lies between `previous` and the
+    /// current input frame.
+    phase: f64,
+    previous: Vec<f32>,
+    frame: Vec<f32>,
+    output: Vec<f32>,
+    /// The anti-aliasing filter, when downsampling.
+    filters: Vec<Biquad>,
+}
+
+impl Converter {
+    pub fn new(
+        input_channels: usize,
+        input_sample_rate: u32,
+        output_channels: usize,
+        output_sample_rate: u32,
+    ) -> Self {
+        let output_channels = output_channels.max(1);
+        let output_sample_rate = output_sample_rate.max(1);
+
+        let filters = if input_sample_rate > output_sample_rate {
+            BUTTERWORTH_Q
+                .iter()
+                .map(|&q| {
+                    Biquad::low_pass(
+                        CUTOFF * output_sample_rate as f64,
+                        input_sample_rate as f64,
+                        q,
+                        output_channels,
+                    )
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            input_channels: input_channels.max(1),
+            output_channels,
+            step: input_sample_rate as f64 / output_sample_rate as f64,
+            phase: 0.0,
+            previous: vec![0.0; output_channels],
+            frame: vec![0.0; output_channels],
+            output: Vec::new(),
+            filters,
+        }
+    }
+
+    /// Converts interleaved input, interpolating linearly between frames.
+    pub fn process<T>(&mut self, input: &[T]) -> &[f32]
+    where
+        T: Sample,
+        f32: FromSample<T>,
+    {
+        self.output.clear();
+
+        for frame in input.chunks_exact(self.input_channels) {
+            self.map_channels(frame);
+            for filter in &mut self.filters {
+                filter.process(&mut self.frame);
+            }
+
+            while self.phase < 1.0 {
+                let phase = self.phase as f32;
+                self.output.extend(
+                    self.previous
+                        .iter()
+                        .zip(self.frame.iter())
+                        .map(|(a, b)| a + (b - a) * phase),
+                );
+                self.phase += self.step;
+            }
+            self.phase -= 1.0;
+            std::mem::swap(&mut self.previous, &mut self.frame);
+        }
+
+        &self.output
+    }
+
+    /// Maps one input frame onto the output channels: mono is spread over
+    /// every channel, a mono output gets the average, and otherwise the
+    /// channels both sides have are kept and the rest are silent.
+    fn map_channels<T>(&mut self, input: &[T])
+    where
+        T: Sample,
+        f32: FromSample<T>,
+    {
+        if self.input_channels == 1 {
+            self.frame.fill(f32::from_sample(input[0]));
+        } else if self.output_channels == 1 {
+            let sum = input.iter().map(|&x| f32::from_sample(x)).sum::<f32>();
+            self.frame[0] = sum / self.input_channels as f32;
+        } else {
+            self.frame.fill(0.0);
+            for (output, &sample) in self.frame.iter_mut().zip(input) {
+                *output = f32::from_sample(sample);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(hz: f64, sample_rate: u32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|x| (TAU * hz * x as f64 / sample_rate as f64).sin() as f32)
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn keeps_the_ratio_of_the_sample_rates() {
+        for (input_rate, output_rate) in [(48_000, 16_000), (44_100, 48_000), (16_000, 44_100)] {
+            let mut converter = Converter::new(1, input_rate, 1, output_rate);
+            let len = converter.process(&vec![0.0_f32; input_rate as usize]).len();
+
+            assert!(
+                len.abs_diff(output_rate as usize) <= 1,
+                "{input_rate} Hz: {len}"
+            );
+        }
+    }
+
+    #[test]
+    fn carries_the_phase_across_buffers() {
+        let input = tone(440.0, 44_100, 4410);
+
+        let whole = Converter::new(1, 44_100, 1, 48_000)
+            .process(&input)
+            .to_vec();
+        let mut converter = Converter::new(1, 44_100, 1, 48_000);
+        let mut chunked = Vec::new();
+        for chunk in input.chunks(137) {
+            chunked.extend_from_slice(converter.process(chunk));
+        }
+
+        assert_eq!(whole, chunked);
+    }
+
+    #[test]
+    fn passes_the_same_rate_through_a_frame_late() {
+        let mut converter = Converter::new(1, 48_000, 1, 48_000);
+
+        assert_eq!(converter.process(&[0.5_f32, -0.25, 1.0]), [0.0, 0.5, -0.25]);
+        assert_eq!(converter.process(&[0.0_f32]), [1.0]);
+    }
+
+    #[test]
+    fn interpolates_between_frames() {
+        let mut converter = Converter::new(1, 24_000, 1, 48_000);
+
+        assert_eq!(converter.process(&[1.0_f32, 0.0]), [0.0, 0.5, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn maps_the_channels() {
+        let mut converter = Converter::new(1, 48_000, 2, 48_000);
+        assert_eq!(converter.process(&[0.5_f32, 0.0]), [0.0, 0.0, 0.5, 0.5]);
+
+        let mut converter = Converter::new(2, 48_000, 1, 48_000);
+        assert_eq!(converter.process(&[0.5_f32, -0.25, 0.0, 0.0]), [0.0, 0.125]);
+
+        let mut converter = Converter::new(4, 48_000, 2, 48_000);
+        let frames = [0.5_f32, -0.5, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0];
+        assert_eq!(converter.process(&frames), [0.0, 0.0, 0.5, -0.5]);
+
+        let mut converter = Converter::new(2, 48_000, 4, 48_000);
+        let frames = [0.5_f32, -0.5, 0.0, 0.0];
+        assert_eq!(converter.process(&frames)[4..], [0.5, -0.5, 0.0, 0.0]);
+
+        let mut converter = Converter::new(1, 48_000, 1, 48_000);
+        assert_eq!(converter.process(&[i16::MIN, 0]), [0.0, -1.0]);
+    }
+
+    #[test]
+    fn filters_what_would_alias_when_downsampling() {
+        // Without the filter, 12 kHz would fold back to 4 kHz at full level.
+        let passed = Converter::new(1, 48_000, 1, 16_000)
+            .process(&tone(1_000.0, 48_000, 48_000))
+            .to_vec();
+        let aliased = Converter::new(1, 48_000, 1, 16_000)
+            .process(&tone(12_000.0, 48_000, 48_000))
+            .to_vec();
+
+        // Past the filter's settling time.
+        assert!(rms(&passed[1600..]) > 0.65);
+        assert!(rms(&aliased[1600..]) < 0.1);
+    }
+}
@@ -0,0 +1,191 @@
+use super::read_samples;
+use crate::error::Result;
+use hound::WavReader;
+use serde::Serialize;
+use std::{f32::consts::PI, path::Path};
+
+/// The rate the input is analyzed at, as only its pitch content matters.
+const SAMPLE_RATE: f64 = 11025.0;
+const FRAME_LEN: usize = 4096;
+const HOP_LEN: usize = FRAME_LEN / 3;
+/// The frequency range mapped onto the chroma bins, in hertz.
+const MIN_FREQUENCY: f32 = 28.0;
+const MAX_FREQUENCY: f32 = 3520.0;
+/// How far apart two fingerprints are searched for a match, in frames.
+const MAX_OFFSET: usize = 120;
+
+/// A compact description of how a recording's pitch content changes over
+/// time, which survives re-encoding, resampling and level changes.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioFingerprint {
+    pub duration_ms: u64,
+    /// One 32-bit value per eighth of a second.
+    pub fingerprint: Vec<u32>,
+}
+
+/// Computes a chromaprint-style fingerprint of a WAV file.
+pub(crate) fn compute(path: &Path) -> Result<AudioFingerprint> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+    let step = spec.sample_rate as f64 / SAMPLE_RATE;
+
+    // Mixed down and averaged into the analysis rate.
+    let mut samples = Vec::new();
+    let (mut mono, mut sum, mut count, mut phase) = (0.0, 0.0, 0, 0.0);
+    let mut frames = 0_u64;
+    for (index, sample) in read_samples(&mut reader).enumerate() {
+        mono += sample?;
+        if (index + 1) % channels != 0 {
+            continue;
+        }
+
+        sum += mono / channels as f32;
+        count += 1;
+        mono = 0.0;
+        frames += 1;
+        phase += 1.0;
+
+        if phase >= step {
+            samples.push(sum / count as f32);
+            sum = 0.0;
+            count = 0;
+            phase -= step;
+        }
+    }
+
+    let window: Vec<f32> = (0..FRAME_LEN)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FRAME_LEN as f32).cos())
+        .collect();
+    let chroma_bins: Vec<Option<usize>> = (0..FRAME_LEN / 2)
+        .map(|bin| {
+            let frequency = bin as f32 * SAMPLE_RATE as f32 / FRAME_LEN as f32;
+            (MIN_FREQUENCY..MAX_FREQUENCY)
+                .contains(&frequency)
+                .then(|| {
+                    let note = 12.0 * (frequency / 440.0).log2() + 69.0;
+                    (note.round() as i64).rem_euclid(12) as usize
+                })
+        })
+        .collect();
+
+    let mut real = vec![0.0; FRAME_LEN];
+    let mut imaginary = vec![0.0; FRAME_LEN];
+    let mut previous = [0.0; 12];
+    let mut fingerprint = Vec::new();
+
+    for start in (0..samples.len().saturating_sub(FRAME_LEN - 1)).step_by(HOP_LEN) {
+        for i in 0..FRAME_LEN {
+            real[i] = samples[start + i] * window[i];
+            imaginary[i] = 0.0;
+        }
+        fft(&mut real, &mut imaginary);
+
+        let mut chroma = [0.0_f32; 12];
+        for (bin, pitch) in chroma_bins.iter().enumerate() {
+            if let Some(pitch) = pitch {
+                chroma[*pitch] += real[bin] * real[bin] + imaginary[bin] * imaginary[bin];
+            }
+        }
+        let norm = chroma.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            chroma.iter_mut().for_each(|x| *x /= norm);
+        }
+
+        fingerprint.push(subfingerprint(&chroma, &previous));
+        previous = chroma;
+    }
+
+    Ok(AudioFingerprint {
+        duration_ms: frames * 1000 / spec.sample_rate.max(1) as u64,
+        fingerprint,
+    })
+}
+
+/// Encodes the shape of a chroma vector, and how it moved since the
+/// previous one, as the signs of differences between its bins.
+fn subfingerprint(chroma: &[f32; 12], previous: &[f32; 12]) -> u32 {
+    let mut bits = 0;
+
+    for i in 0..12 {
+        bits |= ((chroma[i] > chroma[(i + 1) % 12]) as u32) << i;
+        bits |= ((chroma[i] > previous[i]) as u32) << (12 + i);
+    }
+    // Pairs of neighbouring bins give the last bits some tolerance for
+    // slightly detuned copies.
+    for i in 0..8 {
+        let pair = chroma[i] + chroma[(i + 1) % 12];
+        let previous = previous[i] + previous[(i + 1) % 12];
+        bits |= ((pair > previous) as u32) << (24 + i);
+    }
+
+    bits
+}
+
+/// How alike two fingerprints are, from 0 to 1, at the offset where they
+/// match best. Unrelated recordings score around 0.6.
+pub(crate) fn similarity(a: &[u32], b: &[u32]) -> f32 {
+    let min_overlap = (a.len().min(b.len()) / 2).max(1);
+    let mut best = 0.0_f32;
+
+    for offset in -(MAX_OFFSET as isize)..=MAX_OFFSET as isize {
+        let (a, b) = if offset < 0 {
+            (a, b.get((-offset) as usize..).unwrap_or_default())
+        } else {
+            (a.get(offset as usize..).unwrap_or_default(), b)
+        };
+        let overlap = a.len().min(b.len());
+        if overlap < min_overlap {
+            continue;
+        }
+
+        let errors: u32 = a
+            .iter()
+            .zip(b.iter())
+            .map(|(x, y)| (x ^ y).count_ones())
+            .sum();
+        best = best.max(1.0 - errors as f32 / (overlap as f32 * 32.0));
+    }
+
+    best
+}
+
+/// An in-place iterative radix-2 FFT. The length must be a power of two.
+fn fft(real: &mut [f32], imaginary: &mut [f32]) {
+    let len = real.len();
+
+    let mut j = 0;
+    for i in 1..len {
+        let mut bit = len >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+
+        if i < j {
+            real.swap(i, j);
+            imaginary.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= len {
+        let angle = -2.0 * PI / size as f32;
+        for start in (0..len).step_by(size) {
+            for k in 0..size / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + size / 2);
+                let tr = real[b] * cos - imaginary[b] * sin;
+                let ti = real[b] * sin + imaginary[b] * cos;
+
+                real[b] = real[a] - tr;
+                imaginary[b] = imaginary[a] - ti;
+                real[a] += tr;
+                imaginary[a] += ti;
+            }
+        }
+        size *= 2;
+    }
+}
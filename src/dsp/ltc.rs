@@ -0,0 +1,286 @@
+use super::Processor;
+use crate::metadata::{Marker, MetadataHandle};
+use std::fmt;
+
+/// The sync word that ends every LTC frame, as bits 64 to 79.
+const SYNC_WORD: u128 = 0xbffc;
+/// The number of bits in an LTC frame.
+const FRAME_BITS: u32 = 80;
+/// The amplitude an input sample must exceed to count as a transition.
+const HYSTERESIS: f32 = 0.02;
+
+/// A SMPTE timecode decoded from LTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Timecode {
+    hours: u8,
+    minutes: u8,
+    seconds: u8,
+    frames: u8,
+    drop_frame: bool,
+}
+
+impl Timecode {
+    fn decode(bits: u128) -> Self {
+        let field = |start: u32, len: u32| ((bits >> start) & ((1 << len) - 1)) as u8;
+
+        Self {
+            frames: field(8, 2) * 10 + field(0, 4),
+            drop_frame: field(10, 1) == 1,
+            seconds: field(24, 3) * 10 + field(16, 4),
+            minutes: field(40, 3) * 10 + field(32, 4),
+            hours: field(56, 2) * 10 + field(48, 4),
+        }
+    }
+
+    fn seconds_since_midnight(&self) -> u32 {
+        (self.hours as u32 * 60 + self.minutes as u32) * 60 + self.seconds as u32
+    }
+
+    fn frame_index(&self, fps: u32) -> u64 {
+        self.seconds_since_midnight() as u64 * fps as u64 + self.frames as u64
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.drop_frame { ';' } else { ':' };
+
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{separator}{:02}",
+            self.hours, self.minutes, self.seconds, self.frames
+        )
+    }
+}
+
+/// Decodes the biphase-mark encoded LTC on one input channel, recording the
+/// start timecode as the BWF time reference and every discontinuity as a
+/// marker.
+pub(crate) struct LtcReader {
+    channel: usize,
+    sample_rate: u32,
+    metadata: MetadataHandle,
+    /// The polarity of the signal since the last transition.
+    positive: bool,
+    /// Samples elapsed since the last transition.
+    interval: f32,
+    /// The running estimate of one bit period in samples.
+    bit_period: f32,
+    /// Whether the first half of a one bit has been seen.
+    half_bit: bool,
+    /// The last 80 decoded bits, the most recent at bit 79.
+    bits: u128,
+    last: Option<Timecode>,
+}
+
+impl LtcReader {
+    pub fn new(channel: usize, sample_rate: u32, metadata: MetadataHandle) -> Self {
+        Self {
+            channel,
+            sample_rate,
+            metadata,
+            positive: false,
+            interval: 0.0,
+            // 25 fps sits between the common rates, so it locks onto any of them.
+            bit_period: sample_rate as f32 / (25 * FRAME_BITS) as f32,
+            half_bit: false,
+            bits: 0,
+            last: None,
+        }
+    }
+
+    fn push_bit(&mut self, bit: bool, frame: u64) {
+        self.bits = (self.bits >> 1) | ((bit as u128) << (FRAME_BITS - 1));
+
+        if (self.bits >> 64) & 0xffff == SYNC_WORD {
+            let timecode = Timecode::decode(self.bits);
+            // The frame the timecode labels began one LTC frame ago.
+            let start = frame.saturating_sub((self.bit_period * FRAME_BITS as f32) as u64);
+            self.on_timecode(timecode, start);
+        }
+    }
+
+    fn on_timecode(&mut self, timecode: Timecode, frame: u64) {
+        let fps = ((self.sample_rate as f32 / (self.bit_period * FRAME_BITS as f32)).round()
+            as u32)
+            .max(1);
+
+        let continuous = self.last.is_some_and(|last| {
+            // Drop-frame timecode skips up to two frame numbers at a time.
+            let step = timecode.frame_index(fps) as i64 - last.frame_index(fps) as i64;
+            (1..=3).contains(&step)
+        });
+        self.last = Some(timecode);
+
+        if continuous {
+            return;
+        }
+
+        let Ok(mut metadata) = self.metadata.lock() else {
+            return;
+        };
+
+        if metadata.time_reference.is_none() {
+            let since_midnight = (timecode.seconds_since_midnight() as f64
+                + timecode.frames as f64 / fps as f64)
+                * self.sample_rate as f64;
+            let day = 86_400 * self.sample_rate as u64;
+
+            metadata.time_reference = Some((since_midnight as u64 + day - frame % day) % day);
+        }
+
+        metadata.markers.push(Marker {
+            frame,
+            label: format!("LTC {timecode}"),
+        });
+    }
+}
+
+impl Processor for LtcReader {
+    fn process(&mut self, samples: &mut [f32], channels: usize, position: u64) {
+        for (index, frame) in samples.chunks(channels).enumerate() {
+            let sample = frame[self.channel];
+            self.interval += 1.0;
+
+            let flipped =
+                (self.positive && sample < -HYSTERESIS) || (!self.positive && sample > HYSTERESIS);
+            if !flipped {
+                continue;
+            }
+
+            self.positive = !self.positive;
+            let interval = std::mem::take(&mut self.interval);
+
+            if interval > self.bit_period * 0.75 {
+                // A transition only at the bit boundary encodes a zero.
+                self.bit_period = self.bit_period * 0.9 + interval * 0.1;
+                self.half_bit = false;
+                self.push_bit(false, position + index as u64);
+            } else if self.half_bit {
+                // A second transition in the middle of the bit encodes a one.
+                self.bit_period = self.bit_period * 0.9 + interval * 2.0 * 0.1;
+                self.half_bit = false;
+                self.push_bit(true, position + index as u64);
+            } else {
+                self.half_bit = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::RecordingMetadata;
+    use std::sync::{Arc, Mutex};
+
+    const SAMPLE_RATE: u32 = 48_000;
+    const FPS: u32 = 25;
+    /// Samples in one bit at 25 fps.
+    const BIT_PERIOD: usize = (SAMPLE_RATE / (FPS * FRAME_BITS)) as usize;
+
+    fn encode(timecode: Timecode) -> u128 {
+        let digits = |value: u8, start: u32| {
+            ((value % 10) as u128) << start | ((value / 10) as u128) << (start + 8)
+        };
+
+        digits(timecode.frames, 0)
+            | (timecode.drop_frame as u128) << 10
+            | digits(timecode.seconds, 16)
+            | digits(timecode.minutes, 32)
+            | digits(timecode.hours, 48)
+            | SYNC_WORD << 64
+    }
+
+    /// Biphase-mark encodes the frames, bit 0 first, at 25 fps.
+    fn signal(timecodes: &[Timecode]) -> Vec<f32> {
+        let mut level = 0.5;
+        let mut samples = Vec::new();
+
+        for bits in timecodes.iter().map(|x| encode(*x)) {
+            for bit in 0..FRAME_BITS {
+                let one = (bits >> bit) & 1 == 1;
+                level = -level;
+                for i in 0..BIT_PERIOD {
+                    if one && i == BIT_PERIOD / 2 {
+                        level = -level;
+                    }
+                    samples.push(level);
+                }
+            }
+        }
+
+        samples
+    }
+
+    fn timecode(hours: u8, minutes: u8, seconds: u8, frames: u8) -> Timecode {
+        Timecode {
+            hours,
+            minutes,
+            seconds,
+            frames,
+            drop_frame: false,
+        }
+    }
+
+    #[test]
+    fn decodes_the_bcd_fields() {
+        let timecode = Timecode {
+            drop_frame: true,
+            ..timecode(23, 59, 48, 27)
+        };
+
+        assert_eq!(Timecode::decode(encode(timecode)), timecode);
+        assert_eq!(timecode.to_string(), "23:59:48;27");
+        assert_eq!(
+            Timecode::decode(encode(timecode)).frame_index(30),
+            2_591_667
+        );
+    }
+
+    #[test]
+    fn marks_the_start_and_every_jump() {
+        let metadata = Arc::new(Mutex::new(RecordingMetadata::default()));
+        let mut reader = LtcReader::new(1, SAMPLE_RATE, metadata.clone());
+
+        let mono = signal(&[
+            timecode(10, 0, 0, 0),
+            timecode(10, 0, 0, 1),
+            timecode(10, 0, 0, 2),
+            timecode(12, 34, 56, 10),
+            timecode(12, 34, 56, 11),
+        ]);
+        // LTC on the second channel, something louder on the first.
+        let mut samples: Vec<f32> = mono.iter().flat_map(|x| [0.9, *x]).collect();
+        for (index, chunk) in samples.chunks_mut(2 * 480).enumerate() {
+            reader.process(chunk, 2, index as u64 * 480);
+        }
+
+        let metadata = metadata.lock().unwrap();
+        let labels: Vec<_> = metadata.markers.iter().map(|x| x.label.as_str()).collect();
+        assert_eq!(labels, ["LTC 10:00:00:00", "LTC 12:34:56:10"]);
+
+        let frame_samples = BIT_PERIOD as u64 * FRAME_BITS as u64;
+        // Each marker lands within a bit of the frame it labels.
+        for (marker, expected) in metadata.markers.iter().zip([0, 3 * frame_samples]) {
+            assert!(marker.frame.abs_diff(expected) <= BIT_PERIOD as u64);
+        }
+
+        // The recording started at 10:00:00:00.
+        let time_reference = 10 * 3600 * SAMPLE_RATE as u64;
+        assert!(metadata.time_reference.unwrap().abs_diff(time_reference) <= BIT_PERIOD as u64);
+    }
+
+    #[test]
+    fn ignores_silence() {
+        let metadata = Arc::new(Mutex::new(RecordingMetadata::default()));
+        let mut reader = LtcReader::new(0, SAMPLE_RATE, metadata.clone());
+
+        let mut samples = vec![0.01; 48_000];
+        reader.process(&mut samples, 1, 0);
+
+        let metadata = metadata.lock().unwrap();
+        assert!(metadata.markers.is_empty());
+        assert_eq!(metadata.time_reference, None);
+    }
+}
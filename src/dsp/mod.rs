@@ -0,0 +1,99 @@
+use cpal::{FromSample, Sample};
+use hound::{SampleFormat, WavReader, WavWriter};
+use std::io::{Read, Seek, Write};
+
+pub(crate) mod align;
+pub(crate) mod channels;
+pub(crate) mod convert;
+pub(crate) mod fingerprint;
+pub(crate) mod ltc;
+pub(crate) mod pitch;
+pub(crate) mod silence;
+pub(crate) mod tempo;
+#[cfg(feature = "wasm")]
+pub(crate) mod wasm;
+pub(crate) mod watermark;
+
+/// A stage that transforms interleaved `f32` samples in place while they
+/// are being captured.
+pub(crate) trait Processor: Send {
+    /// `position` is the frame of the file the first sample belongs to.
+    fn process(&mut self, samples: &mut [f32], channels: usize, position: u64);
+}
+
+/// The ordered chain of processors applied to every input buffer.
+pub(crate) struct Pipeline {
+    channels: usize,
+    processors: Vec<Box<dyn Processor>>,
+    buffer: Vec<f32>,
+}
+
+impl Pipeline {
+    pub fn new(channels: usize) -> Self {
+        Self {
+            channels,
+            processors: Vec::new(),
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn push<P: Processor + 'static>(&mut self, processor: P) {
+        self.processors.push(Box::new(processor));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    /// Converts the input to `f32` and runs it through every processor.
+    pub fn process<T>(&mut self, input: &[T], position: u64) -> &[f32]
+    where
+        T: Sample,
+        f32: FromSample<T>,
+    {
+        self.buffer.clear();
+        self.buffer
+            .extend(input.iter().map(|&sample| f32::from_sample(sample)));
+
+        for processor in self.processors.iter_mut() {
+            processor.process(&mut self.buffer, self.channels, position);
+        }
+
+        &self.buffer
+    }
+}
+
+/// Reads the interleaved samples of a WAV file, normalized to `f32`.
+pub(crate) fn read_samples<R: Read>(
+    reader: &mut WavReader<R>,
+) -> Box<dyn Iterator<Item = hound::Result<f32>> + '_> {
+    let spec = reader.spec();
+
+    match spec.sample_format {
+        SampleFormat::Float => Box::new(reader.samples::<f32>()),
+        SampleFormat::Int => {
+            let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            Box::new(
+                reader
+                    .samples::<i32>()
+                    .map(move |sample| sample.map(|x| x as f32 / scale)),
+            )
+        }
+    }
+}
+
+/// Writes a normalized `f32` sample in the format of the writer's spec.
+pub(crate) fn write_sample<W: Write + Seek>(
+    writer: &mut WavWriter<W>,
+    sample: f32,
+) -> hound::Result<()> {
+    let spec = writer.spec();
+
+    match spec.sample_format {
+        SampleFormat::Float => writer.write_sample(sample),
+        SampleFormat::Int => {
+            let max = ((1_i64 << (spec.bits_per_sample - 1)) - 1) as f32;
+            writer.write_sample((sample.clamp(-1.0, 1.0) * max).round() as i32)
+        }
+    }
+}
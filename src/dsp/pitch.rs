@@ -0,0 +1,156 @@
+use super::Processor;
+use crate::{
+    error::{Error, Message, Result},
+    events::PitchDetected,
+    models::TunerOptions,
+};
+use std::{collections::VecDeque, sync::mpsc::Sender};
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// The YIN threshold below which a lag counts as periodic.
+const THRESHOLD: f32 = 0.15;
+
+/// The RMS level below which the input is too quiet to have a pitch.
+const MIN_LEVEL: f32 = 0.003;
+
+/// Estimates the pitch of the input with the YIN algorithm, reporting the
+/// nearest note and how far off it is in cents.
+pub(crate) struct PitchDetector {
+    sample_rate: u32,
+    reference_hz: f64,
+    min_lag: usize,
+    max_lag: usize,
+    /// The most recent mono input, twice the longest lag.
+    window: VecDeque<f32>,
+    difference: Vec<f32>,
+    hop_frames: usize,
+    since_analysis: usize,
+    sender: Sender<PitchDetected>,
+}
+
+impl PitchDetector {
+    pub fn new(
+        options: &TunerOptions,
+        sample_rate: u32,
+        sender: Sender<PitchDetected>,
+    ) -> Result<Self> {
+        if !(options.min_frequency > 0.0 && options.min_frequency < options.max_frequency) {
+            return Err(Error::InvalidOptions(Message::new(
+                "invalidOptions.tunerRange",
+                "The tuner's `minFrequency` must be positive and below `maxFrequency`.",
+            )));
+        }
+
+        let min_lag = (sample_rate as f64 / options.max_frequency).max(2.0) as usize;
+        let max_lag = (sample_rate as f64 / options.min_frequency).ceil() as usize;
+
+        Ok(Self {
+            sample_rate,
+            reference_hz: options.reference_hz,
+            min_lag,
+            max_lag,
+            window: VecDeque::with_capacity(max_lag * 2),
+            difference: vec![0.0; max_lag + 1],
+            hop_frames: (sample_rate as u64 * options.interval_ms as u64 / 1000).max(1) as usize,
+            since_analysis: 0,
+            sender,
+        })
+    }
+
+    /// Finds the period of the current window, and how clearly periodic it is.
+    fn period(&mut self) -> Option<(f64, f32)> {
+        let window = self.window.make_contiguous();
+        let len = window.len() - self.max_lag;
+
+        let level = (window.iter().map(|x| x * x).sum::<f32>() / window.len() as f32).sqrt();
+        if level < MIN_LEVEL {
+            return None;
+        }
+
+        // The cumulative mean normalized difference function.
+        let mut sum = 0.0;
+        self.difference[0] = 1.0;
+        for lag in 1..=self.max_lag {
+            let difference = (0..len)
+                .map(|i| {
+                    let delta = window[i] - window[i + lag];
+                    delta * delta
+                })
+                .sum::<f32>();
+            sum += difference;
+            self.difference[lag] = if sum > 0.0 {
+                difference * lag as f32 / sum
+            } else {
+                1.0
+            };
+        }
+
+        let mut lag = (self.min_lag..self.max_lag).find(|&x| self.difference[x] < THRESHOLD)?;
+        while lag + 1 < self.max_lag && self.difference[lag + 1] < self.difference[lag] {
+            lag += 1;
+        }
+
+        // Parabolic interpolation between the neighbouring lags.
+        let (a, b, c) = (
+            self.difference[lag - 1],
+            self.difference[lag],
+            self.difference[lag + 1],
+        );
+        let denominator = a - 2.0 * b + c;
+        let offset = if denominator.abs() > f32::EPSILON {
+            (0.5 * (a - c) / denominator).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+
+        Some((lag as f64 + offset as f64, 1.0 - b))
+    }
+
+    fn analyze(&mut self) {
+        let Some((period, clarity)) = self.period() else {
+            return;
+        };
+
+        let frequency = self.sample_rate as f64 / period;
+        let midi = 69.0 + 12.0 * (frequency / self.reference_hz).log2();
+        let nearest = midi.round();
+        let note = nearest as i64;
+
+        self.sender
+            .send(PitchDetected {
+                frequency,
+                note: format!(
+                    "{}{}",
+                    NOTE_NAMES[note.rem_euclid(12) as usize],
+                    note.div_euclid(12) - 1
+                ),
+                midi_note: note,
+                cents: (midi - nearest) * 100.0,
+                clarity,
+            })
+            .ok();
+    }
+}
+
+impl Processor for PitchDetector {
+    fn process(&mut self, samples: &mut [f32], channels: usize, _position: u64) {
+        let capacity = self.max_lag * 2;
+
+        for frame in samples.chunks(channels) {
+            if self.window.len() == capacity {
+                self.window.pop_front();
+            }
+            self.window
+                .push_back(frame.iter().sum::<f32>() / frame.len() as f32);
+            self.since_analysis += 1;
+
+            if self.since_analysis >= self.hop_frames && self.window.len() == capacity {
+                self.since_analysis = 0;
+                self.analyze();
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use super::Processor;
+use crate::{
+    metadata::{Marker, MetadataHandle},
+    models::{ChapterOptions, CompressSilenceOptions},
+};
+
+/// The length of the windows the level is measured over.
+const WINDOW_MS: u32 = 50;
+
+/// A change in whether the input has been silent for long enough.
+pub(crate) enum SilenceEvent {
+    /// The input has stayed below the threshold for the minimum duration.
+    Started,
+    /// The input rose above the threshold again at `frame`.
+    Ended { frame: u64 },
+}
+
+/// Detects runs of silence lasting at least a minimum duration by
+/// measuring the RMS level of fixed windows.
+pub(crate) struct SilenceTracker {
+    window_frames: u64,
+    threshold: f32,
+    min_windows: u64,
+    sum_squares: f32,
+    frames: u64,
+    silent_windows: u64,
+}
+
+impl SilenceTracker {
+    pub fn new(sample_rate: u32, threshold_db: f32, min_silence_ms: u64) -> Self {
+        Self {
+            window_frames: (sample_rate * WINDOW_MS / 1000).max(1) as u64,
+            // Compared against the mean square, so the dB value maps to power.
+            threshold: 10_f32.powf(threshold_db / 10.0),
+            min_windows: (min_silence_ms / WINDOW_MS as u64).max(1),
+            sum_squares: 0.0,
+            frames: 0,
+            silent_windows: 0,
+        }
+    }
+
+    /// Feeds one frame at file position `position`.
+    pub fn push(&mut self, frame: &[f32], position: u64) -> Option<SilenceEvent> {
+        self.sum_squares += frame.iter().map(|x| x * x).sum::<f32>() / frame.len() as f32;
+        self.frames += 1;
+
+        if self.frames < self.window_frames {
+            return None;
+        }
+
+        let silent = self.sum_squares / (self.frames as f32) < self.threshold;
+        self.sum_squares = 0.0;
+        self.frames = 0;
+
+        if silent {
+            self.silent_windows += 1;
+
+            (self.silent_windows == self.min_windows).then_some(SilenceEvent::Started)
+        } else {
+            let was_long = self.silent_windows >= self.min_windows;
+            self.silent_windows = 0;
+
+            was_long.then_some(SilenceEvent::Ended {
+                frame: (position + 1).saturating_sub(self.window_frames),
+            })
+        }
+    }
+
+    /// Whether the last pushed frame completed a window.
+    fn window_completed(&self) -> bool {
+        self.frames == 0
+    }
+}
+
+/// Leaves long silences out of the file, storing a `Gap N ms` marker where
+/// they were so the original timeline can be reconstructed.
+pub(crate) struct SilenceGate {
+    tracker: SilenceTracker,
+    metadata: MetadataHandle,
+    sample_rate: u32,
+    skipping: bool,
+    /// The frames left out of the current gap so far.
+    skipped: u64,
+    /// The samples of the current window while skipping, written out if the
+    /// window turns out to be where the input resumes.
+    held: Vec<f32>,
+}
+
+impl SilenceGate {
+    pub fn new(
+        options: &CompressSilenceOptions,
+        sample_rate: u32,
+        metadata: MetadataHandle,
+    ) -> Self {
+        Self {
+            tracker: SilenceTracker::new(sample_rate, options.threshold_db, options.min_silence_ms),
+            metadata,
+            sample_rate,
+            skipping: false,
+            skipped: 0,
+            held: Vec::new(),
+        }
+    }
+
+    /// Passes the frames of `samples` that belong in the file to `write`.
+    /// `position` is the frame of the file the first of them lands on.
+    ///
+    /// Returns the number of frames passed on.
+    pub fn filter<F>(
+        &mut self,
+        samples: &[f32],
+        channels: usize,
+        position: u64,
+        mut write: F,
+    ) -> u64
+    where
+        F: FnMut(&[f32]),
+    {
+        let mut written = 0;
+
+        for frame in samples.chunks(channels) {
+            let event = self.tracker.push(frame, position + written);
+
+            if !self.skipping {
+                write(frame);
+                written += 1;
+                // The minimum silence itself is kept, so the gap is audible.
+                self.skipping = matches!(event, Some(SilenceEvent::Started));
+                continue;
+            }
+
+            self.held.extend_from_slice(frame);
+
+            if let Some(SilenceEvent::Ended { .. }) = event {
+                self.mark(position + written);
+                write(&self.held);
+                written += (self.held.len() / channels) as u64;
+                self.held.clear();
+                self.skipping = false;
+                self.skipped = 0;
+            } else if self.tracker.window_completed() {
+                self.skipped += (self.held.len() / channels) as u64;
+                self.held.clear();
+            }
+        }
+
+        written
+    }
+
+    fn mark(&mut self, frame: u64) {
+        if let Ok(mut metadata) = self.metadata.lock() {
+            metadata.markers.push(Marker {
+                frame,
+                label: format!("Gap {} ms", self.skipped * 1000 / self.sample_rate as u64),
+            });
+        }
+    }
+}
+
+/// Marks a new chapter wherever the input resumes after a long silence.
+pub(crate) struct ChapterMarker {
+    tracker: SilenceTracker,
+    metadata: MetadataHandle,
+    chapters: u32,
+}
+
+impl ChapterMarker {
+    pub fn new(options: &ChapterOptions, sample_rate: u32, metadata: MetadataHandle) -> Self {
+        Self {
+            tracker: SilenceTracker::new(sample_rate, options.threshold_db, options.min_silence_ms),
+            metadata,
+            chapters: 0,
+        }
+    }
+
+    fn mark(&mut self, frame: u64) {
+        self.chapters += 1;
+
+        if let Ok(mut metadata) = self.metadata.lock() {
+            metadata.markers.push(Marker {
+                frame,
+                label: format!("Chapter {}", self.chapters),
+            });
+        }
+    }
+}
+
+impl Processor for ChapterMarker {
+    fn process(&mut self, samples: &mut [f32], channels: usize, position: u64) {
+        if position == 0 && self.chapters == 0 {
+            self.mark(0);
+        }
+
+        for (index, frame) in samples.chunks(channels).enumerate() {
+            if let Some(SilenceEvent::Ended { frame }) =
+                self.tracker.push(frame, position + index as u64)
+            {
+                self.mark(frame);
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use super::read_samples;
+use crate::{
+    error::{Error, Message, Result},
+    models::TempoOptions,
+};
+use hound::WavReader;
+use serde::Serialize;
+use std::path::Path;
+
+/// The number of onset envelope values per second.
+const ENVELOPE_RATE: u32 = 100;
+
+/// The tempo octave errors are resolved towards, in BPM.
+const PREFERRED_BPM: f64 = 120.0;
+
+/// The result of `detect_tempo`.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TempoDetection {
+    /// The estimated tempo, or `None` if the recording has no steady beat.
+    pub bpm: Option<f64>,
+    /// The positions of the beats in sample frames.
+    pub beats: Vec<u64>,
+    /// How strongly the onsets repeat at the tempo, from 0 to 1.
+    pub confidence: f32,
+}
+
+/// Estimates the tempo of a WAV file from the autocorrelation of its onset
+/// envelope, then places the beats on the strongest onsets.
+pub(crate) fn detect(path: &Path, options: &TempoOptions) -> Result<TempoDetection> {
+    if !(options.min_bpm > 0.0 && options.min_bpm < options.max_bpm) {
+        return Err(Error::InvalidOptions(Message::new(
+            "invalidOptions.tempoRange",
+            "The `minBpm` must be positive and below `maxBpm`.",
+        )));
+    }
+
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+    let hop = (spec.sample_rate / ENVELOPE_RATE).max(1) as usize;
+
+    // The energy of every hop of the mixed down input.
+    let mut energies = Vec::new();
+    let mut energy = 0.0;
+    let mut mono = 0.0;
+    for (index, sample) in read_samples(&mut reader).enumerate() {
+        mono += sample?;
+
+        if (index + 1) % channels == 0 {
+            energy += mono * mono;
+            mono = 0.0;
+
+            if (index + 1) / channels % hop == 0 {
+                energies.push(energy);
+                energy = 0.0;
+            }
+        }
+    }
+
+    // Rises in loudness, which is where notes and hits begin.
+    let onsets: Vec<f32> = energies
+        .windows(2)
+        .map(|x| ((x[1] + 1e-6).ln() - (x[0] + 1e-6).ln()).max(0.0))
+        .collect();
+
+    let min_lag = (60.0 * ENVELOPE_RATE as f64 / options.max_bpm).floor() as usize;
+    let max_lag = (60.0 * ENVELOPE_RATE as f64 / options.min_bpm).ceil() as usize;
+    let zero_lag = autocorrelation(&onsets, 0);
+
+    if onsets.len() < max_lag * 2 || zero_lag <= 0.0 {
+        return Ok(TempoDetection {
+            bpm: None,
+            beats: Vec::new(),
+            confidence: 0.0,
+        });
+    }
+
+    let correlations: Vec<f32> = (0..=max_lag + 1)
+        .map(|lag| autocorrelation(&onsets, lag))
+        .collect();
+    let weight = |lag: usize| {
+        let bpm = 60.0 * ENVELOPE_RATE as f64 / lag as f64;
+        (-0.5 * (bpm / PREFERRED_BPM).log2().powi(2)).exp() as f32
+    };
+    let mut lag = (min_lag.max(1)..=max_lag)
+        .max_by(|&a, &b| (correlations[a] * weight(a)).total_cmp(&(correlations[b] * weight(b))))
+        .unwrap_or(min_lag);
+
+    // Onsets on every beat repeat as strongly at twice the period, so the
+    // faster tempo wins whenever it explains them about as well. Neighbouring
+    // lags are summed, as a period between two lags spreads over both.
+    let spread = |lag: usize| correlations[lag - 1] + correlations[lag] + correlations[lag + 1];
+    while lag / 2 > min_lag.max(2) && spread(lag / 2) >= spread(lag) * 0.8 {
+        lag = (lag / 2..=lag.div_ceil(2))
+            .max_by(|&a, &b| correlations[a].total_cmp(&correlations[b]))
+            .unwrap_or(lag / 2);
+    }
+
+    // Parabolic interpolation between the neighbouring lags.
+    let (a, b, c) = (
+        correlations[lag - 1],
+        correlations[lag],
+        correlations[lag + 1],
+    );
+    let denominator = a - 2.0 * b + c;
+    let offset = if denominator.abs() > f32::EPSILON {
+        (0.5 * (a - c) / denominator).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    let period = lag as f64 + offset as f64;
+
+    // The phase whose beat grid lines up with the most onset strength.
+    let phase = (0..lag)
+        .max_by(|&a, &b| {
+            grid_strength(&onsets, a, period).total_cmp(&grid_strength(&onsets, b, period))
+        })
+        .unwrap_or(0);
+
+    // Each beat snaps to the strongest onset near where it is expected, so
+    // the grid follows a slightly drifting tempo.
+    let tolerance = (period * 0.1).round() as usize;
+    let mut beats = Vec::new();
+    let mut expected = phase as f64;
+    while (expected.round() as usize) < onsets.len() {
+        let center = expected.round() as usize;
+        let start = center.saturating_sub(tolerance);
+        let end = (center + tolerance + 1).min(onsets.len());
+        let beat = (start..end)
+            .filter(|&x| onsets[x] > 0.0)
+            .max_by(|&a, &b| onsets[a].total_cmp(&onsets[b]))
+            .unwrap_or(center);
+
+        // The onset lies between envelope values `beat` and `beat + 1`.
+        beats.push((beat + 1) as u64 * hop as u64);
+        expected = beat as f64 + period;
+    }
+
+    Ok(TempoDetection {
+        bpm: Some(60.0 * ENVELOPE_RATE as f64 / period),
+        beats,
+        confidence: (correlations[lag] / zero_lag).clamp(0.0, 1.0),
+    })
+}
+
+fn autocorrelation(values: &[f32], lag: usize) -> f32 {
+    let len = values.len().saturating_sub(lag);
+    if len == 0 {
+        return 0.0;
+    }
+
+    (0..len).map(|i| values[i] * values[i + lag]).sum::<f32>() / len as f32
+}
+
+fn grid_strength(onsets: &[f32], phase: usize, period: f64) -> f32 {
+    (0..)
+        .map(|beat| (phase as f64 + beat as f64 * period).round() as usize)
+        .take_while(|&x| x < onsets.len())
+        .map(|x| onsets[x])
+        .sum()
+}
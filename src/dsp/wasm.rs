@@ -0,0 +1,272 @@
+use super::Processor;
+use crate::{
+    error::{Error, Message, Result},
+    events::{WasmMetric, WasmStageFailed},
+    models::WasmOptions,
+};
+use std::{fs, sync::mpsc::Sender};
+use wasmi::{
+    Caller, Config, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+    TypedFunc,
+};
+
+/// The most linear memory a module may grow to.
+const MAX_MEMORY_BYTES: usize = 64 << 20;
+
+/// What a WebAssembly stage reports while it runs.
+pub(crate) enum WasmEvent {
+    Metric(WasmMetric),
+    Failed(WasmStageFailed),
+}
+
+struct Host {
+    limits: StoreLimits,
+    position: u64,
+    sender: Sender<WasmEvent>,
+}
+
+/// Runs the input through a WebAssembly module, which edits the samples in
+/// place and can report metrics.
+///
+/// The module exports its `memory`, an `alloc(bytes: i32) -> i32` that
+/// returns where the plugin may write that many bytes, and a
+/// `process(pointer: i32, samples: i32, channels: i32, position: i64)` that
+/// edits the interleaved little-endian `f32` samples there. It may import
+/// `env.metric(id: i32, value: f64)`. Each buffer may run at most
+/// `fuelPerBuffer` instructions; once the module traps or runs out, the
+/// stage is left out for the rest of the recording.
+pub(crate) struct WasmStage {
+    store: Store<Host>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    process: TypedFunc<(i32, i32, i32, i64), ()>,
+    fuel_per_buffer: u64,
+    /// Where the module lets us write, and how many bytes fit there.
+    pointer: i32,
+    capacity: usize,
+    bytes: Vec<u8>,
+    failed: bool,
+}
+
+impl WasmStage {
+    pub fn new(options: &WasmOptions, sender: Sender<WasmEvent>) -> Result<Self> {
+        let wasm = fs::read(&options.module)?;
+        Self::from_bytes(&wasm, options.fuel_per_buffer, sender).map_err(|err| {
+            Error::InvalidOptions(
+                Message::new(
+                    "invalidOptions.wasmModule",
+                    format!("The WebAssembly module cannot be loaded: {}", err),
+                )
+                .param("path", options.module.display()),
+            )
+        })
+    }
+
+    fn from_bytes(
+        wasm: &[u8],
+        fuel_per_buffer: u64,
+        sender: Sender<WasmEvent>,
+    ) -> std::result::Result<Self, wasmi::Error> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm)?;
+
+        let host = Host {
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY_BYTES)
+                .build(),
+            position: 0,
+            sender,
+        };
+        let mut store = Store::new(&engine, host);
+        store.limiter(|host| &mut host.limits);
+        store.set_fuel(fuel_per_buffer)?;
+
+        let mut linker = Linker::new(&engine);
+        linker.func_wrap(
+            "env",
+            "metric",
+            |caller: Caller<'_, Host>, id: i32, value: f64| {
+                let host = caller.data();
+                let metric = WasmMetric {
+                    id,
+                    value,
+                    position: host.position,
+                };
+                host.sender.send(WasmEvent::Metric(metric)).ok();
+            },
+        )?;
+        let instance = linker.instantiate_and_start(&mut store, &module)?;
+
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| wasmi::Error::new("the module does not export `memory`"))?;
+        let alloc = instance.get_typed_func(&store, "alloc")?;
+        let process = instance.get_typed_func(&store, "process")?;
+
+        Ok(Self {
+            store,
+            memory,
+            alloc,
+            process,
+            fuel_per_buffer,
+            pointer: 0,
+            capacity: 0,
+            bytes: Vec::new(),
+            failed: false,
+        })
+    }
+
+    fn run(
+        &mut self,
+        samples: &mut [f32],
+        channels: usize,
+        position: u64,
+    ) -> std::result::Result<(), wasmi::Error> {
+        self.store.set_fuel(self.fuel_per_buffer)?;
+        self.store.data_mut().position = position;
+
+        self.bytes.clear();
+        self.bytes
+            .extend(samples.iter().flat_map(|sample| sample.to_le_bytes()));
+        if self.bytes.len() > self.capacity {
+            self.pointer = self.alloc.call(&mut self.store, self.bytes.len() as i32)?;
+            self.capacity = self.bytes.len();
+        }
+
+        self.memory
+            .write(&mut self.store, self.pointer as u32 as usize, &self.bytes)?;
+        self.process.call(
+            &mut self.store,
+            (
+                self.pointer,
+                samples.len() as i32,
+                channels as i32,
+                position as i64,
+            ),
+        )?;
+        self.memory
+            .read(&self.store, self.pointer as u32 as usize, &mut self.bytes)?;
+
+        for (sample, bytes) in samples.iter_mut().zip(self.bytes.chunks_exact(4)) {
+            *sample = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+
+        Ok(())
+    }
+}
+
+impl Processor for WasmStage {
+    fn process(&mut self, samples: &mut [f32], channels: usize, position: u64) {
+        if self.failed {
+            return;
+        }
+
+        // The samples are only copied back once the module has returned, so
+        // a buffer it failed on passes through unchanged.
+        if let Err(err) = self.run(samples, channels, position) {
+            self.failed = true;
+            let failed = WasmStageFailed {
+                error: err.to_string(),
+                position,
+            };
+            self.store
+                .data()
+                .sender
+                .send(WasmEvent::Failed(failed))
+                .ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    /// Halves every sample and reports how many there were.
+    const GAIN: &str = r#"
+        (module
+            (import "env" "metric" (func $metric (param i32 f64)))
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 1024)
+            (func (export "process") (param $pointer i32) (param $samples i32)
+                    (param $channels i32) (param $position i64)
+                (local $i i32)
+                (block $done
+                    (loop $next
+                        (br_if $done (i32.ge_u (local.get $i) (local.get $samples)))
+                        (f32.store
+                            (i32.add (local.get $pointer) (i32.mul (local.get $i) (i32.const 4)))
+                            (f32.mul
+                                (f32.load (i32.add (local.get $pointer)
+                                    (i32.mul (local.get $i) (i32.const 4))))
+                                (f32.const 0.5)))
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br $next)))
+                (call $metric (local.get $channels) (f64.convert_i32_u (local.get $samples))))
+        )
+    "#;
+
+    /// Scribbles over the first sample, then never returns.
+    const SPIN: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32)
+                i32.const 0)
+            (func (export "process") (param $pointer i32) (param i32 i32 i64)
+                (f32.store (local.get $pointer) (f32.const 9))
+                (loop $forever (br $forever)))
+        )
+    "#;
+
+    #[test]
+    fn processes_samples_and_reports_metrics() {
+        let (sender, receiver) = mpsc::channel();
+        let mut stage = WasmStage::from_bytes(GAIN.as_bytes(), 1_000_000, sender).unwrap();
+
+        let mut samples = [1.0, -0.5, 0.25, 0.0];
+        stage.process(&mut samples, 2, 480);
+        assert_eq!(samples, [0.5, -0.25, 0.125, 0.0]);
+
+        match receiver.try_recv().unwrap() {
+            WasmEvent::Metric(metric) => assert_eq!(
+                metric,
+                WasmMetric {
+                    id: 2,
+                    value: 4.0,
+                    position: 480
+                }
+            ),
+            WasmEvent::Failed(failed) => panic!("the stage failed: {}", failed.error),
+        }
+    }
+
+    #[test]
+    fn bypasses_a_module_that_runs_out_of_fuel() {
+        let (sender, receiver) = mpsc::channel();
+        let mut stage = WasmStage::from_bytes(SPIN.as_bytes(), 10_000, sender).unwrap();
+
+        let mut samples = [0.1, 0.2];
+        stage.process(&mut samples, 1, 96);
+        assert_eq!(samples, [0.1, 0.2]);
+        match receiver.try_recv().unwrap() {
+            WasmEvent::Failed(failed) => assert_eq!(failed.position, 96),
+            WasmEvent::Metric(_) => panic!("expected a failure"),
+        }
+
+        stage.process(&mut samples, 1, 98);
+        assert_eq!(samples, [0.1, 0.2]);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn rejects_a_module_without_the_exports() {
+        let (sender, _receiver) = mpsc::channel();
+        let module = r#"(module (memory (export "memory") 1))"#;
+
+        assert!(WasmStage::from_bytes(module.as_bytes(), 10_000, sender).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use super::{read_samples, Processor};
+use crate::error::{Error, Message, Result};
+use hound::WavReader;
+use serde::Serialize;
+use std::{f32::consts::TAU, path::Path};
+
+/// The frequency of the carrier, above what most adults can hear but below
+/// the Nyquist frequency of 44.1 kHz recordings.
+const CARRIER_HZ: f32 = 19_000.0;
+/// The lowest sample rate that can represent the carrier.
+const MIN_SAMPLE_RATE: u32 = 44_100;
+/// The peak amplitude of the marker, roughly -50 dBFS.
+const AMPLITUDE: f32 = 0.003;
+/// The number of frames each chip of the spreading sequence lasts.
+const CHIP_FRAMES: u64 = 1024;
+/// The length of the spreading sequence in chips.
+const SEQUENCE_LEN: usize = 127;
+/// How many standard deviations above chance the correlation must be.
+const DETECTION_SIGMAS: f32 = 5.0;
+
+/// The pseudo-random ±1 chip sequence derived from a key.
+fn sequence(key: &str) -> Vec<f32> {
+    // FNV-1a, so that the same key always yields the same sequence.
+    let mut seed = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+
+    (0..SEQUENCE_LEN)
+        .map(|_| {
+            // xorshift64
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            if seed & 1 == 0 {
+                -1.0
+            } else {
+                1.0
+            }
+        })
+        .collect()
+}
+
+fn carrier(frame: u64, sample_rate: u32) -> f32 {
+    let cycles = (frame as f64 * CARRIER_HZ as f64 / sample_rate as f64).fract();
+    (TAU * cycles as f32).sin()
+}
+
+/// Embeds a keyed spread-spectrum marker on an ultrasonic carrier.
+pub(crate) struct Watermark {
+    sample_rate: u32,
+    sequence: Vec<f32>,
+}
+
+impl Watermark {
+    pub fn new(key: &str, sample_rate: u32) -> Result<Self> {
+        // A template or session does not bring its key along.
+        if key.is_empty() {
+            return Err(Error::InvalidOptions(Message::new(
+                "invalidOptions.watermarkKey",
+                "The watermark requires a `key`.",
+            )));
+        }
+        if sample_rate < MIN_SAMPLE_RATE {
+            return Err(Error::InvalidOptions(
+                Message::new(
+                    "invalidOptions.watermarkSampleRate",
+                    format!(
+                        "Watermarking requires a sample rate of at least {MIN_SAMPLE_RATE} Hz, the device uses {sample_rate} Hz."
+                    ),
+                )
+                .param("minSampleRate", MIN_SAMPLE_RATE)
+                .param("sampleRate", sample_rate),
+            ));
+        }
+
+        Ok(Self {
+            sample_rate,
+            sequence: sequence(key),
+        })
+    }
+}
+
+impl Processor for Watermark {
+    fn process(&mut self, samples: &mut [f32], channels: usize, position: u64) {
+        for (index, frame) in samples.chunks_mut(channels).enumerate() {
+            // The carrier is keyed to the file position, which detection relies on.
+            let position = position + index as u64;
+            let chip = self.sequence[((position / CHIP_FRAMES) as usize) % SEQUENCE_LEN];
+            let marker = AMPLITUDE * chip * carrier(position, self.sample_rate);
+
+            for sample in frame.iter_mut() {
+                *sample = (*sample + marker).clamp(-1.0, 1.0);
+            }
+        }
+    }
+}
+
+/// The outcome of `detect_watermark`.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WatermarkDetection {
+    pub detected: bool,
+    /// The normalized correlation with the keyed sequence, from -1 to 1.
+    pub confidence: f32,
+}
+
+/// Correlates a WAV file against the marker that `key` would have embedded.
+pub(crate) fn detect(path: &Path, key: &str) -> Result<WatermarkDetection> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let channels = spec.channels as usize;
+
+    let sequence = sequence(key);
+    let mut chips = Vec::new();
+    let mut chip = 0.0;
+    let mut mono = 0.0;
+
+    for (index, sample) in read_samples(&mut reader).enumerate() {
+        mono += sample?;
+
+        if (index + 1) % channels == 0 {
+            let frame = (index / channels) as u64;
+            chip += mono / channels as f32 * carrier(frame, spec.sample_rate);
+            mono = 0.0;
+
+            if (frame + 1) % CHIP_FRAMES == 0 {
+                chips.push(chip);
+                chip = 0.0;
+            }
+        }
+    }
+
+    if spec.sample_rate < MIN_SAMPLE_RATE || chips.len() < SEQUENCE_LEN {
+        return Ok(WatermarkDetection {
+            detected: false,
+            confidence: 0.0,
+        });
+    }
+
+    let correlation: f32 = chips
+        .iter()
+        .enumerate()
+        .map(|(index, chip)| chip * sequence[index % SEQUENCE_LEN])
+        .sum();
+    let energy = chips.iter().map(|chip| chip * chip).sum::<f32>().sqrt();
+    let confidence = if energy > 0.0 {
+        correlation / (energy * (chips.len() as f32).sqrt())
+    } else {
+        0.0
+    };
+
+    Ok(WatermarkDetection {
+        detected: confidence > DETECTION_SIGMAS / (chips.len() as f32).sqrt(),
+        confidence,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::write_sample;
+    use hound::{SampleFormat, WavSpec, WavWriter};
+    use std::{fs, path::PathBuf};
+
+    const SAMPLE_RATE: u32 = 48_000;
+    /// One full pass through the sequence.
+    const FRAMES: usize = SEQUENCE_LEN * CHIP_FRAMES as usize;
+
+    /// Speech-like program material: a tone under a little noise.
+    fn program(channels: usize) -> Vec<f32> {
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+
+        (0..FRAMES * channels)
+            .map(|index| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                let noise = (seed >> 40) as f32 / (1 << 24) as f32 - 0.5;
+                let frame = (index / channels) as f32;
+                0.3 * (TAU * 220.0 * frame / SAMPLE_RATE as f32).sin() + 0.05 * noise
+            })
+            .collect()
+    }
+
+    /// Writes the samples to a 16-bit WAV file, removed again on drop.
+    struct TempWav(PathBuf);
+
+    impl TempWav {
+        fn new(name: &str, samples: &[f32], channels: u16) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "mic-recorder-{}-{}.wav",
+                name,
+                std::process::id()
+            ));
+            let spec = WavSpec {
+                channels,
+                sample_rate: SAMPLE_RATE,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            };
+            let mut writer = WavWriter::create(&path, spec).unwrap();
+            for sample in samples {
+                write_sample(&mut writer, *sample).unwrap();
+            }
+            writer.finalize().unwrap();
+
+            Self(path)
+        }
+    }
+
+    impl Drop for TempWav {
+        fn drop(&mut self) {
+            fs::remove_file(&self.0).ok();
+        }
+    }
+
+    #[test]
+    fn detects_the_embedded_key_only() {
+        let mut samples = program(2);
+        let mut watermark = Watermark::new("studio-a", SAMPLE_RATE).unwrap();
+        // In buffers, as the pipeline feeds it.
+        for (index, chunk) in samples.chunks_mut(2 * 480).enumerate() {
+            watermark.process(chunk, 2, index as u64 * 480);
+        }
+        let file = TempWav::new("watermark-embedded", &samples, 2);
+
+        let detection = detect(&file.0, "studio-a").unwrap();
+        assert!(detection.detected, "confidence {}", detection.confidence);
+
+        let threshold = DETECTION_SIGMAS / (SEQUENCE_LEN as f32).sqrt();
+        let detection = detect(&file.0, "studio-b").unwrap();
+        assert!(!detection.detected);
+        assert!(detection.confidence < threshold);
+    }
+
+    #[test]
+    fn finds_nothing_in_unmarked_audio() {
+        let file = TempWav::new("watermark-unmarked", &program(1), 1);
+
+        assert!(!detect(&file.0, "studio-a").unwrap().detected);
+    }
+
+    #[test]
+    fn finds_nothing_in_a_short_file() {
+        let mut samples = program(1);
+        samples.truncate(FRAMES / 2);
+        Watermark::new("studio-a", SAMPLE_RATE)
+            .unwrap()
+            .process(&mut samples, 1, 0);
+        let file = TempWav::new("watermark-short", &samples, 1);
+
+        let detection = detect(&file.0, "studio-a").unwrap();
+        assert!(!detection.detected);
+        assert_eq!(detection.confidence, 0.0);
+    }
+
+    #[test]
+    fn rejects_an_empty_key_and_a_low_sample_rate() {
+        assert!(Watermark::new("", SAMPLE_RATE).is_err());
+        assert!(Watermark::new("studio-a", 32_000).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{dsp::fingerprint, library::RecordingInfo, manifest};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// How `find_duplicates` decides that recordings are copies of each other.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DuplicateOptions {
+    /// Also compare the fingerprints of recordings with about the same
+    /// duration, finding copies that were converted or trimmed, not just
+    /// identical files. This decodes every such recording and takes longer.
+    pub fingerprints: bool,
+    /// The fingerprint similarity from 0 to 1 at which recordings count as
+    /// copies. Defaults to `0.9`; unrelated audio scores around 0.6.
+    pub min_similarity: f32,
+    /// How far apart the durations of copies may be, in milliseconds, when
+    /// comparing fingerprints. Defaults to `2000`.
+    pub max_duration_difference_ms: u64,
+}
+
+impl Default for DuplicateOptions {
+    fn default() -> Self {
+        Self {
+            fingerprints: false,
+            min_similarity: 0.9,
+            max_duration_difference_ms: 2000,
+        }
+    }
+}
+
+/// Recordings in the index that are likely copies of each other.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    /// Oldest first, which is usually the one to keep.
+    pub recordings: Vec<RecordingInfo>,
+    /// Whether the files are byte for byte the same, rather than only
+    /// sounding alike.
+    pub identical: bool,
+    /// The storage freed by deleting all but the first recording.
+    pub reclaimable_bytes: u64,
+}
+
+impl DuplicateGroup {
+    fn new(mut recordings: Vec<RecordingInfo>, identical: bool) -> Self {
+        recordings.sort_by_key(|x| x.recorded_at);
+        let reclaimable_bytes = recordings.iter().skip(1).map(|x| x.size_bytes).sum();
+
+        Self {
+            recordings,
+            identical,
+            reclaimable_bytes,
+        }
+    }
+}
+
+/// Groups the recordings that are copies of each other, the groups that
+/// free the most storage first. Recordings whose file is gone or cannot be
+/// read are left out.
+pub(crate) fn find(
+    recordings: Vec<RecordingInfo>,
+    options: &DuplicateOptions,
+) -> Vec<DuplicateGroup> {
+    let mut groups = Vec::new();
+
+    // Identical files have the same size, so only those are hashed.
+    let mut by_size: BTreeMap<u64, Vec<RecordingInfo>> = BTreeMap::new();
+    for info in recordings.into_iter().filter(|x| x.path.is_file()) {
+        by_size.entry(info.size_bytes).or_default().push(info);
+    }
+
+    let mut remaining = Vec::new();
+    for (_, candidates) in by_size {
+        if candidates.len() < 2 {
+            remaining.extend(candidates);
+            continue;
+        }
+
+        let mut by_hash: HashMap<String, Vec<RecordingInfo>> = HashMap::new();
+        for info in candidates {
+            match manifest::hash(&info.path) {
+                Ok((_, sha256)) => by_hash.entry(sha256).or_default().push(info),
+                Err(_) => continue,
+            }
+        }
+
+        for (_, infos) in by_hash {
+            if infos.len() < 2 {
+                remaining.extend(infos);
+            } else {
+                groups.push(DuplicateGroup::new(infos, true));
+            }
+        }
+    }
+
+    if options.fingerprints {
+        groups.extend(find_alike(remaining, options));
+    }
+
+    groups.sort_by_key(|x| std::cmp::Reverse(x.reclaimable_bytes));
+
+    groups
+}
+
+/// Groups recordings of about the same duration whose fingerprints match.
+fn find_alike(
+    mut recordings: Vec<RecordingInfo>,
+    options: &DuplicateOptions,
+) -> Vec<DuplicateGroup> {
+    recordings.retain(|x| x.duration_ms.is_some());
+    recordings.sort_by_key(|x| x.duration_ms);
+
+    let mut fingerprints: HashMap<usize, Option<Vec<u32>>> = HashMap::new();
+    // Links every recording to another in its group, up to the one that
+    // stands for the group.
+    let mut parents: Vec<usize> = (0..recordings.len()).collect();
+
+    for i in 0..recordings.len() {
+        let duration_ms = recordings[i].duration_ms.unwrap_or(0);
+
+        for j in i + 1..recordings.len() {
+            let other_ms = recordings[j].duration_ms.unwrap_or(0);
+            if other_ms - duration_ms > options.max_duration_difference_ms {
+                break;
+            }
+            if root(&parents, i) == root(&parents, j) {
+                continue;
+            }
+
+            for x in [i, j] {
+                fingerprints.entry(x).or_insert_with(|| {
+                    fingerprint::compute(&recordings[x].path)
+                        .ok()
+                        .map(|x| x.fingerprint)
+                });
+            }
+            let (Some(a), Some(b)) = (&fingerprints[&i], &fingerprints[&j]) else {
+                continue;
+            };
+
+            if fingerprint::similarity(a, b) >= options.min_similarity {
+                let (a, b) = (root(&parents, i), root(&parents, j));
+                parents[b] = a;
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<RecordingInfo>> = BTreeMap::new();
+    for (i, info) in recordings.into_iter().enumerate() {
+        groups.entry(root(&parents, i)).or_default().push(info);
+    }
+
+    groups
+        .into_values()
+        .filter(|x| x.len() > 1)
+        .map(|x| DuplicateGroup::new(x, false))
+        .collect()
+}
+
+fn root(parents: &[usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        i = parents[i];
+    }
+    i
+}
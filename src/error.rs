@@ -0,0 +1,330 @@
+use serde::{Serialize, Serializer};
+use std::{any::Any, collections::BTreeMap, fmt, sync::PoisonError};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The kind of an [`Error`], as seen by the frontend.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    AlreadyRecording,
+    NotRecording,
+    RecorderDisabled,
+    ShutDown,
+    InvalidOptions,
+    DeviceNotFound,
+    PermissionDenied,
+    UnsupportedSampleFormat,
+    StreamError,
+    Timeout,
+    Overflow,
+    QuotaExceeded,
+    Vetoed,
+    Io,
+    Internal,
+}
+
+/// The serialized form of an [`Error`].
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorPayload {
+    pub kind: ErrorKind,
+    /// A stable key for the specific error, e.g. `invalidOptions.tempoRange`,
+    /// that frontends can look up in their own message catalog.
+    pub code: String,
+    /// The values of the placeholders in the message, by name.
+    pub params: BTreeMap<String, String>,
+    /// The message in English.
+    pub message: String,
+}
+
+/// The message of an error, along with the stable key and values a
+/// frontend needs to show it in another language.
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub code: &'static str,
+    pub params: BTreeMap<&'static str, String>,
+    pub text: String,
+}
+
+impl Message {
+    pub fn new(code: &'static str, text: impl Into<String>) -> Self {
+        Self {
+            code,
+            params: BTreeMap::new(),
+            text: text.into(),
+        }
+    }
+
+    pub fn param(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.params.insert(name, value.to_string());
+        self
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Recording is already in progress.")]
+    AlreadyRecording,
+    #[error("No recording in progress.")]
+    NotRecording,
+    #[error("The recorder has been disabled.")]
+    RecorderDisabled,
+    #[error("The recorder has been shut down.")]
+    ShutDown,
+    #[error("{0}")]
+    InvalidOptions(Message),
+    #[error("{0}")]
+    DeviceNotFound(Message),
+    #[error("Microphone access denied: {0}")]
+    PermissionDenied(String),
+    #[error("Unsupported sample format: {0}")]
+    UnsupportedSampleFormat(String),
+    #[error("An error occurred on stream: {0}")]
+    StreamError(String),
+    #[error("{0}")]
+    Timeout(Message),
+    #[error("{0}")]
+    Overflow(Message),
+    #[error("{0}")]
+    QuotaExceeded(Message),
+    /// Returned by an `on_before_start` hook to veto a start, with a code
+    /// of the app's own, e.g. `vetoed.policy`.
+    #[error("{0}")]
+    Vetoed(Message),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Wav(#[from] hound::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Tauri(#[from] tauri::Error),
+    #[error("The recorder state is poisoned.")]
+    Poisoned,
+    #[error("The audio thread panicked: {0}")]
+    Panicked(String),
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::AlreadyRecording => ErrorKind::AlreadyRecording,
+            Error::NotRecording => ErrorKind::NotRecording,
+            Error::RecorderDisabled => ErrorKind::RecorderDisabled,
+            Error::ShutDown => ErrorKind::ShutDown,
+            Error::InvalidOptions(_) => ErrorKind::InvalidOptions,
+            Error::DeviceNotFound(_) => ErrorKind::DeviceNotFound,
+            Error::PermissionDenied(_) => ErrorKind::PermissionDenied,
+            Error::UnsupportedSampleFormat(_) => ErrorKind::UnsupportedSampleFormat,
+            Error::StreamError(_) => ErrorKind::StreamError,
+            Error::Timeout(_) => ErrorKind::Timeout,
+            Error::Overflow(_) => ErrorKind::Overflow,
+            Error::QuotaExceeded(_) => ErrorKind::QuotaExceeded,
+            Error::Vetoed(_) => ErrorKind::Vetoed,
+            Error::Io(_) | Error::Wav(hound::Error::IoError(_)) => ErrorKind::Io,
+            Error::Wav(_)
+            | Error::Json(_)
+            | Error::Tauri(_)
+            | Error::Poisoned
+            | Error::Panicked(_) => ErrorKind::Internal,
+        }
+    }
+
+    /// The stable key of the specific error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::AlreadyRecording => "alreadyRecording",
+            Error::NotRecording => "notRecording",
+            Error::RecorderDisabled => "recorderDisabled",
+            Error::ShutDown => "shutDown",
+            Error::InvalidOptions(message)
+            | Error::DeviceNotFound(message)
+            | Error::Timeout(message)
+            | Error::Overflow(message)
+            | Error::QuotaExceeded(message)
+            | Error::Vetoed(message) => message.code,
+            Error::PermissionDenied(_) => "permissionDenied",
+            Error::UnsupportedSampleFormat(_) => "unsupportedSampleFormat",
+            Error::StreamError(_) => "streamError",
+            Error::Io(_) | Error::Wav(hound::Error::IoError(_)) => "io",
+            Error::Wav(_) => "internal.wav",
+            Error::Json(_) => "internal.json",
+            Error::Tauri(_) => "internal.tauri",
+            Error::Poisoned => "internal.poisoned",
+            Error::Panicked(_) => "internal.panicked",
+        }
+    }
+
+    /// The values to fill into the message of `code`.
+    pub fn params(&self) -> BTreeMap<String, String> {
+        let (name, value) = match self {
+            Error::InvalidOptions(message)
+            | Error::DeviceNotFound(message)
+            | Error::Timeout(message)
+            | Error::Overflow(message)
+            | Error::QuotaExceeded(message)
+            | Error::Vetoed(message) => {
+                return message
+                    .params
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.clone()))
+                    .collect()
+            }
+            Error::UnsupportedSampleFormat(format) => ("format", format.clone()),
+            Error::PermissionDenied(detail)
+            | Error::StreamError(detail)
+            | Error::Panicked(detail) => ("detail", detail.clone()),
+            Error::Io(err) => ("detail", err.to_string()),
+            Error::Wav(err) => ("detail", err.to_string()),
+            Error::Json(err) => ("detail", err.to_string()),
+            Error::Tauri(err) => ("detail", err.to_string()),
+            Error::AlreadyRecording
+            | Error::NotRecording
+            | Error::RecorderDisabled
+            | Error::ShutDown
+            | Error::Poisoned => return BTreeMap::new(),
+        };
+
+        BTreeMap::from([(name.to_string(), value)])
+    }
+
+    pub fn payload(&self) -> ErrorPayload {
+        ErrorPayload {
+            kind: self.kind(),
+            code: self.code().to_string(),
+            params: self.params(),
+            message: self.to_string(),
+        }
+    }
+
+    /// Converts the payload of a caught panic.
+    pub(crate) fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|x| x.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+
+        Error::Panicked(message)
+    }
+
+    /// The message of a device that went away or cannot be used.
+    fn unavailable(err: impl ToString) -> Message {
+        Message::new("deviceNotFound.unavailable", err.to_string())
+    }
+
+    /// Classifies a backend-specific cpal error, which is the only way
+    /// hosts such as CoreAudio and WASAPI report a denied microphone.
+    fn backend(description: String) -> Self {
+        let lowercase = description.to_lowercase();
+
+        if ["permission", "denied", "not authorized", "unauthorized"]
+            .iter()
+            .any(|x| lowercase.contains(x))
+        {
+            Error::PermissionDenied(description)
+        } else {
+            Error::StreamError(description)
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.payload().serialize(serializer)
+    }
+}
+
+impl<T> From<PoisonError<T>> for Error {
+    fn from(_: PoisonError<T>) -> Self {
+        Error::Poisoned
+    }
+}
+
+impl From<cpal::HostUnavailable> for Error {
+    fn from(err: cpal::HostUnavailable) -> Self {
+        Error::DeviceNotFound(Message::new(
+            "deviceNotFound.hostUnavailable",
+            err.to_string(),
+        ))
+    }
+}
+
+impl From<cpal::DevicesError> for Error {
+    fn from(err: cpal::DevicesError) -> Self {
+        match err {
+            cpal::DevicesError::BackendSpecific { err } => Error::backend(err.description),
+        }
+    }
+}
+
+impl From<cpal::DeviceNameError> for Error {
+    fn from(err: cpal::DeviceNameError) -> Self {
+        match err {
+            cpal::DeviceNameError::BackendSpecific { err } => Error::backend(err.description),
+        }
+    }
+}
+
+impl From<cpal::DefaultStreamConfigError> for Error {
+    fn from(err: cpal::DefaultStreamConfigError) -> Self {
+        match err {
+            cpal::DefaultStreamConfigError::BackendSpecific { err } => {
+                Error::backend(err.description)
+            }
+            err => Error::DeviceNotFound(Error::unavailable(err)),
+        }
+    }
+}
+
+impl From<cpal::SupportedStreamConfigsError> for Error {
+    fn from(err: cpal::SupportedStreamConfigsError) -> Self {
+        match err {
+            cpal::SupportedStreamConfigsError::BackendSpecific { err } => {
+                Error::backend(err.description)
+            }
+            err => Error::DeviceNotFound(Error::unavailable(err)),
+        }
+    }
+}
+
+impl From<cpal::BuildStreamError> for Error {
+    fn from(err: cpal::BuildStreamError) -> Self {
+        match err {
+            cpal::BuildStreamError::DeviceNotAvailable => {
+                Error::DeviceNotFound(Error::unavailable(err))
+            }
+            cpal::BuildStreamError::BackendSpecific { err } => Error::backend(err.description),
+            err => Error::StreamError(err.to_string()),
+        }
+    }
+}
+
+impl From<cpal::PlayStreamError> for Error {
+    fn from(err: cpal::PlayStreamError) -> Self {
+        match err {
+            cpal::PlayStreamError::DeviceNotAvailable => {
+                Error::DeviceNotFound(Error::unavailable(err))
+            }
+            cpal::PlayStreamError::BackendSpecific { err } => Error::backend(err.description),
+        }
+    }
+}
+
+impl From<cpal::StreamError> for Error {
+    fn from(err: cpal::StreamError) -> Self {
+        match err {
+            cpal::StreamError::DeviceNotAvailable => Error::DeviceNotFound(Error::unavailable(err)),
+            cpal::StreamError::BackendSpecific { err } => Error::backend(err.description),
+        }
+    }
+}
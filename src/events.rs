@@ -0,0 +1,280 @@
+use crate::{
+    chunks::RecordingChunk,
+    error::ErrorPayload,
+    library::{SyncState, SyncStatus},
+    models::{PermissionState, RecorderState},
+};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Emitted once the input stream is playing and audio is being written.
+pub const RECORDING_STARTED: &str = "recording-started";
+/// Emitted once capture has stopped; the file is finalized in the background.
+pub const RECORDING_STOPPED: &str = "recording-stopped";
+/// Emitted as each finalization stage of a stopped recording begins.
+pub const RECORDING_FINALIZING: &str = "recording-finalizing";
+/// Emitted once a stopped recording has been completely written to disk.
+pub const RECORDING_FINALIZED: &str = "recording-finalized";
+/// Emitted when the recording failed while it was in progress.
+pub const RECORDING_ERROR: &str = "recording-error";
+/// Emitted when the recorder has been disabled with `disable_recorder`.
+pub const RECORDER_DISABLED: &str = "recorder-disabled";
+/// Emitted when the recorder has been enabled again with `enable_recorder`.
+pub const RECORDER_ENABLED: &str = "recorder-enabled";
+/// Emitted when the hardware mic-mute key is pressed during a recording.
+pub const MIC_MUTE_CHANGED: &str = "mic-mute-changed";
+/// Emitted when a marker has been added to the recording in progress.
+pub const MARKER_ADDED: &str = "marker-added";
+/// Emitted when audio streamed to the `onAudio` channel had to be dropped.
+pub const AUDIO_DROPPED: &str = "audio-dropped";
+/// Emitted when the input buffer has been grown after an overrun.
+pub const BUFFER_SIZE_CHANGED: &str = "buffer-size-changed";
+/// Emitted when writing to the file failed. Capture goes on in memory
+/// until the recording is redirected with `redirect_recording`.
+pub const RECORDING_IO_ERROR: &str = "recording-io-error";
+/// Emitted every few seconds while recording, even if nothing changed.
+pub const RECORDER_HEARTBEAT: &str = "recorder-heartbeat";
+/// Emitted while recording with the `tuner` option whenever a pitch is heard.
+pub const PITCH_DETECTED: &str = "pitch-detected";
+/// Emitted when a webview is created while a session interrupted by a crash
+/// can be resumed.
+pub const SESSION_RESUMABLE: &str = "session-resumable";
+/// Emitted when an expired recording has been deleted.
+pub const RECORDING_EXPIRED: &str = "recording-expired";
+/// Emitted when the app is granted or denied microphone access while it runs.
+pub const MIC_PERMISSION_CHANGED: &str = "mic-permission-changed";
+/// Emitted when the sync state of a recording has changed.
+pub const SYNC_STATE_CHANGED: &str = "sync-state-changed";
+/// Emitted when a recording starts on a Bluetooth input that captures over
+/// the hands-free profile, at telephone quality.
+pub const HANDS_FREE_INPUT: &str = "hands-free-input";
+/// Emitted while recording with `followDefaultInput` when the recording has
+/// moved over to another input device.
+pub const INPUT_DEVICE_CHANGED: &str = "input-device-changed";
+/// Emitted while recording with `followDefaultInput` or `followPreferred`
+/// when input devices have been attached or detached.
+pub const INPUT_DEVICES_CHANGED: &str = "input-devices-changed";
+/// Emitted while recording with the `chunks` option whenever a chunk has
+/// been written in full, and once the file is done for the chunks that
+/// changed or are new, such as the tail.
+pub const CHUNK_HASHED: &str = "chunk-hashed";
+/// Emitted to every window whenever the snapshot of the recorder returned
+/// by `get_recorder_snapshot` changes, with the new snapshot.
+pub const RECORDER_SNAPSHOT: &str = "recorder-snapshot";
+/// Emitted while recording with the `wasm` option whenever the module
+/// reports a metric.
+pub const WASM_METRIC: &str = "wasm-metric";
+/// Emitted once the module of the `wasm` option has trapped or run out of
+/// fuel, after which the input bypasses it.
+pub const WASM_STAGE_FAILED: &str = "wasm-stage-failed";
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingStarted {
+    pub path: PathBuf,
+    pub device: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingStopped {
+    pub path: PathBuf,
+    pub duration_ms: Option<u64>,
+}
+
+/// A step of writing a stopped recording out to disk.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FinalizeStage {
+    /// Flushing buffered audio and writing the WAV header.
+    Flushing,
+    /// Appending the metadata chunks, such as markers.
+    WritingMetadata,
+    /// Waiting for the operating system to commit the file to storage.
+    Syncing,
+    /// Moving the file from the `tempDir` it was recorded in to the save
+    /// directory.
+    Moving,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingFinalizing {
+    pub path: PathBuf,
+    pub stage: FinalizeStage,
+    /// The fraction of the stages completed so far, from 0 to 1.
+    pub progress: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingFinalized {
+    pub path: PathBuf,
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingError {
+    pub error: ErrorPayload,
+    /// The finalized file holding the audio captured before the failure.
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingExpired {
+    pub path: PathBuf,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MicPermissionChanged {
+    pub state: PermissionState,
+    pub previous: PermissionState,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MicMuteChanged {
+    pub muted: bool,
+    /// Whether writing has been paused because of `autoPauseOnMute`.
+    pub paused: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MarkerAdded {
+    /// The position in sample frames from the start of the file.
+    pub frame: u64,
+    /// The position in milliseconds from the start of the file.
+    pub time_ms: u64,
+    pub label: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecorderHeartbeat {
+    pub state: RecorderState,
+    pub paused: bool,
+    /// The time since the input stream last delivered audio. A value that
+    /// keeps growing means the backend has stalled.
+    pub last_callback_age_ms: u64,
+    /// The number of frames written to the file so far.
+    pub frames: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioDropped {
+    /// The number of frames dropped since the previous event.
+    pub frames: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferSizeChanged {
+    /// The new size of the input buffer in frames.
+    pub buffer_frames: u32,
+    pub previous_frames: u32,
+    /// The frames lost to the overrun that triggered the change.
+    pub lost_frames: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HandsFreeInput {
+    pub device: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InputDevicesChanged {
+    /// The names of the inputs that have been attached.
+    pub attached: Vec<String>,
+    pub detached: Vec<String>,
+    /// Which of the attached inputs are connected over USB, such as audio
+    /// interfaces.
+    pub attached_usb: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InputDeviceChanged {
+    pub device: String,
+    pub previous_device: Option<String>,
+    /// The position in sample frames from the start of the recording at
+    /// which the new device takes over.
+    pub frame: u64,
+    /// The sample rate of the new device, e.g. 16000 for AirPods, which
+    /// caps the quality of the rest of the file.
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// The sample rate of the file, which the input is resampled to.
+    pub file_sample_rate: u32,
+    /// Whether the input is resampled or remixed to fit the file.
+    pub converted: bool,
+    /// Whether the new device captures over the Bluetooth hands-free
+    /// profile.
+    pub hands_free: bool,
+    /// Whether the new device is connected over USB.
+    pub usb: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingIoError {
+    pub error: ErrorPayload,
+    /// The file that could not be written.
+    pub path: Option<PathBuf>,
+    /// How much audio is held in memory before the rest is lost.
+    pub buffer_ms: u32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PitchDetected {
+    pub frequency: f64,
+    /// The nearest note in scientific pitch notation, e.g. `A4`.
+    pub note: String,
+    pub midi_note: i64,
+    /// How far the pitch is from the nearest note, from -50 to 50.
+    pub cents: f64,
+    /// How clearly periodic the input is, from 0 to 1.
+    pub clarity: f32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStateChanged {
+    pub path: PathBuf,
+    pub state: SyncState,
+    pub previous: SyncStatus,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkHashed {
+    pub path: PathBuf,
+    pub chunk: RecordingChunk,
+    /// Whether the chunk has been uploaded, rather than left for later.
+    pub uploaded: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WasmMetric {
+    /// The id the module reported the metric under.
+    pub id: i32,
+    pub value: f64,
+    /// The frame of the file the buffer being processed starts at.
+    pub position: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WasmStageFailed {
+    pub error: String,
+    /// The frame of the file from which the input is no longer processed.
+    pub position: u64,
+}
@@ -0,0 +1,57 @@
+use crate::tasks;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// How often a heartbeat is emitted while recording.
+pub(crate) const INTERVAL: Duration = Duration::from_secs(2);
+
+/// Tracks when the input stream last delivered a buffer.
+pub(crate) struct Liveness {
+    started: Instant,
+    /// Milliseconds from `started` to the latest callback.
+    last_callback_ms: AtomicU64,
+}
+
+impl Liveness {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            last_callback_ms: AtomicU64::new(0),
+        }
+    }
+
+    /// Records that a callback is running now.
+    pub fn touch(&self) {
+        self.last_callback_ms
+            .store(self.started.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// The time since the latest callback, or since the stream was created
+    /// if none has run yet.
+    pub fn last_callback_age(&self) -> Duration {
+        let last_callback = Duration::from_millis(self.last_callback_ms.load(Ordering::Relaxed));
+
+        self.started.elapsed().saturating_sub(last_callback)
+    }
+}
+
+/// Calls `on_beat` with the age of the latest callback every `INTERVAL` on a
+/// separate thread, for as long as `is_recording` stays set and the plugin
+/// is not shut down.
+pub(crate) fn watch<F>(is_recording: Arc<AtomicBool>, liveness: Arc<Liveness>, mut on_beat: F)
+where
+    F: FnMut(Duration) + Send + 'static,
+{
+    tasks::spawn(move || loop {
+        if !tasks::sleep(INTERVAL) || !is_recording.load(Ordering::SeqCst) {
+            break;
+        }
+
+        on_beat(liveness.last_callback_age());
+    });
+}
@@ -0,0 +1,50 @@
+use crate::{error::Result, models::RecordingOptions};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+};
+
+/// Decides whether a recording may start, given the options it is started
+/// with.
+pub(crate) type BeforeStart = Arc<dyn Fn(&RecordingOptions) -> Result<()> + Send + Sync>;
+
+/// Post-processes or moves a finalized recording, returning where it is now.
+pub(crate) type AfterStop = Arc<dyn Fn(&Path) -> Result<PathBuf> + Send + Sync>;
+
+/// The hooks set up with the plugin.
+static BEFORE_START: RwLock<Option<BeforeStart>> = RwLock::new(None);
+static AFTER_STOP: RwLock<Option<AfterStop>> = RwLock::new(None);
+
+pub(crate) fn set(before_start: Option<BeforeStart>, after_stop: Option<AfterStop>) {
+    *BEFORE_START.write().unwrap_or_else(PoisonError::into_inner) = before_start;
+    *AFTER_STOP.write().unwrap_or_else(PoisonError::into_inner) = after_stop;
+}
+
+/// Runs the `on_before_start` hook, whose error vetoes the start.
+pub(crate) fn before_start(options: &RecordingOptions) -> Result<()> {
+    let hook = BEFORE_START
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+
+    hook.map_or(Ok(()), |hook| hook(options))
+}
+
+/// Whether an `on_after_stop` hook has been set up.
+pub(crate) fn has_after_stop() -> bool {
+    AFTER_STOP
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+}
+
+/// Runs the `on_after_stop` hook, returning where the recording is now,
+/// which is `path` itself without a hook.
+pub(crate) fn after_stop(path: &Path) -> Result<PathBuf> {
+    let hook = AFTER_STOP
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+
+    hook.map_or_else(|| Ok(path.to_path_buf()), |hook| hook(path))
+}
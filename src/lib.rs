@@ -1,17 +1,215 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 use tauri::{
-    plugin::{Builder, TauriPlugin},
-    Runtime,
+    plugin::{Builder as PluginBuilder, TauriPlugin},
+    Manager, RunEvent, Runtime,
 };
 
+mod aead;
+#[cfg(target_os = "android")]
+mod android;
+mod audit;
+mod capabilities;
+mod capture;
+mod chunks;
 mod commands;
+mod dsp;
+mod duplicates;
+mod error;
+pub mod events;
+mod heartbeat;
+mod hooks;
+mod library;
+mod manifest;
+mod metadata;
+mod mirror;
+mod models;
+mod mp4;
+mod mute;
+mod network;
+mod permission;
+mod playback;
+mod priority;
+mod proxy;
+mod quota;
+mod reference;
+mod retention;
+mod session;
+#[cfg(feature = "global-shortcut")]
+mod shortcut;
+mod snapshot;
+mod storage;
+mod stream;
+mod tasks;
+mod template;
+mod transport;
+mod upload;
+mod webhook;
 
+pub use audit::{AuditEntry, AuditOperation};
+pub use chunks::RecordingChunk;
 pub use commands::*;
+pub use dsp::{
+    fingerprint::AudioFingerprint, tempo::TempoDetection, watermark::WatermarkDetection,
+};
+pub use duplicates::{DuplicateGroup, DuplicateOptions};
+pub use error::{Error, ErrorKind, ErrorPayload, Message, Result};
+pub use library::{
+    ListOptions, RecordingInfo, RecordingPage, RecordingSort, RecordingUpdate, SearchQuery,
+    SyncState, SyncStatus, SyncUpdate,
+};
+pub use manifest::{ChecksumManifest, ManifestEntry, ManifestVerification};
+pub use metadata::{Marker, RecordingMetadata};
+pub use models::*;
+pub use quota::NamespaceUsage;
+pub use session::ResumableSession;
+pub use storage::{LocalStorage, MemoryStorage, Storage, StorageSink};
+pub use template::RecordingTemplate;
+pub use upload::{UploadSchedule, UploadWindow, Uploader};
+pub use webhook::{WebhookEvent, WebhookOptions};
 
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
-    Builder::new("mic-recorder")
-        .invoke_handler(tauri::generate_handler![
-            commands::start_recording,
-            commands::stop_recording
-        ])
-        .build()
+    Builder::new().build()
+}
+
+/// Initializes the plugin with recordings written to `storage` instead of
+/// files, such as an app's own encrypted container or database.
+pub fn init_with_storage<R: Runtime, S: Storage>(storage: S) -> TauriPlugin<R> {
+    Builder::new().storage(storage).build()
+}
+
+/// Sets up the plugin with more than the defaults of `init`.
+#[derive(Default)]
+pub struct Builder {
+    storage: Option<Arc<dyn Storage>>,
+    uploader: Option<Arc<dyn Uploader>>,
+    before_start: Option<hooks::BeforeStart>,
+    after_stop: Option<hooks::AfterStop>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes recordings to `storage` instead of files, such as an app's
+    /// own encrypted container or database.
+    pub fn storage<S: Storage>(mut self, storage: S) -> Self {
+        self.storage = Some(Arc::new(storage));
+        self
+    }
+
+    /// Uploads finished recordings with `uploader`, on the schedule set
+    /// with `set_upload_schedule`.
+    pub fn uploader<U: Uploader>(mut self, uploader: U) -> Self {
+        self.uploader = Some(Arc::new(uploader));
+        self
+    }
+
+    /// Calls `hook` with the options of every recording about to start,
+    /// including resumed sessions. An error, such as `Error::Vetoed`, vetoes
+    /// the start, which fails with it. Runs while the recorder is locked, so
+    /// it must not start or stop recordings itself.
+    pub fn on_before_start<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&RecordingOptions) -> Result<()> + Send + Sync + 'static,
+    {
+        self.before_start = Some(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` with every recording once it has been finalized, e.g. to
+    /// transcode it or move it into the app's library, and goes on with the
+    /// file it returns. The recording is indexed and reported by
+    /// `recording-finalized` under the returned path, and `stop_recording`,
+    /// `force_stop` and `split_recording` wait for the hook to return it. An
+    /// error is reported like a failed finalization.
+    pub fn on_after_stop<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Path) -> Result<PathBuf> + Send + Sync + 'static,
+    {
+        self.after_stop = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn build<R: Runtime>(self) -> TauriPlugin<R> {
+        storage::set(self.storage);
+        if let Some(uploader) = self.uploader {
+            upload::set_uploader(uploader);
+        }
+        hooks::set(self.before_start, self.after_stop);
+
+        PluginBuilder::new("mic-recorder")
+            .invoke_handler(tauri::generate_handler![
+                commands::start_recording,
+                commands::stop_recording,
+                commands::get_audit_log,
+                commands::disable_recorder,
+                commands::enable_recorder,
+                commands::detect_watermark,
+                commands::process_channels,
+                commands::get_recording_metadata,
+                commands::detect_chapters,
+                commands::add_marker,
+                commands::get_recorder_state,
+                commands::force_stop,
+                commands::redirect_recording,
+                commands::snapshot_recording,
+                commands::split_recording,
+                commands::set_namespace_quota,
+                commands::get_namespace_usage,
+                commands::list_namespace_usage,
+                commands::detect_tempo,
+                commands::fingerprint_recording,
+                commands::compare_fingerprints,
+                commands::get_resumable_session,
+                commands::resume_session,
+                commands::discard_session,
+                commands::get_mic_permission,
+                commands::shutdown,
+                commands::export_template,
+                commands::import_template,
+                commands::set_recording_expiry,
+                commands::get_recording_expiry,
+                commands::delete_expired_recordings,
+                commands::update_recordings,
+                commands::search_recordings,
+                commands::list_recordings,
+                commands::export_m4a,
+                commands::export_captioned,
+                commands::create_manifest,
+                commands::verify_manifest,
+                commands::find_duplicates,
+                commands::update_sync_states,
+                commands::set_webhook,
+                commands::get_webhook,
+                commands::set_upload_schedule,
+                commands::get_upload_schedule,
+                commands::list_input_devices,
+                commands::get_recorder_snapshot,
+                commands::get_capabilities,
+                commands::request_usb_permission
+            ])
+            .setup(|app_handle, _api| {
+                tasks::reset();
+                commands::recover_session(app_handle);
+                commands::watch_permission(app_handle);
+                commands::watch_retention(app_handle);
+                commands::watch_uploads(app_handle);
+                Ok(())
+            })
+            .on_webview_ready(|webview| commands::offer_session(webview.app_handle()))
+            .on_event(|app_handle, event| {
+                if let RunEvent::Exit = event {
+                    if let Err(err) = commands::shut_down(app_handle, None, Duration::from_secs(5))
+                    {
+                        eprintln!("failed to shut down the recorder: {}", err);
+                    }
+                }
+            })
+            .build()
+    }
 }
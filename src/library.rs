@@ -0,0 +1,732 @@
+use crate::{
+    error::{Error, Message, Result},
+    session,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Serializes updates of the index.
+static LIBRARY_LOCK: Mutex<()> = Mutex::new(());
+
+/// The most recordings returned at once.
+const MAX_LIMIT: usize = 1000;
+
+/// A finished recording in the index, with the metadata an app manages
+/// for it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingInfo {
+    pub path: PathBuf,
+    /// Unix time in milliseconds at which the recording started.
+    pub recorded_at: u64,
+    pub duration_ms: Option<u64>,
+    pub device: Option<String>,
+    pub size_bytes: u64,
+    pub namespace: Option<String>,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    /// A transcript the app has made of the recording.
+    pub transcript: Option<String>,
+    /// Any other values the app keeps for the recording.
+    pub custom: BTreeMap<String, String>,
+    /// Whether the app has uploaded the recording. Recordings indexed
+    /// before sync states were kept are pending.
+    #[serde(default)]
+    pub sync: SyncState,
+}
+
+impl RecordingInfo {
+    pub fn new(
+        path: PathBuf,
+        duration_ms: Option<u64>,
+        device: Option<String>,
+        namespace: Option<String>,
+    ) -> Self {
+        Self {
+            path,
+            recorded_at: session::now_ms().saturating_sub(duration_ms.unwrap_or(0)),
+            duration_ms,
+            device,
+            size_bytes: 0,
+            namespace,
+            title: None,
+            tags: Vec::new(),
+            transcript: None,
+            custom: BTreeMap::new(),
+            sync: SyncState::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncStatus {
+    #[default]
+    Pending,
+    Uploaded,
+    Failed,
+}
+
+/// Where a recording stands in the app's uploads, whether the app uploads
+/// it or the uploader set up with the plugin does.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SyncState {
+    pub status: SyncStatus,
+    /// Why the last upload failed.
+    pub error: Option<String>,
+    /// Where the recording was uploaded to, e.g. a URL or an object key.
+    pub remote: Option<String>,
+    /// The uploads that failed since the last one that succeeded.
+    pub failures: u32,
+    /// Unix time in milliseconds of the last change, or 0 if there was none.
+    pub updated_at: u64,
+}
+
+/// A change of the sync state of one recording.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncUpdate {
+    pub path: PathBuf,
+    pub status: SyncStatus,
+    /// Why the upload failed, kept only with `Failed`.
+    pub error: Option<String>,
+    /// Where the recording was uploaded to, replacing the previous value if
+    /// set.
+    pub remote: Option<String>,
+}
+
+impl SyncUpdate {
+    fn apply(self, state: &mut SyncState) {
+        match self.status {
+            SyncStatus::Pending => {}
+            SyncStatus::Uploaded => state.failures = 0,
+            SyncStatus::Failed => state.failures += 1,
+        }
+
+        state.status = self.status;
+        state.error = self.error.filter(|_| self.status == SyncStatus::Failed);
+        if let Some(remote) = self.remote {
+            state.remote = Some(remote);
+        }
+        state.updated_at = session::now_ms();
+    }
+}
+
+/// Changes to the metadata of one recording. Fields left out are kept.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingUpdate {
+    pub path: PathBuf,
+    /// The new title, or an empty string to remove it.
+    pub title: Option<String>,
+    /// Replaces all tags.
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    #[serde(default)]
+    pub remove_tags: Vec<String>,
+    /// The new transcript, or an empty string to remove it.
+    pub transcript: Option<String>,
+    /// The custom values to set, removing those that are `None`.
+    #[serde(default)]
+    pub custom: BTreeMap<String, Option<String>>,
+}
+
+impl RecordingUpdate {
+    fn apply(self, info: &mut RecordingInfo) {
+        if let Some(title) = self.title {
+            info.title = Some(title).filter(|x| !x.is_empty());
+        }
+        if let Some(transcript) = self.transcript {
+            info.transcript = Some(transcript).filter(|x| !x.is_empty());
+        }
+
+        if let Some(tags) = self.tags {
+            info.tags = tags;
+        }
+        info.tags.retain(|x| !self.remove_tags.contains(x));
+        for tag in self.add_tags {
+            if !info.tags.contains(&tag) {
+                info.tags.push(tag);
+            }
+        }
+
+        for (key, value) in self.custom {
+            match value {
+                Some(value) => info.custom.insert(key, value),
+                None => info.custom.remove(&key),
+            };
+        }
+    }
+}
+
+/// What `search_recordings` looks for. Recordings have to match every
+/// criterion that is set.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchQuery {
+    /// Words that all have to appear, ignoring case, in the title, tags or
+    /// transcript.
+    pub text: Option<String>,
+    /// Tags the recording must all have.
+    pub tags: Vec<String>,
+    /// Unix time in milliseconds.
+    pub recorded_after: Option<u64>,
+    /// Unix time in milliseconds.
+    pub recorded_before: Option<u64>,
+    pub min_duration_ms: Option<u64>,
+    pub max_duration_ms: Option<u64>,
+    pub device: Option<String>,
+    pub namespace: Option<String>,
+    pub sync_status: Option<SyncStatus>,
+    /// The number of matching recordings to skip.
+    pub offset: usize,
+    /// The most recordings to return, up to 1000. Defaults to 50.
+    pub limit: usize,
+}
+
+impl Default for SearchQuery {
+    fn default() -> Self {
+        Self {
+            text: None,
+            tags: Vec::new(),
+            recorded_after: None,
+            recorded_before: None,
+            min_duration_ms: None,
+            max_duration_ms: None,
+            device: None,
+            namespace: None,
+            sync_status: None,
+            offset: 0,
+            limit: 50,
+        }
+    }
+}
+
+impl SearchQuery {
+    fn matches(&self, info: &RecordingInfo, words: &[String]) -> bool {
+        let duration_ms = info.duration_ms.unwrap_or(0);
+
+        self.recorded_after.map_or(true, |x| info.recorded_at >= x)
+            && self.recorded_before.map_or(true, |x| info.recorded_at < x)
+            && self.min_duration_ms.map_or(true, |x| duration_ms >= x)
+            && self.max_duration_ms.map_or(true, |x| duration_ms <= x)
+            && self
+                .device
+                .as_ref()
+                .map_or(true, |x| info.device.as_ref() == Some(x))
+            && self
+                .namespace
+                .as_ref()
+                .map_or(true, |x| info.namespace.as_ref() == Some(x))
+            && self.sync_status.map_or(true, |x| info.sync.status == x)
+            && self.tags.iter().all(|x| info.tags.contains(x))
+            && (words.is_empty() || {
+                let haystack = [info.title.as_deref(), info.transcript.as_deref()]
+                    .into_iter()
+                    .flatten()
+                    .chain(info.tags.iter().map(String::as_str))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+                    .to_lowercase();
+
+                words.iter().all(|x| haystack.contains(x.as_str()))
+            })
+    }
+}
+
+/// What `list_recordings` sorts by.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum RecordingSort {
+    #[default]
+    RecordedAt,
+    Duration,
+    Size,
+}
+
+/// Which page of the index `list_recordings` returns.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ListOptions {
+    pub sort_by: RecordingSort,
+    /// Sort in ascending order. Defaults to descending, newest first.
+    pub ascending: bool,
+    /// The number of recordings to skip.
+    pub offset: usize,
+    /// The most recordings to return, up to 1000. Defaults to 50.
+    pub limit: usize,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        Self {
+            sort_by: RecordingSort::default(),
+            ascending: false,
+            offset: 0,
+            limit: 50,
+        }
+    }
+}
+
+/// One page of recordings.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingPage {
+    pub recordings: Vec<RecordingInfo>,
+    /// The number of recordings on all pages.
+    pub total: usize,
+    /// The offset of the next page, or `None` if this is the last one.
+    pub next_offset: Option<usize>,
+}
+
+impl RecordingPage {
+    fn new(recordings: Vec<&RecordingInfo>, offset: usize, limit: usize) -> Self {
+        let total = recordings.len();
+        let limit = limit.clamp(1, MAX_LIMIT);
+        let end = offset.saturating_add(limit);
+
+        Self {
+            recordings: recordings
+                .into_iter()
+                .skip(offset)
+                .take(limit)
+                .cloned()
+                .collect(),
+            total,
+            next_offset: (end < total).then_some(end),
+        }
+    }
+}
+
+fn load(library_path: &Path) -> Result<BTreeMap<PathBuf, RecordingInfo>> {
+    if !library_path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    Ok(serde_json::from_reader(File::open(library_path)?)?)
+}
+
+fn save(library_path: &Path, library: &BTreeMap<PathBuf, RecordingInfo>) -> Result<()> {
+    // Replaced in one step so a crash never leaves a truncated file behind.
+    let temp_path = library_path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec(library)?)?;
+    fs::rename(&temp_path, library_path)?;
+
+    Ok(())
+}
+
+/// Adds a finished recording, replacing any previous one at its path.
+pub(crate) fn add(library_path: &Path, info: RecordingInfo) -> Result<()> {
+    let _guard = LIBRARY_LOCK.lock()?;
+    let mut library = load(library_path)?;
+
+    library.insert(info.path.clone(), info);
+    save(library_path, &library)
+}
+
+pub(crate) fn remove(library_path: &Path, paths: &[PathBuf]) -> Result<()> {
+    let _guard = LIBRARY_LOCK.lock()?;
+    let mut library = load(library_path)?;
+
+    let len = library.len();
+    library.retain(|path, _| !paths.contains(path));
+    if library.len() == len {
+        return Ok(());
+    }
+
+    save(library_path, &library)
+}
+
+pub(crate) fn all(library_path: &Path) -> Result<Vec<RecordingInfo>> {
+    let _guard = LIBRARY_LOCK.lock()?;
+    Ok(load(library_path)?.into_values().collect())
+}
+
+/// Lists the recordings in the index in the order of `options`. Ties are
+/// broken by path, so pages stay stable while nothing changes.
+pub(crate) fn list(library_path: &Path, options: &ListOptions) -> Result<RecordingPage> {
+    let _guard = LIBRARY_LOCK.lock()?;
+    let library = load(library_path)?;
+
+    // The index is ordered by path, and the sort is stable.
+    let mut recordings: Vec<&RecordingInfo> = library.values().collect();
+    match options.sort_by {
+        RecordingSort::RecordedAt => recordings.sort_by_key(|x| x.recorded_at),
+        RecordingSort::Duration => recordings.sort_by_key(|x| x.duration_ms),
+        RecordingSort::Size => recordings.sort_by_key(|x| x.size_bytes),
+    }
+    if !options.ascending {
+        recordings.reverse();
+    }
+
+    Ok(RecordingPage::new(
+        recordings,
+        options.offset,
+        options.limit,
+    ))
+}
+
+/// Finds the recordings matching `query`, newest first.
+pub(crate) fn search(library_path: &Path, query: &SearchQuery) -> Result<RecordingPage> {
+    let _guard = LIBRARY_LOCK.lock()?;
+    let library = load(library_path)?;
+
+    let words: Vec<String> = query
+        .text
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect();
+
+    let mut recordings: Vec<&RecordingInfo> = library
+        .values()
+        .filter(|x| query.matches(x, &words))
+        .collect();
+    recordings.sort_by_key(|x| Reverse(x.recorded_at));
+
+    Ok(RecordingPage::new(recordings, query.offset, query.limit))
+}
+
+/// Applies all updates in a single write, or none of them if one refers to
+/// a recording that is not in the index.
+pub(crate) fn update(
+    library_path: &Path,
+    updates: Vec<RecordingUpdate>,
+) -> Result<Vec<RecordingInfo>> {
+    let _guard = LIBRARY_LOCK.lock()?;
+    let mut library = load(library_path)?;
+
+    if let Some(update) = updates.iter().find(|x| !library.contains_key(&x.path)) {
+        return Err(unknown_recording(&update.path));
+    }
+
+    let mut updated = Vec::new();
+    for update in updates {
+        if let Some(info) = library.get_mut(&update.path) {
+            update.apply(info);
+            updated.push(info.clone());
+        }
+    }
+
+    save(library_path, &library)?;
+
+    Ok(updated)
+}
+
+/// Applies all sync updates in a single write, or none of them if one
+/// refers to a recording that is not in the index. Returns the updated
+/// recordings along with their previous sync states.
+pub(crate) fn update_sync(
+    library_path: &Path,
+    updates: Vec<SyncUpdate>,
+) -> Result<Vec<(RecordingInfo, SyncState)>> {
+    let _guard = LIBRARY_LOCK.lock()?;
+    let mut library = load(library_path)?;
+
+    if let Some(update) = updates.iter().find(|x| !library.contains_key(&x.path)) {
+        return Err(unknown_recording(&update.path));
+    }
+
+    let mut updated = Vec::new();
+    for update in updates {
+        if let Some(info) = library.get_mut(&update.path) {
+            let previous = info.sync.clone();
+            update.apply(&mut info.sync);
+            updated.push((info.clone(), previous));
+        }
+    }
+
+    save(library_path, &library)?;
+
+    Ok(updated)
+}
+
+fn unknown_recording(path: &Path) -> Error {
+    Error::InvalidOptions(
+        Message::new(
+            "invalidOptions.unknownRecording",
+            format!("{} is not in the index.", path.display()),
+        )
+        .param("path", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(path: &str, recorded_at: u64, duration_ms: u64) -> RecordingInfo {
+        RecordingInfo {
+            path: PathBuf::from(path),
+            recorded_at,
+            duration_ms: Some(duration_ms),
+            device: Some("Built-in Microphone".to_string()),
+            size_bytes: duration_ms * 96,
+            namespace: None,
+            title: None,
+            tags: Vec::new(),
+            transcript: None,
+            custom: BTreeMap::new(),
+            sync: SyncState::default(),
+        }
+    }
+
+    fn paths(page: &RecordingPage) -> Vec<&str> {
+        page.recordings
+            .iter()
+            .map(|x| x.path.to_str().unwrap())
+            .collect()
+    }
+
+    fn search_in(recordings: &[RecordingInfo], query: SearchQuery) -> Vec<String> {
+        let words: Vec<String> = query
+            .text
+            .as_deref()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect();
+
+        recordings
+            .iter()
+            .filter(|x| query.matches(x, &words))
+            .map(|x| x.path.display().to_string())
+            .collect()
+    }
+
+    /// An index in the temporary directory, removed again on drop.
+    struct TempLibrary(PathBuf);
+
+    impl TempLibrary {
+        fn new(name: &str, recordings: &[RecordingInfo]) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "mic-recorder-{}-{}.json",
+                name,
+                std::process::id()
+            ));
+            fs::remove_file(&path).ok();
+            for info in recordings {
+                add(&path, info.clone()).unwrap();
+            }
+
+            Self(path)
+        }
+    }
+
+    impl Drop for TempLibrary {
+        fn drop(&mut self) {
+            fs::remove_file(&self.0).ok();
+        }
+    }
+
+    #[test]
+    fn pages_through_the_recordings() {
+        let recordings: Vec<_> = (0..5).map(|x| info(&format!("/{x}.wav"), x, 0)).collect();
+        let all: Vec<&RecordingInfo> = recordings.iter().collect();
+
+        let page = RecordingPage::new(all.clone(), 0, 2);
+        assert_eq!(paths(&page), ["/0.wav", "/1.wav"]);
+        assert_eq!(page.total, 5);
+        assert_eq!(page.next_offset, Some(2));
+
+        let page = RecordingPage::new(all.clone(), 4, 2);
+        assert_eq!(paths(&page), ["/4.wav"]);
+        assert_eq!(page.next_offset, None);
+
+        let page = RecordingPage::new(all.clone(), 3, 2);
+        assert_eq!(page.next_offset, None);
+
+        let page = RecordingPage::new(all, 9, 2);
+        assert!(page.recordings.is_empty());
+        assert_eq!(page.total, 5);
+        assert_eq!(page.next_offset, None);
+    }
+
+    #[test]
+    fn clamps_the_limit() {
+        let recordings: Vec<_> = (0..1200)
+            .map(|x| info(&format!("/{x}.wav"), x, 0))
+            .collect();
+        let all: Vec<&RecordingInfo> = recordings.iter().collect();
+
+        let page = RecordingPage::new(all.clone(), 0, 0);
+        assert_eq!(page.recordings.len(), 1);
+        assert_eq!(page.next_offset, Some(1));
+
+        let page = RecordingPage::new(all.clone(), 0, 5000);
+        assert_eq!(page.recordings.len(), MAX_LIMIT);
+        assert_eq!(page.next_offset, Some(MAX_LIMIT));
+
+        let page = RecordingPage::new(all, usize::MAX, usize::MAX);
+        assert!(page.recordings.is_empty());
+        assert_eq!(page.next_offset, None);
+    }
+
+    #[test]
+    fn filters_by_every_criterion() {
+        let mut interview = info("/interview.wav", 1_000, 60_000);
+        interview.title = Some("Interview with Ada".to_string());
+        interview.tags = vec!["work".to_string(), "podcast".to_string()];
+        interview.namespace = Some("studio".to_string());
+
+        let mut memo = info("/memo.wav", 2_000, 5_000);
+        memo.transcript = Some("Buy milk and EGGS".to_string());
+        memo.device = Some("Headset".to_string());
+        memo.sync.status = SyncStatus::Uploaded;
+
+        let mut untitled = info("/untitled.wav", 3_000, 0);
+        untitled.duration_ms = None;
+
+        let recordings = [interview, memo, untitled];
+        let search = |query| search_in(&recordings, query);
+
+        assert_eq!(search(SearchQuery::default()).len(), 3);
+        assert_eq!(
+            search(SearchQuery {
+                text: Some("  ada   INTERVIEW ".to_string()),
+                ..Default::default()
+            }),
+            ["/interview.wav"]
+        );
+        assert_eq!(
+            search(SearchQuery {
+                text: Some("eggs".to_string()),
+                ..Default::default()
+            }),
+            ["/memo.wav"]
+        );
+        assert!(search(SearchQuery {
+            text: Some("ada eggs".to_string()),
+            ..Default::default()
+        })
+        .is_empty());
+        assert_eq!(
+            search(SearchQuery {
+                text: Some("podcast".to_string()),
+                tags: vec!["work".to_string()],
+                ..Default::default()
+            }),
+            ["/interview.wav"]
+        );
+        assert!(search(SearchQuery {
+            tags: vec!["work".to_string(), "home".to_string()],
+            ..Default::default()
+        })
+        .is_empty());
+        // The start is inclusive and the end exclusive.
+        assert_eq!(
+            search(SearchQuery {
+                recorded_after: Some(2_000),
+                recorded_before: Some(3_000),
+                ..Default::default()
+            }),
+            ["/memo.wav"]
+        );
+        // A recording without a duration counts as empty.
+        assert_eq!(
+            search(SearchQuery {
+                max_duration_ms: Some(5_000),
+                ..Default::default()
+            }),
+            ["/memo.wav", "/untitled.wav"]
+        );
+        assert_eq!(
+            search(SearchQuery {
+                min_duration_ms: Some(5_001),
+                ..Default::default()
+            }),
+            ["/interview.wav"]
+        );
+        assert_eq!(
+            search(SearchQuery {
+                device: Some("Headset".to_string()),
+                ..Default::default()
+            }),
+            ["/memo.wav"]
+        );
+        assert_eq!(
+            search(SearchQuery {
+                namespace: Some("studio".to_string()),
+                ..Default::default()
+            }),
+            ["/interview.wav"]
+        );
+        assert_eq!(
+            search(SearchQuery {
+                sync_status: Some(SyncStatus::Pending),
+                ..Default::default()
+            }),
+            ["/interview.wav", "/untitled.wav"]
+        );
+    }
+
+    #[test]
+    fn lists_and_searches_the_index() {
+        let library = TempLibrary::new(
+            "library-list",
+            &[
+                info("/a.wav", 3_000, 10),
+                info("/b.wav", 1_000, 30),
+                info("/c.wav", 2_000, 30),
+            ],
+        );
+
+        let page = list(&library.0, &ListOptions::default()).unwrap();
+        assert_eq!(paths(&page), ["/a.wav", "/c.wav", "/b.wav"]);
+
+        // Ties keep the order of the paths.
+        let options = ListOptions {
+            sort_by: RecordingSort::Duration,
+            ascending: true,
+            ..Default::default()
+        };
+        let page = list(&library.0, &options).unwrap();
+        assert_eq!(paths(&page), ["/a.wav", "/b.wav", "/c.wav"]);
+
+        let query = SearchQuery {
+            min_duration_ms: Some(20),
+            limit: 1,
+            ..Default::default()
+        };
+        let page = search(&library.0, &query).unwrap();
+        assert_eq!(paths(&page), ["/c.wav"]);
+        assert_eq!(page.total, 2);
+        assert_eq!(page.next_offset, Some(1));
+    }
+
+    #[test]
+    fn updates_all_recordings_or_none() {
+        let mut tagged = info("/a.wav", 0, 0);
+        tagged.tags = vec!["draft".to_string(), "work".to_string()];
+        tagged.title = Some("Old".to_string());
+        let library = TempLibrary::new("library-update", &[tagged]);
+
+        let update_of = |path: &str| RecordingUpdate {
+            path: PathBuf::from(path),
+            title: Some(String::new()),
+            tags: None,
+            add_tags: vec!["final".to_string(), "work".to_string()],
+            remove_tags: vec!["draft".to_string()],
+            transcript: Some("Hello".to_string()),
+            custom: BTreeMap::from([("take".to_string(), Some("2".to_string()))]),
+        };
+
+        assert!(update(&library.0, vec![update_of("/a.wav"), update_of("/b.wav")]).is_err());
+        assert_eq!(all(&library.0).unwrap()[0].title.as_deref(), Some("Old"));
+
+        let updated = update(&library.0, vec![update_of("/a.wav")]).unwrap();
+        assert_eq!(updated[0].title, None);
+        assert_eq!(updated[0].tags, ["work", "final"]);
+        assert_eq!(updated[0].transcript.as_deref(), Some("Hello"));
+        assert_eq!(updated[0].custom["take"], "2");
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{error::Result, session};
+use hound::WavReader;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+/// The name of the manifest within the folder it describes.
+pub(crate) const MANIFEST_NAME: &str = "checksums.json";
+
+/// One file of a checksum manifest.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    /// The path relative to the folder, separated by `/` on every platform.
+    pub path: String,
+    pub size_bytes: u64,
+    /// The SHA-256 hash of the file as lowercase hex.
+    pub sha256: String,
+    /// The duration of WAV files.
+    pub duration_ms: Option<u64>,
+}
+
+/// The checksums of every file in a recordings folder, stored next to them
+/// so a backup or an old archive can be checked against it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChecksumManifest {
+    /// Unix time in milliseconds at which the manifest was created.
+    pub created_at: u64,
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// How a folder compares to its manifest.
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestVerification {
+    /// The number of files that still match.
+    pub verified: usize,
+    /// Files in the manifest that are gone.
+    pub missing: Vec<String>,
+    /// Files whose content changed, e.g. through bit rot or a bad copy.
+    pub changed: Vec<String>,
+    /// Files that are not in the manifest.
+    pub added: Vec<String>,
+}
+
+/// Hashes every file in a folder and its subfolders, and writes the
+/// manifest into it. Symbolic links are not followed.
+pub(crate) fn create(directory: &Path) -> Result<ChecksumManifest> {
+    let mut entries = Vec::new();
+    for (path, file) in files(directory)? {
+        entries.push(entry(path, &file)?);
+    }
+
+    let manifest = ChecksumManifest {
+        created_at: session::now_ms(),
+        entries,
+    };
+
+    // Replaced in one step so a crash never leaves a truncated file behind.
+    let manifest_path = directory.join(MANIFEST_NAME);
+    let temp_path = manifest_path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(&manifest)?)?;
+    fs::rename(&temp_path, &manifest_path)?;
+
+    Ok(manifest)
+}
+
+/// Hashes every file in a folder again and compares it to the manifest.
+pub(crate) fn verify(directory: &Path) -> Result<ManifestVerification> {
+    let manifest: ChecksumManifest =
+        serde_json::from_reader(File::open(directory.join(MANIFEST_NAME))?)?;
+    let mut files = files(directory)?;
+    let mut verification = ManifestVerification::default();
+
+    for expected in manifest.entries {
+        let Some(file) = files.remove(&expected.path) else {
+            verification.missing.push(expected.path);
+            continue;
+        };
+
+        let (size_bytes, sha256) = hash(&file)?;
+        if size_bytes == expected.size_bytes && sha256 == expected.sha256 {
+            verification.verified += 1;
+        } else {
+            verification.changed.push(expected.path);
+        }
+    }
+    verification.added = files.into_keys().collect();
+
+    Ok(verification)
+}
+
+/// Lists the files of a folder by their relative path, leaving out the
+/// manifest itself.
+fn files(directory: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    let mut files = BTreeMap::new();
+    let mut pending = vec![(String::new(), directory.to_path_buf())];
+
+    while let Some((prefix, dir)) = pending.pop() {
+        for item in fs::read_dir(&dir)? {
+            let item = item?;
+            let name = item.file_name().to_string_lossy().into_owned();
+            let path = format!("{prefix}{name}");
+            let file_type = item.file_type()?;
+
+            if file_type.is_dir() {
+                pending.push((format!("{path}/"), item.path()));
+            } else if file_type.is_file()
+                && path != MANIFEST_NAME
+                && !path.ends_with(&format!("{MANIFEST_NAME}.tmp"))
+            {
+                files.insert(path, item.path());
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+fn entry(path: String, file: &Path) -> Result<ManifestEntry> {
+    let (size_bytes, sha256) = hash(file)?;
+    let duration_ms = WavReader::open(file)
+        .ok()
+        .map(|x| x.duration() as u64 * 1000 / x.spec().sample_rate.max(1) as u64);
+
+    Ok(ManifestEntry {
+        path,
+        size_bytes,
+        sha256,
+        duration_ms,
+    })
+}
+
+/// Returns the size and SHA-256 hash of a file.
+pub(crate) fn hash(path: &Path) -> Result<(u64, String)> {
+    Ok(hash_reader(File::open(path)?)?)
+}
+
+/// Returns the number of bytes read and their SHA-256 hash as lowercase hex.
+pub(crate) fn hash_reader(mut reader: impl Read) -> std::io::Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+
+    loop {
+        let len = reader.read(&mut buffer)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buffer[..len]);
+        size += len as u64;
+    }
+
+    let sha256 = hasher
+        .finalize()
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect();
+
+    Ok((size, sha256))
+}
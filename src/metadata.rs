@@ -0,0 +1,580 @@
+use crate::{error::Result, models::AmbisonicFormat, storage::StorageSink};
+use serde::Serialize;
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// The offset of `TimeReference` within a `bext` chunk.
+const BEXT_TIME_REFERENCE: usize = 338;
+/// The size of a `bext` chunk without coding history.
+const BEXT_LEN: usize = 602;
+/// The offset of `dwChannelMask` in the `WAVEFORMATEXTENSIBLE` header
+/// written for files with more than two channels.
+const CHANNEL_MASK: u64 = 40;
+/// Precedes the ambisonic format in the `USER` field of the `iXML` chunk.
+const AMBISONIC_TAG: &str = "AMBISONIC_FORMAT=";
+
+/// A labelled position within a recording, stored as a WAV cue point.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Marker {
+    /// The position in sample frames from the start of the file.
+    pub frame: u64,
+    pub label: String,
+}
+
+/// The metadata stored in a recording's extra RIFF chunks.
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingMetadata {
+    pub sample_rate: u32,
+    /// The BWF `TimeReference`: the file's start in samples since midnight.
+    pub time_reference: Option<u64>,
+    pub markers: Vec<Marker>,
+    /// How the channels are laid out, if the file was recorded from an
+    /// ambisonic mic.
+    pub ambisonic: Option<AmbisonicFormat>,
+}
+
+/// Metadata collected while recording and written once the file is finalized.
+pub(crate) type MetadataHandle = Arc<Mutex<RecordingMetadata>>;
+
+impl RecordingMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.time_reference.is_none() && self.markers.is_empty() && self.ambisonic.is_none()
+    }
+}
+
+/// Appends `bext`, `cue `, `LIST`/`adtl` and `iXML` chunks to a finalized
+/// WAV file.
+pub(crate) fn write(path: &Path, metadata: &RecordingMetadata) -> Result<()> {
+    if metadata.is_empty() {
+        return Ok(());
+    }
+
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    append(&mut file, metadata)
+}
+
+/// Appends the metadata chunks to a finalized WAV recording in any storage.
+pub(crate) fn append<S: Write + Seek + ?Sized>(
+    file: &mut S,
+    metadata: &RecordingMetadata,
+) -> Result<()> {
+    if metadata.is_empty() {
+        return Ok(());
+    }
+
+    let mut chunks = Vec::new();
+
+    if let Some(time_reference) = metadata.time_reference {
+        let mut bext = vec![0; BEXT_LEN];
+        bext[BEXT_TIME_REFERENCE..BEXT_TIME_REFERENCE + 8]
+            .copy_from_slice(&time_reference.to_le_bytes());
+        // Version 1 of the broadcast extension.
+        bext[BEXT_TIME_REFERENCE + 8] = 1;
+        push_chunk(&mut chunks, b"bext", &bext);
+    }
+
+    if !metadata.markers.is_empty() {
+        let mut cue = Vec::new();
+        let mut adtl = b"adtl".to_vec();
+
+        cue.extend((metadata.markers.len() as u32).to_le_bytes());
+
+        for (index, marker) in metadata.markers.iter().enumerate() {
+            let id = index as u32 + 1;
+            let frame = marker.frame.min(u32::MAX as u64) as u32;
+
+            cue.extend(id.to_le_bytes());
+            cue.extend(frame.to_le_bytes());
+            cue.extend(b"data");
+            cue.extend(0_u32.to_le_bytes());
+            cue.extend(0_u32.to_le_bytes());
+            cue.extend(frame.to_le_bytes());
+
+            let mut label = id.to_le_bytes().to_vec();
+            label.extend(marker.label.as_bytes());
+            label.push(0);
+            push_chunk(&mut adtl, b"labl", &label);
+        }
+
+        push_chunk(&mut chunks, b"cue ", &cue);
+        push_chunk(&mut chunks, b"LIST", &adtl);
+    }
+
+    if let Some(ambisonic) = metadata.ambisonic {
+        push_chunk(&mut chunks, b"iXML", ixml(ambisonic).as_bytes());
+
+        // The channels feed no speakers, so players must not treat the file
+        // as quadraphonic.
+        file.seek(SeekFrom::Start(CHANNEL_MASK))?;
+        file.write_all(&0_u32.to_le_bytes())?;
+    }
+
+    let mut end = file.seek(SeekFrom::End(0))?;
+
+    // Keep the appended chunks word aligned after an odd-sized data chunk.
+    if end % 2 == 1 {
+        file.write_all(&[0])?;
+        end += 1;
+    }
+
+    file.write_all(&chunks)?;
+
+    let riff_len = (end + chunks.len() as u64 - 8) as u32;
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&riff_len.to_le_bytes())?;
+
+    Ok(())
+}
+
+/// Describes the ambisonic channels as an iXML track list, which DAWs and
+/// field recorders show as channel names, along with the format itself.
+fn ixml(ambisonic: AmbisonicFormat) -> String {
+    let mut tracks = String::new();
+    for (index, name) in ambisonic.channel_names().iter().enumerate() {
+        tracks.push_str(&format!(
+            "<TRACK><CHANNEL_INDEX>{0}</CHANNEL_INDEX><INTERLEAVE_INDEX>{0}</INTERLEAVE_INDEX>\
+             <NAME>{name}</NAME></TRACK>",
+            index + 1
+        ));
+    }
+
+    let mut user = format!(
+        "{AMBISONIC_TAG}{};ORDER=1;CHANNELS={}",
+        ambisonic.name(),
+        ambisonic.channel_names().join(",")
+    );
+    if let Some(normalization) = ambisonic.normalization() {
+        user.push_str(&format!(";NORMALIZATION={normalization}"));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><BWFXML><IXML_VERSION>2.10</IXML_VERSION>\
+         <TRACK_LIST><TRACK_COUNT>4</TRACK_COUNT>{tracks}</TRACK_LIST><USER>{user}</USER></BWFXML>"
+    )
+}
+
+/// Reads the metadata chunks back from a WAV file.
+pub(crate) fn read(path: &Path) -> Result<RecordingMetadata> {
+    let mut metadata = RecordingMetadata::default();
+    let mut positions = Vec::new();
+    let mut labels = Vec::new();
+
+    for (id, data) in read_chunks(path)? {
+        let data = data.as_slice();
+
+        match &id {
+            b"fmt " if data.len() >= 8 => metadata.sample_rate = u32_at(data, 4),
+            b"bext" if data.len() >= BEXT_TIME_REFERENCE + 8 => {
+                let mut time_reference = [0; 8];
+                time_reference.copy_from_slice(&data[BEXT_TIME_REFERENCE..BEXT_TIME_REFERENCE + 8]);
+                metadata.time_reference = Some(u64::from_le_bytes(time_reference));
+            }
+            b"cue " if data.len() >= 4 => {
+                for point in data[4..].chunks_exact(24) {
+                    positions.push((u32_at(point, 0), u32_at(point, 20) as u64));
+                }
+            }
+            b"iXML" => {
+                let text = String::from_utf8_lossy(data);
+                metadata.ambisonic = text.split(AMBISONIC_TAG).nth(1).and_then(|x| {
+                    AmbisonicFormat::from_name(x.split([';', '<']).next().unwrap_or_default())
+                });
+            }
+            b"LIST" if data.starts_with(b"adtl") => {
+                for (id, data) in chunks(&data[4..]) {
+                    if id == b"labl" && data.len() >= 4 {
+                        let text = &data[4..];
+                        let text = &text[..text.iter().position(|&x| x == 0).unwrap_or(text.len())];
+                        labels.push((u32_at(data, 0), String::from_utf8_lossy(text).to_string()));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    metadata.markers = positions
+        .into_iter()
+        .map(|(id, frame)| Marker {
+            frame,
+            label: labels
+                .iter()
+                .find(|(label_id, _)| *label_id == id)
+                .map(|(_, label)| label.clone())
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    Ok(metadata)
+}
+
+fn push_chunk(buffer: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    buffer.extend(id);
+    buffer.extend((data.len() as u32).to_le_bytes());
+    buffer.extend(data);
+
+    // Chunks are word aligned.
+    if data.len() % 2 == 1 {
+        buffer.push(0);
+    }
+}
+
+/// The position, id and length of a top-level RIFF chunk.
+struct ChunkHeader {
+    position: u64,
+    id: [u8; 4],
+    len: u64,
+}
+
+impl ChunkHeader {
+    fn end(&self) -> u64 {
+        self.position + 8 + self.len + self.len % 2
+    }
+}
+
+fn chunk_headers(file: &mut File) -> Result<Vec<ChunkHeader>> {
+    let len = file.metadata()?.len();
+    let mut headers = Vec::new();
+    let mut position = 12;
+
+    while position + 8 <= len {
+        let mut header = [0; 8];
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut header)?;
+
+        let mut id = [0; 4];
+        id.copy_from_slice(&header[..4]);
+        let header = ChunkHeader {
+            position,
+            id,
+            len: u32_at(&header, 4) as u64,
+        };
+
+        position = header.end();
+        headers.push(header);
+    }
+
+    Ok(headers)
+}
+
+/// Reads the top-level chunks of a RIFF file that carry metadata, seeking
+/// past the audio so that large recordings are never loaded into memory.
+fn read_chunks(path: &Path) -> Result<Vec<([u8; 4], Vec<u8>)>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut chunks = Vec::new();
+
+    for header in chunk_headers(&mut file)? {
+        if matches!(&header.id, b"fmt " | b"bext" | b"cue " | b"LIST" | b"iXML") {
+            let mut data = vec![0; header.len.min(len - header.position - 8) as usize];
+            file.seek(SeekFrom::Start(header.position + 8))?;
+            file.read_exact(&mut data)?;
+            chunks.push((header.id, data));
+        }
+    }
+
+    Ok(chunks)
+}
+
+/// Removes the chunks `write` appends. Trailing ones are truncated away,
+/// any others are renamed to `JUNK` so the audio never has to be moved.
+fn strip(path: &Path) -> Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let headers = chunk_headers(&mut file)?;
+    let mut end = 12;
+
+    for header in headers.iter() {
+        let mut list_type = [0; 4];
+        if &header.id == b"LIST" {
+            file.seek(SeekFrom::Start(header.position + 8))?;
+            file.read_exact(&mut list_type)?;
+        }
+
+        if matches!(&header.id, b"bext" | b"cue " | b"iXML") || &list_type == b"adtl" {
+            file.seek(SeekFrom::Start(header.position))?;
+            file.write_all(b"JUNK")?;
+        } else if &header.id != b"JUNK" {
+            end = header.end();
+        }
+    }
+
+    file.set_len(end)?;
+    file.seek(SeekFrom::Start(4))?;
+    file.write_all(&((end - 8) as u32).to_le_bytes())?;
+
+    Ok(())
+}
+
+/// Reads the metadata of a WAV file, lets `f` change it and writes it back.
+pub(crate) fn update<F>(path: &Path, f: F) -> Result<RecordingMetadata>
+where
+    F: FnOnce(&mut RecordingMetadata),
+{
+    let mut metadata = read(path)?;
+    f(&mut metadata);
+
+    strip(path)?;
+    write(path, &metadata)?;
+
+    Ok(metadata)
+}
+
+/// Rewrites the RIFF and `data` sizes of a WAV recording whose writer never
+/// finalized it, keeping every whole frame that reached the storage.
+pub(crate) fn repair<S: StorageSink + ?Sized>(file: &mut S) -> Result<()> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut block_align = 1;
+    let mut position = 12;
+
+    // The `data` size is still zero, so chunks are walked only up to it.
+    while position + 8 <= len {
+        let mut header = [0; 8];
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut header)?;
+        let chunk_len = u32_at(&header, 4) as u64;
+
+        if &header[..4] == b"fmt " {
+            let mut fmt = [0; 14];
+            file.read_exact(&mut fmt)?;
+            block_align = u16::from_le_bytes([fmt[12], fmt[13]]).max(1) as u64;
+        } else if &header[..4] == b"data" {
+            let data_len = (len - position - 8) / block_align * block_align;
+            let end = position + 8 + data_len;
+
+            file.set_len(end)?;
+            file.seek(SeekFrom::Start(position + 4))?;
+            file.write_all(&(data_len.min(u32::MAX as u64) as u32).to_le_bytes())?;
+            file.seek(SeekFrom::Start(4))?;
+            file.write_all(&((end - 8).min(u32::MAX as u64) as u32).to_le_bytes())?;
+
+            return Ok(());
+        }
+
+        position += 8 + chunk_len + chunk_len % 2;
+    }
+
+    Ok(())
+}
+
+/// Iterates over the `(id, data)` of consecutive RIFF chunks.
+fn chunks(mut bytes: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    std::iter::from_fn(move || {
+        if bytes.len() < 8 {
+            return None;
+        }
+
+        let id = &bytes[..4];
+        let len = u32_at(bytes, 4) as usize;
+        let data = bytes.get(8..8 + len)?;
+        bytes = bytes.get(8 + len + len % 2..).unwrap_or_default();
+
+        Some((id, data))
+    })
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    let mut value = [0; 4];
+    value.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+    use std::{fs, io::Cursor, path::PathBuf};
+
+    /// Writes a WAV file in memory with `frames` frames of a ramp.
+    fn wav(channels: u16, bits_per_sample: u16, frames: usize) -> Vec<u8> {
+        let spec = WavSpec {
+            channels,
+            sample_rate: 48_000,
+            bits_per_sample,
+            sample_format: SampleFormat::Int,
+        };
+        let mut buffer = Cursor::new(Vec::new());
+        let mut writer = WavWriter::new(&mut buffer, spec).unwrap();
+        for i in 0..frames * channels as usize {
+            writer.write_sample((i % 100) as i8).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        buffer.into_inner()
+    }
+
+    /// Keeps a WAV file in the temporary directory for the test's duration.
+    struct TempWav(PathBuf);
+
+    impl TempWav {
+        fn new(name: &str, data: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "mic-recorder-{}-{}.wav",
+                name,
+                std::process::id()
+            ));
+            fs::write(&path, data).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempWav {
+        fn drop(&mut self) {
+            fs::remove_file(&self.0).ok();
+        }
+    }
+
+    /// The ids of the top-level chunks, checking that every one starts on
+    /// an even offset and that the RIFF size matches the file.
+    fn chunk_ids(data: &[u8]) -> Vec<[u8; 4]> {
+        assert_eq!(u32_at(data, 4) as usize, data.len() - 8);
+
+        let mut ids = Vec::new();
+        let mut position = 12;
+        while position + 8 <= data.len() {
+            assert_eq!(position % 2, 0);
+            ids.push(data[position..position + 4].try_into().unwrap());
+            let len = u32_at(data, position + 4) as usize;
+            position += 8 + len + len % 2;
+        }
+        assert_eq!(position, data.len());
+
+        ids
+    }
+
+    fn metadata() -> RecordingMetadata {
+        RecordingMetadata {
+            sample_rate: 48_000,
+            time_reference: Some(48_000 * 3600 * 9),
+            markers: vec![
+                Marker {
+                    frame: 10,
+                    label: "Intro".to_string(),
+                },
+                Marker {
+                    frame: 250,
+                    label: "Take 2".to_string(),
+                },
+            ],
+            ambisonic: None,
+        }
+    }
+
+    #[test]
+    fn round_trips_the_chunks() {
+        let mut buffer = Cursor::new(wav(2, 16, 400));
+        append(&mut buffer, &metadata()).unwrap();
+        let data = buffer.into_inner();
+        assert_eq!(
+            chunk_ids(&data),
+            [*b"fmt ", *b"data", *b"bext", *b"cue ", *b"LIST"]
+        );
+
+        let file = TempWav::new("metadata-round-trip", &data);
+        let read = read(&file.0).unwrap();
+        assert_eq!(read.sample_rate, 48_000);
+        assert_eq!(read.time_reference, metadata().time_reference);
+        let markers: Vec<_> = read
+            .markers
+            .iter()
+            .map(|x| (x.frame, x.label.as_str()))
+            .collect();
+        assert_eq!(markers, [(10, "Intro"), (250, "Take 2")]);
+
+        let reader = WavReader::open(&file.0).unwrap();
+        assert_eq!(reader.duration(), 400);
+    }
+
+    #[test]
+    fn pads_an_odd_data_chunk() {
+        let original = wav(1, 8, 3);
+        let mut buffer = Cursor::new(original.clone());
+        append(&mut buffer, &metadata()).unwrap();
+        let data = buffer.into_inner();
+
+        assert_eq!(chunk_ids(&data)[2], *b"bext");
+        let file = TempWav::new("metadata-odd", &data);
+        assert_eq!(read(&file.0).unwrap().markers.len(), 2);
+        assert_eq!(WavReader::open(&file.0).unwrap().duration(), 3);
+    }
+
+    #[test]
+    fn strips_and_updates_the_chunks() {
+        let original = wav(2, 16, 400);
+        let mut buffer = Cursor::new(original.clone());
+        append(&mut buffer, &metadata()).unwrap();
+        let file = TempWav::new("metadata-strip", &buffer.into_inner());
+
+        strip(&file.0).unwrap();
+        assert_eq!(fs::read(&file.0).unwrap(), original);
+        assert!(read(&file.0).unwrap().is_empty());
+
+        let updated = update(&file.0, |x| {
+            x.markers.push(Marker {
+                frame: 5,
+                label: "Added".to_string(),
+            })
+        })
+        .unwrap();
+        assert_eq!(updated.markers.len(), 1);
+        let read = read(&file.0).unwrap();
+        assert_eq!(read.markers[0].label, "Added");
+        assert_eq!(read.time_reference, None);
+        assert_eq!(WavReader::open(&file.0).unwrap().duration(), 400);
+    }
+
+    #[test]
+    fn stores_the_ambisonic_format() {
+        let mut buffer = Cursor::new(wav(4, 16, 100));
+        let data = buffer.get_ref();
+        assert_ne!(u32_at(data, CHANNEL_MASK as usize), 0);
+
+        let metadata = RecordingMetadata {
+            ambisonic: Some(AmbisonicFormat::AmbiX),
+            ..Default::default()
+        };
+        append(&mut buffer, &metadata).unwrap();
+        let data = buffer.into_inner();
+        assert_eq!(u32_at(&data, CHANNEL_MASK as usize), 0);
+        assert_eq!(chunk_ids(&data)[2], *b"iXML");
+
+        let file = TempWav::new("metadata-ambisonic", &data);
+        assert_eq!(
+            read(&file.0).unwrap().ambisonic,
+            Some(AmbisonicFormat::AmbiX)
+        );
+        assert_eq!(WavReader::open(&file.0).unwrap().spec().channels, 4);
+    }
+
+    #[test]
+    fn repairs_an_unfinalized_recording() {
+        let mut data = wav(2, 16, 400);
+        // As left by a crash: the sizes are still zero, and half a frame
+        // made it to the disk.
+        let data_position = chunk_ids(&data).iter().position(|x| x == b"data").unwrap();
+        assert_eq!(data_position, 1);
+        let data_len_offset = data.len() - 400 * 4 - 4;
+        data[4..8].copy_from_slice(&[0; 4]);
+        data[data_len_offset..data_len_offset + 4].copy_from_slice(&[0; 4]);
+        data.extend([1, 2]);
+
+        let file = TempWav::new("metadata-repair", &data);
+        let mut sink = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&file.0)
+            .unwrap();
+        repair(&mut sink).unwrap();
+        drop(sink);
+
+        let repaired = fs::read(&file.0).unwrap();
+        assert_eq!(repaired.len(), data.len() - 2);
+        chunk_ids(&repaired);
+        assert_eq!(WavReader::open(&file.0).unwrap().duration(), 400);
+    }
+}
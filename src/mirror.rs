@@ -0,0 +1,82 @@
+use crate::error::Result;
+use hound::{WavSpec, WavWriter};
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+/// A redundant copy of the recording, written straight to a file on another
+/// disk so that losing either file or disk does not lose the take.
+///
+/// It bypasses the configured storage, and a failure to write it only ends
+/// the copy, never the recording.
+pub(crate) struct Mirror {
+    writer: Option<WavWriter<BufWriter<File>>>,
+    path: PathBuf,
+    samples_per_second: u32,
+    /// The samples written since the header was last updated.
+    pending: u32,
+}
+
+impl Mirror {
+    pub fn create(path: &Path, spec: WavSpec) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        Ok(Self {
+            writer: Some(WavWriter::create(path, spec)?),
+            path: path.to_path_buf(),
+            samples_per_second: spec.sample_rate * spec.channels as u32,
+            pending: 0,
+        })
+    }
+
+    pub fn write<S: hound::Sample>(&mut self, sample: S) {
+        let Some(writer) = self.writer.as_mut() else {
+            return;
+        };
+
+        if let Err(err) = writer.write_sample(sample) {
+            self.fail(err);
+            return;
+        }
+
+        self.pending += 1;
+    }
+
+    /// Updates the header about once a second, so the copy stays playable
+    /// if the app crashes.
+    pub fn flush(&mut self) {
+        if self.pending < self.samples_per_second {
+            return;
+        }
+
+        if let Some(Err(err)) = self.writer.as_mut().map(WavWriter::flush) {
+            self.fail(err);
+        }
+        self.pending = 0;
+    }
+
+    fn fail(&mut self, err: hound::Error) {
+        eprintln!(
+            "failed to write the redundant copy {}: {}",
+            self.path.display(),
+            err
+        );
+        self.writer = None;
+    }
+
+    /// Completes the header and commits the copy to disk.
+    pub fn finish(self) -> Result<()> {
+        let Some(writer) = self.writer else {
+            return Ok(());
+        };
+
+        writer.finalize()?;
+        File::open(&self.path)?.sync_all()?;
+
+        Ok(())
+    }
+}
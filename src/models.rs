@@ -0,0 +1,707 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// What the recorder is doing, as returned by `get_recorder_state`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RecorderState {
+    Idle,
+    Recording,
+    /// Capture has stopped, but a recording is still being written to disk.
+    Finalizing,
+    /// The last recording ended because of an error. A new one can be
+    /// started as usual.
+    Failed,
+}
+
+/// Whether an optional feature can be used, as returned by
+/// `get_capabilities`.
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct Capability {
+    /// Whether the feature was compiled into the plugin for this platform.
+    pub compiled: bool,
+    /// Whether the feature works right now, e.g. with the JACK server
+    /// running.
+    pub usable: bool,
+}
+
+impl Capability {
+    pub(crate) fn new(compiled: bool, usable: bool) -> Self {
+        Self { compiled, usable }
+    }
+}
+
+/// The optional features of the plugin, as returned by `get_capabilities`.
+/// Everything else is always available.
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// `bookmarkShortcut`, with the `global-shortcut` feature.
+    pub global_shortcut: Capability,
+    /// Recording over JACK, with the `jack` feature on Linux and the BSDs.
+    pub jack: Capability,
+    /// Pausing on the mic-mute key and `mic-mute-changed`, on Windows.
+    pub mute_detection: Capability,
+    /// `getMicPermission` and `mic-permission-changed`, on Windows and
+    /// macOS.
+    pub permission_state: Capability,
+    /// Telling Bluetooth hands-free inputs apart reliably, on Windows, macOS
+    /// and Android. Elsewhere only some are recognized by their names.
+    pub hands_free_detection: Capability,
+    /// A reference track of the default output without naming a device,
+    /// on Windows.
+    pub loopback_reference: Capability,
+    /// Holding uploads back on metered connections, on Windows, Linux and
+    /// Android.
+    pub metered_network: Capability,
+    /// `threadPriority` above normal, usable if the OS lets the plugin raise
+    /// it, e.g. on Linux with `CAP_SYS_NICE`.
+    pub thread_priority: Capability,
+    /// Recordings written to files, which features such as `tempDir` and
+    /// `getRecordingMetadata` need. Not usable with a custom storage.
+    pub file_storage: Capability,
+    /// The `wasm` option, with the `wasm` feature.
+    pub wasm: Capability,
+}
+
+/// What windows need to render the recorder, as returned by
+/// `get_recorder_snapshot` and broadcast as `recorder-snapshot`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecorderSnapshot {
+    /// Increases with every change, so a window can tell whether the
+    /// snapshot it queried is older than the one it was sent.
+    pub revision: u64,
+    pub state: RecorderState,
+    pub disabled: bool,
+    pub paused: bool,
+    /// The file being recorded into.
+    pub path: Option<PathBuf>,
+    pub device: Option<String>,
+    pub namespace: Option<String>,
+    /// When recording into the current file began, as Unix time in
+    /// milliseconds.
+    pub started_at: Option<u64>,
+}
+
+/// Whether the app may use the microphone, as returned by
+/// `get_mic_permission`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PermissionState {
+    Granted,
+    Denied,
+    /// The user has not been asked yet. macOS only.
+    NotDetermined,
+    /// The platform does not expose the permission.
+    Unknown,
+}
+
+/// The result of `redirect_recording`.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingRedirected {
+    pub path: PathBuf,
+    pub previous_path: Option<PathBuf>,
+    /// The frames captured after the memory buffer was full, which are lost.
+    pub lost_frames: u64,
+}
+
+/// An input device and the configurations it can capture in, as returned by
+/// `list_input_devices`.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InputDevice {
+    pub name: String,
+    pub is_default: bool,
+    /// Whether the device is a Bluetooth headset that captures over the
+    /// hands-free profile, at telephone quality. `false` where the plugin
+    /// cannot tell.
+    pub is_hands_free: bool,
+    /// Whether the device is connected over USB, such as an audio interface.
+    /// `false` where the plugin cannot tell.
+    pub is_usb: bool,
+    /// The configuration recorded unless `channelCount` asks for another.
+    pub default_config: InputConfig,
+    /// The channel counts the device supports, in ascending order, e.g.
+    /// `[1, 2]` for a mic that can record in mono or stereo.
+    pub channel_counts: Vec<u16>,
+    pub configs: Vec<InputConfigRange>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InputConfig {
+    pub channels: u16,
+    pub sample_rate: u32,
+    /// As named by cpal, e.g. `i16` or `f32`.
+    pub sample_format: String,
+}
+
+/// A set of configurations the device supports, at any sample rate between
+/// the minimum and the maximum.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InputConfigRange {
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    pub sample_format: String,
+}
+
+/// Options accepted by `start_recording`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RecordingOptions {
+    /// Input device names in order of preference. The first one that is
+    /// connected and can be opened is recorded, `default` standing for the
+    /// default input. Overrides the `--device` flag if set.
+    pub preferred_devices: Vec<String>,
+    /// Stop writing audio while the hardware mic-mute key is engaged.
+    pub auto_pause_on_mute: bool,
+    /// Embed an inaudible marker that `detect_watermark` can find later.
+    pub watermark: Option<WatermarkOptions>,
+    /// Record this many channels, e.g. `2` for stereo, instead of the
+    /// device's default. Must be one of the device's `channelCounts`.
+    pub channel_count: Option<u16>,
+    /// Move over to the system's default input whenever it changes, e.g.
+    /// to or from AirPods on macOS, or when the device being recorded goes
+    /// away, converting the new input to the file's format.
+    pub follow_default_input: bool,
+    /// Move over to the first of `preferred_devices` that is connected
+    /// whenever that changes, e.g. to a USB audio interface plugged into a
+    /// phone and back once it is unplugged, converting it like
+    /// `follow_default_input`.
+    pub follow_preferred: bool,
+    /// Write the file with this many bits per sample: `16`, `24` or `32`,
+    /// capturing in a format at least as precise, e.g. 24-bit integer
+    /// samples from a 32-bit interface. The device's own width by default.
+    pub bit_depth: Option<u16>,
+    /// Record a 4-channel ambisonic mic and tag the file with how its
+    /// channels are ordered and normalized.
+    pub ambisonic: Option<AmbisonicFormat>,
+    /// Rewire the left and right channels while capturing.
+    pub channels: Option<ChannelOptions>,
+    /// Pad the start with silence so the file begins on a wall-clock
+    /// boundary or shared start signal.
+    pub align: Option<AlignOptions>,
+    /// Decode LTC timecode present on one input channel.
+    pub ltc: Option<LtcOptions>,
+    /// Mark a new chapter wherever the input resumes after a long silence.
+    pub chapters: Option<ChapterOptions>,
+    /// A global shortcut, e.g. `CommandOrControl+Shift+B`, that adds a
+    /// bookmark even while the app is unfocused. Requires the
+    /// `global-shortcut` feature.
+    pub bookmark_shortcut: Option<String>,
+    /// How audio streamed to the `onAudio` channel is buffered.
+    pub stream: Option<StreamOptions>,
+    /// Grow the input buffer whenever the backend drops audio.
+    pub adaptive_buffer: Option<AdaptiveBufferOptions>,
+    /// How much audio, in milliseconds, is held in memory when the file
+    /// cannot be written. Defaults to 60000.
+    pub io_error_buffer_ms: Option<u32>,
+    /// The priority of the threads that capture and stream audio. Left
+    /// untouched if unset.
+    pub thread_priority: Option<ThreadPriority>,
+    /// Store the recording in its own directory for a user or workspace,
+    /// subject to that namespace's quota.
+    pub namespace: Option<String>,
+    /// Record the app's playback into a separate track next to the
+    /// recording, for echo cancellation afterwards.
+    pub reference: Option<ReferenceOptions>,
+    /// Play a backing track while recording, and line the take up with it.
+    pub overdub: Option<OverdubOptions>,
+    /// Play a click on the output while recording, which is not recorded.
+    pub metronome: Option<MetronomeOptions>,
+    /// Emit `pitch-detected` events for the input, e.g. to build a tuner.
+    pub tuner: Option<TunerOptions>,
+    /// Run the input through a WebAssembly module, with the `wasm` feature.
+    pub wasm: Option<WasmOptions>,
+    /// Leave long silences out of the file, marking where they were.
+    pub compress_silence: Option<CompressSilenceOptions>,
+    /// Write a small low-rate copy of the recording next to it, e.g. to
+    /// upload right away while the full-quality file waits for Wi-Fi.
+    pub proxy: Option<ProxyOptions>,
+    /// Persist the session, so that it can be resumed into a new file if
+    /// the app crashes and is restarted soon enough.
+    pub resume: Option<ResumeOptions>,
+    /// Write a second copy of the recording, e.g. to another disk, so that
+    /// a single failing file or disk does not lose the take.
+    pub redundant: Option<RedundantOptions>,
+    /// Delete the recording this many milliseconds after it was started.
+    pub expire_after_ms: Option<u64>,
+    /// Hash the recording in chunks while it is captured, and upload them,
+    /// so only the tail is left to transfer once it is stopped.
+    pub chunks: Option<ChunkOptions>,
+    /// Identifies this start, so that retrying it, e.g. after a webview
+    /// reload, returns the recording it started instead of starting
+    /// another one. Never written to templates or sessions.
+    #[serde(skip_serializing)]
+    pub idempotency_key: Option<String>,
+    /// How long to wait for a start or stop that is already in progress
+    /// before failing with `Timeout`. Waits for as long as it takes by
+    /// default.
+    pub timeout_ms: Option<u64>,
+    /// Write the file to this directory while recording, e.g. on a fast
+    /// local disk, and move it to the save directory once it has been
+    /// finalized, so a synced or network folder never has to keep up with
+    /// capture.
+    pub temp_dir: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RedundantOptions {
+    /// The directory the copy is written to, under the recording's file
+    /// name. Must not be the directory of the recording itself.
+    pub directory: PathBuf,
+}
+
+/// How a recording is split into chunks while it is captured.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChunkOptions {
+    /// The size of every chunk but the last, at least 64 KiB. Defaults to
+    /// 8 MiB.
+    pub size_bytes: u64,
+    /// Upload the chunks with the uploader set up with the plugin, on its
+    /// schedule, rather than only hashing them.
+    pub upload: bool,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        Self {
+            size_bytes: 8 * 1024 * 1024,
+            upload: false,
+        }
+    }
+}
+
+/// How long a session interrupted by a crash stays resumable.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ResumeOptions {
+    /// The time from the last sign of the recording to the restart of the
+    /// app, in milliseconds. Defaults to 300000.
+    pub grace_ms: u64,
+}
+
+impl Default for ResumeOptions {
+    fn default() -> Self {
+        Self { grace_ms: 300_000 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TunerOptions {
+    /// The frequency of A4. Defaults to 440.
+    pub reference_hz: f64,
+    /// How often the pitch is measured, in milliseconds. Defaults to 100.
+    pub interval_ms: u32,
+    /// The lowest pitch detected, in hertz. Defaults to 50.
+    pub min_frequency: f64,
+    /// The highest pitch detected, in hertz. Defaults to 2000.
+    pub max_frequency: f64,
+}
+
+impl Default for TunerOptions {
+    fn default() -> Self {
+        Self {
+            reference_hz: 440.0,
+            interval_ms: 100,
+            min_frequency: 50.0,
+            max_frequency: 2000.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WasmOptions {
+    /// The `.wasm` file to load.
+    pub module: PathBuf,
+    /// The instructions the module may run for each buffer before it is
+    /// bypassed. Defaults to 10 000 000.
+    #[serde(default = "default_fuel_per_buffer")]
+    pub fuel_per_buffer: u64,
+}
+
+fn default_fuel_per_buffer() -> u64 {
+    10_000_000
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MetronomeOptions {
+    pub bpm: f64,
+    /// The beats in a bar, the first of which is accented. Defaults to 4.
+    #[serde(default = "default_beats_per_bar")]
+    pub beats_per_bar: u32,
+    /// Bars clicked before the recording, and any backing track, begins.
+    #[serde(default)]
+    pub count_in_bars: u32,
+    /// The volume from 0 to 1. Defaults to 0.5.
+    #[serde(default = "default_click_volume")]
+    pub volume: f32,
+    /// The output device to play it on. Defaults to the one of the
+    /// backing track, or the default output.
+    pub device: Option<String>,
+}
+
+fn default_beats_per_bar() -> u32 {
+    4
+}
+
+fn default_click_volume() -> f32 {
+    0.5
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OverdubOptions {
+    /// The WAV file to play from the start of the recording.
+    pub backing_track: PathBuf,
+    /// The output device to play it on. Defaults to the default output.
+    pub device: Option<String>,
+    /// Latency the audio backend does not report, e.g. of the converters,
+    /// in milliseconds. Positive values drop more of the start of the take.
+    #[serde(default)]
+    pub latency_ms: f64,
+}
+
+/// The format of the low-rate proxy.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProxyOptions {
+    /// Defaults to 8000, which gives a bitrate of 64 kbps.
+    pub sample_rate: u32,
+}
+
+impl Default for ProxyOptions {
+    fn default() -> Self {
+        Self { sample_rate: 8000 }
+    }
+}
+
+/// Where the playback reference is captured from.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReferenceOptions {
+    /// The device to capture, e.g. a monitor source or a virtual loopback
+    /// device. Defaults to the default output device on Windows, and has
+    /// to be set elsewhere.
+    pub device: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ThreadPriority {
+    Normal,
+    /// The highest regular priority.
+    High,
+    /// Real-time scheduling, falling back to `High` where it is not allowed.
+    Realtime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AdaptiveBufferOptions {
+    /// The largest buffer to grow to, in milliseconds. Defaults to 200.
+    pub max_buffer_ms: u32,
+}
+
+impl Default for AdaptiveBufferOptions {
+    fn default() -> Self {
+        Self { max_buffer_ms: 200 }
+    }
+}
+
+/// Buffering of the audio streamed to a frontend channel.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StreamOptions {
+    /// The most audio, in bytes, held for a consumer that falls behind.
+    /// Defaults to 8 MiB.
+    pub max_buffered_bytes: usize,
+    /// What to do once `max_buffered_bytes` is reached.
+    pub drop_policy: DropPolicy,
+    /// Encrypt every chunk before it reaches the frontend, so whatever
+    /// relays the stream never sees the audio.
+    pub encryption: Option<StreamEncryption>,
+    /// Frame every chunk with a sequence number and a timestamp, and add
+    /// parity to recover lost packets, for relays over lossy networks.
+    pub packets: Option<PacketOptions>,
+}
+
+/// How streamed audio is framed for receivers that may lose packets.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PacketOptions {
+    /// The share of packets expected to be lost, up to 50. From 1 on, a
+    /// parity packet follows every `100 / lossPercent` packets, at least
+    /// every other one. Defaults to 0, for no parity.
+    pub loss_percent: u8,
+}
+
+/// The key audio is streamed under. Each chunk is sealed with
+/// ChaCha20-Poly1305 as its 12-byte nonce, the ciphertext and the 16-byte
+/// tag, without associated data.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamEncryption {
+    /// The 32-byte key as 64 hex digits. It is left out of sessions and
+    /// templates, so it never reaches the disk.
+    #[serde(default, skip_serializing)]
+    pub key: String,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            max_buffered_bytes: 8 * 1024 * 1024,
+            drop_policy: DropPolicy::DropOldest,
+            encryption: None,
+            packets: None,
+        }
+    }
+}
+
+/// How the four channels of a first-order ambisonic recording are laid out.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AmbisonicFormat {
+    /// The raw capsule signals of a tetrahedral mic: front-left-up,
+    /// front-right-down, back-left-down and back-right-up.
+    AFormat,
+    /// B-format in ACN order (W, Y, Z, X) with SN3D normalization, as
+    /// expected by YouTube and most VR tools.
+    AmbiX,
+    /// B-format in Furse-Malham order (W, X, Y, Z), with W attenuated by
+    /// 3 dB.
+    FuMa,
+}
+
+impl AmbisonicFormat {
+    const ALL: [Self; 3] = [Self::AFormat, Self::AmbiX, Self::FuMa];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::AFormat => "aFormat",
+            Self::AmbiX => "ambiX",
+            Self::FuMa => "fuMa",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.name() == name)
+    }
+
+    /// The names of the channels in the order they are stored.
+    pub fn channel_names(self) -> [&'static str; 4] {
+        match self {
+            Self::AFormat => ["FLU", "FRD", "BLD", "BRU"],
+            Self::AmbiX => ["W", "Y", "Z", "X"],
+            Self::FuMa => ["W", "X", "Y", "Z"],
+        }
+    }
+
+    pub fn normalization(self) -> Option<&'static str> {
+        match self {
+            Self::AFormat => None,
+            Self::AmbiX => Some("SN3D"),
+            Self::FuMa => Some("FuMa"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DropPolicy {
+    /// Discard the oldest queued audio to make room.
+    DropOldest,
+    /// Discard the audio that does not fit.
+    DropNewest,
+    /// End the recording with an `Overflow` error.
+    StopWithError,
+}
+
+/// Options accepted by `stop_recording`.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StopOptions {
+    /// How long to wait for the stream and writer to shut down before
+    /// failing with `Timeout`, including the time spent waiting for a
+    /// start in progress. Defaults to 5000.
+    pub timeout_ms: Option<u64>,
+    /// Identifies this stop, so that retrying it returns the file it
+    /// stopped instead of failing with `NotRecording`.
+    pub idempotency_key: Option<String>,
+}
+
+/// The recording `start_recording` started.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StartedRecording {
+    pub path: PathBuf,
+    pub device: String,
+    /// Whether the idempotency key had been used before, so this is the
+    /// recording started back then, which may have been stopped since.
+    pub replayed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WatermarkOptions {
+    /// The secret the marker is derived from; the same key must be passed
+    /// to `detect_watermark`. It is left out of sessions and templates, so
+    /// it never reaches the disk.
+    #[serde(default, skip_serializing)]
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Channel {
+    Left,
+    Right,
+}
+
+/// Channel manipulation for miswired or mid/side microphone setups, applied
+/// in the order swap, invert, mid/side encode.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChannelOptions {
+    /// Swap the left and right channels.
+    pub swap: bool,
+    /// Invert the phase of one channel.
+    pub invert: Option<Channel>,
+    /// Encode left/right into mid (L+R)/2 and side (L-R)/2.
+    pub mid_side: bool,
+}
+
+/// Where the first sample of the recording should sit in wall-clock time.
+/// Exactly one of the fields must be set.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AlignOptions {
+    /// Start on the previous multiple of this many milliseconds since the
+    /// Unix epoch, e.g. `1000` for the start of the current second.
+    pub boundary_ms: Option<u64>,
+    /// Start at this Unix time in milliseconds, shared with the other
+    /// devices being aligned. Input captured before it is dropped.
+    pub start_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LtcOptions {
+    /// The zero-based input channel carrying the timecode signal.
+    pub channel: u16,
+}
+
+/// How long and how quiet a silence must be to start a new chapter.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChapterOptions {
+    /// The RMS level in dBFS below which the input counts as silent.
+    pub threshold_db: f32,
+    /// The shortest silence, in milliseconds, that separates chapters.
+    pub min_silence_ms: u64,
+}
+
+impl Default for ChapterOptions {
+    fn default() -> Self {
+        Self {
+            threshold_db: -45.0,
+            min_silence_ms: 3000,
+        }
+    }
+}
+
+/// How `export_m4a` writes a recording.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct M4aOptions {
+    /// Write an `.m4b` audiobook instead of an `.m4a` file, which podcast
+    /// and audiobook apps open with chapter navigation and resume.
+    pub audiobook: bool,
+    /// The title shown by players, and of the chapter before the first
+    /// marker. Defaults to the file name.
+    pub title: Option<String>,
+}
+
+/// The subtitle tracks `export_captioned` adds to a recording.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CaptionOptions {
+    /// Add a track showing the time every second: the time of day if the
+    /// recording has a BWF time reference, and otherwise the time since its
+    /// start. Defaults to `true`.
+    pub timestamps: bool,
+    /// Add a track showing the label of each marker until the next one.
+    /// Defaults to `true`.
+    pub markers: bool,
+    /// Timed captions to add as a track of their own, e.g. the segments of
+    /// a transcript.
+    pub captions: Vec<Caption>,
+}
+
+impl Default for CaptionOptions {
+    fn default() -> Self {
+        Self {
+            timestamps: true,
+            markers: true,
+            captions: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Caption {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+}
+
+/// How long and how quiet a silence must be to be left out of the file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompressSilenceOptions {
+    /// The RMS level in dBFS below which the input counts as silent.
+    pub threshold_db: f32,
+    /// How much of a silence, in milliseconds, is kept before the rest of
+    /// it is left out.
+    pub min_silence_ms: u64,
+}
+
+impl Default for CompressSilenceOptions {
+    fn default() -> Self {
+        Self {
+            threshold_db: -50.0,
+            min_silence_ms: 2000,
+        }
+    }
+}
+
+/// The range of tempos `detect_tempo` considers.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TempoOptions {
+    /// Defaults to 60.
+    pub min_bpm: f64,
+    /// Defaults to 200.
+    pub max_bpm: f64,
+}
+
+impl Default for TempoOptions {
+    fn default() -> Self {
+        Self {
+            min_bpm: 60.0,
+            max_bpm: 200.0,
+        }
+    }
+}
@@ -0,0 +1,826 @@
+use crate::error::Result;
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// The sample frames in each ALAC frame.
+const FRAME_LEN: usize = 4096;
+/// The language code `und`, packed as in the `mdhd` box.
+const UNDETERMINED: u16 = 0x55c4;
+/// The identity transformation of the `mvhd` and `tkhd` boxes.
+const MATRIX: [u32; 9] = [0x10000, 0, 0, 0, 0x10000, 0, 0, 0, 0x4000_0000];
+/// The font size and RGBA color of text that has no style of its own.
+const DEFAULT_FONT_SIZE: u8 = 18;
+const DEFAULT_TEXT_COLOR: u32 = 0xffff_ffff;
+/// Marks chapter titles as UTF-8 for Apple players.
+const ENCD: [u8; 12] = [0, 0, 0, 12, b'e', b'n', b'c', b'd', 0, 0, 1, 0];
+
+/// A text shown from `start_ms` until `end_ms`, or else until the next cue.
+pub(crate) struct Cue {
+    pub start_ms: u64,
+    pub end_ms: Option<u64>,
+    pub text: String,
+}
+
+pub(crate) enum TextKind {
+    /// Chapter titles, used by players for navigation.
+    Chapters,
+    /// Subtitles, shown by players while the audio plays.
+    Subtitles,
+}
+
+pub(crate) struct TextTrack {
+    pub kind: TextKind,
+    pub cues: Vec<Cue>,
+}
+
+/// A text track as written to the file.
+struct WrittenTrack<'a> {
+    kind: &'a TextKind,
+    /// The start, duration and text of every sample, in milliseconds.
+    samples: Vec<(u64, u64, &'a str)>,
+    sizes: Vec<u32>,
+    offsets: Vec<u64>,
+}
+
+/// Writes 16-bit audio as Apple Lossless to an MPEG-4 file, the format of
+/// M4A and M4B files.
+///
+/// Every ALAC frame is stored uncompressed, so the file is about as large as
+/// the WAV recording but needs no codec beyond the container.
+pub(crate) struct Mp4Writer {
+    file: BufWriter<File>,
+    sample_rate: u32,
+    channels: u16,
+    /// The interleaved samples of the frame being collected.
+    pending: Vec<i16>,
+    frame_sizes: Vec<u32>,
+    frame_offsets: Vec<u64>,
+    /// The sample frames written so far.
+    len: u64,
+    mdat_offset: u64,
+    position: u64,
+}
+
+impl Mp4Writer {
+    /// Creates the file, branded e.g. `M4A ` or `M4B `. Only mono and stereo
+    /// audio can be written.
+    pub fn create(path: &Path, brand: [u8; 4], sample_rate: u32, channels: u16) -> Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        let ftyp = atom(b"ftyp", [&brand[..], &[0; 4], b"M4A mp42isom"].concat());
+        file.write_all(&ftyp)?;
+
+        // The size of the media data is filled in once it is known.
+        let mdat_offset = ftyp.len() as u64;
+        file.write_all(&1_u32.to_be_bytes())?;
+        file.write_all(b"mdat")?;
+        file.write_all(&0_u64.to_be_bytes())?;
+
+        Ok(Self {
+            file,
+            sample_rate,
+            channels,
+            pending: Vec::with_capacity(FRAME_LEN * channels as usize),
+            frame_sizes: Vec::new(),
+            frame_offsets: Vec::new(),
+            len: 0,
+            mdat_offset,
+            position: mdat_offset + 16,
+        })
+    }
+
+    /// Writes a normalized sample, interleaved like in a WAV file.
+    pub fn write(&mut self, sample: f32) -> Result<()> {
+        let sample = (sample * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+        self.pending.push(sample);
+
+        if self.pending.len() == self.pending.capacity() {
+            self.write_frame()?;
+        }
+
+        Ok(())
+    }
+
+    fn write_frame(&mut self) -> Result<()> {
+        let channels = self.channels as usize;
+        let len = self.pending.len() / channels;
+        if len == 0 {
+            return Ok(());
+        }
+
+        let frame = encode_frame(&self.pending[..len * channels], channels);
+        self.file.write_all(&frame)?;
+
+        self.frame_sizes.push(frame.len() as u32);
+        self.frame_offsets.push(self.position);
+        self.position += frame.len() as u64;
+        self.len += len as u64;
+        self.pending.clear();
+
+        Ok(())
+    }
+
+    /// Writes the text tracks and the index of the file, with the title
+    /// shown by players if there is one.
+    pub fn finish(mut self, tracks: &[TextTrack], title: Option<&str>) -> Result<()> {
+        self.write_frame()?;
+
+        let duration_ms = self.len * 1000 / self.sample_rate as u64;
+        let mut written = Vec::new();
+
+        for track in tracks {
+            let samples = timeline(&track.cues, duration_ms);
+            if samples.is_empty() {
+                continue;
+            }
+
+            let mut sizes = Vec::new();
+            let mut offsets = Vec::new();
+            for &(_, _, text) in &samples {
+                let text = truncate(text, u16::MAX as usize);
+                let sample = [
+                    &(text.len() as u16).to_be_bytes()[..],
+                    text.as_bytes(),
+                    &ENCD,
+                ]
+                .concat();
+                self.file.write_all(&sample)?;
+
+                sizes.push(sample.len() as u32);
+                offsets.push(self.position);
+                self.position += sample.len() as u64;
+            }
+
+            written.push(WrittenTrack {
+                kind: &track.kind,
+                samples,
+                sizes,
+                offsets,
+            });
+        }
+
+        let moov = self.moov(&written, title, duration_ms);
+        self.file.write_all(&moov)?;
+
+        let mut file = self.file.into_inner().map_err(|x| x.into_error())?;
+        file.seek(SeekFrom::Start(self.mdat_offset + 8))?;
+        file.write_all(&(self.position - self.mdat_offset).to_be_bytes())?;
+        file.sync_all()?;
+
+        Ok(())
+    }
+
+    fn moov(&self, tracks: &[WrittenTrack], title: Option<&str>, duration_ms: u64) -> Vec<u8> {
+        let mvhd = full_atom(
+            b"mvhd",
+            0,
+            0,
+            [
+                &[0; 8][..],
+                &1000_u32.to_be_bytes(),
+                &(duration_ms.min(u32::MAX as u64) as u32).to_be_bytes(),
+                &0x10000_u32.to_be_bytes(),
+                &0x100_u16.to_be_bytes(),
+                &[0; 10],
+                &matrix(),
+                &[0; 24],
+                &(tracks.len() as u32 + 2).to_be_bytes(),
+            ]
+            .concat(),
+        );
+
+        // Audio track, which refers to the chapter tracks.
+        let mut deltas = vec![(self.frame_sizes.len() as u32, FRAME_LEN as u32)];
+        let remainder = (self.len % FRAME_LEN as u64) as u32;
+        if remainder > 0 {
+            deltas[0].0 -= 1;
+            deltas.push((1, remainder));
+        }
+        deltas.retain(|x| x.0 > 0);
+
+        let alac = full_atom(b"alac", 0, 0, self.magic_cookie().to_vec());
+        let sample_entry = atom(
+            b"alac",
+            [
+                &[0; 6][..],
+                &1_u16.to_be_bytes(),
+                &[0; 8],
+                &self.channels.to_be_bytes(),
+                &16_u16.to_be_bytes(),
+                &[0; 4],
+                &(self.sample_rate.min(u16::MAX as u32) << 16).to_be_bytes(),
+                &alac,
+            ]
+            .concat(),
+        );
+
+        let chapter_ids: Vec<u32> = (2..)
+            .zip(tracks)
+            .filter(|(_, x)| matches!(x.kind, TextKind::Chapters))
+            .map(|(id, _)| id)
+            .collect();
+        let tref = (!chapter_ids.is_empty()).then(|| {
+            atom(
+                b"tref",
+                atom(
+                    b"chap",
+                    chapter_ids.iter().flat_map(|x| x.to_be_bytes()).collect(),
+                ),
+            )
+        });
+
+        let mut moov = mvhd;
+        moov.extend(track(
+            1,
+            0x7,
+            0,
+            0x100,
+            duration_ms,
+            self.sample_rate,
+            self.len,
+            b"soun",
+            full_atom(b"smhd", 0, 0, vec![0; 4]),
+            sample_entry,
+            &deltas,
+            &self.frame_sizes,
+            &self.frame_offsets,
+            tref,
+        ));
+
+        // The text sample entry: no justification, background or text box,
+        // and a default style of the first font at 18 points in opaque
+        // white, which players fall back on for unstyled samples.
+        let mut tx3g = vec![0; 6];
+        tx3g.extend_from_slice(&1_u16.to_be_bytes());
+        tx3g.extend_from_slice(&[0, 0, 0, 1]);
+        tx3g.extend_from_slice(&[0; 18]);
+        tx3g.extend_from_slice(&1_u16.to_be_bytes());
+        tx3g.extend_from_slice(&[0, DEFAULT_FONT_SIZE]);
+        tx3g.extend_from_slice(&DEFAULT_TEXT_COLOR.to_be_bytes());
+        tx3g.extend_from_slice(&atom(b"ftab", vec![0, 1, 0, 1, 0]));
+
+        let mut shown = false;
+        for (id, text) in (2..).zip(tracks) {
+            // Chapter tracks are disabled so players do not show them as
+            // text, and only the first subtitles are shown by default.
+            let (flags, group, handler) = match text.kind {
+                TextKind::Chapters => (0x2, 0, b"text"),
+                TextKind::Subtitles => (if shown { 0x2 } else { 0x3 }, 2, b"sbtl"),
+            };
+            shown |= matches!(text.kind, TextKind::Subtitles);
+
+            let deltas: Vec<(u32, u32)> = text
+                .samples
+                .iter()
+                .map(|&(_, duration, _)| (1, duration.min(u32::MAX as u64) as u32))
+                .collect();
+
+            moov.extend(track(
+                id,
+                flags,
+                group,
+                0,
+                duration_ms,
+                1000,
+                duration_ms,
+                handler,
+                full_atom(b"nmhd", 0, 0, Vec::new()),
+                atom(b"tx3g", tx3g.clone()),
+                &deltas,
+                &text.sizes,
+                &text.offsets,
+                None,
+            ));
+        }
+
+        let mut udta = Vec::new();
+        if let Some(chapters) = tracks.iter().find(|x| matches!(x.kind, TextKind::Chapters)) {
+            udta.extend(chpl(&chapters.samples));
+        }
+        if let Some(title) = title {
+            let hdlr = full_atom(b"hdlr", 0, 0, [&[0; 4][..], b"mdirappl", &[0; 9]].concat());
+            let data = atom(
+                b"data",
+                [&1_u32.to_be_bytes()[..], &[0; 4], title.as_bytes()].concat(),
+            );
+            let ilst = atom(b"ilst", atom(b"\xa9nam", data));
+            udta.extend(full_atom(b"meta", 0, 0, [hdlr, ilst].concat()));
+        }
+        if !udta.is_empty() {
+            moov.extend(atom(b"udta", udta));
+        }
+
+        atom(b"moov", moov)
+    }
+
+    /// The decoder configuration of the ALAC stream.
+    fn magic_cookie(&self) -> [u8; 24] {
+        let max_frame_bytes = self.frame_sizes.iter().copied().max().unwrap_or(0);
+        let bit_rate = self.sample_rate as u64 * self.channels as u64 * 16;
+
+        let mut cookie = [0; 24];
+        cookie[..4].copy_from_slice(&(FRAME_LEN as u32).to_be_bytes());
+        // Compatible version, bit depth, and the Rice parameters `pb`, `mb`
+        // and `kb` Apple's encoder uses.
+        cookie[4..9].copy_from_slice(&[0, 16, 40, 10, 14]);
+        cookie[9] = self.channels as u8;
+        cookie[10..12].copy_from_slice(&255_u16.to_be_bytes());
+        cookie[12..16].copy_from_slice(&max_frame_bytes.to_be_bytes());
+        cookie[16..20].copy_from_slice(&(bit_rate.min(u32::MAX as u64) as u32).to_be_bytes());
+        cookie[20..].copy_from_slice(&self.sample_rate.to_be_bytes());
+        cookie
+    }
+}
+
+/// Lays the cues out back to back, filling the gaps between them with
+/// empty samples. Cues are cut off where the next one starts, or where the
+/// audio ends.
+fn timeline(cues: &[Cue], duration_ms: u64) -> Vec<(u64, u64, &str)> {
+    let mut cues: Vec<&Cue> = cues.iter().filter(|x| x.start_ms < duration_ms).collect();
+    cues.sort_by_key(|x| x.start_ms);
+
+    let mut samples = Vec::new();
+    let mut time = 0;
+    for (i, cue) in cues.iter().enumerate() {
+        let next = cues.get(i + 1).map_or(duration_ms, |x| x.start_ms);
+        let start = cue.start_ms.max(time);
+        let end = cue.end_ms.unwrap_or(next).min(next);
+        if end <= start {
+            continue;
+        }
+
+        if start > time {
+            samples.push((time, start - time, ""));
+        }
+        samples.push((start, end - start, cue.text.as_str()));
+        time = end;
+    }
+
+    if !samples.is_empty() && time < duration_ms {
+        samples.push((time, duration_ms - time, ""));
+    }
+
+    samples
+}
+
+/// Encodes an ALAC frame holding the samples uncompressed, as a single
+/// channel element for mono or a channel pair element for stereo.
+fn encode_frame(samples: &[i16], channels: usize) -> Vec<u8> {
+    let len = samples.len() / channels;
+    let partial = len < FRAME_LEN;
+    let mut bits = BitWriter::default();
+
+    // The element tag and instance, and 12 unused bits.
+    bits.put(channels as u32 - 1, 3);
+    bits.put(0, 4);
+    bits.put(0, 12);
+    // Whether the frame is shorter than usual, the bytes shifted off
+    // every sample, and that the samples are not compressed.
+    bits.put(if partial { 0b1001 } else { 0b0001 }, 4);
+    if partial {
+        bits.put(len as u32, 32);
+    }
+
+    for &sample in samples {
+        bits.put(sample as u16 as u32, 16);
+    }
+
+    // The end of the frame.
+    bits.put(7, 3);
+    bits.finish()
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    value: u64,
+    len: u32,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, len: u32) {
+        self.value = (self.value << len) | (value as u64 & ((1 << len) - 1));
+        self.len += len;
+
+        while self.len >= 8 {
+            self.len -= 8;
+            self.bytes.push((self.value >> self.len) as u8);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.bytes.push((self.value << (8 - self.len)) as u8);
+        }
+        self.bytes
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn track(
+    id: u32,
+    flags: u32,
+    alternate_group: i16,
+    volume: u16,
+    duration_ms: u64,
+    timescale: u32,
+    duration: u64,
+    handler: &[u8; 4],
+    media_header: Vec<u8>,
+    sample_entry: Vec<u8>,
+    deltas: &[(u32, u32)],
+    sizes: &[u32],
+    offsets: &[u64],
+    tref: Option<Vec<u8>>,
+) -> Vec<u8> {
+    let tkhd = full_atom(
+        b"tkhd",
+        0,
+        flags,
+        [
+            &[0; 8][..],
+            &id.to_be_bytes(),
+            &[0; 4],
+            &(duration_ms.min(u32::MAX as u64) as u32).to_be_bytes(),
+            &[0; 10],
+            &alternate_group.to_be_bytes(),
+            &volume.to_be_bytes(),
+            &[0; 2],
+            &matrix(),
+            &[0; 8],
+        ]
+        .concat(),
+    );
+
+    let mdhd = if duration > u32::MAX as u64 {
+        full_atom(
+            b"mdhd",
+            1,
+            0,
+            [
+                &[0; 16][..],
+                &timescale.to_be_bytes(),
+                &duration.to_be_bytes(),
+                &UNDETERMINED.to_be_bytes(),
+                &[0; 2],
+            ]
+            .concat(),
+        )
+    } else {
+        full_atom(
+            b"mdhd",
+            0,
+            0,
+            [
+                &[0; 8][..],
+                &timescale.to_be_bytes(),
+                &(duration as u32).to_be_bytes(),
+                &UNDETERMINED.to_be_bytes(),
+                &[0; 2],
+            ]
+            .concat(),
+        )
+    };
+
+    let hdlr = full_atom(b"hdlr", 0, 0, [&[0; 4][..], handler, &[0; 13]].concat());
+    let dref = full_atom(
+        b"dref",
+        0,
+        0,
+        [
+            &1_u32.to_be_bytes()[..],
+            &full_atom(b"url ", 0, 1, Vec::new()),
+        ]
+        .concat(),
+    );
+
+    let stsd = full_atom(
+        b"stsd",
+        0,
+        0,
+        [&1_u32.to_be_bytes()[..], &sample_entry].concat(),
+    );
+    let stts = full_atom(
+        b"stts",
+        0,
+        0,
+        table(deltas.len(), deltas.iter().flat_map(|&(x, y)| [x, y])),
+    );
+    // Every sample is a chunk of its own.
+    let stsc = full_atom(b"stsc", 0, 0, table(1, [1, 1, 1]));
+    let stsz = full_atom(
+        b"stsz",
+        0,
+        0,
+        [&[0; 4][..], &table(sizes.len(), sizes.iter().copied())].concat(),
+    );
+    let stco = if offsets.last().is_some_and(|&x| x > u32::MAX as u64) {
+        let mut body = (offsets.len() as u32).to_be_bytes().to_vec();
+        body.extend(offsets.iter().flat_map(|x| x.to_be_bytes()));
+        full_atom(b"co64", 0, 0, body)
+    } else {
+        full_atom(
+            b"stco",
+            0,
+            0,
+            table(offsets.len(), offsets.iter().map(|&x| x as u32)),
+        )
+    };
+
+    let stbl = atom(b"stbl", [stsd, stts, stsc, stsz, stco].concat());
+    let minf = atom(b"minf", [media_header, atom(b"dinf", dref), stbl].concat());
+    let mdia = atom(b"mdia", [mdhd, hdlr, minf].concat());
+
+    atom(b"trak", [tkhd, tref.unwrap_or_default(), mdia].concat())
+}
+
+/// The Nero chapter list, read by players that ignore chapter tracks.
+fn chpl(samples: &[(u64, u64, &str)]) -> Vec<u8> {
+    let samples = &samples[..samples.len().min(u8::MAX as usize)];
+    let mut body = vec![0; 4];
+    body.push(samples.len() as u8);
+
+    for &(start_ms, _, title) in samples {
+        let title = truncate(title, u8::MAX as usize);
+
+        // In units of 100 nanoseconds.
+        body.extend_from_slice(&(start_ms * 10_000).to_be_bytes());
+        body.push(title.len() as u8);
+        body.extend_from_slice(title.as_bytes());
+    }
+
+    full_atom(b"chpl", 1, 0, body)
+}
+
+/// Cuts a text down to at most `len` bytes without splitting a character.
+fn truncate(text: &str, mut len: usize) -> &str {
+    if len >= text.len() {
+        return text;
+    }
+    while !text.is_char_boundary(len) {
+        len -= 1;
+    }
+    &text[..len]
+}
+
+fn table<I: IntoIterator<Item = u32>>(len: usize, values: I) -> Vec<u8> {
+    let mut body = (len as u32).to_be_bytes().to_vec();
+    body.extend(values.into_iter().flat_map(u32::to_be_bytes));
+    body
+}
+
+fn matrix() -> Vec<u8> {
+    MATRIX.iter().flat_map(|x| x.to_be_bytes()).collect()
+}
+
+fn atom(kind: &[u8; 4], body: Vec<u8>) -> Vec<u8> {
+    [&(body.len() as u32 + 8).to_be_bytes()[..], kind, &body].concat()
+}
+
+fn full_atom(kind: &[u8; 4], version: u8, flags: u32, body: Vec<u8>) -> Vec<u8> {
+    let header = (version as u32) << 24 | flags;
+    atom(kind, [&header.to_be_bytes()[..], &body].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const SAMPLE_RATE: u32 = 48_000;
+    /// A full ALAC frame and a partial one.
+    const FRAMES: usize = FRAME_LEN + 100;
+
+    /// Splits the body of a box into its children, checking that their
+    /// sizes add up to it exactly.
+    fn children(mut data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+        let mut boxes = Vec::new();
+
+        while !data.is_empty() {
+            let size = u32::from_be_bytes(data[..4].try_into().unwrap()) as usize;
+            let kind: [u8; 4] = data[4..8].try_into().unwrap();
+            let (header, size) = match size {
+                1 => (
+                    16,
+                    u64::from_be_bytes(data[8..16].try_into().unwrap()) as usize,
+                ),
+                size => (8, size),
+            };
+            assert!(
+                header <= size && size <= data.len(),
+                "`{}` overruns its parent",
+                String::from_utf8_lossy(&kind)
+            );
+
+            boxes.push((kind, &data[header..size]));
+            data = &data[size..];
+        }
+
+        boxes
+    }
+
+    fn all<'a>(data: &'a [u8], kind: &[u8; 4]) -> Vec<&'a [u8]> {
+        children(data)
+            .into_iter()
+            .filter(|(x, _)| x == kind)
+            .map(|(_, body)| body)
+            .collect()
+    }
+
+    /// Follows a path of boxes, taking the first of each kind.
+    fn find<'a>(mut data: &'a [u8], path: &[&[u8; 4]]) -> &'a [u8] {
+        for kind in path {
+            data = all(data, kind)
+                .first()
+                .unwrap_or_else(|| panic!("no `{}`", String::from_utf8_lossy(*kind)));
+        }
+        data
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// Reads `len` bits from `start` on, most significant first.
+    fn bits(data: &[u8], start: usize, len: usize) -> u64 {
+        (start..start + len).fold(0, |value, bit| {
+            value << 1 | (data[bit / 8] >> (7 - bit % 8) & 1) as u64
+        })
+    }
+
+    /// The entries of a table in a full box, after `skip` bytes, flattened.
+    fn entries(full_box: &[u8], skip: usize) -> Vec<u32> {
+        let body = &full_box[4 + skip..];
+        let values: Vec<u32> = body[4..]
+            .chunks(4)
+            .map(|x| u32::from_be_bytes(x.try_into().unwrap()))
+            .collect();
+        assert_eq!(values.len() % u32_at(body, 0).max(1) as usize, 0);
+        values
+    }
+
+    fn mux(name: &str) -> Vec<u8> {
+        let path =
+            std::env::temp_dir().join(format!("mic-recorder-{}-{}.m4a", name, std::process::id()));
+
+        let mut writer = Mp4Writer::create(&path, *b"M4A ", SAMPLE_RATE, 2).unwrap();
+        for i in 0..FRAMES * 2 {
+            writer.write((i % 100) as f32 / 200.0).unwrap();
+        }
+        let chapters = TextTrack {
+            kind: TextKind::Chapters,
+            cues: vec![
+                Cue {
+                    start_ms: 40,
+                    end_ms: None,
+                    text: "Verse".to_string(),
+                },
+                Cue {
+                    start_ms: 0,
+                    end_ms: None,
+                    text: "Intro".to_string(),
+                },
+            ],
+        };
+        writer.finish(&[chapters], Some("Take 1")).unwrap();
+
+        let data = fs::read(&path).unwrap();
+        fs::remove_file(&path).ok();
+        data
+    }
+
+    #[test]
+    fn boxes_add_up_to_the_file() {
+        let data = mux("boxes");
+
+        let kinds: Vec<_> = children(&data).into_iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, [*b"ftyp", *b"mdat", *b"moov"]);
+        assert_eq!(&find(&data, &[b"ftyp"])[..4], b"M4A ");
+        assert_eq!(all(find(&data, &[b"moov"]), b"trak").len(), 2);
+    }
+
+    #[test]
+    fn indexes_the_alac_frames() {
+        let data = mux("alac");
+        let moov = find(&data, &[b"moov"]);
+        let audio = all(moov, b"trak")[0];
+        let stbl = find(audio, &[b"mdia", b"minf", b"stbl"]);
+
+        // A full frame of uncompressed stereo, and a partial one that also
+        // stores its length: the header bits, the samples and the end tag.
+        let full = (23 + FRAME_LEN as u32 * 2 * 16 + 3).div_ceil(8);
+        let partial = (23_u32 + 32 + 100 * 2 * 16 + 3).div_ceil(8);
+        assert_eq!(entries(find(stbl, &[b"stsz"]), 4), [full, partial]);
+
+        let offsets = entries(find(stbl, &[b"stco"]), 0);
+        let mdat_start = find(&data, &[b"ftyp"]).len() as u32 + 8 + 16;
+        assert_eq!(offsets, [mdat_start, mdat_start + full]);
+
+        // The partial frame is marked as such, stores its length and holds
+        // the samples unchanged.
+        let frame = &data[offsets[1] as usize..];
+        assert_eq!(bits(frame, 0, 3), 1);
+        assert_eq!(bits(frame, 19, 4), 0b1001);
+        assert_eq!(bits(frame, 23, 32), 100);
+        let first = FRAME_LEN * 2;
+        let expected = ((first % 100) as f32 / 200.0 * 32768.0).round() as u64;
+        assert_eq!(bits(frame, 55, 16), expected);
+
+        let stts = entries(find(stbl, &[b"stts"]), 0);
+        assert_eq!(stts, [1, FRAME_LEN as u32, 1, 100]);
+
+        let mdhd = find(audio, &[b"mdia", b"mdhd"]);
+        assert_eq!(u32_at(mdhd, 12), SAMPLE_RATE);
+        assert_eq!(u32_at(mdhd, 16), FRAMES as u32);
+        let mvhd = find(moov, &[b"mvhd"]);
+        assert_eq!(
+            u32_at(mvhd, 16),
+            (FRAMES as u64 * 1000 / SAMPLE_RATE as u64) as u32
+        );
+    }
+
+    #[test]
+    fn writes_the_magic_cookie() {
+        let data = mux("cookie");
+        let audio = all(find(&data, &[b"moov"]), b"trak")[0];
+        let stsd = find(audio, &[b"mdia", b"minf", b"stbl", b"stsd"]);
+        let entry = find(&stsd[8..], &[b"alac"]);
+        assert_eq!(u16::from_be_bytes([entry[16], entry[17]]), 2);
+        assert_eq!(u16::from_be_bytes([entry[18], entry[19]]), 16);
+
+        let cookie = &find(&entry[28..], &[b"alac"])[4..];
+        assert_eq!(cookie.len(), 24);
+        assert_eq!(u32_at(cookie, 0), FRAME_LEN as u32);
+        assert_eq!(cookie[5], 16);
+        assert_eq!(cookie[9], 2);
+        assert_eq!(
+            u32_at(cookie, 12),
+            (23 + FRAME_LEN as u32 * 2 * 16 + 3).div_ceil(8)
+        );
+        assert_eq!(u32_at(cookie, 16), SAMPLE_RATE * 2 * 16);
+        assert_eq!(u32_at(cookie, 20), SAMPLE_RATE);
+    }
+
+    #[test]
+    fn writes_chapters_as_a_track_and_a_list() {
+        let data = mux("chapters");
+        let moov = find(&data, &[b"moov"]);
+        let duration_ms = (FRAMES as u64 * 1000 / SAMPLE_RATE as u64) as u32;
+
+        let audio = all(moov, b"trak")[0];
+        assert_eq!(u32_at(find(audio, &[b"tref", b"chap"]), 0), 2);
+
+        let text = all(moov, b"trak")[1];
+        let stbl = find(text, &[b"mdia", b"minf", b"stbl"]);
+        assert_eq!(
+            entries(find(stbl, &[b"stts"]), 0),
+            [1, 40, 1, duration_ms - 40]
+        );
+        let sizes = entries(find(stbl, &[b"stsz"]), 4);
+        let offsets = entries(find(stbl, &[b"stco"]), 0);
+        let titles: Vec<_> = offsets
+            .iter()
+            .zip(&sizes)
+            .map(|(&offset, &size)| {
+                let sample = &data[offset as usize..(offset + size) as usize];
+                let len = u16::from_be_bytes([sample[0], sample[1]]) as usize;
+                assert_eq!(&sample[2 + len..], ENCD);
+                String::from_utf8(sample[2..2 + len].to_vec()).unwrap()
+            })
+            .collect();
+        assert_eq!(titles, ["Intro", "Verse"]);
+
+        let chpl = find(moov, &[b"udta", b"chpl"]);
+        assert_eq!(chpl[..4], [1, 0, 0, 0]);
+        assert_eq!(chpl[8], 2);
+        assert_eq!(u64::from_be_bytes(chpl[9..17].try_into().unwrap()), 0);
+        assert_eq!(&chpl[18..23], b"Intro");
+        assert_eq!(
+            u64::from_be_bytes(chpl[23..31].try_into().unwrap()),
+            40 * 10_000
+        );
+        assert_eq!(&chpl[32..37], b"Verse");
+    }
+
+    #[test]
+    fn text_has_a_visible_default_style() {
+        let data = mux("style");
+        let text = all(find(&data, &[b"moov"]), b"trak")[1];
+        let stsd = find(text, &[b"mdia", b"minf", b"stbl", b"stsd"]);
+        let tx3g = find(&stsd[8..], &[b"tx3g"]);
+
+        // After the display flags, justification, background and text box
+        // comes the style record: the characters it covers, the font id,
+        // the face, the size and the color.
+        assert_eq!(u32_at(tx3g, 8), 1);
+        assert_eq!(tx3g[12..26], [0; 14]);
+        assert_eq!(
+            tx3g[26..38],
+            [0, 0, 0, 0, 0, 1, 0, 18, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(&find(&tx3g[38..], &[b"ftab"])[..5], [0, 1, 0, 1, 0]);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::tasks;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// How often the endpoint mute state is polled.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Whether the platform exposes the hardware mute state, which only Windows
+/// does.
+pub(crate) fn is_supported() -> bool {
+    platform::is_supported()
+}
+
+/// Watches the hardware mute state of the default capture endpoint on a
+/// separate thread for as long as `is_recording` stays set, calling
+/// `on_change` with the new state whenever it flips.
+///
+/// Only Windows exposes the state toggled by mic-mute keys; elsewhere no
+/// thread is spawned and `on_change` is never called.
+pub(crate) fn watch<F>(is_recording: Arc<AtomicBool>, mut on_change: F)
+where
+    F: FnMut(bool) + Send + 'static,
+{
+    if !platform::is_supported() {
+        return;
+    }
+
+    tasks::spawn(move || {
+        let Some(probe) = platform::MuteProbe::new() else {
+            return;
+        };
+        let mut last = false;
+
+        while is_recording.load(Ordering::SeqCst) {
+            if let Some(muted) = probe.is_muted() {
+                if muted != last {
+                    last = muted;
+                    on_change(muted);
+                }
+            }
+
+            if !tasks::sleep(POLL_INTERVAL) {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows::Win32::{
+        Media::Audio::{
+            eCapture, eConsole, Endpoints::IAudioEndpointVolume, IMMDeviceEnumerator,
+            MMDeviceEnumerator,
+        },
+        System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_MULTITHREADED},
+    };
+
+    pub fn is_supported() -> bool {
+        true
+    }
+
+    pub struct MuteProbe(IAudioEndpointVolume);
+
+    impl MuteProbe {
+        /// Opens the default capture endpoint, which is the one the
+        /// mic-mute key toggles.
+        pub fn new() -> Option<Self> {
+            unsafe {
+                CoInitializeEx(None, COINIT_MULTITHREADED).ok().ok()?;
+
+                let enumerator: IMMDeviceEnumerator =
+                    CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL).ok()?;
+                let device = enumerator
+                    .GetDefaultAudioEndpoint(eCapture, eConsole)
+                    .ok()?;
+                let volume = device
+                    .Activate::<IAudioEndpointVolume>(CLSCTX_ALL, None)
+                    .ok()?;
+
+                Some(Self(volume))
+            }
+        }
+
+        pub fn is_muted(&self) -> Option<bool> {
+            unsafe { self.0.GetMute().ok().map(|x| x.as_bool()) }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    pub fn is_supported() -> bool {
+        false
+    }
+
+    pub struct MuteProbe;
+
+    impl MuteProbe {
+        pub fn new() -> Option<Self> {
+            None
+        }
+
+        pub fn is_muted(&self) -> Option<bool> {
+            None
+        }
+    }
+}
@@ -0,0 +1,50 @@
+/// Whether the connection the device reaches the internet through is
+/// metered, such as a cellular one, or `None` if the operating system does
+/// not let the plugin find out.
+pub(crate) fn is_metered() -> Option<bool> {
+    platform::is_metered()
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows::Networking::Connectivity::{NetworkCostType, NetworkInformation};
+
+    /// Asks for the cost of the internet connection profile, which is also
+    /// what Windows itself uses to hold back updates.
+    pub fn is_metered() -> Option<bool> {
+        let profile = NetworkInformation::GetInternetConnectionProfile().ok()?;
+        if profile.IsWwanConnectionProfile().unwrap_or(false) {
+            return Some(true);
+        }
+
+        let cost = profile.GetConnectionCost().ok()?.NetworkCostType().ok()?;
+        Some(cost != NetworkCostType::Unrestricted)
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod platform {
+    /// The prefixes of the interfaces of cellular modems, including those
+    /// on Qualcomm and MediaTek phones.
+    const CELLULAR_PREFIXES: [&str; 5] = ["rmnet", "ccmni", "wwan", "ww", "ppp"];
+
+    /// Looks at the interface of the default route. Newer Android versions
+    /// hide the routing table from apps, in which case it is unknown.
+    pub fn is_metered() -> Option<bool> {
+        let routes = std::fs::read_to_string("/proc/net/route").ok()?;
+        let interface = routes.lines().skip(1).find_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?;
+            (fields.next()? == "00000000").then_some(name)
+        })?;
+
+        Some(CELLULAR_PREFIXES.iter().any(|x| interface.starts_with(x)))
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "android")))]
+mod platform {
+    pub fn is_metered() -> Option<bool> {
+        None
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{error::Result, models::PermissionState, tasks};
+use std::time::Duration;
+
+/// How often the permission state is polled.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Whether the app may currently use the microphone, as far as the
+/// operating system lets it find out without prompting the user.
+pub(crate) fn state() -> PermissionState {
+    platform::state().unwrap_or(PermissionState::Unknown)
+}
+
+/// Whether the platform exposes the permission, which only Windows and
+/// macOS do.
+pub(crate) fn is_supported() -> bool {
+    platform::is_supported()
+}
+
+/// Asks the user to let the app open the USB device called `name`, waiting
+/// up to `timeout` for them to allow it. Only Android asks, and the state is
+/// `Unknown` elsewhere, where USB audio devices can be opened right away.
+pub(crate) fn request_usb(name: &str, timeout: Duration) -> Result<PermissionState> {
+    usb::request(name, timeout)
+}
+
+/// Polls the permission on a separate thread until the plugin is shut down,
+/// calling `on_change` with the new and the previous state whenever it
+/// changes, e.g. because the user flipped the toggle in the system settings.
+///
+/// Only Windows and macOS expose the permission; elsewhere no thread is
+/// spawned and `on_change` is never called.
+pub(crate) fn watch<F>(mut on_change: F)
+where
+    F: FnMut(PermissionState, PermissionState) + Send + 'static,
+{
+    if !platform::is_supported() {
+        return;
+    }
+
+    tasks::spawn(move || {
+        let mut last = state();
+
+        while tasks::sleep(POLL_INTERVAL) {
+            let current = state();
+            if current != last {
+                on_change(current, last);
+                last = current;
+            }
+        }
+    });
+}
+
+#[cfg(windows)]
+mod platform {
+    use crate::models::PermissionState;
+    use windows::{
+        core::{w, PCWSTR},
+        Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_SZ},
+    };
+
+    pub fn is_supported() -> bool {
+        true
+    }
+
+    /// Reads a consent value of the capability access manager, which is
+    /// either `Allow` or `Deny`.
+    fn consent(subkey: PCWSTR) -> Option<bool> {
+        let mut buffer = [0_u16; 16];
+        let mut size = std::mem::size_of_val(&buffer) as u32;
+
+        unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                subkey,
+                w!("Value"),
+                RRF_RT_REG_SZ,
+                None,
+                Some(buffer.as_mut_ptr().cast()),
+                Some(&mut size),
+            )
+            .ok()
+            .ok()?;
+        }
+
+        let len = (size as usize / 2).saturating_sub(1);
+        Some(String::from_utf16_lossy(&buffer[..len]) != "Deny")
+    }
+
+    /// Desktop apps need both the global microphone toggle and the one for
+    /// desktop apps to be on.
+    pub fn state() -> Option<PermissionState> {
+        let global = consent(w!(
+            r"Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore\microphone"
+        ))?;
+        let desktop = consent(w!(
+            r"Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore\microphone\NonPackaged"
+        ))
+        .unwrap_or(true);
+
+        Some(if global && desktop {
+            PermissionState::Granted
+        } else {
+            PermissionState::Denied
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use crate::models::PermissionState;
+    use std::ffi::{c_char, c_void};
+
+    #[link(name = "AVFoundation", kind = "framework")]
+    extern "C" {
+        static AVMediaTypeAudio: *const c_void;
+    }
+
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> *const c_void;
+        fn sel_registerName(name: *const c_char) -> *const c_void;
+        fn objc_msgSend();
+    }
+
+    pub fn is_supported() -> bool {
+        true
+    }
+
+    /// Asks `AVCaptureDevice` for the authorization status of audio, which
+    /// never prompts the user.
+    pub fn state() -> Option<PermissionState> {
+        unsafe {
+            let class = objc_getClass(c"AVCaptureDevice".as_ptr());
+            if class.is_null() {
+                return None;
+            }
+            let selector = sel_registerName(c"authorizationStatusForMediaType:".as_ptr());
+
+            // `objc_msgSend` has to be called through the signature of the
+            // method it dispatches to.
+            let send: unsafe extern "C" fn(*const c_void, *const c_void, *const c_void) -> isize =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+
+            Some(match send(class, selector, AVMediaTypeAudio) {
+                0 => PermissionState::NotDetermined,
+                // Restricted by parental controls or device management.
+                1 | 2 => PermissionState::Denied,
+                _ => PermissionState::Granted,
+            })
+        }
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+mod platform {
+    use crate::models::PermissionState;
+
+    pub fn is_supported() -> bool {
+        false
+    }
+
+    pub fn state() -> Option<PermissionState> {
+        None
+    }
+}
+
+#[cfg(target_os = "android")]
+mod usb {
+    use crate::{
+        android,
+        error::{Error, Message, Result},
+        models::PermissionState,
+    };
+    use jni::{
+        objects::{JObject, JObjectArray, JValue},
+        JNIEnv,
+    };
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    /// How often the permission is checked while the dialog is open.
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    /// `PendingIntent.FLAG_IMMUTABLE`, which Android 12 and later require.
+    const FLAG_IMMUTABLE: i32 = 0x0400_0000;
+
+    /// What the dialog broadcasts the answer as. Nothing receives it, the
+    /// permission is polled instead.
+    const ACTION: &str = "app.tauri.micrecorder.USB_PERMISSION";
+
+    fn has_permission(
+        env: &mut JNIEnv,
+        manager: &JObject,
+        device: &JObject,
+    ) -> jni::errors::Result<bool> {
+        env.call_method(
+            manager,
+            "hasPermission",
+            "(Landroid/hardware/usb/UsbDevice;)Z",
+            &[JValue::Object(device)],
+        )?
+        .z()
+    }
+
+    /// Finds the device in the ones the `UsbManager` lists by its product
+    /// name, which Android also names its audio input after, and shows the
+    /// permission dialog unless the app already has the permission.
+    pub fn request(name: &str, timeout: Duration) -> Result<PermissionState> {
+        let granted = android::with_context(|env, context| {
+            let manager = android::system_service(env, context, "usb")?;
+            let devices = env
+                .call_method(&manager, "getDeviceList", "()Ljava/util/HashMap;", &[])?
+                .l()?;
+            let devices = env
+                .call_method(&devices, "values", "()Ljava/util/Collection;", &[])?
+                .l()?;
+            let devices: JObjectArray = env
+                .call_method(&devices, "toArray", "()[Ljava/lang/Object;", &[])?
+                .l()?
+                .into();
+
+            let mut found = None;
+            for index in 0..env.get_array_length(&devices)? {
+                let device = env.get_object_array_element(&devices, index)?;
+                let product = env
+                    .call_method(&device, "getProductName", "()Ljava/lang/String;", &[])?
+                    .l()?;
+                if android::to_string(env, &product)?.as_deref() == Some(name) {
+                    found = Some(device);
+                    break;
+                }
+            }
+            let Some(device) = found else {
+                return Ok(None);
+            };
+            if has_permission(env, &manager, &device)? {
+                return Ok(Some(true));
+            }
+
+            let action = env.new_string(ACTION)?;
+            let intent = env.new_object(
+                "android/content/Intent",
+                "(Ljava/lang/String;)V",
+                &[JValue::Object(&action)],
+            )?;
+            let package = env
+                .call_method(context, "getPackageName", "()Ljava/lang/String;", &[])?
+                .l()?;
+            env.call_method(
+                &intent,
+                "setPackage",
+                "(Ljava/lang/String;)Landroid/content/Intent;",
+                &[JValue::Object(&package)],
+            )?;
+            let pending = env
+                .call_static_method(
+                    "android/app/PendingIntent",
+                    "getBroadcast",
+                    "(Landroid/content/Context;ILandroid/content/Intent;I)Landroid/app/PendingIntent;",
+                    &[
+                        JValue::Object(context),
+                        JValue::Int(0),
+                        JValue::Object(&intent),
+                        JValue::Int(FLAG_IMMUTABLE),
+                    ],
+                )?
+                .l()?;
+            env.call_method(
+                &manager,
+                "requestPermission",
+                "(Landroid/hardware/usb/UsbDevice;Landroid/app/PendingIntent;)V",
+                &[JValue::Object(&device), JValue::Object(&pending)],
+            )?;
+
+            // Android does not tell the app that the user declined, so the
+            // answer is awaited until the timeout.
+            let deadline = Instant::now() + timeout;
+            loop {
+                if has_permission(env, &manager, &device)? {
+                    return Ok(Some(true));
+                }
+                if Instant::now() >= deadline {
+                    return Ok(Some(false));
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+
+        match granted {
+            Some(Some(true)) => Ok(PermissionState::Granted),
+            Some(Some(false)) => Ok(PermissionState::Denied),
+            Some(None) => Err(Error::DeviceNotFound(
+                Message::new(
+                    "deviceNotFound.usb",
+                    format!("No USB device is attached by the name {}", name),
+                )
+                .param("name", name),
+            )),
+            None => Ok(PermissionState::Unknown),
+        }
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+mod usb {
+    use crate::{error::Result, models::PermissionState};
+    use std::time::Duration;
+
+    pub fn request(_name: &str, _timeout: Duration) -> Result<PermissionState> {
+        Ok(PermissionState::Unknown)
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{
+    dsp,
+    error::{Error, Result},
+};
+use cpal::{
+    traits::{DeviceTrait, StreamTrait},
+    FromSample, OutputCallbackInfo, Sample, Stream,
+};
+use hound::WavReader;
+use std::{path::Path, time::Duration};
+
+/// A sound played to the output while recording, which never reaches the
+/// recording itself.
+pub(crate) trait Source: Send {
+    /// Adds the next frames of the source to the interleaved `output`.
+    fn mix(&mut self, output: &mut [f32], channels: usize);
+}
+
+/// A file played from the start of the recording, e.g. for overdubbing.
+pub(crate) struct BackingTrack {
+    samples: Vec<f32>,
+    /// Interleaved samples of silence left to play before the track.
+    delay: usize,
+    position: usize,
+}
+
+impl BackingTrack {
+    /// Loads a WAV file, converted to the rate and channels of the output,
+    /// to be played after `delay_frames` of silence.
+    pub fn open(
+        path: &Path,
+        sample_rate: u32,
+        channels: usize,
+        delay_frames: usize,
+    ) -> Result<Self> {
+        let mut reader = WavReader::open(path)?;
+        let spec = reader.spec();
+        let source_channels = spec.channels.max(1) as usize;
+        let source = dsp::read_samples(&mut reader).collect::<hound::Result<Vec<_>>>()?;
+
+        let source_frames = source.len() / source_channels;
+        let ratio = spec.sample_rate as f64 / sample_rate as f64;
+        let frames = (source_frames as f64 / ratio) as usize;
+        let mut samples = Vec::with_capacity(frames * channels);
+
+        // Linear interpolation is plenty for a track that is only listened to.
+        for frame in 0..frames {
+            let at = frame as f64 * ratio;
+            let index = at as usize;
+            let fraction = (at - index as f64) as f32;
+            let next = (index + 1).min(source_frames - 1);
+
+            for channel in 0..channels {
+                let channel = channel % source_channels;
+                let a = source[index * source_channels + channel];
+                let b = source[next * source_channels + channel];
+                samples.push(a + (b - a) * fraction);
+            }
+        }
+
+        Ok(Self {
+            samples,
+            delay: delay_frames * channels,
+            position: 0,
+        })
+    }
+}
+
+impl Source for BackingTrack {
+    fn mix(&mut self, output: &mut [f32], _channels: usize) {
+        let delay = self.delay.min(output.len());
+        self.delay -= delay;
+        let output = &mut output[delay..];
+
+        let remaining = &self.samples[self.position..];
+        let len = remaining.len().min(output.len());
+
+        for (output, &sample) in output.iter_mut().zip(remaining[..len].iter()) {
+            *output += sample;
+        }
+        self.position += len;
+    }
+}
+
+/// A click on every beat, accented on the first beat of each bar.
+pub(crate) struct Click {
+    sample_rate: f64,
+    frames_per_beat: f64,
+    beats_per_bar: u64,
+    volume: f32,
+    /// The frames played so far.
+    position: u64,
+}
+
+impl Click {
+    /// How long a click sounds, in seconds.
+    const LENGTH: f64 = 0.03;
+
+    pub fn new(sample_rate: u32, bpm: f64, beats_per_bar: u32, volume: f32) -> Self {
+        Self {
+            sample_rate: sample_rate as f64,
+            frames_per_beat: sample_rate as f64 * 60.0 / bpm,
+            beats_per_bar: beats_per_bar.max(1) as u64,
+            volume,
+            position: 0,
+        }
+    }
+}
+
+impl Source for Click {
+    fn mix(&mut self, output: &mut [f32], channels: usize) {
+        for frame in output.chunks_mut(channels) {
+            let beat = (self.position as f64 / self.frames_per_beat) as u64;
+            let t = (self.position as f64 - beat as f64 * self.frames_per_beat) / self.sample_rate;
+            self.position += 1;
+
+            if t >= Self::LENGTH {
+                continue;
+            }
+
+            let frequency = if beat % self.beats_per_bar == 0 {
+                1500.0
+            } else {
+                1000.0
+            };
+            // A sine burst that decays linearly, so it never clicks itself.
+            let envelope = 1.0 - t / Self::LENGTH;
+            let sample = (2.0 * std::f64::consts::PI * frequency * t).sin() * envelope;
+
+            for output in frame.iter_mut() {
+                *output += sample as f32 * self.volume;
+            }
+        }
+    }
+}
+
+/// Mixes the sources of an output stream.
+pub(crate) struct Mixer {
+    sources: Vec<Box<dyn Source>>,
+    channels: usize,
+    buffer: Vec<f32>,
+    /// Called in the first callback with how long it takes the first frame
+    /// to be played.
+    on_start: Option<Box<dyn FnOnce(Duration) + Send>>,
+}
+
+impl Mixer {
+    pub fn new(channels: usize) -> Self {
+        Self {
+            sources: Vec::new(),
+            channels,
+            buffer: Vec::new(),
+            on_start: None,
+        }
+    }
+
+    pub fn push<S: Source + 'static>(&mut self, source: S) {
+        self.sources.push(Box::new(source));
+    }
+
+    pub fn on_start<F: FnOnce(Duration) + Send + 'static>(&mut self, f: F) {
+        self.on_start = Some(Box::new(f));
+    }
+
+    fn fill<T>(&mut self, output: &mut [T], info: &OutputCallbackInfo)
+    where
+        T: FromSample<f32>,
+    {
+        if let Some(on_start) = self.on_start.take() {
+            let timestamp = info.timestamp();
+            on_start(
+                timestamp
+                    .playback
+                    .duration_since(&timestamp.callback)
+                    .unwrap_or_default(),
+            );
+        }
+
+        self.buffer.clear();
+        self.buffer.resize(output.len(), 0.0);
+        for source in self.sources.iter_mut() {
+            source.mix(&mut self.buffer, self.channels);
+        }
+
+        for (output, &sample) in output.iter_mut().zip(self.buffer.iter()) {
+            *output = sample.clamp(-1.0, 1.0).to_sample::<T>();
+        }
+    }
+}
+
+/// Plays the mixer on `device` until the returned stream is dropped.
+pub(crate) fn play(
+    device: &cpal::Device,
+    config: &cpal::SupportedStreamConfig,
+    mixer: Mixer,
+) -> Result<Stream> {
+    let stream_config = config.clone().into();
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::I8 => build::<i8>(device, &stream_config, mixer)?,
+        cpal::SampleFormat::I16 => build::<i16>(device, &stream_config, mixer)?,
+        cpal::SampleFormat::I32 => build::<i32>(device, &stream_config, mixer)?,
+        cpal::SampleFormat::F32 => build::<f32>(device, &stream_config, mixer)?,
+        sample_format => return Err(Error::UnsupportedSampleFormat(sample_format.to_string())),
+    };
+    stream.play()?;
+
+    Ok(stream)
+}
+
+fn build<T>(device: &cpal::Device, config: &cpal::StreamConfig, mut mixer: Mixer) -> Result<Stream>
+where
+    T: cpal::SizedSample + FromSample<f32>,
+{
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], info: &_| mixer.fill(data, info),
+        |err| eprintln!("an error occurred on the output stream: {}", err),
+        None,
+    )?;
+
+    Ok(stream)
+}
@@ -0,0 +1,82 @@
+use crate::models::ThreadPriority;
+
+/// Sets the priority of the calling thread, falling back to the next lower
+/// priority where the OS does not allow it. Returns whether any change took.
+pub(crate) fn set_current(priority: ThreadPriority) -> bool {
+    match priority {
+        ThreadPriority::Normal => true,
+        ThreadPriority::High => platform::set_high(),
+        ThreadPriority::Realtime => platform::set_realtime() || platform::set_high(),
+    }
+}
+
+/// Whether the OS lets the plugin raise the priority of its threads, found
+/// out on a throwaway thread so no other thread is changed.
+pub(crate) fn can_raise() -> bool {
+    std::thread::spawn(|| set_current(ThreadPriority::High))
+        .join()
+        .unwrap_or(false)
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::mem::zeroed;
+
+    pub fn set_realtime() -> bool {
+        unsafe {
+            let mut param: libc::sched_param = zeroed();
+            param.sched_priority = (libc::sched_get_priority_min(libc::SCHED_FIFO)
+                + libc::sched_get_priority_max(libc::SCHED_FIFO))
+                / 2;
+
+            libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param) == 0
+        }
+    }
+
+    /// On Linux the nice value of a single thread can be lowered, which
+    /// needs `CAP_SYS_NICE` or a suitable `RLIMIT_NICE`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_high() -> bool {
+        unsafe { libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, -10) == 0 }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn set_high() -> bool {
+        unsafe {
+            let mut policy = 0;
+            let mut param: libc::sched_param = zeroed();
+            if libc::pthread_getschedparam(libc::pthread_self(), &mut policy, &mut param) != 0 {
+                return false;
+            }
+
+            param.sched_priority = libc::sched_get_priority_max(policy);
+            libc::pthread_setschedparam(libc::pthread_self(), policy, &param) == 0
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows::Win32::System::Threading::{
+        GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_HIGHEST, THREAD_PRIORITY_TIME_CRITICAL,
+    };
+
+    pub fn set_realtime() -> bool {
+        unsafe { SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_TIME_CRITICAL).is_ok() }
+    }
+
+    pub fn set_high() -> bool {
+        unsafe { SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_HIGHEST).is_ok() }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    pub fn set_realtime() -> bool {
+        false
+    }
+
+    pub fn set_high() -> bool {
+        false
+    }
+}
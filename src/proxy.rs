@@ -0,0 +1,175 @@
+use crate::{error::Result, storage::SharedSink};
+use std::io::{Seek, SeekFrom, Write};
+
+/// The WAVE format tag of G.711 μ-law.
+const FORMAT_MULAW: u16 = 7;
+
+/// The length of the header written by `Proxy::create`.
+const HEADER_LEN: u64 = 58;
+
+/// A small preview of the recording, written next to it as 8-bit μ-law
+/// mono so it can be uploaded long before the full-quality file.
+pub(crate) struct Proxy {
+    sink: SharedSink,
+    sample_rate: u32,
+    channels: usize,
+    /// The input frames per proxy frame.
+    step: f64,
+    phase: f64,
+    /// The sum of the samples of the current input frame.
+    frame_sum: f32,
+    channel: usize,
+    /// The sum of the input frames averaged into the next proxy frame.
+    sum: f32,
+    count: u32,
+    buffer: Vec<u8>,
+    frames: u32,
+    /// The frames the header was last updated for.
+    header_frames: u32,
+    failed: bool,
+}
+
+impl Proxy {
+    pub fn create(
+        mut sink: SharedSink,
+        sample_rate: u32,
+        input_sample_rate: u32,
+        channels: usize,
+    ) -> Result<Self> {
+        sink.write_all(&header(sample_rate, 0))?;
+
+        Ok(Self {
+            sink,
+            sample_rate,
+            channels: channels.max(1),
+            step: input_sample_rate as f64 / sample_rate.max(1) as f64,
+            phase: 0.0,
+            frame_sum: 0.0,
+            channel: 0,
+            sum: 0.0,
+            count: 0,
+            buffer: Vec::new(),
+            frames: 0,
+            header_frames: 0,
+            failed: false,
+        })
+    }
+
+    /// Adds one interleaved input sample.
+    pub fn push(&mut self, sample: f32) {
+        self.frame_sum += sample;
+        self.channel += 1;
+        if self.channel < self.channels {
+            return;
+        }
+
+        self.sum += self.frame_sum / self.channels as f32;
+        self.count += 1;
+        self.frame_sum = 0.0;
+        self.channel = 0;
+        self.phase += 1.0;
+
+        // Averaging the input frames of every proxy frame also filters out
+        // most of what would alias.
+        if self.phase >= self.step {
+            self.buffer.push(mu_law(self.sum / self.count as f32));
+            self.sum = 0.0;
+            self.count = 0;
+            self.phase -= self.step;
+        }
+    }
+
+    /// Writes the frames added since the last call. The header is updated
+    /// about once a second, so the file stays playable while it grows.
+    pub fn flush(&mut self) {
+        if self.failed || self.buffer.is_empty() {
+            return;
+        }
+
+        let result = self.sink.write_all(&self.buffer).and_then(|()| {
+            self.frames += self.buffer.len() as u32;
+            self.buffer.clear();
+
+            if self.frames - self.header_frames >= self.sample_rate {
+                self.write_header()?;
+            }
+
+            Ok(())
+        });
+
+        if let Err(err) = result {
+            eprintln!("failed to write the proxy: {}", err);
+            self.failed = true;
+        }
+    }
+
+    fn write_header(&mut self) -> std::io::Result<()> {
+        let mut sink = self.sink.lock();
+        let end = sink.stream_position()?;
+
+        sink.seek(SeekFrom::Start(0))?;
+        sink.write_all(&header(self.sample_rate, self.frames))?;
+        sink.seek(SeekFrom::Start(end))?;
+        self.header_frames = self.frames;
+
+        Ok(())
+    }
+
+    /// Writes what is left and completes the header.
+    pub fn finish(mut self) -> Result<()> {
+        self.flush();
+        if self.failed {
+            return Ok(());
+        }
+
+        // RIFF chunks are padded to an even length.
+        if self.frames % 2 == 1 {
+            self.sink.write_all(&[0])?;
+        }
+        self.write_header()?;
+        self.sink.lock().sync()?;
+
+        Ok(())
+    }
+}
+
+fn header(sample_rate: u32, frames: u32) -> [u8; HEADER_LEN as usize] {
+    let mut header = [0; HEADER_LEN as usize];
+    let riff_len = HEADER_LEN as u32 - 8 + frames + frames % 2;
+
+    header[0..4].copy_from_slice(b"RIFF");
+    header[4..8].copy_from_slice(&riff_len.to_le_bytes());
+    header[8..12].copy_from_slice(b"WAVE");
+    header[12..16].copy_from_slice(b"fmt ");
+    header[16..20].copy_from_slice(&18_u32.to_le_bytes());
+    header[20..22].copy_from_slice(&FORMAT_MULAW.to_le_bytes());
+    header[22..24].copy_from_slice(&1_u16.to_le_bytes());
+    header[24..28].copy_from_slice(&sample_rate.to_le_bytes());
+    header[28..32].copy_from_slice(&sample_rate.to_le_bytes());
+    header[32..34].copy_from_slice(&1_u16.to_le_bytes());
+    header[34..36].copy_from_slice(&8_u16.to_le_bytes());
+    // The bytes of the format extension, of which there are none.
+    header[36..38].copy_from_slice(&0_u16.to_le_bytes());
+    header[38..42].copy_from_slice(b"fact");
+    header[42..46].copy_from_slice(&4_u32.to_le_bytes());
+    header[46..50].copy_from_slice(&frames.to_le_bytes());
+    header[50..54].copy_from_slice(b"data");
+    header[54..58].copy_from_slice(&frames.to_le_bytes());
+
+    header
+}
+
+/// Encodes a sample with the G.711 μ-law curve.
+fn mu_law(sample: f32) -> u8 {
+    const BIAS: i32 = 0x84;
+    const CLIP: i32 = 32635;
+
+    let pcm = (sample.clamp(-1.0, 1.0) * 32767.0) as i32;
+    let sign = if pcm < 0 { 0x80 } else { 0 };
+    let magnitude = pcm.abs().min(CLIP) + BIAS;
+
+    let exponent = 24 - magnitude.leading_zeros() as i32;
+    let mantissa = (magnitude >> (exponent + 3)) & 0x0F;
+
+    !(sign | (exponent << 4) | mantissa) as u8
+}
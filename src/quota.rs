@@ -0,0 +1,101 @@
+use crate::error::{Error, Message, Result};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    path::{Component, Path},
+    sync::Mutex,
+};
+
+/// Serializes updates of the quota file.
+static QUOTA_LOCK: Mutex<()> = Mutex::new(());
+
+/// How much storage a namespace of recordings takes up.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NamespaceUsage {
+    /// The namespace, or `None` for recordings made without one.
+    pub namespace: Option<String>,
+    pub used_bytes: u64,
+    pub quota_bytes: Option<u64>,
+    pub recordings: u32,
+}
+
+/// Checks that a namespace maps to a single directory below the save
+/// directory, so it can never reach outside of it.
+pub(crate) fn validate(namespace: &str) -> Result<()> {
+    let mut components = Path::new(namespace).components();
+
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(()),
+        _ => Err(Error::InvalidOptions(
+            Message::new(
+                "invalidOptions.namespace",
+                format!("Invalid namespace \"{namespace}\"."),
+            )
+            .param("namespace", namespace),
+        )),
+    }
+}
+
+/// Reads the quota of every namespace that has one.
+pub(crate) fn read(quota_path: &Path) -> Result<BTreeMap<String, u64>> {
+    let _guard = QUOTA_LOCK.lock()?;
+    load(quota_path)
+}
+
+fn load(quota_path: &Path) -> Result<BTreeMap<String, u64>> {
+    if !quota_path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    Ok(serde_json::from_reader(File::open(quota_path)?)?)
+}
+
+/// Sets the quota of a namespace, or removes it when `quota_bytes` is `None`.
+pub(crate) fn set(quota_path: &Path, namespace: &str, quota_bytes: Option<u64>) -> Result<()> {
+    let _guard = QUOTA_LOCK.lock()?;
+    let mut quotas = load(quota_path)?;
+
+    match quota_bytes {
+        Some(quota_bytes) => quotas.insert(namespace.to_string(), quota_bytes),
+        None => quotas.remove(namespace),
+    };
+
+    // Replaced in one step so a crash never leaves a truncated file behind.
+    let temp_path = quota_path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(&quotas)?)?;
+    fs::rename(&temp_path, quota_path)?;
+
+    Ok(())
+}
+
+/// Adds up the recordings stored directly in `dir`.
+pub(crate) fn usage(
+    dir: &Path,
+    namespace: Option<String>,
+    quota_bytes: Option<u64>,
+) -> Result<NamespaceUsage> {
+    let mut usage = NamespaceUsage {
+        namespace,
+        used_bytes: 0,
+        quota_bytes,
+        recordings: 0,
+    };
+
+    if !dir.exists() {
+        return Ok(usage);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let is_recording = entry.path().extension().is_some_and(|x| x == "wav");
+
+        if is_recording && entry.file_type()?.is_file() {
+            usage.used_bytes += entry.metadata()?.len();
+            usage.recordings += 1;
+        }
+    }
+
+    Ok(usage)
+}
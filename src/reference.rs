@@ -0,0 +1,101 @@
+use crate::{error::Result, storage::SinkWriter};
+use cpal::{FromSample, Sample};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+
+/// Writes the app's playback into a track of its own, kept in step with
+/// the microphone recording so that echo can be cancelled offline.
+pub(crate) struct Reference {
+    writer: SinkWriter,
+    channels: usize,
+    /// The reference's sample rate divided by the microphone's.
+    rate_ratio: f64,
+    /// The frames written to the microphone recording.
+    position: Arc<AtomicU64>,
+    paused: Arc<AtomicBool>,
+    written: u64,
+    /// How far the track may fall behind before silence is inserted.
+    slack: u64,
+    failed: bool,
+}
+
+impl Reference {
+    pub fn new(
+        writer: SinkWriter,
+        sample_rate: u32,
+        mic_sample_rate: u32,
+        position: Arc<AtomicU64>,
+        paused: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            channels: writer.spec().channels.max(1) as usize,
+            writer,
+            rate_ratio: sample_rate as f64 / mic_sample_rate.max(1) as f64,
+            position,
+            paused,
+            written: 0,
+            slack: sample_rate as u64 / 50,
+            failed: false,
+        }
+    }
+
+    /// The frames the track should hold to line up with the microphone.
+    fn target(&self) -> u64 {
+        (self.position.load(Ordering::Relaxed) as f64 * self.rate_ratio) as u64
+    }
+
+    /// Writes one buffer of playback. Loopback capture delivers nothing
+    /// while nothing plays, so the gap is filled with silence first.
+    pub fn write<T>(&mut self, data: &[T])
+    where
+        T: Sample,
+        f32: FromSample<T>,
+    {
+        if self.failed || self.paused.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let frames = (data.len() / self.channels) as u64;
+        let behind = self.target().saturating_sub(self.written + frames);
+        let result = if behind > self.slack {
+            self.pad(behind)
+        } else {
+            Ok(())
+        }
+        .and_then(|()| {
+            for &sample in data.iter() {
+                self.writer.write_sample(f32::from_sample(sample))?;
+            }
+            self.written += frames;
+
+            Ok(())
+        });
+
+        if let Err(err) = result {
+            eprintln!("failed to write the reference track: {}", err);
+            self.failed = true;
+        }
+    }
+
+    fn pad(&mut self, frames: u64) -> Result<()> {
+        for _ in 0..frames * self.channels as u64 {
+            self.writer.write_sample(0.0_f32)?;
+        }
+        self.written += frames;
+
+        Ok(())
+    }
+
+    /// Pads the track to the length of the microphone recording and
+    /// finalizes it.
+    pub fn finish(mut self) -> Result<()> {
+        if !self.failed {
+            self.pad(self.target().saturating_sub(self.written))?;
+        }
+        self.writer.finalize()?;
+
+        Ok(())
+    }
+}
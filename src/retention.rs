@@ -0,0 +1,148 @@
+use crate::{
+    error::{Error, Message, Result},
+    session,
+};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Serializes updates of the expiry file.
+static EXPIRY_LOCK: Mutex<()> = Mutex::new(());
+
+/// The files written next to a recording that expire along with it.
+const SIDECARS: [&str; 2] = ["proxy", "reference"];
+
+/// When the recordings of a recording in progress expire, applied to every
+/// file it is written to.
+pub(crate) struct Policy {
+    pub expiry_path: PathBuf,
+    pub expire_after_ms: u64,
+}
+
+impl Policy {
+    pub fn apply(&self, recording: &Path) -> Result<()> {
+        let expires_at = session::now_ms().saturating_add(self.expire_after_ms);
+        set(&self.expiry_path, recording, Some(expires_at))
+    }
+}
+
+fn load(expiry_path: &Path) -> Result<BTreeMap<PathBuf, u64>> {
+    if !expiry_path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    Ok(serde_json::from_reader(File::open(expiry_path)?)?)
+}
+
+fn save(expiry_path: &Path, expiries: &BTreeMap<PathBuf, u64>) -> Result<()> {
+    // Replaced in one step so a crash never leaves a truncated file behind.
+    let temp_path = expiry_path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(expiries)?)?;
+    fs::rename(&temp_path, expiry_path)?;
+
+    Ok(())
+}
+
+/// Checks that a recording lies within the save directory, so an expiry can
+/// never delete anything outside of it.
+pub(crate) fn validate(save_dir: &Path, recording: &Path) -> Result<()> {
+    let inside = recording.is_absolute()
+        && recording.starts_with(save_dir)
+        && !recording.components().any(|x| x == Component::ParentDir);
+
+    if inside {
+        Ok(())
+    } else {
+        Err(Error::InvalidOptions(
+            Message::new(
+                "invalidOptions.expiryPath",
+                format!(
+                    "Only recordings in the save directory can expire, not {}.",
+                    recording.display()
+                ),
+            )
+            .param("path", recording.display()),
+        ))
+    }
+}
+
+/// Sets when a recording expires, in Unix milliseconds, or keeps it for
+/// good when `expires_at` is `None`.
+pub(crate) fn set(expiry_path: &Path, recording: &Path, expires_at: Option<u64>) -> Result<()> {
+    let _guard = EXPIRY_LOCK.lock()?;
+    let mut expiries = load(expiry_path)?;
+
+    match expires_at {
+        Some(expires_at) => expiries.insert(recording.to_path_buf(), expires_at),
+        None => expiries.remove(recording),
+    };
+
+    save(expiry_path, &expiries)
+}
+
+pub(crate) fn get(expiry_path: &Path, recording: &Path) -> Result<Option<u64>> {
+    let _guard = EXPIRY_LOCK.lock()?;
+    Ok(load(expiry_path)?.get(recording).copied())
+}
+
+/// Deletes the recordings that have expired, along with their proxy and
+/// reference track, and returns their paths. Recordings for which `keep`
+/// returns `true`, such as the one in progress, are left for a later
+/// sweep, as are those that cannot be deleted right now.
+pub(crate) fn sweep<F>(expiry_path: &Path, keep: F) -> Result<Vec<PathBuf>>
+where
+    F: Fn(&Path) -> bool,
+{
+    let _guard = EXPIRY_LOCK.lock()?;
+    let mut expiries = load(expiry_path)?;
+    let now = session::now_ms();
+    let mut deleted = Vec::new();
+
+    expiries.retain(|recording, &mut expires_at| {
+        if expires_at > now || keep(recording) {
+            return true;
+        }
+
+        match delete(recording) {
+            Ok(()) => {
+                deleted.push(recording.clone());
+                false
+            }
+            Err(err) => {
+                eprintln!(
+                    "failed to delete expired recording {}: {}",
+                    recording.display(),
+                    err
+                );
+                true
+            }
+        }
+    });
+
+    if !deleted.is_empty() {
+        save(expiry_path, &expiries)?;
+    }
+
+    Ok(deleted)
+}
+
+/// Deletes a recording and its sidecars, any of which may already be gone.
+fn delete(recording: &Path) -> io::Result<()> {
+    let stem = recording.file_stem().unwrap_or_default().to_string_lossy();
+    let sidecars = SIDECARS
+        .iter()
+        .map(|suffix| recording.with_file_name(format!("{stem}-{suffix}.wav")));
+
+    for path in std::iter::once(recording.to_path_buf()).chain(sidecars) {
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
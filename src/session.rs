@@ -0,0 +1,173 @@
+use crate::{error::Result, models::RecordingOptions};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Serializes updates of the session file.
+static SESSION_LOCK: Mutex<()> = Mutex::new(());
+
+/// The session left behind by a crash and still within its grace period,
+/// found when the plugin was set up.
+static RESUMABLE: Mutex<Option<Session>> = Mutex::new(None);
+
+/// The intent to go on recording, persisted for as long as a resumable
+/// recording runs and removed once it ends in any other way than a crash.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Session {
+    pub id: String,
+    /// The files recorded so far, in order.
+    pub segments: Vec<PathBuf>,
+    pub started_at: u64,
+    /// When the session was last known to be recording, refreshed by the
+    /// heartbeat.
+    pub last_seen: u64,
+    pub grace_ms: u64,
+    pub options: RecordingOptions,
+}
+
+/// A session interrupted by a crash that can be resumed.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumableSession {
+    pub id: String,
+    /// The files recorded before the interruption, in order.
+    pub segments: Vec<PathBuf>,
+    /// Unix time in milliseconds.
+    pub started_at: u64,
+    /// Unix time in milliseconds at which the recording was last seen.
+    pub interrupted_at: u64,
+}
+
+impl Session {
+    pub fn new(save_path: &Path, grace_ms: u64, options: RecordingOptions) -> Self {
+        let now = now_ms();
+
+        Self {
+            id: save_path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            segments: vec![save_path.to_path_buf()],
+            started_at: now,
+            last_seen: now,
+            grace_ms,
+            options,
+        }
+    }
+
+    /// The file the next segment is recorded to, next to the first one.
+    pub fn next_segment_path(&self) -> PathBuf {
+        let first = self.segments.first().cloned().unwrap_or_default();
+        let mut index = self.segments.len() + 1;
+
+        loop {
+            let path = first.with_file_name(format!("{}-part{index}.wav", self.id));
+            if !path.exists() {
+                return path;
+            }
+            index += 1;
+        }
+    }
+
+    pub fn summary(&self) -> ResumableSession {
+        ResumableSession {
+            id: self.id.clone(),
+            segments: self.segments.clone(),
+            started_at: self.started_at,
+            interrupted_at: self.last_seen,
+        }
+    }
+}
+
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn load(session_path: &Path) -> Result<Option<Session>> {
+    if !session_path.exists() {
+        return Ok(None);
+    }
+
+    Ok(serde_json::from_reader(File::open(session_path)?)?)
+}
+
+fn save(session_path: &Path, session: &Session) -> Result<()> {
+    // Replaced in one step so a crash never leaves a truncated file behind.
+    let temp_path = session_path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(session)?)?;
+    fs::rename(&temp_path, session_path)?;
+
+    Ok(())
+}
+
+pub(crate) fn write(session_path: &Path, session: &Session) -> Result<()> {
+    let _guard = SESSION_LOCK.lock()?;
+    save(session_path, session)
+}
+
+/// Applies `f` to the persisted session, if there is one.
+pub(crate) fn update<F>(session_path: &Path, f: F) -> Result<()>
+where
+    F: FnOnce(&mut Session),
+{
+    let _guard = SESSION_LOCK.lock()?;
+    let Some(mut session) = load(session_path)? else {
+        return Ok(());
+    };
+
+    f(&mut session);
+    save(session_path, &session)
+}
+
+pub(crate) fn remove(session_path: &Path) -> Result<()> {
+    let _guard = SESSION_LOCK.lock()?;
+
+    match fs::remove_file(session_path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Looks for a session left behind by a crash, offering it for resumption
+/// if its grace period has not passed and discarding it otherwise.
+pub(crate) fn recover(session_path: &Path) -> Result<Option<ResumableSession>> {
+    let session = {
+        let _guard = SESSION_LOCK.lock()?;
+        // A file that cannot be read could never be resumed either.
+        load(session_path).ok().flatten()
+    };
+
+    let summary = match session {
+        Some(session) if now_ms() <= session.last_seen.saturating_add(session.grace_ms) => {
+            let summary = session.summary();
+            *RESUMABLE.lock()? = Some(session);
+            Some(summary)
+        }
+        _ => {
+            remove(session_path)?;
+            None
+        }
+    };
+
+    Ok(summary)
+}
+
+/// The session offered for resumption, if any.
+pub(crate) fn resumable() -> Result<Option<Session>> {
+    Ok(RESUMABLE.lock()?.clone())
+}
+
+/// Withdraws the offered session, e.g. because it was resumed.
+pub(crate) fn take_resumable() -> Result<Option<Session>> {
+    Ok(RESUMABLE.lock()?.take())
+}
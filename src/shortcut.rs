@@ -0,0 +1,40 @@
+use crate::error::{Error, Message, Result};
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_global_shortcut::{GlobalShortcut, ShortcutState};
+
+/// Registers a global shortcut that calls `on_press` even while the app is
+/// unfocused. The global shortcut plugin is set up on demand if the app has
+/// not registered it itself.
+pub(crate) fn register<R, F>(app_handle: &AppHandle<R>, shortcut: &str, on_press: F) -> Result<()>
+where
+    R: Runtime,
+    F: Fn(&AppHandle<R>) + Send + Sync + 'static,
+{
+    if app_handle.try_state::<GlobalShortcut<R>>().is_none() {
+        app_handle.plugin(tauri_plugin_global_shortcut::Builder::new().build())?;
+    }
+
+    app_handle
+        .state::<GlobalShortcut<R>>()
+        .on_shortcut(shortcut, move |app_handle, _, event| {
+            if event.state == ShortcutState::Pressed {
+                on_press(app_handle);
+            }
+        })
+        .map_err(|err| {
+            Error::InvalidOptions(
+                Message::new(
+                    "invalidOptions.shortcut",
+                    format!("Invalid bookmark shortcut: {err}"),
+                )
+                .param("shortcut", shortcut)
+                .param("detail", err),
+            )
+        })
+}
+
+pub(crate) fn unregister<R: Runtime>(app_handle: &AppHandle<R>, shortcut: &str) {
+    if let Some(global_shortcut) = app_handle.try_state::<GlobalShortcut<R>>() {
+        global_shortcut.unregister(shortcut).ok();
+    }
+}
@@ -0,0 +1,52 @@
+use crate::{
+    events,
+    models::{RecorderSnapshot, RecorderState},
+};
+use std::sync::{LazyLock, Mutex, PoisonError};
+use tauri::{AppHandle, Emitter, Runtime};
+
+/// The snapshot last broadcast, for windows that open after it was sent.
+static SNAPSHOT: LazyLock<Mutex<RecorderSnapshot>> = LazyLock::new(|| {
+    Mutex::new(RecorderSnapshot {
+        revision: 0,
+        state: RecorderState::Idle,
+        disabled: false,
+        paused: false,
+        path: None,
+        device: None,
+        namespace: None,
+        started_at: None,
+    })
+});
+
+/// Gets the snapshot last broadcast.
+pub(crate) fn get() -> RecorderSnapshot {
+    SNAPSHOT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Updates the snapshot and broadcasts it as `recorder-snapshot` to every
+/// window, unless nothing changed.
+pub(crate) fn publish<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    update: impl FnOnce(&mut RecorderSnapshot),
+) {
+    // Emitted after the lock is released, so a listener may query it. The
+    // revision tells windows which snapshot is the latest if two cross.
+    let snapshot = {
+        let mut snapshot = SNAPSHOT.lock().unwrap_or_else(PoisonError::into_inner);
+        let previous = snapshot.clone();
+        update(&mut snapshot);
+        snapshot.revision = previous.revision;
+        if *snapshot == previous {
+            return;
+        }
+
+        snapshot.revision += 1;
+        snapshot.clone()
+    };
+
+    app_handle.emit(events::RECORDER_SNAPSHOT, snapshot).ok();
+}
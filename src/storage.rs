@@ -0,0 +1,376 @@
+use crate::error::Result;
+use hound::{WavSpec, WavWriter};
+use std::{
+    collections::HashMap,
+    fs::{self, create_dir_all, File, OpenOptions},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock},
+};
+
+/// Where a recording is written, such as a file or an app's own encrypted
+/// container. The WAV header is rewritten in place, so it has to seek.
+pub trait StorageSink: Read + Write + Seek + Send {
+    /// Truncates or extends the recording to `len` bytes.
+    fn set_len(&mut self, len: u64) -> io::Result<()>;
+
+    /// Commits the recording to durable storage once it has been finalized.
+    fn sync(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl StorageSink for File {
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        File::set_len(self, len)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.sync_all()
+    }
+}
+
+/// Creates the sinks recordings are written to. The path is the one
+/// reported to the frontend, and identifies the recording to the storage.
+pub trait Storage: Send + Sync + 'static {
+    /// Creates the recording at `path`, replacing any previous one.
+    fn create(&self, path: &Path) -> io::Result<Box<dyn StorageSink>>;
+
+    /// Opens a finalized recording for reading, e.g. to upload it. Reads
+    /// the file at `path` by default.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    /// Whether there is a recording at `path`, so a new one is not given
+    /// its name. Checks for the file at `path` by default.
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    /// Removes the recording at `path`, e.g. one a start that failed had
+    /// already created. Removes the file at `path` by default.
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+}
+
+/// Writes recordings to files on the local file system, which is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalStorage;
+
+impl Storage for LocalStorage {
+    fn create(&self, path: &Path) -> io::Result<Box<dyn StorageSink>> {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        Ok(Box::new(file))
+    }
+}
+
+/// Keeps recordings in memory, where the app takes them from once they
+/// have been finalized.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    recordings: Arc<Mutex<HashMap<PathBuf, MemoryRecording>>>,
+}
+
+type MemoryRecording = Arc<Mutex<Vec<u8>>>;
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets a copy of the recording at `path`.
+    pub fn get(&self, path: &Path) -> Option<Vec<u8>> {
+        let recordings = self
+            .recordings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let data = recordings.get(path)?;
+        let data = data.lock().unwrap_or_else(PoisonError::into_inner).clone();
+
+        Some(data)
+    }
+
+    /// Removes the recording at `path` and returns it.
+    pub fn take(&self, path: &Path) -> Option<Vec<u8>> {
+        let data = self
+            .recordings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(path)?;
+        let data = std::mem::take(&mut *data.lock().unwrap_or_else(PoisonError::into_inner));
+
+        Some(data)
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn create(&self, path: &Path) -> io::Result<Box<dyn StorageSink>> {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        self.recordings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(path.to_path_buf(), data.clone());
+
+        Ok(Box::new(MemorySink { data, position: 0 }))
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        let data = self
+            .get(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such recording"))?;
+
+        Ok(Box::new(io::Cursor::new(data)))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.recordings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains_key(path)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.take(path)
+            .map(|_| ())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such recording"))
+    }
+}
+
+struct MemorySink {
+    data: MemoryRecording,
+    position: u64,
+}
+
+impl MemorySink {
+    fn data(&self) -> MutexGuard<'_, Vec<u8>> {
+        self.data.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Read for MemorySink {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.data();
+        let start = (self.position as usize).min(data.len());
+        let len = buf.len().min(data.len() - start);
+        buf[..len].copy_from_slice(&data[start..start + len]);
+        drop(data);
+
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl Write for MemorySink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = self.data();
+        let start = self.position as usize;
+        if data.len() < start + buf.len() {
+            data.resize(start + buf.len(), 0);
+        }
+        data[start..start + buf.len()].copy_from_slice(buf);
+        drop(data);
+
+        self.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for MemorySink {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => (self.data().len() as u64).checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        self.position = position
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the start"))?;
+        Ok(self.position)
+    }
+}
+
+impl StorageSink for MemorySink {
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.data().resize(len as usize, 0);
+        Ok(())
+    }
+}
+
+/// The storage set up with the plugin, or files when there is none.
+static STORAGE: RwLock<Option<Arc<dyn Storage>>> = RwLock::new(None);
+
+pub(crate) fn set(storage: Option<Arc<dyn Storage>>) {
+    *STORAGE.write().unwrap_or_else(PoisonError::into_inner) = storage;
+}
+
+/// Whether recordings are written to files, rather than to a storage the
+/// app set up.
+pub(crate) fn is_local() -> bool {
+    STORAGE
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_none()
+}
+
+/// A sink shared between the WAV writer and whatever has to reach the
+/// recording after the writer is gone, such as appending metadata.
+#[derive(Clone)]
+pub(crate) struct SharedSink(Arc<Mutex<Box<dyn StorageSink>>>);
+
+impl SharedSink {
+    pub fn lock(&self) -> MutexGuard<'_, Box<dyn StorageSink>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Gets the current length of the recording.
+    pub fn len(&self) -> io::Result<u64> {
+        let mut sink = self.lock();
+        let position = sink.stream_position()?;
+        let len = sink.seek(SeekFrom::End(0))?;
+        sink.seek(SeekFrom::Start(position))?;
+
+        Ok(len)
+    }
+
+    /// Reads from `offset` without moving the position the writer is at.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let mut sink = self.lock();
+        let position = sink.stream_position()?;
+        sink.seek(SeekFrom::Start(offset))?;
+        let len = sink.read(buf);
+        sink.seek(SeekFrom::Start(position))?;
+
+        len
+    }
+}
+
+impl Read for SharedSink {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.lock().read(buf)
+    }
+}
+
+impl Write for SharedSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().flush()
+    }
+}
+
+impl Seek for SharedSink {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.lock().seek(pos)
+    }
+}
+
+/// The writer of a recording in progress.
+pub(crate) type SinkWriter = WavWriter<BufWriter<SharedSink>>;
+
+/// Creates the recording at `path` with the configured storage.
+pub(crate) fn create(path: &Path) -> Result<SharedSink> {
+    let storage = STORAGE
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    let sink = match storage {
+        Some(storage) => storage.create(path)?,
+        None => LocalStorage.create(path)?,
+    };
+
+    Ok(SharedSink(Arc::new(Mutex::new(sink))))
+}
+
+/// Opens a finalized recording at `path` with the configured storage.
+pub(crate) fn open(path: &Path) -> Result<Box<dyn Read + Send>> {
+    let storage = STORAGE
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    let reader = match storage {
+        Some(storage) => storage.open(path)?,
+        None => LocalStorage.open(path)?,
+    };
+
+    Ok(reader)
+}
+
+/// Whether the configured storage has a recording at `path`.
+pub(crate) fn exists(path: &Path) -> bool {
+    let storage = STORAGE
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+
+    match storage {
+        Some(storage) => storage.exists(path),
+        None => LocalStorage.exists(path),
+    }
+}
+
+/// Removes the recording at `path` from the configured storage.
+pub(crate) fn remove(path: &Path) -> Result<()> {
+    let storage = STORAGE
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+
+    match storage {
+        Some(storage) => storage.remove(path)?,
+        None => LocalStorage.remove(path)?,
+    }
+
+    Ok(())
+}
+
+/// Opens the file at `path` for reading as a sink, e.g. one that replaced
+/// the recording after it was finalized.
+pub(crate) fn open_file(path: &Path) -> Result<SharedSink> {
+    let sink: Box<dyn StorageSink> = Box::new(File::open(path)?);
+
+    Ok(SharedSink(Arc::new(Mutex::new(sink))))
+}
+
+/// Moves a finalized recording, copying it if `to` is on another volume,
+/// e.g. a network drive, and committing the copy before the original is
+/// removed.
+pub(crate) fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    fs::copy(from, to)?;
+    OpenOptions::new().write(true).open(to)?.sync_all()?;
+    fs::remove_file(from)?;
+
+    Ok(())
+}
+
+/// Creates a WAV writer for a new recording at `path`.
+pub(crate) fn create_writer(path: &Path, spec: WavSpec) -> Result<(SinkWriter, SharedSink)> {
+    let sink = create(path)?;
+    let writer = WavWriter::new(BufWriter::new(sink.clone()), spec)?;
+
+    Ok((writer, sink))
+}
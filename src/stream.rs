@@ -0,0 +1,458 @@
+use crate::{
+    error::{Error, Message, Result},
+    models::{DropPolicy, PacketOptions},
+};
+use std::{
+    collections::VecDeque,
+    sync::{Condvar, Mutex},
+};
+
+/// A bounded queue of audio chunks between the capture callback and a
+/// consumer that may fall behind, such as a frontend channel.
+pub(crate) struct AudioQueue {
+    inner: Mutex<Inner>,
+    ready: Condvar,
+    max_bytes: usize,
+    policy: DropPolicy,
+    /// The size of one frame of the queued audio.
+    frame_bytes: usize,
+}
+
+#[derive(Default)]
+struct Inner {
+    chunks: VecDeque<Queued>,
+    bytes: usize,
+    /// Bytes dropped after the last queued chunk, which the next one pushed
+    /// follows.
+    dropped: usize,
+    closed: bool,
+}
+
+struct Queued {
+    /// Bytes dropped between the chunk before and this one.
+    dropped_before: usize,
+    chunk: Vec<u8>,
+}
+
+/// A chunk taken off an `AudioQueue`.
+pub(crate) struct Popped {
+    /// The frames dropped between the chunk popped before and this one.
+    pub dropped_frames: u64,
+    pub chunk: Vec<u8>,
+}
+
+impl AudioQueue {
+    pub fn new(max_bytes: usize, policy: DropPolicy, frame_bytes: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+            ready: Condvar::new(),
+            max_bytes,
+            policy,
+            frame_bytes: frame_bytes.max(1),
+        }
+    }
+
+    /// Queues a chunk, applying the drop policy if it does not fit. Fails
+    /// with `Overflow` if the policy is to stop.
+    pub fn push(&self, chunk: Vec<u8>) -> Result<()> {
+        let mut inner = self.inner.lock()?;
+
+        if inner.closed {
+            return Ok(());
+        }
+
+        if inner.bytes + chunk.len() > self.max_bytes {
+            match self.policy {
+                DropPolicy::DropNewest => {
+                    inner.dropped += chunk.len();
+                    return Ok(());
+                }
+                DropPolicy::DropOldest => {
+                    while inner.bytes + chunk.len() > self.max_bytes {
+                        let Some(oldest) = inner.chunks.pop_front() else {
+                            break;
+                        };
+                        inner.bytes -= oldest.chunk.len();
+
+                        // The gap moves up to the chunk that is now oldest.
+                        let dropped = oldest.dropped_before + oldest.chunk.len();
+                        match inner.chunks.front_mut() {
+                            Some(next) => next.dropped_before += dropped,
+                            None => inner.dropped += dropped,
+                        }
+                    }
+                }
+                DropPolicy::StopWithError => {
+                    return Err(Error::Overflow(
+                        Message::new(
+                            "overflow.stream",
+                            format!(
+                                "The audio stream consumer fell more than {} bytes behind.",
+                                self.max_bytes
+                            ),
+                        )
+                        .param("maxBufferedBytes", self.max_bytes),
+                    ));
+                }
+            }
+        }
+
+        inner.bytes += chunk.len();
+        let dropped_before = std::mem::take(&mut inner.dropped);
+        inner.chunks.push_back(Queued {
+            dropped_before,
+            chunk,
+        });
+        self.ready.notify_one();
+
+        Ok(())
+    }
+
+    /// Waits for the next chunk, returning `None` once the queue has been
+    /// closed and drained.
+    pub fn pop(&self) -> Result<Option<Popped>> {
+        let mut inner = self.inner.lock()?;
+
+        loop {
+            if let Some(queued) = inner.chunks.pop_front() {
+                inner.bytes -= queued.chunk.len();
+                return Ok(Some(Popped {
+                    dropped_frames: (queued.dropped_before / self.frame_bytes) as u64,
+                    chunk: queued.chunk,
+                }));
+            }
+            if inner.closed {
+                return Ok(None);
+            }
+
+            inner = self.ready.wait(inner)?;
+        }
+    }
+
+    /// Gets the number of frames dropped after the last chunk, e.g. once
+    /// the queue has been drained, and forgets them.
+    pub fn take_dropped_frames(&self) -> Result<u64> {
+        let mut inner = self.inner.lock()?;
+
+        Ok((std::mem::take(&mut inner.dropped) / self.frame_bytes) as u64)
+    }
+
+    /// Stops accepting chunks, letting the consumer drain what is queued.
+    pub fn close(&self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.closed = true;
+            self.ready.notify_all();
+        }
+    }
+}
+
+/// The version of the packet header, which is its first byte.
+const PACKET_VERSION: u8 = 1;
+
+/// A packet of audio.
+const PACKET_AUDIO: u8 = 0;
+
+/// A packet of parity over the audio packets of a group.
+const PACKET_PARITY: u8 = 1;
+
+/// Frames streamed audio as packets with a sequence number and a timestamp,
+/// so a receiver can tell where audio is missing and conceal it. After every
+/// group of packets, a parity packet follows from which any single packet
+/// of the group that was lost can be rebuilt.
+pub(crate) struct Packetizer {
+    frame_bytes: usize,
+    /// The number of audio packets a parity packet covers, or 0 for none.
+    group_len: u16,
+    sequence: u32,
+    /// The position of the next packet in frames since the stream began.
+    timestamp: u64,
+    /// The first sequence number and timestamp of the current group.
+    group_start: (u32, u64),
+    grouped: u16,
+    /// The XOR of the lengths and of the payloads of the current group.
+    parity: Vec<u8>,
+}
+
+impl Packetizer {
+    pub fn new(options: &PacketOptions, frame_bytes: usize) -> Result<Self> {
+        if options.loss_percent > MAX_LOSS_PERCENT {
+            return Err(Error::InvalidOptions(
+                Message::new(
+                    "invalidOptions.packetLoss",
+                    format!("The expected packet loss must be at most {MAX_LOSS_PERCENT}%."),
+                )
+                .param("maxPercent", MAX_LOSS_PERCENT),
+            ));
+        }
+
+        let group_len = match options.loss_percent {
+            0 => 0,
+            percent => (100 / percent as u16).clamp(2, 100),
+        };
+
+        Ok(Self {
+            frame_bytes: frame_bytes.max(1),
+            group_len,
+            sequence: 0,
+            timestamp: 0,
+            group_start: (0, 0),
+            grouped: 0,
+            parity: Vec::new(),
+        })
+    }
+
+    /// Leaves a gap of `frames` in the timestamps, for audio that was
+    /// dropped before it could be sent.
+    pub fn skip(&mut self, frames: u64) {
+        self.timestamp += frames;
+    }
+
+    /// Frames a chunk as an audio packet, followed by a parity packet if it
+    /// completes a group.
+    pub fn packetize(&mut self, chunk: &[u8]) -> Vec<Vec<u8>> {
+        let mut packet = header(PACKET_AUDIO, 0, self.sequence, self.timestamp);
+        packet.extend_from_slice(chunk);
+        let mut packets = vec![packet];
+
+        if self.group_len > 0 {
+            if self.grouped == 0 {
+                self.group_start = (self.sequence, self.timestamp);
+            }
+
+            if self.parity.len() < 4 + chunk.len() {
+                self.parity.resize(4 + chunk.len(), 0);
+            }
+            for (x, y) in self
+                .parity
+                .iter_mut()
+                .zip((chunk.len() as u32).to_le_bytes())
+            {
+                *x ^= y;
+            }
+            for (x, y) in self.parity[4..].iter_mut().zip(chunk) {
+                *x ^= y;
+            }
+            self.grouped += 1;
+
+            if self.grouped == self.group_len {
+                packets.extend(self.finish());
+            }
+        }
+
+        self.sequence = self.sequence.wrapping_add(1);
+        self.timestamp += (chunk.len() / self.frame_bytes) as u64;
+
+        packets
+    }
+
+    /// Takes the parity packet of the current group, e.g. once the stream
+    /// ends before the group is complete.
+    pub fn finish(&mut self) -> Option<Vec<u8>> {
+        if self.grouped == 0 {
+            return None;
+        }
+
+        let (sequence, timestamp) = self.group_start;
+        let mut packet = header(PACKET_PARITY, self.grouped, sequence, timestamp);
+        packet.append(&mut self.parity);
+        self.grouped = 0;
+
+        Some(packet)
+    }
+}
+
+/// The expected packet loss from which parity is sent after every other
+/// packet, doubling the bandwidth.
+const MAX_LOSS_PERCENT: u8 = 50;
+
+/// Writes the 16-byte header of a packet: the version, the kind, the number
+/// of packets a parity packet covers, the sequence number and the timestamp,
+/// all little-endian.
+fn header(kind: u8, count: u16, sequence: u32, timestamp: u64) -> Vec<u8> {
+    let mut header = Vec::with_capacity(16);
+    header.extend_from_slice(&[PACKET_VERSION, kind]);
+    header.extend_from_slice(&count.to_le_bytes());
+    header.extend_from_slice(&sequence.to_le_bytes());
+    header.extend_from_slice(&timestamp.to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Interleaved stereo `f32` samples.
+    const FRAME_BYTES: usize = 8;
+
+    fn packetizer(loss_percent: u8) -> Packetizer {
+        Packetizer::new(&PacketOptions { loss_percent }, FRAME_BYTES).unwrap()
+    }
+
+    fn chunk(frames: usize, fill: u8) -> Vec<u8> {
+        (0..frames * FRAME_BYTES)
+            .map(|x| fill.wrapping_add(x as u8))
+            .collect()
+    }
+
+    fn kind(packet: &[u8]) -> u8 {
+        packet[1]
+    }
+
+    fn count(packet: &[u8]) -> u16 {
+        u16::from_le_bytes([packet[2], packet[3]])
+    }
+
+    fn sequence(packet: &[u8]) -> u32 {
+        u32::from_le_bytes(packet[4..8].try_into().unwrap())
+    }
+
+    fn timestamp(packet: &[u8]) -> u64 {
+        u64::from_le_bytes(packet[8..16].try_into().unwrap())
+    }
+
+    /// Sends chunks until the first parity packet, returning how many audio
+    /// packets it covers.
+    fn group_len(loss_percent: u8) -> Option<usize> {
+        let mut packetizer = packetizer(loss_percent);
+
+        (1..=200).find(|_| packetizer.packetize(&chunk(4, 0)).len() == 2)
+    }
+
+    /// Pops what is queued, as the frames dropped before each chunk and
+    /// the first byte of the chunk.
+    fn drain(queue: &AudioQueue) -> Vec<(u64, u8)> {
+        queue.close();
+
+        std::iter::from_fn(|| queue.pop().unwrap())
+            .map(|x| (x.dropped_frames, x.chunk[0]))
+            .collect()
+    }
+
+    #[test]
+    fn drop_oldest_reports_the_gap_before_the_oldest_chunk_kept() {
+        let queue = AudioQueue::new(3 * 4 * FRAME_BYTES, DropPolicy::DropOldest, FRAME_BYTES);
+        for fill in 0..5 {
+            queue.push(chunk(4, fill * 10)).unwrap();
+        }
+
+        assert_eq!(drain(&queue), [(8, 20), (0, 30), (0, 40)]);
+        assert_eq!(queue.take_dropped_frames().unwrap(), 0);
+    }
+
+    #[test]
+    fn drop_newest_reports_the_gap_after_the_last_chunk_kept() {
+        let queue = AudioQueue::new(2 * 4 * FRAME_BYTES, DropPolicy::DropNewest, FRAME_BYTES);
+        for fill in 0..4 {
+            queue.push(chunk(4, fill * 10)).unwrap();
+        }
+        assert_eq!(queue.pop().unwrap().unwrap().chunk[0], 0);
+        queue.push(chunk(4, 40)).unwrap();
+        queue.push(chunk(4, 50)).unwrap();
+
+        assert_eq!(drain(&queue), [(0, 10), (8, 40)]);
+        assert_eq!(queue.take_dropped_frames().unwrap(), 4);
+    }
+
+    /// Packetizes what is queued the way the stream is forwarded, returning
+    /// the timestamps of the audio packets.
+    fn timestamps(queue: &AudioQueue, packetizer: &mut Packetizer) -> Vec<u64> {
+        queue.close();
+
+        std::iter::from_fn(|| queue.pop().unwrap())
+            .map(|popped| {
+                packetizer.skip(popped.dropped_frames);
+                timestamp(&packetizer.packetize(&popped.chunk)[0])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn timestamps_leave_gaps_where_chunks_were_dropped() {
+        let queue = AudioQueue::new(3 * 4 * FRAME_BYTES, DropPolicy::DropOldest, FRAME_BYTES);
+        for fill in 0..5 {
+            queue.push(chunk(4, fill)).unwrap();
+        }
+        assert_eq!(timestamps(&queue, &mut packetizer(0)), [8, 12, 16]);
+
+        let queue = AudioQueue::new(2 * 4 * FRAME_BYTES, DropPolicy::DropNewest, FRAME_BYTES);
+        let mut packetizer = packetizer(0);
+        for fill in 0..4 {
+            queue.push(chunk(4, fill)).unwrap();
+        }
+        let first = queue.pop().unwrap().unwrap();
+        assert_eq!(timestamp(&packetizer.packetize(&first.chunk)[0]), 0);
+        // The third and fourth chunk were dropped, after the second.
+        queue.push(chunk(2, 4)).unwrap();
+        assert_eq!(timestamps(&queue, &mut packetizer), [4, 16]);
+    }
+
+    #[test]
+    fn rebuilds_a_lost_packet_from_the_parity() {
+        let mut packetizer = packetizer(25);
+        let chunks = [chunk(4, 1), chunk(2, 7), chunk(5, 13), chunk(3, 29)];
+
+        let mut packets = Vec::new();
+        for chunk in &chunks {
+            packets.extend(packetizer.packetize(chunk));
+        }
+        assert_eq!(packets.len(), 5);
+
+        let parity = packets.pop().unwrap();
+        assert_eq!(kind(&parity), PACKET_PARITY);
+        assert_eq!(count(&parity), 4);
+        assert_eq!(sequence(&parity), 0);
+        assert_eq!(timestamp(&parity), 0);
+
+        // XOR-ing the parity with everything but the lost packet leaves it.
+        let lost = 2;
+        let mut rebuilt = parity[16..].to_vec();
+        for (index, packet) in packets.iter().enumerate() {
+            assert_eq!(kind(packet), PACKET_AUDIO);
+            if index == lost {
+                continue;
+            }
+
+            let payload = &packet[16..];
+            for (x, y) in rebuilt.iter_mut().zip((payload.len() as u32).to_le_bytes()) {
+                *x ^= y;
+            }
+            for (x, y) in rebuilt[4..].iter_mut().zip(payload) {
+                *x ^= y;
+            }
+        }
+
+        let len = u32::from_le_bytes(rebuilt[..4].try_into().unwrap()) as usize;
+        assert_eq!(&rebuilt[4..4 + len], chunks[lost].as_slice());
+        assert_eq!(timestamp(&packets[lost]), 6);
+    }
+
+    #[test]
+    fn finish_emits_a_partial_group() {
+        let mut packetizer = packetizer(25);
+        assert_eq!(packetizer.packetize(&chunk(4, 0)).len(), 1);
+        assert_eq!(packetizer.packetize(&chunk(4, 0)).len(), 1);
+
+        let parity = packetizer.finish().unwrap();
+        assert_eq!(kind(&parity), PACKET_PARITY);
+        assert_eq!(count(&parity), 2);
+        assert_eq!(sequence(&parity), 0);
+        assert!(packetizer.finish().is_none());
+
+        // The next group starts after the partial one.
+        packetizer.packetize(&chunk(4, 0));
+        let parity = packetizer.finish().unwrap();
+        assert_eq!(count(&parity), 1);
+        assert_eq!(sequence(&parity), 2);
+        assert_eq!(timestamp(&parity), 8);
+    }
+
+    #[test]
+    fn group_length_follows_the_expected_loss() {
+        assert_eq!(group_len(0), None);
+        assert_eq!(group_len(1), Some(100));
+        assert_eq!(group_len(10), Some(10));
+        assert_eq!(group_len(30), Some(3));
+        assert_eq!(group_len(50), Some(2));
+        assert!(Packetizer::new(&PacketOptions { loss_percent: 51 }, FRAME_BYTES).is_err());
+    }
+}
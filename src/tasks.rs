@@ -0,0 +1,78 @@
+use std::{
+    sync::{Condvar, Mutex, PoisonError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Set by `shutdown` and cleared when the plugin is set up again. Waking
+/// the condition ends the sleep of every background thread at once.
+static CANCELLED: Mutex<bool> = Mutex::new(false);
+static CANCELLED_CHANGED: Condvar = Condvar::new();
+
+/// The background threads `shutdown` waits for.
+static TASKS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+/// How often `join` checks whether the threads have finished.
+const JOIN_INTERVAL: Duration = Duration::from_millis(10);
+
+pub(crate) fn is_cancelled() -> bool {
+    *CANCELLED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Asks every background thread to end and makes new recordings fail.
+pub(crate) fn cancel() {
+    *CANCELLED.lock().unwrap_or_else(PoisonError::into_inner) = true;
+    CANCELLED_CHANGED.notify_all();
+}
+
+/// Lets the plugin run again after `cancel`, e.g. when a test sets it up
+/// once more.
+pub(crate) fn reset() {
+    *CANCELLED.lock().unwrap_or_else(PoisonError::into_inner) = false;
+}
+
+/// Sleeps for `duration`, returning `false` right away once cancelled.
+pub(crate) fn sleep(duration: Duration) -> bool {
+    let cancelled = CANCELLED.lock().unwrap_or_else(PoisonError::into_inner);
+    let (cancelled, _) = CANCELLED_CHANGED
+        .wait_timeout_while(cancelled, duration, |cancelled| !*cancelled)
+        .unwrap_or_else(PoisonError::into_inner);
+
+    !*cancelled
+}
+
+/// Spawns a background thread that `shutdown` waits for. It has to end by
+/// itself once cancelled, e.g. by sleeping with `sleep`.
+pub(crate) fn spawn<F>(f: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    let mut tasks = TASKS.lock().unwrap_or_else(PoisonError::into_inner);
+    tasks.retain(|task| !task.is_finished());
+    tasks.push(thread::spawn(f));
+}
+
+/// Waits for the background threads until `deadline`, returning how many
+/// are still running. Threads spawned while waiting are waited for too.
+pub(crate) fn join(deadline: Instant) -> usize {
+    loop {
+        let pending = {
+            let mut tasks = TASKS.lock().unwrap_or_else(PoisonError::into_inner);
+            for task in std::mem::take(&mut *tasks) {
+                if task.is_finished() {
+                    task.join().ok();
+                } else {
+                    tasks.push(task);
+                }
+            }
+
+            tasks.len()
+        };
+
+        if pending == 0 || Instant::now() >= deadline {
+            return pending;
+        }
+
+        thread::sleep(JOIN_INTERVAL);
+    }
+}
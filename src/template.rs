@@ -0,0 +1,60 @@
+use crate::{
+    error::{Error, Message, Result},
+    models::RecordingOptions,
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// The version of the template format written by `export`.
+const VERSION: u32 = 1;
+
+/// A named recording setup, e.g. a team's standard processing chain and
+/// destination, shared between machines as a JSON file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingTemplate {
+    /// The version of the format, filled in on export.
+    #[serde(default)]
+    pub version: u32,
+    pub name: String,
+    pub description: Option<String>,
+    /// The options to pass to `start_recording`.
+    pub options: RecordingOptions,
+}
+
+pub(crate) fn export(path: &Path, template: &RecordingTemplate) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let template = RecordingTemplate {
+        version: VERSION,
+        ..template.clone()
+    };
+    fs::write(path, serde_json::to_vec_pretty(&template)?)?;
+
+    Ok(())
+}
+
+/// Reads a template, which may be from an older version of the plugin.
+/// Options it does not know are ignored, and those it lacks take their
+/// defaults.
+pub(crate) fn import(path: &Path) -> Result<RecordingTemplate> {
+    let template: RecordingTemplate = serde_json::from_slice(&fs::read(path)?)?;
+
+    if template.version > VERSION {
+        return Err(Error::InvalidOptions(
+            Message::new(
+                "invalidOptions.templateVersion",
+                format!(
+                    "The template is of version {}, but only up to {} is supported.",
+                    template.version, VERSION
+                ),
+            )
+            .param("version", template.version)
+            .param("maxVersion", VERSION),
+        ));
+    }
+
+    Ok(template)
+}
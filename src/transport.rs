@@ -0,0 +1,329 @@
+/// How an input device is connected to the computer or phone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Transport {
+    /// A Bluetooth headset recording over the hands-free profile (HFP).
+    HandsFree,
+    /// A USB audio class device, such as an audio interface or a USB mic.
+    Usb,
+    Other,
+}
+
+/// Whether recording from the input device named `name` goes through the
+/// Bluetooth hands-free profile (HFP), which limits it to telephone quality
+/// at 8 or 16 kHz and on most headsets drops playback to the same quality
+/// while the mic is open. `None` if the platform does not let the plugin
+/// find out.
+pub(crate) fn is_hands_free(name: &str) -> Option<bool> {
+    platform::transport(name).map(|x| x == Transport::HandsFree)
+}
+
+/// Whether the input device named `name` is connected over USB. `None` if
+/// the platform does not let the plugin find out.
+pub(crate) fn is_usb(name: &str) -> Option<bool> {
+    platform::transport(name).map(|x| x == Transport::Usb)
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::Transport;
+    use windows::Win32::{
+        Devices::FunctionDiscovery::{PKEY_Device_EnumeratorName, PKEY_Device_FriendlyName},
+        Media::Audio::{eCapture, IMMDeviceEnumerator, MMDeviceEnumerator, DEVICE_STATE_ACTIVE},
+        System::Com::{
+            CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_MULTITHREADED, STGM_READ,
+        },
+    };
+
+    /// The enumerator of the capture endpoints Windows creates for the
+    /// hands-free profile of Bluetooth headsets.
+    const HANDS_FREE_ENUMERATOR: &str = "BTHHFENUM";
+
+    /// The enumerator of USB audio class devices.
+    const USB_ENUMERATOR: &str = "USB";
+
+    /// Looks up the capture endpoint by its friendly name, which is the name
+    /// cpal reports, and checks which bus enumerated it.
+    pub fn transport(name: &str) -> Option<Transport> {
+        unsafe {
+            // Fails if the thread already joined another apartment, which
+            // works just as well.
+            let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+
+            let enumerator: IMMDeviceEnumerator =
+                CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL).ok()?;
+            let devices = enumerator
+                .EnumAudioEndpoints(eCapture, DEVICE_STATE_ACTIVE)
+                .ok()?;
+
+            for index in 0..devices.GetCount().ok()? {
+                let Ok(properties) = devices
+                    .Item(index)
+                    .and_then(|x| x.OpenPropertyStore(STGM_READ))
+                else {
+                    continue;
+                };
+                let Ok(friendly_name) = properties.GetValue(&PKEY_Device_FriendlyName) else {
+                    continue;
+                };
+                if friendly_name.to_string() != name {
+                    continue;
+                }
+
+                let enumerator = properties.GetValue(&PKEY_Device_EnumeratorName).ok()?;
+                return Some(match enumerator.to_string().as_str() {
+                    HANDS_FREE_ENUMERATOR => Transport::HandsFree,
+                    USB_ENUMERATOR => Transport::Usb,
+                    _ => Transport::Other,
+                });
+            }
+
+            None
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::Transport;
+    use std::ffi::{c_char, c_void, CStr};
+
+    #[repr(C)]
+    struct AudioObjectPropertyAddress {
+        selector: u32,
+        scope: u32,
+        element: u32,
+    }
+
+    #[link(name = "CoreAudio", kind = "framework")]
+    extern "C" {
+        fn AudioObjectGetPropertyDataSize(
+            object: u32,
+            address: *const AudioObjectPropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            size: *mut u32,
+        ) -> i32;
+        fn AudioObjectGetPropertyData(
+            object: u32,
+            address: *const AudioObjectPropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            size: *mut u32,
+            data: *mut c_void,
+        ) -> i32;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFStringGetCString(
+            string: *const c_void,
+            buffer: *mut c_char,
+            size: isize,
+            encoding: u32,
+        ) -> u8;
+        fn CFRelease(object: *const c_void);
+    }
+
+    const SYSTEM_OBJECT: u32 = 1;
+    const UTF8: u32 = 0x0800_0100;
+
+    const fn code(id: &[u8; 4]) -> u32 {
+        u32::from_be_bytes(*id)
+    }
+
+    fn address(selector: &[u8; 4]) -> AudioObjectPropertyAddress {
+        AudioObjectPropertyAddress {
+            selector: code(selector),
+            scope: code(b"glob"),
+            element: 0,
+        }
+    }
+
+    unsafe fn get<T: Default>(object: u32, selector: &[u8; 4]) -> Option<T> {
+        let mut value = T::default();
+        let mut size = size_of::<T>() as u32;
+        let status = AudioObjectGetPropertyData(
+            object,
+            &address(selector),
+            0,
+            std::ptr::null(),
+            &mut size,
+            (&mut value as *mut T).cast(),
+        );
+
+        (status == 0).then_some(value)
+    }
+
+    unsafe fn name(device: u32) -> Option<String> {
+        let string = get::<usize>(device, b"lnam")? as *const c_void;
+        if string.is_null() {
+            return None;
+        }
+
+        let mut buffer = [0 as c_char; 256];
+        let copied = CFStringGetCString(string, buffer.as_mut_ptr(), buffer.len() as isize, UTF8);
+        CFRelease(string);
+
+        (copied != 0).then(|| {
+            CStr::from_ptr(buffer.as_ptr())
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+
+    /// Finds the device by the name cpal reports and checks its transport.
+    /// macOS records from Bluetooth headsets over the hands-free profile
+    /// only, so any Bluetooth input is one.
+    pub fn transport(name: &str) -> Option<Transport> {
+        unsafe {
+            let devices = address(b"dev#");
+            let mut size = 0;
+            let status = AudioObjectGetPropertyDataSize(
+                SYSTEM_OBJECT,
+                &devices,
+                0,
+                std::ptr::null(),
+                &mut size,
+            );
+            if status != 0 {
+                return None;
+            }
+
+            let mut ids = vec![0_u32; size as usize / size_of::<u32>()];
+            let status = AudioObjectGetPropertyData(
+                SYSTEM_OBJECT,
+                &devices,
+                0,
+                std::ptr::null(),
+                &mut size,
+                ids.as_mut_ptr().cast(),
+            );
+            if status != 0 {
+                return None;
+            }
+
+            let device = ids
+                .into_iter()
+                .find(|&id| self::name(id).is_some_and(|x| x == name))?;
+            let transport = get::<u32>(device, b"tran")?;
+
+            Some(match transport {
+                x if x == code(b"blue") || x == code(b"blea") => Transport::HandsFree,
+                x if x == code(b"usb ") => Transport::Usb,
+                _ => Transport::Other,
+            })
+        }
+    }
+}
+
+#[cfg(target_os = "android")]
+mod platform {
+    use super::Transport;
+    use crate::android;
+    use jni::objects::{JObjectArray, JValue};
+
+    /// `AudioManager.GET_DEVICES_INPUTS`.
+    const GET_DEVICES_INPUTS: i32 = 1;
+
+    /// The `AudioDeviceInfo` types of hands-free headsets and of USB devices.
+    const TYPE_BLUETOOTH_SCO: i32 = 7;
+    const TYPE_USB_DEVICE: i32 = 11;
+    const TYPE_USB_ACCESSORY: i32 = 12;
+    const TYPE_USB_HEADSET: i32 = 22;
+
+    /// Looks up the input by its product name, which is the name cpal
+    /// reports, in the inputs the `AudioManager` lists.
+    pub fn transport(name: &str) -> Option<Transport> {
+        android::with_context(|env, context| {
+            let manager = android::system_service(env, context, "audio")?;
+            let devices: JObjectArray = env
+                .call_method(
+                    &manager,
+                    "getDevices",
+                    "(I)[Landroid/media/AudioDeviceInfo;",
+                    &[JValue::Int(GET_DEVICES_INPUTS)],
+                )?
+                .l()?
+                .into();
+
+            for index in 0..env.get_array_length(&devices)? {
+                let device = env.get_object_array_element(&devices, index)?;
+                let product = env
+                    .call_method(&device, "getProductName", "()Ljava/lang/CharSequence;", &[])?
+                    .l()?;
+                if android::to_string(env, &product)?.as_deref() != Some(name) {
+                    continue;
+                }
+
+                return Ok(Some(
+                    match env.call_method(&device, "getType", "()I", &[])?.i()? {
+                        TYPE_BLUETOOTH_SCO => Transport::HandsFree,
+                        TYPE_USB_DEVICE | TYPE_USB_ACCESSORY | TYPE_USB_HEADSET => Transport::Usb,
+                        _ => Transport::Other,
+                    },
+                ));
+            }
+
+            Ok(None)
+        })
+        .flatten()
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "android")))]
+mod platform {
+    use super::Transport;
+    use std::path::Path;
+
+    /// Goes by the name, as PulseAudio and PipeWire name the hands-free
+    /// sources of Bluetooth headsets after the profile and USB sources after
+    /// the bus. ALSA names the card, which on Linux is a USB one if it has a
+    /// USB id.
+    pub fn transport(name: &str) -> Option<Transport> {
+        let lowercase = name.to_lowercase();
+        if lowercase.contains("bluez")
+            || lowercase.contains("hands-free")
+            || lowercase.contains("handsfree")
+        {
+            return Some(Transport::HandsFree);
+        }
+        if lowercase.contains("usb") {
+            return Some(Transport::Usb);
+        }
+
+        let card = name.split("CARD=").nth(1)?.split(',').next()?;
+        if cfg!(target_os = "linux") {
+            let usb = Path::new("/proc/asound").join(card).join("usbid").exists();
+            return Some(if usb {
+                Transport::Usb
+            } else {
+                Transport::Other
+            });
+        }
+
+        None
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_inputs_by_name() {
+        assert_eq!(
+            platform::transport("bluez_input.00_1B_66_AA_BB_CC.0"),
+            Some(Transport::HandsFree)
+        );
+        assert_eq!(
+            platform::transport("alsa_input.usb-Focusrite_Scarlett_2i2_USB-00.analog-stereo"),
+            Some(Transport::Usb)
+        );
+        assert_eq!(
+            platform::transport("front:CARD=NoSuchCard,DEV=0"),
+            Some(Transport::Other)
+        );
+        assert_eq!(platform::transport("pipewire"), None);
+        assert_eq!(is_usb("pipewire"), None);
+    }
+}
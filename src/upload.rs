@@ -0,0 +1,402 @@
+use crate::{
+    chunks::RecordingChunk,
+    error::{Error, Message, Result},
+    library::RecordingInfo,
+    network, tasks,
+};
+use chrono::{Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError, RwLock},
+    time::{Duration, Instant},
+};
+
+/// Serializes updates of the schedule file.
+static SCHEDULE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Held while recordings are uploaded, so no recording is uploaded twice.
+pub(crate) static UPLOAD_LOCK: Mutex<()> = Mutex::new(());
+
+/// How often an upload in progress checks whether it may go on.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Sends finished recordings to the app's backend. The plugin decides when
+/// to upload, how fast and how often to retry, and records the outcome in
+/// the index.
+pub trait Uploader: Send + Sync + 'static {
+    /// Uploads `recording`, reading its content from `body`, and returns
+    /// where it was uploaded to, e.g. a URL or an object key.
+    ///
+    /// Reading from `body` fails once the schedule no longer allows the
+    /// upload, in which case the recording is uploaded again from the start
+    /// later and the failure does not count as a retry.
+    fn upload(&self, recording: &RecordingInfo, body: &mut dyn Read) -> io::Result<Option<String>>;
+
+    /// Whether the uploader takes recordings in chunks while they are
+    /// captured, with `upload_chunk` and `complete_chunks`.
+    fn supports_chunks(&self) -> bool {
+        false
+    }
+
+    /// Uploads one chunk of the recording at `path`, during capture or once
+    /// it has been finalized. A chunk whose content changed at finalization,
+    /// such as the first one with the rewritten header, is uploaded again
+    /// with the same index.
+    fn upload_chunk(
+        &self,
+        _path: &Path,
+        _chunk: &RecordingChunk,
+        _body: &mut dyn Read,
+    ) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Puts the recording together once all its `chunks` have been
+    /// uploaded, and returns where it was uploaded to.
+    fn complete_chunks(
+        &self,
+        _recording: &RecordingInfo,
+        _chunks: &[RecordingChunk],
+    ) -> io::Result<Option<String>> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+/// The uploader set up with the plugin; without one nothing is uploaded.
+static UPLOADER: RwLock<Option<Arc<dyn Uploader>>> = RwLock::new(None);
+
+pub(crate) fn set_uploader(uploader: Arc<dyn Uploader>) {
+    *UPLOADER.write().unwrap_or_else(PoisonError::into_inner) = Some(uploader);
+}
+
+pub(crate) fn uploader() -> Option<Arc<dyn Uploader>> {
+    UPLOADER
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// A time of day during which uploads may run, in local time.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadWindow {
+    /// As `HH:MM`, e.g. `01:00`.
+    pub start: String,
+    /// As `HH:MM`. An end before the start crosses midnight, e.g. `22:00`
+    /// to `06:00`.
+    pub end: String,
+}
+
+impl UploadWindow {
+    fn parse(&self) -> Result<(NaiveTime, NaiveTime)> {
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| {
+                Error::InvalidOptions(
+                    Message::new(
+                        "invalidOptions.uploadWindow",
+                        format!("{time} is not a time of day as HH:MM."),
+                    )
+                    .param("time", time),
+                )
+            })
+        };
+
+        Ok((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    fn contains(&self, time: NaiveTime) -> bool {
+        match self.parse() {
+            Ok((start, end)) if start <= end => start <= time && time < end,
+            Ok((start, end)) => start <= time || time < end,
+            Err(_) => false,
+        }
+    }
+}
+
+/// When and how fast recordings are uploaded, so large masters go out
+/// overnight instead of taking up the user's connection during the day.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UploadSchedule {
+    /// Limits the upload rate. Unlimited by default.
+    pub max_bytes_per_second: Option<u64>,
+    /// Only uploads over connections that are not metered, such as Wi-Fi or
+    /// Ethernet rather than cellular. Where the plugin cannot tell, uploads
+    /// wait on Android and iOS and go ahead on desktops.
+    pub wifi_only: bool,
+    /// The times of day uploads may run. Always when empty.
+    pub windows: Vec<UploadWindow>,
+    /// How often a failed upload is retried before it is left to the app.
+    /// Defaults to `3`.
+    pub max_retries: u32,
+}
+
+impl Default for UploadSchedule {
+    fn default() -> Self {
+        Self {
+            max_bytes_per_second: None,
+            wifi_only: false,
+            windows: Vec::new(),
+            max_retries: 3,
+        }
+    }
+}
+
+impl UploadSchedule {
+    /// Checks that every window is made of valid times of day.
+    pub fn validate(&self) -> Result<()> {
+        for window in &self.windows {
+            window.parse()?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether uploads may run right now.
+    pub(crate) fn allows_now(&self) -> bool {
+        if self.wifi_only {
+            let mobile = cfg!(any(target_os = "android", target_os = "ios"));
+            if network::is_metered().unwrap_or(mobile) {
+                return false;
+            }
+        }
+
+        let now = Local::now().time();
+        self.windows.is_empty() || self.windows.iter().any(|x| x.contains(now))
+    }
+}
+
+pub(crate) fn get(schedule_path: &Path) -> Result<UploadSchedule> {
+    let _guard = SCHEDULE_LOCK.lock()?;
+
+    if !schedule_path.exists() {
+        return Ok(UploadSchedule::default());
+    }
+
+    Ok(serde_json::from_reader(File::open(schedule_path)?)?)
+}
+
+pub(crate) fn set(schedule_path: &Path, schedule: &UploadSchedule) -> Result<()> {
+    let _guard = SCHEDULE_LOCK.lock()?;
+
+    // Replaced in one step so a crash never leaves a truncated file behind.
+    let temp_path = schedule_path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(schedule)?)?;
+    fs::rename(&temp_path, schedule_path)?;
+
+    Ok(())
+}
+
+/// The body of an upload, read no faster than the schedule's rate limit and
+/// failing once the schedule no longer allows the upload or the plugin is
+/// shut down. The schedule is read again every `CHECK_INTERVAL`, so changes
+/// apply to the upload in progress.
+pub(crate) struct Throttled<'a, R> {
+    inner: R,
+    schedule: UploadSchedule,
+    schedule_path: &'a Path,
+    started: Instant,
+    checked: Instant,
+    sent: u64,
+    paused: bool,
+}
+
+impl<'a, R: Read> Throttled<'a, R> {
+    pub fn new(inner: R, schedule: &UploadSchedule, schedule_path: &'a Path) -> Self {
+        let now = Instant::now();
+
+        Self {
+            inner,
+            schedule: schedule.clone(),
+            schedule_path,
+            started: now,
+            checked: now,
+            sent: 0,
+            paused: false,
+        }
+    }
+
+    /// Whether reading stopped because the upload has to wait.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn pause(&mut self) -> io::Error {
+        self.paused = true;
+        io::Error::other("the upload has to wait for its schedule")
+    }
+}
+
+impl<R: Read> Read for Throttled<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.paused {
+            return Err(self.pause());
+        }
+        if self.checked.elapsed() >= CHECK_INTERVAL {
+            // Keeps the schedule it has if the file cannot be read.
+            if let Ok(schedule) = get(self.schedule_path) {
+                if schedule.max_bytes_per_second != self.schedule.max_bytes_per_second {
+                    // A new rate is kept from now on, not averaged over what
+                    // was sent before.
+                    self.started = Instant::now();
+                    self.sent = 0;
+                }
+                self.schedule = schedule;
+            }
+            if tasks::is_cancelled() || !self.schedule.allows_now() {
+                return Err(self.pause());
+            }
+            self.checked = Instant::now();
+        }
+
+        let Some(rate) = self.schedule.max_bytes_per_second.filter(|x| *x > 0) else {
+            let len = self.inner.read(buf)?;
+            self.sent += len as u64;
+            return Ok(len);
+        };
+
+        // Waits until the bytes sent so far are within the rate, reading a
+        // tenth of a second's worth at a time so the rate stays even.
+        let due = Duration::from_secs_f64(self.sent as f64 / rate as f64);
+        if let Some(wait) = due.checked_sub(self.started.elapsed()) {
+            if !tasks::sleep(wait) {
+                return Err(self.pause());
+            }
+        }
+
+        let chunk = buf.len().min((rate / 10).max(1) as usize);
+        let len = self.inner.read(&mut buf[..chunk])?;
+        self.sent += len as u64;
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use std::path::PathBuf;
+
+    fn window(start: &str, end: &str) -> UploadWindow {
+        UploadWindow {
+            start: start.to_string(),
+            end: end.to_string(),
+        }
+    }
+
+    fn time(value: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(value, "%H:%M").unwrap()
+    }
+
+    /// A schedule file in the temporary directory, removed again on drop.
+    struct TempSchedule(PathBuf);
+
+    impl TempSchedule {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "mic-recorder-{}-{}.json",
+                name,
+                std::process::id()
+            ));
+            Self(path)
+        }
+    }
+
+    impl Drop for TempSchedule {
+        fn drop(&mut self) {
+            fs::remove_file(&self.0).ok();
+        }
+    }
+
+    #[test]
+    fn matches_a_window_within_a_day() {
+        let window = window("01:00", "05:30");
+
+        assert!(window.contains(time("01:00")));
+        assert!(window.contains(time("05:29")));
+        assert!(!window.contains(time("05:30")));
+        assert!(!window.contains(time("00:59")));
+        assert!(!window.contains(time("12:00")));
+    }
+
+    #[test]
+    fn matches_a_window_across_midnight() {
+        let window = window("22:00", "06:00");
+
+        assert!(window.contains(time("22:00")));
+        assert!(window.contains(time("23:59")));
+        assert!(window.contains(time("00:00")));
+        assert!(window.contains(time("05:59")));
+        assert!(!window.contains(time("06:00")));
+        assert!(!window.contains(time("21:59")));
+    }
+
+    #[test]
+    fn rejects_a_window_that_is_not_a_time_of_day() {
+        for (start, end) in [("1am", "05:00"), ("01:00", "24:00"), ("01:00", "")] {
+            let schedule = UploadSchedule {
+                windows: vec![window(start, end)],
+                ..Default::default()
+            };
+
+            assert!(schedule.validate().is_err());
+            assert!(!schedule.windows[0].contains(time("03:00")));
+        }
+    }
+
+    #[test]
+    fn keeps_to_the_rate_limit() {
+        let schedule = UploadSchedule {
+            max_bytes_per_second: Some(1000),
+            ..Default::default()
+        };
+        let path = TempSchedule::new("upload-rate");
+        let data: Vec<u8> = (0..250).map(|x| x as u8).collect();
+
+        let started = Instant::now();
+        let mut body = Vec::new();
+        Throttled::new(data.as_slice(), &schedule, &path.0)
+            .read_to_end(&mut body)
+            .unwrap();
+
+        assert_eq!(body, data);
+        // The last 50 bytes may only go once 200 have been sent at 1000/s.
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn pauses_once_the_schedule_no_longer_allows_the_upload() {
+        let path = TempSchedule::new("upload-pause");
+        let data = vec![0; 64];
+        let mut body = Throttled::new(data.as_slice(), &UploadSchedule::default(), &path.0);
+
+        let mut buf = [0; 16];
+        assert_eq!(body.read(&mut buf).unwrap(), 16);
+
+        // A window that starts in six hours.
+        let now = Local::now().time();
+        let format = |time: NaiveTime| time.format("%H:%M").to_string();
+        let schedule = UploadSchedule {
+            windows: vec![window(
+                &format(now + TimeDelta::hours(6)),
+                &format(now + TimeDelta::hours(7)),
+            )],
+            ..Default::default()
+        };
+        set(&path.0, &schedule).unwrap();
+
+        // Read before the schedule is checked again.
+        assert_eq!(body.read(&mut buf).unwrap(), 16);
+        body.checked -= CHECK_INTERVAL;
+        assert!(body.read(&mut buf).is_err());
+        assert!(body.is_paused());
+        // It stays paused, even once the schedule allows it again.
+        set(&path.0, &UploadSchedule::default()).unwrap();
+        body.checked -= CHECK_INTERVAL;
+        assert!(body.read(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,404 @@
+use crate::{
+    error::{Error, Message, Result},
+    library::RecordingInfo,
+    tasks,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    net::IpAddr,
+    path::Path,
+    sync::Mutex,
+    time::Duration,
+};
+use tauri::Url;
+
+/// Serializes updates of the webhook file.
+static WEBHOOK_LOCK: Mutex<()> = Mutex::new(());
+
+/// How often a delivery is attempted before it is given up.
+const ATTEMPTS: u32 = 3;
+
+/// What a webhook is called for.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum WebhookEvent {
+    /// A recording has been finalized and added to the index.
+    Finalized,
+    /// A recording has been uploaded, by the uploader or by the app, which
+    /// marks it with `update_sync_states`.
+    Uploaded,
+}
+
+impl WebhookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            WebhookEvent::Finalized => "finalized",
+            WebhookEvent::Uploaded => "uploaded",
+        }
+    }
+}
+
+/// An HTTP endpoint that is sent a JSON request about new recordings, so a
+/// backend learns about them without the frontend passing them on.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookOptions {
+    /// An `https` URL that is sent a `POST` request, or an `http` one on
+    /// this machine.
+    pub url: String,
+    /// The events to call the webhook for. Defaults to both.
+    #[serde(default = "default_events")]
+    pub events: Vec<WebhookEvent>,
+    /// The JSON body, in which strings can refer to the event and the
+    /// recording's metadata as `{{event}}`, `{{path}}`, `{{sync.remote}}`
+    /// and so on. A string that is a single reference takes the value as
+    /// is, e.g. a number. Defaults to `{ "event": ..., "recording": ... }`.
+    pub template: Option<Value>,
+    /// Extra request headers, e.g. for authorization.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// How long to wait for the endpoint on each attempt. Defaults to 10 s.
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_events() -> Vec<WebhookEvent> {
+    vec![WebhookEvent::Finalized, WebhookEvent::Uploaded]
+}
+
+fn default_timeout_ms() -> u64 {
+    10_000
+}
+
+impl WebhookOptions {
+    /// Checks that the URL can be called and the headers cannot break out
+    /// of the request.
+    pub fn validate(&self) -> Result<()> {
+        // The body carries the recording's metadata, which must not cross
+        // the network in cleartext.
+        let url = Url::parse(&self.url).ok().filter(|x| {
+            let secure = match x.scheme() {
+                "https" => true,
+                "http" => x.host_str().is_some_and(is_loopback),
+                _ => false,
+            };
+            secure && x.host_str().is_some()
+        });
+        if url.is_none() {
+            return Err(Error::InvalidOptions(
+                Message::new(
+                    "invalidOptions.webhookUrl",
+                    format!(
+                        "{} is not an https URL or an http URL on this machine.",
+                        self.url
+                    ),
+                )
+                .param("url", &self.url),
+            ));
+        }
+
+        for (name, value) in &self.headers {
+            let valid = !name.is_empty()
+                && name.bytes().all(|x| x.is_ascii_graphic() && x != b':')
+                && !value.contains(['\r', '\n']);
+            if !valid {
+                return Err(Error::InvalidOptions(
+                    Message::new(
+                        "invalidOptions.webhookHeader",
+                        format!("Invalid header \"{name}\"."),
+                    )
+                    .param("name", name),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn get(webhook_path: &Path) -> Result<Option<WebhookOptions>> {
+    let _guard = WEBHOOK_LOCK.lock()?;
+
+    if !webhook_path.exists() {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_reader(File::open(webhook_path)?)?))
+}
+
+/// Sets the webhook, or removes it when `options` is `None`.
+pub(crate) fn set(webhook_path: &Path, options: Option<&WebhookOptions>) -> Result<()> {
+    let _guard = WEBHOOK_LOCK.lock()?;
+
+    let Some(options) = options else {
+        if webhook_path.exists() {
+            fs::remove_file(webhook_path)?;
+        }
+        return Ok(());
+    };
+
+    // Replaced in one step so a crash never leaves a truncated file behind.
+    let temp_path = webhook_path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(options)?)?;
+    fs::rename(&temp_path, webhook_path)?;
+
+    Ok(())
+}
+
+/// Calls the webhook, if one is set for `event`, on a separate thread,
+/// retrying with a growing delay when the endpoint cannot be reached or
+/// does not answer with a success status.
+pub(crate) fn notify(webhook_path: &Path, event: WebhookEvent, info: &RecordingInfo) {
+    let options = match get(webhook_path) {
+        Ok(Some(options)) if options.events.contains(&event) => options,
+        Ok(_) => return,
+        Err(err) => {
+            eprintln!("failed to read the webhook: {}", err);
+            return;
+        }
+    };
+
+    let mut values = serde_json::to_value(info)
+        .ok()
+        .and_then(|x| x.as_object().cloned())
+        .unwrap_or_default();
+    values.insert("event".to_string(), event.name().into());
+
+    let body = match &options.template {
+        Some(template) => render(template, &values),
+        None => serde_json::json!({ "event": event.name(), "recording": info }),
+    };
+    let body = body.to_string();
+
+    tasks::spawn(move || {
+        for attempt in 1..=ATTEMPTS {
+            match post(&options, &body) {
+                Ok(()) => return,
+                Err(err) if attempt == ATTEMPTS => {
+                    eprintln!("failed to call the webhook {}: {}", options.url, err);
+                }
+                Err(_) => {
+                    if !tasks::sleep(Duration::from_secs(1 << attempt)) {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Fills the references to `values` into the strings of a template.
+fn render(template: &Value, values: &Map<String, Value>) -> Value {
+    match template {
+        Value::String(text) => {
+            if let Some(name) = text
+                .strip_prefix("{{")
+                .and_then(|x| x.strip_suffix("}}"))
+                .filter(|x| !x.contains("{{") && !x.contains("}}"))
+            {
+                return lookup(values, name.trim()).clone();
+            }
+
+            let mut rendered = String::new();
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("{{") {
+                let Some(end) = rest[start..].find("}}") else {
+                    break;
+                };
+
+                rendered.push_str(&rest[..start]);
+                match lookup(values, rest[start + 2..start + end].trim()) {
+                    Value::String(x) => rendered.push_str(x),
+                    Value::Null => {}
+                    x => rendered.push_str(&x.to_string()),
+                }
+                rest = &rest[start + end + 2..];
+            }
+            rendered.push_str(rest);
+
+            Value::String(rendered)
+        }
+        Value::Array(items) => Value::Array(items.iter().map(|x| render(x, values)).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, x)| (key.clone(), render(x, values)))
+                .collect(),
+        ),
+        _ => template.clone(),
+    }
+}
+
+/// Looks up a value by its dotted path, e.g. `sync.remote`.
+fn lookup<'a>(values: &'a Map<String, Value>, name: &str) -> &'a Value {
+    let mut parts = name.split('.');
+    let first = parts.next().and_then(|x| values.get(x));
+
+    parts
+        .try_fold(first.unwrap_or(&Value::Null), |value, part| value.get(part))
+        .unwrap_or(&Value::Null)
+}
+
+/// Whether `host` names this machine.
+fn is_loopback(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    host.eq_ignore_ascii_case("localhost") || host.parse::<IpAddr>().is_ok_and(|x| x.is_loopback())
+}
+
+/// Sends a JSON `POST` request.
+fn post(options: &WebhookOptions, body: &str) -> std::io::Result<()> {
+    let timeout = Duration::from_millis(options.timeout_ms.max(1));
+    let agent = ureq::AgentBuilder::new()
+        .timeout(timeout)
+        .user_agent("tauri-plugin-mic-recorder")
+        .build();
+
+    let mut request = agent
+        .post(&options.url)
+        .set("Content-Type", "application/json");
+    for (name, value) in &options.headers {
+        request = request.set(name, value);
+    }
+
+    // Statuses other than 2xx are returned as errors.
+    request
+        .send_string(body)
+        .map_err(|err| std::io::Error::other(err.to_string()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn options(url: &str) -> WebhookOptions {
+        WebhookOptions {
+            url: url.to_string(),
+            events: default_events(),
+            template: None,
+            headers: BTreeMap::new(),
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+
+    fn values() -> Map<String, Value> {
+        json!({
+            "event": "finalized",
+            "path": "/recordings/take \"1\".wav",
+            "durationMs": 1500,
+            "sync": { "remote": "s3://bucket/take.wav", "attempts": 2 },
+            "tags": null,
+        })
+        .as_object()
+        .cloned()
+        .unwrap()
+    }
+
+    #[test]
+    fn fills_in_references() {
+        let template = json!({
+            "text": "{{event}}: {{ path }} ({{durationMs}} ms)",
+            "remote": "{{sync.remote}}",
+            "items": ["{{sync.attempts}}", "{{tags}}", 7],
+        });
+
+        assert_eq!(
+            render(&template, &values()),
+            json!({
+                "text": "finalized: /recordings/take \"1\".wav (1500 ms)",
+                "remote": "s3://bucket/take.wav",
+                "items": [2, null, 7],
+            })
+        );
+    }
+
+    #[test]
+    fn keeps_the_type_of_a_single_reference() {
+        let values = values();
+
+        assert_eq!(render(&json!("{{durationMs}}"), &values), json!(1500));
+        assert_eq!(render(&json!("{{sync}}"), &values)["attempts"], json!(2));
+        assert_eq!(render(&json!("{{missing.field}}"), &values), Value::Null);
+        assert_eq!(render(&json!("x{{missing}}y"), &values), json!("xy"));
+    }
+
+    #[test]
+    fn leaves_unterminated_references_alone() {
+        let values = values();
+
+        assert_eq!(render(&json!("{{event"), &values), json!("{{event"));
+        assert_eq!(
+            render(&json!("{{event}} {{path"), &values),
+            json!("finalized {{path")
+        );
+        assert_eq!(
+            render(&json!("{{event}}{{event}}"), &values),
+            json!("finalizedfinalized")
+        );
+    }
+
+    #[test]
+    fn escapes_values_in_the_body() {
+        let body = render(&json!({ "path": "{{path}}" }), &values()).to_string();
+
+        assert_eq!(body, r#"{"path":"/recordings/take \"1\".wav"}"#);
+    }
+
+    #[test]
+    fn accepts_https_and_http_on_this_machine() {
+        for url in [
+            "https://example.com/hooks",
+            "http://localhost:8080/hooks",
+            "http://LOCALHOST/hooks",
+            "http://127.0.0.1/hooks",
+            "http://127.1.2.3/hooks",
+            "http://[::1]:3000/hooks",
+        ] {
+            assert!(options(url).validate().is_ok(), "rejected {url}");
+        }
+    }
+
+    #[test]
+    fn rejects_urls_off_this_machine_in_cleartext() {
+        for url in [
+            "http://example.com/hooks",
+            "http://localhost.evil.com/hooks",
+            "http://127.0.0.1.evil.com/hooks",
+            "http://[::2]/hooks",
+            "http://0.0.0.0/hooks",
+            "ftp://localhost/hooks",
+            "file:///etc/passwd",
+            "localhost:8080",
+            "",
+        ] {
+            assert!(options(url).validate().is_err(), "accepted {url}");
+        }
+    }
+
+    #[test]
+    fn rejects_headers_that_break_out_of_the_request() {
+        for (name, value) in [
+            ("", "x"),
+            ("X Token", "x"),
+            ("X-Token:", "x"),
+            ("X-Token", "x\r\nHost: evil.com"),
+        ] {
+            let mut options = options("https://example.com");
+            options.headers.insert(name.to_string(), value.to_string());
+
+            assert!(options.validate().is_err(), "accepted {name:?}");
+        }
+
+        let mut options = options("https://example.com");
+        options
+            .headers
+            .insert("Authorization".to_string(), "Bearer abc".to_string());
+        assert!(options.validate().is_ok());
+    }
+}